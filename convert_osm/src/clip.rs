@@ -148,6 +148,9 @@ pub fn clip_map(map: &mut RawMap, timer: &mut Timer) {
             .all(|pt| boundary_polygon.contains_pt(*pt))
     });
 
+    map.bike_share_stations
+        .retain(|s| boundary_polygon.contains_pt(s.point));
+
     let mut result_areas = Vec::new();
     for orig_area in map.areas.drain(..) {
         for polygon in map.boundary_polygon.intersection(&orig_area.polygon) {
@@ -0,0 +1,272 @@
+//! Reads digital elevation models (DEMs) and looks up the elevation anywhere inside them. Two
+//! formats are supported:
+//!
+//! - SRTM HGT tiles (https://dds.cr.usgs.gov/srtm/version2_1/Documentation/SRTM_Topo.pdf). Each
+//!   file covers one degree of latitude and longitude, named by the southwest corner, like
+//!   N47W122.hgt. The resolution (1 or 3 arc-seconds) is determined by the file size.
+//! - GeoTIFFs in WGS84 longitude/latitude, like the ones produced by
+//!   `gdalwarp -t_srs EPSG:4326`. Elevation must be in the first band, in meters.
+//!
+//! Elevation between the samples of a grid is found by bilinear interpolation.
+
+use std::fs::File;
+use std::path::Path;
+
+use byteorder::{BigEndian, ByteOrder};
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::tags::Tag;
+
+use geom::{Distance, LonLat};
+
+// GeoTIFF tags and keys. See http://docs.opengeospatial.org/is/19-008r4/19-008r4.html.
+const MODEL_PIXEL_SCALE_TAG: u16 = 33550;
+const MODEL_TIEPOINT_TAG: u16 = 33922;
+const GEO_KEY_DIRECTORY_TAG: u16 = 34735;
+const GT_MODEL_TYPE_GEO_KEY: u32 = 1024;
+const MODEL_TYPE_GEOGRAPHIC: u32 = 2;
+const GT_RASTER_TYPE_GEO_KEY: u32 = 1025;
+const RASTER_PIXEL_IS_POINT: u32 = 2;
+
+/// All of the grids loaded from a file or directory.
+pub struct Elevation {
+    grids: Vec<Grid>,
+}
+
+impl Elevation {
+    /// Loads one HGT or GeoTIFF file, or every such file in a directory.
+    pub fn load(path: &str) -> Result<Elevation, String> {
+        let is_dir = Path::new(path).is_dir();
+        let mut paths = Vec::new();
+        if is_dir {
+            for entry in std::fs::read_dir(path).map_err(|err| format!("{}: {}", path, err))? {
+                let entry = entry.map_err(|err| format!("{}: {}", path, err))?;
+                paths.push(entry.path().display().to_string());
+            }
+            paths.sort();
+        } else {
+            paths.push(path.to_string());
+        }
+
+        let mut grids = Vec::new();
+        for path in paths {
+            let lower = path.to_lowercase();
+            if lower.ends_with(".hgt") {
+                println!("Reading SRTM elevation data from {}", path);
+                grids.push(Grid::load_hgt(&path)?);
+            } else if lower.ends_with(".tif") || lower.ends_with(".tiff") {
+                println!("Reading GeoTIFF elevation data from {}", path);
+                grids.push(Grid::load_geotiff(&path)?);
+            } else if !is_dir {
+                // Other files in a directory are ignored, but not ones passed directly
+                return Err(format!("{} isn't a .hgt or .tif file", path));
+            }
+        }
+        if grids.is_empty() {
+            return Err(format!("No elevation data found in {}", path));
+        }
+        Ok(Elevation { grids })
+    }
+
+    /// Returns None if no grid covers the point, or there's no data there.
+    pub fn get(&self, pt: LonLat) -> Option<Distance> {
+        self.grids
+            .iter()
+            .find_map(|g| g.get(pt))
+            .map(Distance::meters)
+    }
+}
+
+/// A regular grid of elevation samples, in meters. Rows go from north to south, and columns from
+/// west to east.
+struct Grid {
+    /// The longitude and latitude of the first (northwest) sample
+    origin: LonLat,
+    /// Degrees of longitude between columns
+    dx: f64,
+    /// Degrees of latitude between rows
+    dy: f64,
+    width: usize,
+    height: usize,
+    /// Row-major. NaN means there's no data for a sample.
+    data: Vec<f32>,
+}
+
+impl Grid {
+    fn load_hgt(path: &str) -> Result<Grid, String> {
+        // The file is named after the southwest corner, like N47W122.hgt
+        let name = Path::new(path)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("")
+            .to_uppercase();
+        let bad_name = || format!("{} should be named like N47W122.hgt", path);
+        if name.len() != 7 {
+            return Err(bad_name());
+        }
+        let lat = name[1..3].parse::<f64>().map_err(|_| bad_name())?;
+        let lon = name[4..7].parse::<f64>().map_err(|_| bad_name())?;
+        let lat = match &name[0..1] {
+            "N" => lat,
+            "S" => -lat,
+            _ => {
+                return Err(bad_name());
+            }
+        };
+        let lon = match &name[3..4] {
+            "E" => lon,
+            "W" => -lon,
+            _ => {
+                return Err(bad_name());
+            }
+        };
+
+        // Big-endian signed 16-bit integers, in a square grid. The edge rows and columns overlap
+        // with the neighboring tiles.
+        let bytes = std::fs::read(path).map_err(|err| format!("{}: {}", path, err))?;
+        let dim = ((bytes.len() / 2) as f64).sqrt() as usize;
+        if dim < 2 || dim * dim * 2 != bytes.len() {
+            return Err(format!(
+                "{} has {} bytes, which isn't a square grid of 16-bit samples",
+                path,
+                bytes.len()
+            ));
+        }
+        let data = bytes
+            .chunks_exact(2)
+            .map(|pair| match BigEndian::read_i16(pair) {
+                -32768 => std::f32::NAN,
+                x => f32::from(x),
+            })
+            .collect();
+
+        let spacing = 1.0 / ((dim - 1) as f64);
+        Ok(Grid {
+            origin: LonLat::new(lon, lat + 1.0),
+            dx: spacing,
+            dy: spacing,
+            width: dim,
+            height: dim,
+            data,
+        })
+    }
+
+    fn load_geotiff(path: &str) -> Result<Grid, String> {
+        let err = |err: tiff::TiffError| format!("{}: {}", path, err);
+        let mut decoder =
+            Decoder::new(File::open(path).map_err(|err| format!("{}: {}", path, err))?)
+                .map_err(err)?;
+
+        let keys = decoder
+            .find_tag_u32_vec(Tag::Unknown(GEO_KEY_DIRECTORY_TAG))
+            .map_err(err)?
+            .unwrap_or_else(Vec::new);
+        if geo_key(&keys, GT_MODEL_TYPE_GEO_KEY).map_or(false, |x| x != MODEL_TYPE_GEOGRAPHIC) {
+            return Err(format!(
+                "{} isn't in longitude/latitude. Reproject it with gdalwarp -t_srs EPSG:4326",
+                path
+            ));
+        }
+        let pixel_is_point = geo_key(&keys, GT_RASTER_TYPE_GEO_KEY) == Some(RASTER_PIXEL_IS_POINT);
+
+        let scale = decoder
+            .get_tag_f64_vec(Tag::Unknown(MODEL_PIXEL_SCALE_TAG))
+            .map_err(err)?;
+        let tiepoint = decoder
+            .get_tag_f64_vec(Tag::Unknown(MODEL_TIEPOINT_TAG))
+            .map_err(err)?;
+        if scale.len() < 2 || tiepoint.len() < 6 {
+            return Err(format!("{} doesn't have a valid georeference", path));
+        }
+        let (dx, dy) = (scale[0], scale[1]);
+        // The tiepoint maps raster position (i, j) to (lon, lat). By default, raster positions
+        // refer to the corner of a pixel, but the sample is in the middle.
+        let offset = if pixel_is_point { 0.0 } else { 0.5 };
+        let origin = LonLat::new(
+            tiepoint[3] + (offset - tiepoint[0]) * dx,
+            tiepoint[4] - (offset - tiepoint[1]) * dy,
+        );
+
+        let (width, height) = decoder.dimensions().map_err(err)?;
+        let (width, height) = (width as usize, height as usize);
+        let data: Vec<f32> = match decoder.read_image().map_err(err)? {
+            DecodingResult::U8(v) => v.into_iter().map(f32::from).collect(),
+            DecodingResult::U16(v) => v.into_iter().map(f32::from).collect(),
+            DecodingResult::I16(v) => v.into_iter().map(f32::from).collect(),
+            DecodingResult::U32(v) => v.into_iter().map(|x| x as f32).collect(),
+            DecodingResult::F32(v) => v,
+            DecodingResult::F64(v) => v.into_iter().map(|x| x as f32).collect(),
+            _ => {
+                return Err(format!("{} has an unsupported sample format", path));
+            }
+        };
+        if width < 2 || height < 2 || data.len() < width * height {
+            return Err(format!(
+                "{} only has {} samples, but should be {}x{}",
+                path,
+                data.len(),
+                width,
+                height
+            ));
+        }
+        // If there are multiple bands, just keep the first
+        let bands = data.len() / (width * height);
+        let data = data
+            .into_iter()
+            .step_by(bands)
+            // The "no data" value varies. Nowhere on land is this low.
+            .map(|x| if x < -1000.0 { std::f32::NAN } else { x })
+            .collect();
+
+        Ok(Grid {
+            origin,
+            dx,
+            dy,
+            width,
+            height,
+            data,
+        })
+    }
+
+    /// Bilinear interpolation between the 4 samples surrounding the point. Samples without data
+    /// are skipped.
+    fn get(&self, pt: LonLat) -> Option<f64> {
+        // Fractional column and row
+        let x = (pt.x() - self.origin.x()) / self.dx;
+        let y = (self.origin.y() - pt.y()) / self.dy;
+        if x < 0.0 || y < 0.0 || x > (self.width - 1) as f64 || y > (self.height - 1) as f64 {
+            return None;
+        }
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+
+        let mut total = 0.0;
+        let mut total_weight = 0.0;
+        for (col, row, weight) in vec![
+            (x0, y0, (1.0 - fx) * (1.0 - fy)),
+            (x1, y0, fx * (1.0 - fy)),
+            (x0, y1, (1.0 - fx) * fy),
+            (x1, y1, fx * fy),
+        ] {
+            let value = self.data[row * self.width + col];
+            if weight > 0.0 && !value.is_nan() {
+                total += weight * f64::from(value);
+                total_weight += weight;
+            }
+        }
+        if total_weight == 0.0 {
+            return None;
+        }
+        Some(total / total_weight)
+    }
+}
+
+/// Looks up the value of a key in a GeoKeyDirectory. Only handles keys stored directly in the
+/// directory, which covers all the short ones.
+fn geo_key(keys: &Vec<u32>, key: u32) -> Option<u32> {
+    // A header of 4 values, then 4 values per key: ID, location, count, and value
+    keys.get(4..)?
+        .chunks_exact(4)
+        .find(|entry| entry[0] == key && entry[1] == 0)
+        .map(|entry| entry[3])
+}
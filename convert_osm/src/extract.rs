@@ -5,7 +5,10 @@ use osm::{NodeID, OsmID, RelationID, WayID};
 use abstutil::{retain_btreemap, Tags, Timer};
 use geom::{HashablePt2D, Polygon, Pt2D, Ring};
 use kml::{ExtraShape, ExtraShapes};
-use map_model::raw::{RawArea, RawBuilding, RawMap, RawParkingLot, RawRoad, RestrictionType};
+use map_model::raw::{
+    RawArea, RawBikeShareStation, RawBuilding, RawMap, RawParkingLot, RawRoad, RestrictionScope,
+    RestrictionType,
+};
 use map_model::{osm, Amenity, AreaType, NamePerLanguage};
 
 use crate::osm_geom::{get_multipolygon_members, glue_multipolygon, multipoly_geometry};
@@ -17,10 +20,17 @@ pub struct OsmExtract {
     /// Traffic signals to the direction they apply (or just true if unspecified)
     pub traffic_signals: HashMap<HashablePt2D, bool>,
     pub osm_node_ids: HashMap<HashablePt2D, NodeID>,
-    /// (ID, restriction type, from way ID, via node ID, to way ID)
-    pub simple_turn_restrictions: Vec<(RestrictionType, WayID, NodeID, WayID)>,
-    /// (relation ID, from way ID, via way ID, to way ID)
-    pub complicated_turn_restrictions: Vec<(RelationID, WayID, WayID, WayID)>,
+    /// (restriction type, from way ID, via node ID, to way ID, scope)
+    pub simple_turn_restrictions: Vec<(RestrictionType, WayID, NodeID, WayID, RestrictionScope)>,
+    /// (relation ID, restriction type, from way ID, via way IDs, to way ID, scope)
+    pub complicated_turn_restrictions: Vec<(
+        RelationID,
+        RestrictionType,
+        WayID,
+        Vec<WayID>,
+        WayID,
+        RestrictionScope,
+    )>,
     /// (location, amenity)
     pub amenities: Vec<(Pt2D, Amenity)>,
 }
@@ -55,6 +65,13 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
         for amenity in get_bldg_amenities(&node.tags) {
             out.amenities.push((node.pt, amenity));
         }
+        if node.tags.is("amenity", "bicycle_rental") {
+            map.bike_share_stations.push(RawBikeShareStation {
+                osm_id: OsmID::Node(*id),
+                point: node.pt,
+                osm_tags: node.tags.clone(),
+            });
+        }
     }
 
     // and cycleways
@@ -85,6 +102,7 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
                     osm_tags: way.tags.clone(),
                     turn_restrictions: Vec::new(),
                     complicated_turn_restrictions: Vec::new(),
+                    elevation: Vec::new(),
                 },
             ));
             continue;
@@ -142,6 +160,13 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
                 polygon,
                 osm_tags: way.tags.clone(),
             });
+        } else if way.tags.is("amenity", "bicycle_rental") {
+            // Some stations are mapped as the area covered by the docks
+            map.bike_share_stations.push(RawBikeShareStation {
+                osm_id: OsmID::Way(id),
+                point: polygon.center(),
+                osm_tags: way.tags.clone(),
+            });
         } else if way.tags.is("historic", "memorial") {
             memorial_areas.push(polygon);
         }
@@ -195,19 +220,20 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
                 }
             }
         } else if rel.tags.is("type", "restriction") {
-            let mut from_way_id: Option<WayID> = None;
+            // no_entry has multiple from ways, and no_exit multiple to ways
+            let mut from_way_ids: Vec<WayID> = Vec::new();
             let mut via_node_id: Option<NodeID> = None;
-            let mut via_way_id: Option<WayID> = None;
-            let mut to_way_id: Option<WayID> = None;
+            let mut via_way_ids: Vec<WayID> = Vec::new();
+            let mut to_way_ids: Vec<WayID> = Vec::new();
             for (role, member) in &rel.members {
                 match member {
                     OsmID::Way(w) => {
                         if role == "from" {
-                            from_way_id = Some(*w);
+                            from_way_ids.push(*w);
                         } else if role == "to" {
-                            to_way_id = Some(*w);
+                            to_way_ids.push(*w);
                         } else if role == "via" {
-                            via_way_id = Some(*w);
+                            via_way_ids.push(*w);
                         }
                     }
                     OsmID::Node(n) => {
@@ -220,24 +246,31 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
                     }
                 }
             }
-            if let Some(restriction) = rel.tags.get("restriction") {
-                if let Some(rt) = RestrictionType::new(restriction) {
-                    if let (Some(from), Some(via), Some(to)) = (from_way_id, via_node_id, to_way_id)
-                    {
-                        out.simple_turn_restrictions.push((rt, from, via, to));
-                    } else if let (Some(from), Some(via), Some(to)) =
-                        (from_way_id, via_way_id, to_way_id)
-                    {
-                        if rt == RestrictionType::BanTurns {
-                            out.complicated_turn_restrictions.push((id, from, via, to));
-                        } else {
-                            timer.warn(format!(
-                                "Weird complicated turn restriction \"{}\" from {} to {} via {}: \
-                                 {}",
-                                restriction, from, to, via, id
-                            ));
+            for (rt, scope) in RestrictionType::parse_osm_relation(id, &rel.tags, timer) {
+                if let Some(via) = via_node_id {
+                    for from in &from_way_ids {
+                        for to in &to_way_ids {
+                            out.simple_turn_restrictions
+                                .push((rt, *from, via, *to, scope.clone()));
                         }
                     }
+                } else if !via_way_ids.is_empty()
+                    && from_way_ids.len() == 1
+                    && to_way_ids.len() == 1
+                {
+                    out.complicated_turn_restrictions.push((
+                        id,
+                        rt,
+                        from_way_ids[0],
+                        via_way_ids.clone(),
+                        to_way_ids[0],
+                        scope,
+                    ));
+                } else {
+                    timer.warn(format!(
+                        "Skipping turn restriction {} with from {:?}, via {:?}, to {:?}",
+                        id, from_way_ids, via_way_ids, to_way_ids
+                    ));
                 }
             }
         } else if is_bldg(&rel.tags) {
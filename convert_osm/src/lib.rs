@@ -2,19 +2,19 @@
 extern crate log;
 
 use abstutil::{MapName, Timer};
-use geom::{Distance, FindClosest, GPSBounds, LonLat, Pt2D, Ring};
+use geom::{Distance, FindClosest, GPSBounds, Line, LonLat, Pt2D, Ring};
 use map_model::raw::RawMap;
 use map_model::{osm, Amenity, MapConfig};
 use serde::{Deserialize, Serialize};
 
 mod clip;
+mod elevation;
 mod extract;
 pub mod osm_geom;
 mod parking;
 pub mod reader;
 mod snappy;
 mod split_ways;
-mod srtm;
 mod transit;
 
 pub struct Options {
@@ -28,8 +28,8 @@ pub struct Options {
     pub onstreet_parking: OnstreetParking,
     pub public_offstreet_parking: PublicOffstreetParking,
     pub private_offstreet_parking: PrivateOffstreetParking,
-    /// If provided, pull elevation data from this SRTM file. The SRTM parser is incorrect, so the
-    /// results will be nonsense.
+    /// If provided, pull elevation data from this SRTM .hgt file, GeoTIFF in WGS84, or a
+    /// directory containing several of them.
     pub elevation: Option<String>,
     /// OSM railway=rail will be included as light rail if so. Cosmetic only.
     pub include_railroads: bool,
@@ -135,15 +135,57 @@ fn use_amenities(map: &mut RawMap, amenities: Vec<(Pt2D, Amenity)>, timer: &mut
 }
 
 fn use_elevation(map: &mut RawMap, path: &str, timer: &mut Timer) {
-    timer.start("apply elevation data to intersections");
-    let elevation = srtm::Elevation::load(path).unwrap();
+    timer.start("apply elevation data");
+    let elevation = match elevation::Elevation::load(path) {
+        Ok(e) => e,
+        Err(err) => {
+            timer.error(format!("Not using elevation data: {}", err));
+            timer.stop("apply elevation data");
+            return;
+        }
+    };
+    let gps_bounds = &map.gps_bounds;
+    let mut missing = 0;
     for i in map.intersections.values_mut() {
         // TODO Not sure why, but I've seen nodes from South Carolina wind up in the updated
         // Seattle extract. And I think there's a bug with clipping, because they survive to this
         // point. O_O
         if map.boundary_polygon.contains_pt(i.point) {
-            i.elevation = elevation.get(i.point.to_gps(&map.gps_bounds));
+            if let Some(e) = elevation.get(i.point.to_gps(gps_bounds)) {
+                i.elevation = e;
+            } else {
+                missing += 1;
+            }
         }
     }
-    timer.stop("apply elevation data to intersections");
+
+    // DEMs are usually about 30m apart, so also sample between the points of long, straight roads.
+    let spacing = Distance::meters(10.0);
+    for r in map.roads.values_mut() {
+        let mut pts = Vec::new();
+        for pair in r.center_points.windows(2) {
+            pts.push(pair[0]);
+            if let Some(line) = Line::new(pair[0], pair[1]) {
+                let mut dist = spacing;
+                while dist < line.length() {
+                    pts.push(line.must_dist_along(dist));
+                    dist += spacing;
+                }
+            }
+        }
+        pts.extend(r.center_points.last().cloned());
+
+        r.elevation = pts
+            .into_iter()
+            .filter_map(|pt| elevation.get(pt.to_gps(gps_bounds)).map(|e| (pt, e)))
+            .collect();
+    }
+
+    if missing > 0 {
+        timer.warn(format!(
+            "{} intersections aren't covered by the elevation data",
+            abstutil::prettyprint_usize(missing)
+        ));
+    }
+    timer.stop("apply elevation data");
 }
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 use abstutil::{Counter, Timer};
 use geom::{Distance, HashablePt2D, Pt2D};
@@ -98,73 +98,51 @@ pub fn split_up_roads(
 
     // Resolve simple turn restrictions (via a node)
     let mut restrictions = Vec::new();
-    for (restriction, from_osm, via_osm, to_osm) in input.simple_turn_restrictions {
+    for (rt, from_osm, via_osm, to_osm, scope) in input.simple_turn_restrictions {
         let roads = map.roads_per_intersection(via_osm);
+        if from_osm == to_osm {
+            // A U-turn back onto the same way. If the way continues through the node, the pieces
+            // on either side are different roads, and going straight between them is fine.
+            for r in roads.iter().filter(|r| r.osm_way_id == from_osm) {
+                restrictions.push((*r, rt, *r, scope.clone()));
+            }
+            continue;
+        }
         // If some of the roads are missing, they were likely filtered out -- usually service
         // roads.
         if let (Some(from), Some(to)) = (
             roads.iter().find(|r| r.osm_way_id == from_osm),
             roads.iter().find(|r| r.osm_way_id == to_osm),
         ) {
-            restrictions.push((*from, restriction, *to));
+            restrictions.push((*from, rt, *to, scope));
         }
     }
-    for (from, rt, to) in restrictions {
+    for (from, rt, to, scope) in restrictions {
         map.roads
             .get_mut(&from)
             .unwrap()
             .turn_restrictions
-            .push((rt, to));
+            .push((rt, to, scope));
     }
 
-    // Resolve complicated turn restrictions (via a way). TODO Only handle via ways immediately
-    // connected to both roads, for now
+    // Resolve complicated turn restrictions (via one or more ways)
     let mut complicated_restrictions = Vec::new();
-    for (rel_osm, from_osm, via_osm, to_osm) in input.complicated_turn_restrictions {
-        let via_candidates: Vec<OriginalRoad> = map
-            .roads
-            .keys()
-            .filter(|r| r.osm_way_id == via_osm)
-            .cloned()
-            .collect();
-        if via_candidates.len() != 1 {
+    for (rel_osm, rt, from_osm, via_osm, to_osm, scope) in input.complicated_turn_restrictions {
+        if let Some((from, via, to)) = resolve_via_ways(map, from_osm, &via_osm, to_osm) {
+            complicated_restrictions.push((from, rt, via, to, scope));
+        } else {
             timer.warn(format!(
-                "Couldn't resolve turn restriction from way {} to way {} via way {}. Candidate \
-                 roads for via: {:?}. See {}",
-                from_osm, to_osm, via_osm, via_candidates, rel_osm
+                "Couldn't resolve turn restriction from way {} to way {} via ways {:?}. See {}",
+                from_osm, to_osm, via_osm, rel_osm
             ));
-            continue;
-        }
-        let via = via_candidates[0];
-
-        let maybe_from = map
-            .roads_per_intersection(via.i1)
-            .into_iter()
-            .chain(map.roads_per_intersection(via.i2).into_iter())
-            .find(|r| r.osm_way_id == from_osm);
-        let maybe_to = map
-            .roads_per_intersection(via.i1)
-            .into_iter()
-            .chain(map.roads_per_intersection(via.i2).into_iter())
-            .find(|r| r.osm_way_id == to_osm);
-        match (maybe_from, maybe_to) {
-            (Some(from), Some(to)) => {
-                complicated_restrictions.push((from, via, to));
-            }
-            _ => {
-                timer.warn(format!(
-                    "Couldn't resolve turn restriction from {} to {} via {:?}",
-                    from_osm, to_osm, via
-                ));
-            }
         }
     }
-    for (from, via, to) in complicated_restrictions {
+    for (from, rt, via, to, scope) in complicated_restrictions {
         map.roads
             .get_mut(&from)
             .unwrap()
             .complicated_turn_restrictions
-            .push((via, to));
+            .push((rt, via, to, scope));
     }
 
     timer.start("match traffic signals to intersections");
@@ -213,3 +191,70 @@ fn dedupe_angles(pts: Vec<Pt2D>) -> Vec<Pt2D> {
     }
     result
 }
+
+/// Ways are split into many roads, so find the shortest sequence of roads belonging to the via ways
+/// that connects a road from the 'from' way to a road from the 'to' way. Returns (from, via, to).
+fn resolve_via_ways(
+    map: &RawMap,
+    from_osm: osm::WayID,
+    via_osm: &Vec<osm::WayID>,
+    to_osm: osm::WayID,
+) -> Option<(OriginalRoad, Vec<OriginalRoad>, OriginalRoad)> {
+    let via_roads: Vec<OriginalRoad> = map
+        .roads
+        .keys()
+        .filter(|r| via_osm.contains(&r.osm_way_id))
+        .cloned()
+        .collect();
+
+    let mut best: Option<(OriginalRoad, Vec<OriginalRoad>, OriginalRoad)> = None;
+    for from in map.roads.keys().filter(|r| r.osm_way_id == from_osm) {
+        for start in vec![from.i1, from.i2] {
+            // Breadth-first search along the via roads, remembering how each intersection was
+            // reached
+            let mut backrefs: HashMap<osm::NodeID, OriginalRoad> = HashMap::new();
+            let mut queue = VecDeque::new();
+            queue.push_back(start);
+            while let Some(i) = queue.pop_front() {
+                if i != start {
+                    if let Some(to) = map
+                        .roads_per_intersection(i)
+                        .into_iter()
+                        .find(|r| r.osm_way_id == to_osm && r != from)
+                    {
+                        let mut via = Vec::new();
+                        let mut current = i;
+                        while current != start {
+                            let r = backrefs[&current];
+                            via.push(r);
+                            current = if r.i1 == current { r.i2 } else { r.i1 };
+                        }
+                        via.reverse();
+                        if best
+                            .as_ref()
+                            .map(|(_, v, _)| via.len() < v.len())
+                            .unwrap_or(true)
+                        {
+                            best = Some((*from, via, to));
+                        }
+                        break;
+                    }
+                }
+                for r in &via_roads {
+                    let next = if r.i1 == i {
+                        r.i2
+                    } else if r.i2 == i {
+                        r.i1
+                    } else {
+                        continue;
+                    };
+                    if next != start && !backrefs.contains_key(&next) {
+                        backrefs.insert(next, *r);
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+    }
+    best
+}
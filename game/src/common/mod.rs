@@ -337,9 +337,12 @@ pub fn cmp_duration_shorter(app: &App, after: Duration, before: Duration) -> Vec
 pub fn color_for_mode(app: &App, m: TripMode) -> Color {
     match m {
         TripMode::Walk => app.cs.unzoomed_pedestrian,
-        TripMode::Bike => app.cs.unzoomed_bike,
-        TripMode::Transit => app.cs.unzoomed_bus,
-        TripMode::Drive => app.cs.unzoomed_car,
+        TripMode::Bike | TripMode::BikeShare => app.cs.unzoomed_bike,
+        TripMode::Transit | TripMode::ParkAndRide | TripMode::BikeAndRide => app.cs.unzoomed_bus,
+        TripMode::Drive | TripMode::RideHail => app.cs.unzoomed_car,
+        TripMode::Truck => app.cs.unzoomed_truck,
+        TripMode::Scooter => app.cs.unzoomed_scooter,
+        TripMode::Motorcycle => app.cs.unzoomed_motorcycle,
     }
 }
 
@@ -350,6 +353,9 @@ pub fn color_for_agent_type(app: &App, a: AgentType) -> Color {
         AgentType::Bus | AgentType::Train => app.cs.unzoomed_bus,
         AgentType::TransitRider => app.cs.bus_trip,
         AgentType::Car => app.cs.unzoomed_car,
+        AgentType::Truck => app.cs.unzoomed_truck,
+        AgentType::Scooter => app.cs.unzoomed_scooter,
+        AgentType::Motorcycle => app.cs.unzoomed_motorcycle,
     }
 }
 
@@ -361,6 +367,8 @@ pub fn color_for_trip_phase(app: &App, tpt: TripPhaseType) -> Color {
         TripPhaseType::Parking => app.cs.parking_trip,
         TripPhaseType::WaitingForBus(_, _) => app.cs.bus_layer,
         TripPhaseType::RidingBus(_, _, _) => app.cs.bus_trip,
+        TripPhaseType::WaitingForRideHail => app.cs.bus_layer,
+        TripPhaseType::RidingRideHail(_) => app.cs.unzoomed_car,
         TripPhaseType::Cancelled | TripPhaseType::Finished => unreachable!(),
         TripPhaseType::DelayedStart => Color::YELLOW,
     }
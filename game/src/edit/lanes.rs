@@ -1,6 +1,7 @@
 use map_gui::render::Renderable;
+use map_gui::tools::PopupMsg;
 use map_gui::ID;
-use map_model::{EditCmd, LaneID, LaneType, Map};
+use map_model::{ConditionalLaneType, EditCmd, LaneID, LaneType, Map, TimeWindows};
 use widgetry::{
     Btn, Choice, Color, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel, State,
     Text, TextExt, VerticalAlignment, Widget,
@@ -11,7 +12,8 @@ use crate::app::Transition;
 use crate::common::CommonState;
 use crate::edit::zones::ZoneEditor;
 use crate::edit::{
-    apply_map_edits, can_edit_lane, maybe_edit_intersection, speed_limit_choices, try_change_lt,
+    apply_map_edits, can_edit_lane, maybe_edit_intersection, speed_limit_choices,
+    try_change_conditional_lt, try_change_lt,
 };
 use crate::sandbox::GameplayMode;
 
@@ -73,6 +75,7 @@ impl LaneEditor {
         }
 
         let parent = app.primary.map.get_parent(l);
+        let conditional = app.primary.map.get_l(l).conditional_type.clone();
         let col = vec![
             Widget::row(vec![
                 Line(format!("Editing {}", l)).small_heading().draw(ctx),
@@ -85,6 +88,7 @@ impl LaneEditor {
             "Type of lane".draw_text(ctx),
             Widget::custom_row(row).centered(),
             Btn::text_fg("reverse direction").build_def(ctx, Key::F),
+            conditional_lt_controls(ctx, lt, conditional),
             {
                 let mut choices = speed_limit_choices(app);
                 if !choices.iter().any(|c| c.data == parent.speed_limit) {
@@ -160,6 +164,43 @@ impl State<App> for LaneEditor {
                 "Finish" => {
                     return Transition::Pop;
                 }
+                "change time-of-day lane type" => {
+                    let map = &mut app.primary.map;
+                    let conditional = match self.panel.dropdown_value("time-of-day lane type") {
+                        Some(lt) => match TimeWindows::parse(&self.panel.text_box("during")) {
+                            Ok(during) if !during.is_empty() => {
+                                Some(ConditionalLaneType { lt, during })
+                            }
+                            Ok(_) => {
+                                return Transition::Push(PopupMsg::new(
+                                    ctx,
+                                    "Error",
+                                    vec!["Those times never happen on a weekday"],
+                                ));
+                            }
+                            Err(err) => {
+                                return Transition::Push(PopupMsg::new(ctx, "Error", vec![err]));
+                            }
+                        },
+                        None => None,
+                    };
+                    match try_change_conditional_lt(ctx, map, self.l, conditional) {
+                        Ok(cmd) => {
+                            let mut edits = map.get_edits().clone();
+                            edits.commands.push(cmd);
+                            apply_map_edits(ctx, app, edits);
+                            return Transition::Replace(LaneEditor::new(
+                                ctx,
+                                app,
+                                self.l,
+                                self.mode.clone(),
+                            ));
+                        }
+                        Err(err) => {
+                            return Transition::Push(err);
+                        }
+                    }
+                }
                 x => {
                     let map = &mut app.primary.map;
                     let result = match x {
@@ -201,15 +242,24 @@ impl State<App> for LaneEditor {
                 }
             },
             Outcome::Changed => {
-                let mut edits = app.primary.map.get_edits().clone();
-                edits.commands.push(app.primary.map.edit_road_cmd(
-                    app.primary.map.get_l(self.l).parent,
-                    |new| {
-                        new.speed_limit = self.panel.dropdown_value("speed limit");
-                    },
-                ));
-                apply_map_edits(ctx, app, edits);
-                return Transition::Replace(LaneEditor::new(ctx, app, self.l, self.mode.clone()));
+                // The time-of-day controls only take effect when applied
+                let speed_limit = self.panel.dropdown_value("speed limit");
+                if speed_limit != app.primary.map.get_parent(self.l).speed_limit {
+                    let mut edits = app.primary.map.get_edits().clone();
+                    edits.commands.push(app.primary.map.edit_road_cmd(
+                        app.primary.map.get_l(self.l).parent,
+                        |new| {
+                            new.speed_limit = speed_limit;
+                        },
+                    ));
+                    apply_map_edits(ctx, app, edits);
+                    return Transition::Replace(LaneEditor::new(
+                        ctx,
+                        app,
+                        self.l,
+                        self.mode.clone(),
+                    ));
+                }
             }
             _ => {}
         }
@@ -230,6 +280,41 @@ impl State<App> for LaneEditor {
     }
 }
 
+// Peak-hour bus lanes, parking lanes that become travel lanes at rush hour, etc
+fn conditional_lt_controls(
+    ctx: &mut EventCtx,
+    lt: LaneType,
+    conditional: Option<ConditionalLaneType>,
+) -> Widget {
+    let mut choices = vec![Choice::new("never", None)];
+    for other in vec![
+        LaneType::Driving,
+        LaneType::Bus,
+        LaneType::Biking,
+        LaneType::Parking,
+    ] {
+        if ConditionalLaneType::is_allowed(lt, other) {
+            choices.push(Choice::new(other.short_name(), Some(other)));
+        }
+    }
+    if choices.len() == 1 {
+        return Widget::nothing();
+    }
+    let (current, during) = match conditional {
+        Some(cond) => (Some(cond.lt), cond.during.describe()),
+        None => (None, String::new()),
+    };
+    Widget::col(vec![
+        Widget::row(vec![
+            "Becomes a".draw_text(ctx).centered_vert(),
+            Widget::dropdown(ctx, "time-of-day lane type", current, choices),
+            "during".draw_text(ctx).centered_vert(),
+            Widget::text_entry(ctx, during, false).named("during"),
+        ]),
+        Btn::text_fg("change time-of-day lane type").build_def(ctx, None),
+    ])
+}
+
 // Allow doing this anywhere. Players can create really wacky roads with many direction changes,
 // but it's not really useful to limit creativity. ;)
 fn reverse_lane(map: &Map, l: LaneID) -> EditCmd {
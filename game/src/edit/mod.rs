@@ -19,7 +19,9 @@ pub use self::lanes::LaneEditor;
 pub use self::routes::RouteEditor;
 pub use self::stop_signs::StopSignEditor;
 pub use self::traffic_signals::TrafficSignalEditor;
-pub use self::validate::{check_blackholes, check_sidewalk_connectivity, try_change_lt};
+pub use self::validate::{
+    check_blackholes, check_sidewalk_connectivity, try_change_conditional_lt, try_change_lt,
+};
 use crate::app::{App, Transition};
 use crate::common::{tool_panel, CommonState, Warping};
 use crate::debug::DebugMode;
@@ -98,7 +100,9 @@ impl EditMode {
                 app.primary
                     .sim
                     .handle_live_edited_traffic_signals(&app.primary.map);
-                let (trips, parked_cars) = app.primary.sim.handle_live_edits(&app.primary.map);
+                let (vehicles, peds, parked_cars) =
+                    app.primary.sim.handle_live_edits(&app.primary.map);
+                let trips = vehicles + peds;
                 if trips == 0 && parked_cars == 0 {
                     Transition::Pop
                 } else {
@@ -682,6 +686,9 @@ pub fn apply_map_edits(ctx: &mut EventCtx, app: &mut App, edits: MapEdits) {
     if app.primary.layer.as_ref().and_then(|l| l.name()) == Some("map edits") {
         app.primary.layer = Some(Box::new(crate::layer::map::Static::edits(ctx, app)));
     }
+    if app.primary.layer.as_ref().and_then(|l| l.name()) == Some("traffic stress") {
+        app.primary.layer = Some(Box::new(crate::layer::map::TrafficStress::new(ctx, app)));
+    }
 
     // Autosave
     app.primary.map.save_edits();
@@ -808,7 +815,8 @@ fn cmd_to_id(cmd: &EditCmd) -> Option<ID> {
     match cmd {
         EditCmd::ChangeRoad { r, .. } => Some(ID::Road(*r)),
         EditCmd::ChangeIntersection { i, .. } => Some(ID::Intersection(*i)),
-        EditCmd::ChangeRouteSchedule { .. } => None,
+        EditCmd::ChangeRouteSchedule { .. } | EditCmd::ChangeRouteVehicles { .. } => None,
+        EditCmd::Batch(cmds) => cmds.iter().find_map(cmd_to_id),
     }
 }
 
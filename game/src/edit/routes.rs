@@ -1,8 +1,9 @@
 use geom::{Duration, Time};
-use map_model::{BusRouteID, EditCmd};
+use map_model::{BusRouteID, EditCmd, FarePayment, PathConstraints, RouteVehicles};
+use sim::VehicleType;
 use widgetry::{
-    Btn, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel, Spinner, State, TextExt,
-    VerticalAlignment, Widget,
+    Btn, Checkbox, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel, Spinner,
+    State, TextExt, VerticalAlignment, Widget,
 };
 
 use crate::app::App;
@@ -19,6 +20,7 @@ impl RouteEditor {
         app.primary.current_selection = None;
 
         let route = app.primary.map.get_br(id);
+        let vehicle_type = vehicle_type(app, id);
         Box::new(RouteEditor {
             panel: Panel::new(Widget::col(vec![
                 Widget::row(vec![
@@ -31,6 +33,39 @@ impl RouteEditor {
                     "Frequency in minutes".draw_text(ctx),
                     Spinner::new(ctx, (1, 120), 60).named("freq_mins"),
                 ]),
+                Widget::row(vec![
+                    "Passengers per vehicle".draw_text(ctx),
+                    Spinner::new(
+                        ctx,
+                        (1, 2000),
+                        route
+                            .vehicle_capacity
+                            .or_else(|| vehicle_type.default_capacity())
+                            .unwrap() as isize,
+                    )
+                    .named("capacity"),
+                ]),
+                Widget::row(vec![
+                    "Doors per vehicle".draw_text(ctx),
+                    Spinner::new(
+                        ctx,
+                        (1, 20),
+                        route
+                            .vehicle_doors
+                            .or_else(|| vehicle_type.default_doors())
+                            .unwrap() as isize,
+                    )
+                    .named("doors"),
+                ]),
+                Checkbox::switch(
+                    ctx,
+                    "all-door boarding",
+                    None,
+                    route
+                        .fare_payment
+                        .unwrap_or(default_fare_payment(vehicle_type))
+                        == FarePayment::AllDoor,
+                ),
                 Btn::text_bg2("Apply").build_def(ctx, Key::Enter),
             ]))
             .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
@@ -59,11 +94,42 @@ impl State<App> for RouteEditor {
                     }
 
                     let mut edits = app.primary.map.get_edits().clone();
-                    edits.commands.push(EditCmd::ChangeRouteSchedule {
+                    let mut cmds = vec![EditCmd::ChangeRouteSchedule {
                         id: self.route,
                         old: app.primary.map.get_br(self.route).spawn_times.clone(),
                         new: hourly_times,
-                    });
+                    }];
+
+                    // Only store values that differ from the defaults
+                    let vehicle_type = vehicle_type(app, self.route);
+                    let capacity = self.panel.spinner("capacity") as usize;
+                    let doors = self.panel.spinner("doors") as usize;
+                    let fare_payment = if self.panel.is_checked("all-door boarding") {
+                        FarePayment::AllDoor
+                    } else {
+                        FarePayment::FrontDoor
+                    };
+                    let new = RouteVehicles {
+                        capacity: Some(capacity)
+                            .filter(|x| Some(*x) != vehicle_type.default_capacity()),
+                        doors: Some(doors).filter(|x| Some(*x) != vehicle_type.default_doors()),
+                        fare_payment: Some(fare_payment)
+                            .filter(|x| *x != default_fare_payment(vehicle_type)),
+                    };
+                    let old = app.primary.map.get_br(self.route).vehicles();
+                    if old != new {
+                        cmds.push(EditCmd::ChangeRouteVehicles {
+                            id: self.route,
+                            old,
+                            new,
+                        });
+                    }
+
+                    if cmds.len() == 1 {
+                        edits.commands.extend(cmds);
+                    } else {
+                        edits.commands.push(EditCmd::Batch(cmds));
+                    }
                     apply_map_edits(ctx, app, edits);
 
                     return Transition::Pop;
@@ -80,3 +146,20 @@ impl State<App> for RouteEditor {
         self.panel.draw(g);
     }
 }
+
+fn vehicle_type(app: &App, id: BusRouteID) -> VehicleType {
+    if app.primary.map.get_br(id).route_type == PathConstraints::Bus {
+        VehicleType::Bus
+    } else {
+        VehicleType::Train
+    }
+}
+
+/// Matches what the simulation assumes when a route doesn't specify fare payment
+fn default_fare_payment(vehicle_type: VehicleType) -> FarePayment {
+    if vehicle_type == VehicleType::Bus {
+        FarePayment::FrontDoor
+    } else {
+        FarePayment::AllDoor
+    }
+}
@@ -169,7 +169,7 @@ impl State<App> for StopSignEditor {
                                 self.id,
                                 &mut Timer::throwaway(),
                             )
-                            .export(&app.primary.map),
+                            .to_raw(&app.primary.map),
                         ),
                     });
                     apply_map_edits(ctx, app, edits);
@@ -1,8 +1,11 @@
+use std::collections::BTreeSet;
+
 use abstutil::Timer;
 use geom::Duration;
-use map_gui::tools::ChooseSomething;
+use map_gui::tools::{ChooseSomething, PopupMsg};
 use map_model::{
-    ControlStopSign, ControlTrafficSignal, EditCmd, EditIntersection, IntersectionID, PhaseType,
+    ControlStopSign, ControlTrafficSignal, DirectedRoadID, EditCmd, EditIntersection,
+    IntersectionID, PhaseType, TransitPriority,
 };
 use widgetry::{
     Btn, Checkbox, Choice, DrawBaselayer, EventCtx, GfxCtx, Key, Line, Outcome, Panel, Spinner,
@@ -25,6 +28,18 @@ impl ChangeDuration {
         signal: &ControlTrafficSignal,
         idx: usize,
     ) -> Box<dyn State<App>> {
+        let stage = &signal.stages[idx];
+        let phase_type = &stage.phase_type;
+        let (policy, max_duration, gap) = match phase_type {
+            PhaseType::Fixed(d) => ("fixed", *d * 2.0, Duration::seconds(3.0)),
+            PhaseType::Adaptive(d) => ("adaptive", *d * 2.0, Duration::seconds(3.0)),
+            PhaseType::Actuated { max_green, gap, .. } => ("actuated", *max_green, *gap),
+            PhaseType::MaxPressure { max_green, .. } => {
+                ("max-pressure", *max_green, Duration::seconds(3.0))
+            }
+        };
+        let min_duration = signal.get_min_crossing_time(idx).inner_seconds() as isize;
+
         Box::new(ChangeDuration {
             panel: Panel::new(Widget::col(vec![
                 Widget::row(vec![
@@ -34,37 +49,75 @@ impl ChangeDuration {
                     Btn::close(ctx),
                 ]),
                 Widget::row(vec![
-                    "Seconds:".draw_text(ctx),
+                    "Type:".draw_text(ctx),
+                    Widget::dropdown(
+                        ctx,
+                        "phase type",
+                        policy,
+                        vec![
+                            Choice::new("fixed", "fixed"),
+                            Choice::new("adaptive", "adaptive"),
+                            Choice::new("actuated", "actuated"),
+                            Choice::new("max-pressure", "max-pressure"),
+                        ],
+                    ),
+                ]),
+                Widget::row(vec![
+                    "Seconds (minimum for actuated and max-pressure):".draw_text(ctx),
                     Spinner::new(
                         ctx,
-                        (
-                            signal.get_min_crossing_time(idx).inner_seconds() as isize,
-                            300,
-                        ),
-                        signal.stages[idx]
-                            .phase_type
-                            .simple_duration()
-                            .inner_seconds() as isize,
+                        (min_duration, 300),
+                        phase_type.simple_duration().inner_seconds() as isize,
                     )
                     .named("duration"),
                 ]),
                 Widget::row(vec![
-                    "Type:".draw_text(ctx),
-                    Checkbox::toggle(
+                    "Maximum seconds (actuated and max-pressure):".draw_text(ctx),
+                    Spinner::new(
                         ctx,
-                        "phase type",
-                        "fixed",
-                        "adaptive",
-                        None,
-                        match signal.stages[idx].phase_type {
-                            PhaseType::Fixed(_) => true,
-                            PhaseType::Adaptive(_) => false,
-                        },
-                    ),
+                        (min_duration, 600),
+                        max_duration.inner_seconds() as isize,
+                    )
+                    .named("max duration"),
+                ]),
+                Widget::row(vec![
+                    "Gap seconds (actuated):".draw_text(ctx),
+                    Spinner::new(ctx, (1, 30), gap.inner_seconds() as isize).named("gap"),
                 ]),
                 Line("Minimum time is set by the time required for crosswalk")
                     .secondary()
                     .draw(ctx),
+                Line("Actuated stages extend while vehicles keep arriving.")
+                    .secondary()
+                    .draw(ctx),
+                Line("Max-pressure picks the stage with the most queued demand next.")
+                    .secondary()
+                    .draw(ctx),
+                Line("Pedestrians").small_heading().draw(ctx),
+                Widget::row(vec![
+                    "Leading pedestrian interval (seconds):".draw_text(ctx),
+                    Spinner::new(
+                        ctx,
+                        (0, 15),
+                        stage.leading_pedestrian_interval.inner_seconds() as isize,
+                    )
+                    .named("leading pedestrian interval"),
+                ]),
+                Widget::row(vec![
+                    "Flashing don't walk (seconds):".draw_text(ctx),
+                    Spinner::new(
+                        ctx,
+                        (0, 30),
+                        stage.flashing_dont_walk.inner_seconds() as isize,
+                    )
+                    .named("flashing don't walk"),
+                ]),
+                Checkbox::checkbox(
+                    ctx,
+                    "pedestrians must push a button to cross",
+                    None,
+                    stage.ped_push_button,
+                ),
                 Btn::text_bg2("Apply").build_def(ctx, Key::Enter),
             ]))
             .build(ctx),
@@ -80,11 +133,41 @@ impl State<App> for ChangeDuration {
                 "close" => Transition::Pop,
                 "Apply" => {
                     let dt = Duration::seconds(self.panel.spinner("duration") as f64);
-                    let new_type = if self.panel.is_checked("phase type") {
-                        PhaseType::Fixed(dt)
-                    } else {
-                        PhaseType::Adaptive(dt)
+                    // Don't let the maximum fall below the minimum
+                    let max_dt =
+                        Duration::seconds(self.panel.spinner("max duration") as f64).max(dt);
+                    let gap = Duration::seconds(self.panel.spinner("gap") as f64);
+                    let new_type = match self.panel.dropdown_value("phase type") {
+                        "fixed" => PhaseType::Fixed(dt),
+                        "adaptive" => PhaseType::Adaptive(dt),
+                        "actuated" => PhaseType::Actuated {
+                            min_green: dt,
+                            max_green: max_dt,
+                            gap,
+                        },
+                        "max-pressure" => PhaseType::MaxPressure {
+                            min_green: dt,
+                            max_green: max_dt,
+                        },
+                        _ => unreachable!(),
                     };
+                    let lpi =
+                        Duration::seconds(self.panel.spinner("leading pedestrian interval") as f64);
+                    let flashing_dont_walk =
+                        Duration::seconds(self.panel.spinner("flashing don't walk") as f64);
+                    let push_button = self
+                        .panel
+                        .is_checked("pedestrians must push a button to cross");
+                    if lpi + flashing_dont_walk > Duration::ZERO && lpi + flashing_dont_walk >= dt {
+                        return Transition::Push(PopupMsg::new(
+                            ctx,
+                            "Error",
+                            vec![
+                                "The stage is too short for the leading pedestrian interval and \
+                                 flashing don't walk",
+                            ],
+                        ));
+                    }
                     let idx = self.idx;
                     return Transition::Multi(vec![
                         Transition::Pop,
@@ -92,6 +175,9 @@ impl State<App> for ChangeDuration {
                             let editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
                             editor.add_new_edit(ctx, app, idx, |ts| {
                                 ts.stages[idx].phase_type = new_type.clone();
+                                ts.stages[idx].leading_pedestrian_interval = lpi;
+                                ts.stages[idx].flashing_dont_walk = flashing_dont_walk;
+                                ts.stages[idx].ped_push_button = push_button;
                             });
                         })),
                     ]);
@@ -116,6 +202,153 @@ impl State<App> for ChangeDuration {
     }
 }
 
+pub struct ChangeTransitPriority {
+    panel: Panel,
+    i: IntersectionID,
+    approaches: Vec<(DirectedRoadID, String)>,
+}
+
+impl ChangeTransitPriority {
+    pub fn new(
+        ctx: &mut EventCtx,
+        app: &App,
+        signal: &ControlTrafficSignal,
+    ) -> Box<dyn State<App>> {
+        let map = &app.primary.map;
+        let approaches: Vec<(DirectedRoadID, String)> = signal
+            .movements
+            .keys()
+            .filter(|m| !m.crosswalk)
+            .map(|m| m.from)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|dr| {
+                (
+                    dr,
+                    format!(
+                        "approaching on {} ({})",
+                        map.get_r(dr.id).get_name(app.opts.language.as_ref()),
+                        dr.id
+                    ),
+                )
+            })
+            .collect();
+        let (max_green_extension, max_early_green) = match signal.transit_priority {
+            Some(ref tsp) => (tsp.max_green_extension, tsp.max_early_green),
+            None => (Duration::seconds(10.0), Duration::seconds(10.0)),
+        };
+
+        let mut col = vec![
+            Widget::row(vec![
+                Line("Transit signal priority").small_heading().draw(ctx),
+                Btn::close(ctx),
+            ]),
+            Checkbox::checkbox(
+                ctx,
+                "give buses and trains priority",
+                None,
+                signal.transit_priority.is_some(),
+            ),
+            Widget::row(vec![
+                "Extend the green by up to (seconds):".draw_text(ctx),
+                Spinner::new(ctx, (0, 60), max_green_extension.inner_seconds() as isize)
+                    .named("green extension"),
+            ]),
+            Widget::row(vec![
+                "End other stages early by up to (seconds):".draw_text(ctx),
+                Spinner::new(ctx, (0, 60), max_early_green.inner_seconds() as isize)
+                    .named("early green"),
+            ]),
+            Line("Detect vehicles...").secondary().draw(ctx),
+        ];
+        for (dr, label) in &approaches {
+            let enabled = signal
+                .transit_priority
+                .as_ref()
+                .map(|tsp| tsp.movements.iter().any(|m| m.from == *dr))
+                .unwrap_or(true);
+            col.push(Checkbox::checkbox(ctx, label, None, enabled));
+        }
+        col.push(Btn::text_bg2("Apply").build_def(ctx, Key::Enter));
+
+        Box::new(ChangeTransitPriority {
+            panel: Panel::new(Widget::col(col)).build(ctx),
+            i: signal.id,
+            approaches,
+        })
+    }
+}
+
+impl State<App> for ChangeTransitPriority {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        match self.panel.event(ctx) {
+            Outcome::Clicked(x) => match x.as_ref() {
+                "close" => Transition::Pop,
+                "Apply" => {
+                    let mut tsp = None;
+                    if self.panel.is_checked("give buses and trains priority") {
+                        let approaches: BTreeSet<DirectedRoadID> = self
+                            .approaches
+                            .iter()
+                            .filter(|(_, label)| self.panel.is_checked(label))
+                            .map(|(dr, _)| *dr)
+                            .collect();
+                        let movements: BTreeSet<_> = app
+                            .primary
+                            .map
+                            .get_traffic_signal(self.i)
+                            .movements
+                            .keys()
+                            .filter(|m| !m.crosswalk && approaches.contains(&m.from))
+                            .cloned()
+                            .collect();
+                        if movements.is_empty() {
+                            return Transition::Push(PopupMsg::new(
+                                ctx,
+                                "Error",
+                                vec!["Pick at least one approach to detect buses and trains on"],
+                            ));
+                        }
+                        tsp = Some(TransitPriority {
+                            movements,
+                            max_green_extension: Duration::seconds(
+                                self.panel.spinner("green extension") as f64,
+                            ),
+                            max_early_green: Duration::seconds(
+                                self.panel.spinner("early green") as f64
+                            ),
+                        });
+                    }
+                    Transition::Multi(vec![
+                        Transition::Pop,
+                        Transition::ModifyState(Box::new(move |state, ctx, app| {
+                            let editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
+                            editor.add_new_edit(ctx, app, 0, |ts| {
+                                ts.transit_priority = tsp.clone();
+                            });
+                        })),
+                    ])
+                }
+                _ => unreachable!(),
+            },
+            _ => {
+                if ctx.normal_left_click() && ctx.canvas.get_cursor_in_screen_space().is_none() {
+                    return Transition::Pop;
+                }
+                Transition::Keep
+            }
+        }
+    }
+
+    fn draw(&self, g: &mut GfxCtx, _: &App) {
+        self.panel.draw(g);
+    }
+
+    fn draw_baselayer(&self) -> DrawBaselayer {
+        DrawBaselayer::PreviousState
+    }
+}
+
 pub fn edit_entire_signal(
     ctx: &mut EventCtx,
     app: &App,
@@ -132,6 +365,7 @@ pub fn edit_entire_signal(
 
     let use_template = "use template";
     let all_walk = "add an all-walk stage at the end";
+    let transit_priority = "configure transit signal priority";
     let stop_sign = "convert to stop signs";
     let close = "close intersection for construction";
     let reset = "reset to default";
@@ -140,6 +374,7 @@ pub fn edit_entire_signal(
     if has_sidewalks {
         choices.push(all_walk);
     }
+    choices.push(transit_priority);
     // TODO Conflating stop signs and construction here
     if mode.can_edit_stop_signs() {
         choices.push(stop_sign);
@@ -184,6 +419,11 @@ pub fn edit_entire_signal(
                     }
                 })),
             ]),
+            x if x == transit_priority => Transition::Replace(ChangeTransitPriority::new(
+                ctx,
+                app,
+                app.primary.map.get_traffic_signal(i),
+            )),
             x if x == stop_sign => {
                 original.apply(app);
 
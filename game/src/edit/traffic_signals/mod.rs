@@ -6,7 +6,7 @@ use map_gui::options::TrafficSignalStyle;
 use map_gui::render::{traffic_signal, DrawMovement, DrawOptions};
 use map_gui::tools::PopupMsg;
 use map_model::{
-    ControlTrafficSignal, EditCmd, EditIntersection, IntersectionID, MovementID, PhaseType, Stage,
+    ControlTrafficSignal, EditCmd, EditIntersection, IntersectionID, MovementID, Stage,
     TurnPriority,
 };
 use widgetry::{
@@ -214,6 +214,13 @@ impl State<App> for TrafficSignalEditor {
                         self.members.clone(),
                     ));
                 }
+                if x == "Make a green wave" {
+                    return Transition::Push(offsets::GreenWave::new(
+                        ctx,
+                        app,
+                        self.members.clone(),
+                    ));
+                }
                 if x == "Add a new stage" {
                     self.add_new_edit(ctx, app, num_stages, |ts| {
                         ts.stages.push(Stage::new());
@@ -329,12 +336,25 @@ impl State<App> for TrafficSignalEditor {
                     ));
                 }
                 "Export" => {
+                    let mut errors = Vec::new();
                     for signal in BundleEdits::get_current(app, &self.members).signals {
-                        let ts = signal.export(&app.primary.map);
-                        abstutil::write_json(
-                            format!("traffic_signal_data/{}.json", ts.intersection_osm_node_id),
-                            &ts,
-                        );
+                        match signal.export(&app.primary.map) {
+                            Ok(ts) => {
+                                abstutil::write_json(
+                                    format!(
+                                        "traffic_signal_data/{}.json",
+                                        ts.intersection_osm_node_id
+                                    ),
+                                    &ts,
+                                );
+                            }
+                            Err(err) => {
+                                errors.push(err);
+                            }
+                        }
+                    }
+                    if !errors.is_empty() {
+                        return Transition::Push(PopupMsg::new(ctx, "Can't export", errors));
                     }
                 }
                 "Preview" => {
@@ -608,11 +628,21 @@ fn make_side_panel(
         // TODO Say "normally" to account for adaptive stages?
         col.push(format!("One full cycle lasts {}", total).draw_text(ctx));
     }
+    if let Some(ref tsp) = canonical_signal.transit_priority {
+        col.push(
+            format!(
+                "Transit priority: extend green up to {}, end early up to {}",
+                tsp.max_green_extension, tsp.max_early_green
+            )
+            .draw_text(ctx),
+        );
+    }
 
     if members.len() == 1 {
         col.push(Btn::text_bg2("Edit entire signal").build_def(ctx, Key::E));
     } else {
         col.push(Btn::text_bg2("Tune offsets between signals").build_def(ctx, Key::O));
+        col.push(Btn::text_bg2("Make a green wave").build_def(ctx, Key::G));
     }
 
     let translations = squish_polygons_together(
@@ -641,10 +671,11 @@ fn make_side_panel(
             .centered_vert(),
             Widget::col(vec![
                 Widget::row(vec![
-                    match canonical_stage.phase_type {
-                        PhaseType::Fixed(d) => format!("Stage {}: {}", idx + 1, d),
-                        PhaseType::Adaptive(d) => format!("Stage {}: {} (adaptive)", idx + 1, d),
-                    }
+                    format!(
+                        "Stage {}: {}",
+                        idx + 1,
+                        canonical_stage.phase_type.describe()
+                    )
                     .draw_text(ctx),
                     Btn::svg_def("system/assets/tools/edit.svg").build(
                         ctx,
@@ -697,13 +728,26 @@ impl BundleEdits {
         }
 
         let mut edits = app.primary.map.get_edits().clone();
-        // TODO Can we batch these commands somehow, so undo/redo in edit mode behaves properly?
+        let mut cmds = Vec::new();
         for signal in self.signals {
-            edits.commands.push(EditCmd::ChangeIntersection {
-                i: signal.id,
-                old: app.primary.map.get_i_edit(signal.id),
-                new: EditIntersection::TrafficSignal(signal.export(&app.primary.map)),
-            });
+            let old = app.primary.map.get_i_edit(signal.id);
+            let new = EditIntersection::TrafficSignal(signal.to_raw(&app.primary.map));
+            if old != new {
+                cmds.push(EditCmd::ChangeIntersection {
+                    i: signal.id,
+                    old,
+                    new,
+                });
+            }
+        }
+        if cmds.is_empty() {
+            return;
+        }
+        // Batch changes to multiple signals, so undo/redo in edit mode treats them as one change.
+        if cmds.len() == 1 {
+            edits.commands.extend(cmds);
+        } else {
+            edits.commands.push(EditCmd::Batch(cmds));
         }
         apply_map_edits(ctx, app, edits);
     }
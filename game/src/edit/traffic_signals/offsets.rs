@@ -2,18 +2,19 @@ use std::collections::BTreeSet;
 
 use maplit::btreeset;
 
-use geom::{Distance, Duration};
+use geom::{Distance, Duration, Speed};
+use map_gui::tools::PopupMsg;
 use map_gui::ID;
-use map_model::IntersectionID;
+use map_model::{ControlTrafficSignal, IntersectionID};
 use sim::Scenario;
 use widgetry::{
-    Btn, Color, Drawable, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel,
-    RewriteColor, Spinner, State, Text, TextExt, VerticalAlignment, Widget,
+    Btn, Checkbox, Color, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Key, Line,
+    Outcome, Panel, RewriteColor, Spinner, State, Text, TextExt, VerticalAlignment, Widget,
 };
 
 use crate::app::{App, Transition};
 use crate::common::CommonState;
-use crate::edit::traffic_signals::fade_irrelevant;
+use crate::edit::traffic_signals::{fade_irrelevant, TrafficSignalEditor};
 
 pub struct ShowAbsolute {
     members: BTreeSet<IntersectionID>,
@@ -319,3 +320,198 @@ impl State<App> for TuneRelative {
         g.redraw(&self.labels);
     }
 }
+
+/// Pick an ordered corridor of signals, then coordinate their offsets for a green wave.
+pub struct GreenWave {
+    members: BTreeSet<IntersectionID>,
+    corridor: Vec<IntersectionID>,
+    panel: Panel,
+    draw: Drawable,
+}
+
+impl GreenWave {
+    pub fn new(
+        ctx: &mut EventCtx,
+        app: &App,
+        members: BTreeSet<IntersectionID>,
+    ) -> Box<dyn State<App>> {
+        let mut state = GreenWave {
+            members,
+            corridor: Vec::new(),
+            panel: Panel::empty(ctx),
+            draw: ctx.upload(GeomBatch::new()),
+        };
+        state.panel = state.make_panel(ctx, app);
+        state.recalc_draw(ctx, app);
+        Box::new(state)
+    }
+
+    fn make_panel(&self, ctx: &mut EventCtx, app: &App) -> Panel {
+        let map = &app.primary.map;
+        let cycle_length: Duration = map
+            .get_traffic_signal(*self.members.iter().next().unwrap())
+            .stages
+            .iter()
+            .map(|s| s.phase_type.simple_duration())
+            .sum();
+        let (speed_label, default_speed) = if app.opts.units.metric {
+            ("Progression speed (km/h):", 40)
+        } else {
+            ("Progression speed (mph):", 25)
+        };
+
+        Panel::new(Widget::col(vec![
+            Widget::row(vec![
+                Line("Make a green wave").small_heading().draw(ctx),
+                Btn::close(ctx),
+            ]),
+            format!(
+                "Select signals in order along the corridor ({} selected)",
+                self.corridor.len()
+            )
+            .draw_text(ctx),
+            Widget::row(vec![
+                speed_label.draw_text(ctx),
+                Spinner::new(ctx, (5, 80), default_speed).named("speed"),
+            ]),
+            Checkbox::checkbox(ctx, "change the cycle length of every signal", None, false),
+            Widget::row(vec![
+                "Cycle length (seconds):".draw_text(ctx),
+                Spinner::new(ctx, (30, 300), cycle_length.inner_seconds() as isize)
+                    .named("cycle length"),
+            ]),
+            Widget::row(vec![
+                if self.corridor.is_empty() {
+                    Btn::text_bg2("Start over").inactive(ctx)
+                } else {
+                    Btn::text_bg2("Start over").build_def(ctx, None)
+                },
+                if self.corridor.len() < 2 {
+                    Btn::text_bg2("Apply").inactive(ctx)
+                } else {
+                    Btn::text_bg2("Apply").build_def(ctx, Key::Enter)
+                },
+            ]),
+        ]))
+        .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
+        .build(ctx)
+    }
+
+    fn recalc_draw(&mut self, ctx: &mut EventCtx, app: &App) {
+        let map = &app.primary.map;
+        let mut batch = fade_irrelevant(app, &self.members);
+        for pair in self.corridor.windows(2) {
+            for r in map
+                .simple_path_btwn(pair[0], pair[1])
+                .unwrap_or_else(Vec::new)
+            {
+                batch.push(app.cs.route, map.get_r(r).get_thick_polygon(map));
+            }
+        }
+        for (idx, i) in self.corridor.iter().enumerate() {
+            batch.push(Color::BLUE.alpha(0.8), map.get_i(*i).polygon.clone());
+            batch.append(
+                Text::from(Line(format!("{}", idx + 1)))
+                    .bg(Color::PURPLE)
+                    .render_to_batch(ctx.prerender)
+                    .color(RewriteColor::ChangeAlpha(0.8))
+                    .scale(0.3)
+                    .centered_on(map.get_i(*i).polygon.center()),
+            );
+        }
+        self.draw = ctx.upload(batch);
+    }
+
+    fn corridor_changed(&mut self, ctx: &mut EventCtx, app: &App) {
+        let mut new = self.make_panel(ctx, app);
+        new.restore(ctx, &self.panel);
+        self.panel = new;
+        self.recalc_draw(ctx, app);
+    }
+}
+
+impl State<App> for GreenWave {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        ctx.canvas_movement();
+        if ctx.redo_mouseover() {
+            app.primary.current_selection = app.mouseover_unzoomed_roads_and_intersections(ctx);
+        }
+        if let Some(ID::Intersection(i)) = app.primary.current_selection {
+            if self.members.contains(&i) && !self.corridor.contains(&i) {
+                if app.per_obj.left_click(ctx, "add to the corridor") {
+                    self.corridor.push(i);
+                    self.corridor_changed(ctx, app);
+                }
+            } else {
+                app.primary.current_selection = None;
+            }
+        } else {
+            app.primary.current_selection = None;
+        }
+
+        match self.panel.event(ctx) {
+            Outcome::Clicked(x) => match x.as_ref() {
+                "close" => {
+                    return Transition::Pop;
+                }
+                "Start over" => {
+                    self.corridor.clear();
+                    self.corridor_changed(ctx, app);
+                }
+                "Apply" => {
+                    let speed = self.panel.spinner("speed") as f64;
+                    let speed = if app.opts.units.metric {
+                        Speed::km_per_hour(speed)
+                    } else {
+                        Speed::miles_per_hour(speed)
+                    };
+                    let cycle_length = if self
+                        .panel
+                        .is_checked("change the cycle length of every signal")
+                    {
+                        Some(Duration::seconds(self.panel.spinner("cycle length") as f64))
+                    } else {
+                        None
+                    };
+                    match ControlTrafficSignal::make_green_wave(
+                        &app.primary.map,
+                        &self.corridor,
+                        speed,
+                        cycle_length,
+                    ) {
+                        Ok(signals) => {
+                            return Transition::Multi(vec![
+                                Transition::Pop,
+                                Transition::ModifyState(Box::new(move |state, ctx, app| {
+                                    let editor =
+                                        state.downcast_mut::<TrafficSignalEditor>().unwrap();
+                                    // The timing of all the stages may have changed, but it's
+                                    // all one edit
+                                    editor.add_new_edit(ctx, app, 0, |ts| {
+                                        if let Some(new) = signals.iter().find(|s| s.id == ts.id) {
+                                            *ts = new.clone();
+                                        }
+                                    });
+                                })),
+                            ]);
+                        }
+                        Err(err) => {
+                            return Transition::Push(PopupMsg::new(ctx, "Error", vec![err]));
+                        }
+                    }
+                }
+                _ => unreachable!(),
+            },
+            _ => {}
+        }
+
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        self.panel.draw(g);
+        CommonState::draw_osd(g, app);
+
+        g.redraw(&self.draw);
+    }
+}
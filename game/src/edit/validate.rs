@@ -2,7 +2,9 @@ use std::collections::BTreeSet;
 
 use abstutil::Timer;
 use map_gui::tools::{ColorDiscrete, PopupMsg};
-use map_model::{connectivity, EditCmd, LaneID, LaneType, Map, PathConstraints};
+use map_model::{
+    connectivity, ConditionalLaneType, EditCmd, LaneID, LaneType, Map, PathConstraints,
+};
 use widgetry::{Color, EventCtx, State};
 
 use crate::app::App;
@@ -124,15 +126,47 @@ pub fn try_change_lt(
     l: LaneID,
     new_lt: LaneType,
 ) -> Result<EditCmd, Box<dyn State<App>>> {
-    let orig_edits = map.get_edits().clone();
+    let cmd = {
+        let r = map.get_l(l).parent;
+        map.edit_road_cmd(r, |new| {
+            let idx = map.get_r(r).offset(l);
+            new.lanes_ltr[idx].0 = new_lt;
+            // Drop a time-of-day lane type that doesn't make sense anymore
+            if let Some(ref cond) = new.conditional_lane_types[idx] {
+                if !ConditionalLaneType::is_allowed(new_lt, cond.lt) {
+                    new.conditional_lane_types[idx] = None;
+                }
+            }
+        })
+    };
+    try_edit_road(ctx, map, l, cmd)
+}
 
-    let mut edits = orig_edits.clone();
+/// Sets or clears the lane type used only at certain times of day.
+pub fn try_change_conditional_lt(
+    ctx: &mut EventCtx,
+    map: &mut Map,
+    l: LaneID,
+    conditional: Option<ConditionalLaneType>,
+) -> Result<EditCmd, Box<dyn State<App>>> {
     let cmd = {
         let r = map.get_l(l).parent;
         map.edit_road_cmd(r, |new| {
-            new.lanes_ltr[map.get_r(r).offset(l)].0 = new_lt;
+            new.conditional_lane_types[map.get_r(r).offset(l)] = conditional.clone();
         })
     };
+    try_edit_road(ctx, map, l, cmd)
+}
+
+fn try_edit_road(
+    ctx: &mut EventCtx,
+    map: &mut Map,
+    l: LaneID,
+    cmd: EditCmd,
+) -> Result<EditCmd, Box<dyn State<App>>> {
+    let orig_edits = map.get_edits().clone();
+
+    let mut edits = orig_edits.clone();
     edits.commands.push(cmd.clone());
     map.try_apply_edits(edits, &mut Timer::throwaway());
 
@@ -141,9 +175,18 @@ pub fn try_change_lt(
 
     // TODO Ban two adjacent parking lanes (What about dppd though?)
 
-    // A parking lane must have a driving lane somewhere on the road.
-    let all_types: BTreeSet<LaneType> = r.lanes_ltr().into_iter().map(|(_, _, lt)| lt).collect();
-    if all_types.contains(&LaneType::Parking) && !all_types.contains(&LaneType::Driving) {
+    // A parking lane must have a driving lane somewhere on the road, usable at all times of day.
+    let all_types: BTreeSet<LaneType> = r
+        .lanes_ltr()
+        .into_iter()
+        .flat_map(|(l, _, _)| map.get_l(l).lane_types_during(None))
+        .collect();
+    if all_types.contains(&LaneType::Parking)
+        && !r
+            .lanes_ltr()
+            .into_iter()
+            .any(|(l, _, _)| PathConstraints::Car.can_use_during(map.get_l(l), None, map))
+    {
         errors.push(format!(
             "A parking lane needs a driving lane somewhere on the same road"
         ));
@@ -156,7 +199,7 @@ pub fn try_change_lt(
         && !r
             .lanes_ltr()
             .into_iter()
-            .any(|(l, _, _)| PathConstraints::Bus.can_use(map.get_l(l), map))
+            .any(|(l, _, _)| PathConstraints::Bus.can_use_during(map.get_l(l), None, map))
     {
         errors.push(format!("You need a driving or bus lane for the bus stop!"));
     }
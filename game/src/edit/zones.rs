@@ -3,8 +3,8 @@ use std::collections::BTreeSet;
 use enumset::EnumSet;
 use maplit::btreeset;
 
-use map_gui::tools::ColorDiscrete;
-use map_model::{AccessRestrictions, PathConstraints, RoadID};
+use map_gui::tools::{ColorDiscrete, PopupMsg};
+use map_model::{AccessRestrictions, ConditionalAccess, PathConstraints, RoadID, TimeWindows};
 use sim::TripMode;
 use widgetry::{
     Btn, Color, Drawable, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel,
@@ -35,13 +35,29 @@ impl ZoneEditor {
             // Starting a new zone
             btreeset! { start.id }
         };
-        let allow_through_traffic = start
-            .access_restrictions
-            .allow_through_traffic
+        // A zone only restricted at some times of day has one conditional rule
+        let (allow_through_traffic, cap_vehicles_per_hour, during) =
+            match start.access_restrictions.conditional.as_slice() {
+                [cond]
+                    if start.access_restrictions.allow_through_traffic == EnumSet::all()
+                        && start.access_restrictions.cap_vehicles_per_hour.is_none() =>
+                {
+                    (
+                        cond.allow_through_traffic,
+                        cond.cap_vehicles_per_hour,
+                        cond.during.describe(),
+                    )
+                }
+                _ => (
+                    start.access_restrictions.allow_through_traffic,
+                    start.access_restrictions.cap_vehicles_per_hour,
+                    String::new(),
+                ),
+            };
+        let allow_through_traffic = allow_through_traffic
             .into_iter()
             .map(|c| TripMode::from_constraints(c))
             .collect();
-        let cap_vehicles_per_hour = start.access_restrictions.cap_vehicles_per_hour;
 
         let (unzoomed, zoomed, legend) = draw_zone(ctx, app, &members);
         let orig_members = members.clone();
@@ -62,6 +78,11 @@ impl ZoneEditor {
                     Spinner::new(ctx, (0, 1000), cap_vehicles_per_hour.unwrap_or(0) as isize)
                         .named("cap_vehicles"),
                 ]),
+                Widget::row(vec![
+                    "Only restrict during (like \"Mo-Fr 08:00-09:00\"; blank = always):"
+                        .draw_text(ctx),
+                    Widget::text_entry(ctx, during, false).named("during"),
+                ]),
                 Widget::custom_row(vec![
                     Btn::text_fg("Apply").build_def(ctx, Key::Enter),
                     Btn::text_fg("Cancel").build_def(ctx, Key::Escape),
@@ -104,16 +125,47 @@ impl State<App> for ZoneEditor {
                     // The original allow_through_traffic always includes this, and there's no way
                     // to exclude it, so stay consistent.
                     allow_through_traffic.insert(PathConstraints::Train);
-                    let new_access_restrictions = AccessRestrictions {
-                        allow_through_traffic,
-                        cap_vehicles_per_hour: {
-                            let n = self.panel.spinner("cap_vehicles") as usize;
-                            if n == 0 {
-                                None
-                            } else {
-                                Some(n)
+                    let cap_vehicles_per_hour = {
+                        let n = self.panel.spinner("cap_vehicles") as usize;
+                        if n == 0 {
+                            None
+                        } else {
+                            Some(n)
+                        }
+                    };
+                    let during = self.panel.text_box("during");
+                    let new_access_restrictions = if during.trim().is_empty() {
+                        AccessRestrictions {
+                            allow_through_traffic,
+                            cap_vehicles_per_hour,
+                            conditional: Vec::new(),
+                        }
+                    } else {
+                        match TimeWindows::parse(&during) {
+                            Ok(during) if !during.is_empty() => {
+                                let mut restrictions = AccessRestrictions::new();
+                                restrictions.conditional.push(ConditionalAccess {
+                                    during,
+                                    allow_through_traffic,
+                                    cap_vehicles_per_hour,
+                                });
+                                restrictions
+                            }
+                            Ok(_) => {
+                                return Transition::Push(PopupMsg::new(
+                                    ctx,
+                                    "Invalid times",
+                                    vec![format!("\"{}\" never happens on a weekday", during)],
+                                ));
                             }
-                        },
+                            Err(err) => {
+                                return Transition::Push(PopupMsg::new(
+                                    ctx,
+                                    "Invalid times",
+                                    vec![err],
+                                ));
+                            }
+                        }
                     };
                     for r in &self.selector.roads {
                         let old_access_restrictions =
@@ -40,11 +40,22 @@ pub fn stop(ctx: &mut EventCtx, app: &App, details: &mut Details, id: BusStopID)
         } else {
             txt.add(Line("  No arrivals yet").secondary());
         }
+        if let Some((avg, max)) = sim.get_analytics().dwell_time_stats(id, r.id) {
+            txt.add(
+                Line(format!(
+                    "  Dwell time: {} on average, {} at most",
+                    avg.to_string(&app.opts.units),
+                    max.to_string(&app.opts.units)
+                ))
+                .secondary(),
+            );
+        }
         rows.push(txt.draw(ctx));
     }
 
     let mut boardings: Counter<BusRouteID> = Counter::new();
     let mut alightings: Counter<BusRouteID> = Counter::new();
+    let mut denied: Counter<BusRouteID> = Counter::new();
     if let Some(list) = app.primary.sim.get_analytics().passengers_boarding.get(&id) {
         for (_, r, _) in list {
             boardings.inc(*r);
@@ -61,13 +72,25 @@ pub fn stop(ctx: &mut EventCtx, app: &App, details: &mut Details, id: BusStopID)
             alightings.inc(*r);
         }
     }
+    if let Some(list) = app
+        .primary
+        .sim
+        .get_analytics()
+        .passengers_denied_boarding
+        .get(&id)
+    {
+        for (_, r) in list {
+            denied.inc(*r);
+        }
+    }
     let mut txt = Text::new();
     txt.add(Line("Total"));
     txt.append(
         Line(format!(
-            ": {} boardings, {} alightings",
+            ": {} boardings, {} alightings, {} denied boarding",
             prettyprint_usize(boardings.sum()),
-            prettyprint_usize(alightings.sum())
+            prettyprint_usize(alightings.sum()),
+            prettyprint_usize(denied.sum())
         ))
         .secondary(),
     );
@@ -75,9 +98,10 @@ pub fn stop(ctx: &mut EventCtx, app: &App, details: &mut Details, id: BusStopID)
         txt.add(Line(format!("Route {}", r.short_name)));
         txt.append(
             Line(format!(
-                ": {} boardings, {} alightings",
+                ": {} boardings, {} alightings, {} denied boarding",
                 prettyprint_usize(boardings.get(r.id)),
-                prettyprint_usize(alightings.get(r.id))
+                prettyprint_usize(alightings.get(r.id)),
+                prettyprint_usize(denied.get(r.id))
             ))
             .secondary(),
         );
@@ -109,8 +133,9 @@ pub fn bus_status(ctx: &mut EventCtx, app: &App, details: &mut Details, id: CarI
 
     rows.push(
         Line(format!(
-            "Currently has {} passengers",
+            "Currently has {} / {} passengers",
             app.primary.sim.num_transit_passengers(id),
+            app.primary.sim.transit_capacity(id),
         ))
         .draw(ctx),
     );
@@ -235,6 +260,8 @@ pub fn route(ctx: &mut EventCtx, app: &App, details: &mut Details, id: BusRouteI
         .draw(ctx),
     );
 
+    let loads = app.primary.sim.get_analytics().transit_load_profile(id);
+
     rows.push(format!("{} stops", route.stops.len()).draw_text(ctx));
     {
         let i = map.get_i(map.get_l(route.start).src_i);
@@ -252,23 +279,42 @@ pub fn route(ctx: &mut EventCtx, app: &App, details: &mut Details, id: BusRouteI
     for (idx, bs) in route.stops.iter().enumerate() {
         let bs = map.get_bs(*bs);
         let name = format!("Stop {}: {}", idx + 1, bs.name);
+        let mut txt = Text::from_all(vec![
+            Line(&bs.name),
+            Line(format!(
+                ": {} boardings, {} alightings, {} currently waiting",
+                prettyprint_usize(boardings.get(bs.id)),
+                prettyprint_usize(alightings.get(bs.id)),
+                prettyprint_usize(waiting.get(bs.id))
+            ))
+            .secondary(),
+        ]);
+        if let Some((avg, max)) = app.primary.sim.get_analytics().dwell_time_stats(bs.id, id) {
+            txt.add(
+                Line(format!(
+                    "  Dwelling for {} on average, {} at most",
+                    avg.to_string(&app.opts.units),
+                    max.to_string(&app.opts.units)
+                ))
+                .secondary(),
+            );
+        }
+        if let Some((avg, max)) = loads.get(&bs.id) {
+            txt.add(
+                Line(format!(
+                    "  Departing with {:.1} passengers on average, {} at most",
+                    avg, max
+                ))
+                .secondary(),
+            );
+        }
         rows.push(Widget::row(vec![
             Btn::svg(
                 "system/assets/tools/pin.svg",
                 RewriteColor::Change(Color::hex("#CC4121"), app.cs.hovering),
             )
             .build(ctx, &name, None),
-            Text::from_all(vec![
-                Line(&bs.name),
-                Line(format!(
-                    ": {} boardings, {} alightings, {} currently waiting",
-                    prettyprint_usize(boardings.get(bs.id)),
-                    prettyprint_usize(alightings.get(bs.id)),
-                    prettyprint_usize(waiting.get(bs.id))
-                ))
-                .secondary(),
-            ])
-            .draw(ctx),
+            txt.draw(ctx),
         ]));
         details.warpers.insert(name, ID::BusStop(bs.id));
     }
@@ -4,7 +4,7 @@ use abstutil::prettyprint_usize;
 use geom::{ArrowCap, Distance, Duration, PolyLine, Polygon, Time};
 use map_gui::options::TrafficSignalStyle;
 use map_gui::render::traffic_signal::draw_signal_stage;
-use map_model::{IntersectionID, IntersectionType, PhaseType};
+use map_model::{IntersectionID, IntersectionType};
 use sim::AgentType;
 use widgetry::{
     Btn, Checkbox, Color, DrawWithTooltips, EventCtx, FanChart, GeomBatch, Line, PlotOptions,
@@ -281,10 +281,11 @@ pub fn traffic_signal(
 
     for (idx, stage) in signal.stages.iter().enumerate() {
         rows.push(
-            match stage.phase_type {
-                PhaseType::Fixed(d) => Line(format!("Stage {}: {}", idx + 1, d)),
-                PhaseType::Adaptive(d) => Line(format!("Stage {}: {} (adaptive)", idx + 1, d)),
-            }
+            Line(format!(
+                "Stage {}: {}",
+                idx + 1,
+                stage.phase_type.describe()
+            ))
             .draw(ctx),
         );
 
@@ -1,5 +1,7 @@
 use std::collections::HashSet;
 
+use enumset::EnumSet;
+
 use abstutil::prettyprint_usize;
 use map_model::{LaneID, PathConstraints};
 use widgetry::{Btn, EventCtx, Line, LinePlot, PlotOptions, Series, Text, TextExt, Widget};
@@ -18,17 +20,37 @@ pub fn info(ctx: &EventCtx, app: &App, details: &mut Details, id: LaneID) -> Vec
     if !l.is_walkable() {
         kv.push(("Type", l.lane_type.describe().to_string()));
     }
+    if let Some(ref cond) = l.conditional_type {
+        kv.push((
+            "Time of day",
+            format!("{} during {}", cond.lt.short_name(), cond.during.describe()),
+        ));
+    }
     if r.is_private() {
-        let mut ban = Vec::new();
-        for p in PathConstraints::all() {
-            if !r.access_restrictions.allow_through_traffic.contains(p) {
-                ban.push(format!("{:?}", p).to_ascii_lowercase());
-            }
-        }
+        let describe_ban = |allow: EnumSet<PathConstraints>| -> Vec<String> {
+            PathConstraints::all()
+                .into_iter()
+                .filter(|p| !allow.contains(*p))
+                .map(|p| format!("{:?}", p).to_ascii_lowercase())
+                .collect()
+        };
+        let ban = describe_ban(r.access_restrictions.allow_through_traffic);
         if !ban.is_empty() {
             kv.push(("No through-traffic for", ban.join(", ")));
         }
-        if let Some(cap) = r.access_restrictions.cap_vehicles_per_hour {
+        for cond in &r.access_restrictions.conditional {
+            let ban = describe_ban(cond.allow_through_traffic);
+            if !ban.is_empty() {
+                kv.push((
+                    "No through-traffic",
+                    format!("for {} during {}", ban.join(", "), cond.during.describe()),
+                ));
+            }
+        }
+        if let Some(cap) = r
+            .access_restrictions
+            .cap_vehicles_per_hour_at(app.primary.sim.time())
+        {
             kv.push((
                 "Cap for vehicles this hour",
                 format!(
@@ -55,6 +77,13 @@ pub fn info(ctx: &EventCtx, app: &App, details: &mut Details, id: LaneID) -> Vec
 
     kv.push(("Length", l.length().to_string(&app.opts.units)));
 
+    if PathConstraints::Bike.can_use(l, &app.primary.map) {
+        let lts = l
+            .get_directed_parent(&app.primary.map)
+            .level_of_traffic_stress(&app.primary.map);
+        kv.push(("Biking", format!("{} ({})", lts, lts.describe())));
+    }
+
     rows.extend(make_table(ctx, kv));
 
     if l.is_parking() {
@@ -122,14 +151,31 @@ pub fn debug(ctx: &EventCtx, app: &App, details: &mut Details, id: LaneID) -> Ve
             format!("{:?}", types.into_iter().collect::<Vec<_>>()),
         ));
     }
-    for (restriction, to) in &r.turn_restrictions {
+    for (restriction, to, scope) in &r.turn_restrictions {
         kv.push((
             format!("Restriction from this road to {}", to),
-            format!("{:?}", restriction),
+            format!("{:?} {}", restriction, scope.describe())
+                .trim_end()
+                .to_string(),
+        ));
+    }
+    for (restriction, via, to, scope) in &r.complicated_turn_restrictions {
+        kv.push((
+            format!(
+                "Restriction from this road via {} to {}",
+                via.iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                to
+            ),
+            format!("{:?} {}", restriction, scope.describe())
+                .trim_end()
+                .to_string(),
         ));
     }
 
-    // TODO Simplify and expose everywhere after there's better data
+    // TODO Simplify and expose everywhere
     kv.push((
         "Elevation change".to_string(),
         format!(
@@ -150,6 +196,11 @@ pub fn debug(ctx: &EventCtx, app: &App, details: &mut Details, id: LaneID) -> Ve
             l.length()
         ),
     ));
+    let (climb, descent) = r.elevation_change();
+    kv.push((
+        "Total climb / descent along road".to_string(),
+        format!("{} / {}", climb, descent),
+    ));
     kv.push((
         "Dir and offset".to_string(),
         format!("{}, {}", r.dir(l.id), r.offset(l.id)),
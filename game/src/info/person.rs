@@ -133,9 +133,16 @@ pub fn trips(
                     ctx.prerender,
                     match trip.mode {
                         TripMode::Walk => "system/assets/meters/pedestrian.svg",
-                        TripMode::Bike => "system/assets/meters/bike.svg",
-                        TripMode::Drive => "system/assets/meters/car.svg",
-                        TripMode::Transit => "system/assets/meters/bus.svg",
+                        TripMode::Bike | TripMode::Scooter | TripMode::BikeShare => {
+                            "system/assets/meters/bike.svg"
+                        }
+                        TripMode::Drive
+                        | TripMode::Truck
+                        | TripMode::Motorcycle
+                        | TripMode::RideHail => "system/assets/meters/car.svg",
+                        TripMode::Transit | TripMode::ParkAndRide | TripMode::BikeAndRide => {
+                            "system/assets/meters/bus.svg"
+                        }
                     },
                 )
                 // we want the icon to be about the same height as the text
@@ -322,7 +329,7 @@ pub fn bio(
 
     let mut has_bike = false;
     for v in &person.vehicles {
-        if v.vehicle_type == VehicleType::Bike {
+        if v.vehicle_type.is_bike_like() {
             has_bike = true;
         } else {
             if app.primary.sim.lookup_parked_car(v.id).is_some() {
@@ -574,6 +581,11 @@ fn header(
                     AgentID::Car(c) => match c.1 {
                         VehicleType::Car => ("driving", Some("system/assets/meters/car.svg")),
                         VehicleType::Bike => ("biking", Some("system/assets/meters/bike.svg")),
+                        VehicleType::Truck => ("trucking", Some("system/assets/meters/car.svg")),
+                        VehicleType::Scooter => ("scooting", Some("system/assets/meters/bike.svg")),
+                        VehicleType::Motorcycle => {
+                            ("riding a motorcycle", Some("system/assets/meters/car.svg"))
+                        }
                         VehicleType::Bus | VehicleType::Train => unreachable!(),
                     },
                     AgentID::BusPassenger(_, _) => {
@@ -549,6 +549,10 @@ fn make_timeline(
                         "system/assets/timeline/waiting_for_bus.svg"
                     }
                     TripPhaseType::RidingBus(_, _, _) => "system/assets/timeline/riding_bus.svg",
+                    TripPhaseType::WaitingForRideHail => {
+                        "system/assets/timeline/waiting_for_bus.svg"
+                    }
+                    TripPhaseType::RidingRideHail(_) => "system/assets/timeline/driving.svg",
                     TripPhaseType::Cancelled | TripPhaseType::Finished => unreachable!(),
                     TripPhaseType::DelayedStart => "system/assets/timeline/delayed_start.svg",
                 },
@@ -729,10 +733,22 @@ fn make_trip_details(
 
     let timeline = make_timeline(ctx, app, trip_id, &phases, progress_along_path);
     let mut elevation = Vec::new();
+    let mut climbs = Vec::new();
     let mut path_impossible = false;
     for (idx, p) in phases.into_iter().enumerate() {
         let color = color_for_trip_phase(app, p.phase_type).alpha(0.7);
         if let Some((dist, ref path)) = p.path {
+            if p.phase_type == TripPhaseType::Biking {
+                let (climb, descent) = path.get_total_elevation_change(map);
+                climbs.push(
+                    format!(
+                        "Biking route climbs {} and descends {}",
+                        climb.to_string(&app.opts.units),
+                        descent.to_string(&app.opts.units)
+                    )
+                    .draw_text(ctx),
+                );
+            }
             if app.opts.dev
                 && (p.phase_type == TripPhaseType::Walking || p.phase_type == TripPhaseType::Biking)
             {
@@ -835,6 +851,7 @@ fn make_trip_details(
             },
         ]),
     ];
+    col.extend(climbs);
     if path_impossible {
         col.push("Map edits have disconnected the path taken before".draw_text(ctx));
     }
@@ -851,8 +868,19 @@ fn make_elevation(ctx: &EventCtx, color: Color, walking: bool, path: &Path, map:
     let mut pts: Vec<(Distance, Distance)> = Vec::new();
     let mut dist = Distance::ZERO;
     for step in path.get_steps() {
-        if let PathStep::Turn(t) = step {
-            pts.push((dist, map.get_i(t.parent).elevation));
+        match step {
+            PathStep::Lane(l) => {
+                for (d, e) in map.get_l(*l).elevation_profile(map) {
+                    pts.push((dist + d, e));
+                }
+            }
+            PathStep::ContraflowLane(l) => {
+                let len = map.get_l(*l).length();
+                for (d, e) in map.get_l(*l).elevation_profile(map).into_iter().rev() {
+                    pts.push((dist + len - d, e));
+                }
+            }
+            PathStep::Turn(_) => {}
         }
         dist += step.as_traversable().length(map);
     }
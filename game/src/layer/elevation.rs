@@ -1,8 +1,9 @@
 use geom::{ArrowCap, Distance, PolyLine};
 use map_gui::tools::{ColorLegend, ColorNetwork};
+use map_model::BikePreference;
 use widgetry::{
-    Btn, Color, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Line, Panel, Text,
-    TextExt, VerticalAlignment, Widget,
+    Btn, Checkbox, Color, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Line,
+    Outcome, Panel, Text, TextExt, VerticalAlignment, Widget,
 };
 
 use crate::app::App;
@@ -18,8 +19,33 @@ impl Layer for Elevation {
     fn name(&self) -> Option<&'static str> {
         Some("elevation")
     }
-    fn event(&mut self, ctx: &mut EventCtx, _: &mut App, minimap: &Panel) -> Option<LayerOutcome> {
-        Layer::simple_event(ctx, minimap, &mut self.panel)
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &mut App,
+        minimap: &Panel,
+    ) -> Option<LayerOutcome> {
+        self.panel.align_above(ctx, minimap);
+        match self.panel.event(ctx) {
+            Outcome::Clicked(x) => match x.as_ref() {
+                "close" => {
+                    return Some(LayerOutcome::Close);
+                }
+                _ => unreachable!(),
+            },
+            Outcome::Changed => {
+                let preference = if self.panel.is_checked("Cyclists avoid hills") {
+                    BikePreference::AvoidHills
+                } else {
+                    BikePreference::Fastest
+                };
+                ctx.loading_screen("prepare bike routing", |_, timer| {
+                    app.primary.map.set_bike_preference(preference, timer)
+                });
+            }
+            _ => {}
+        }
+        None
     }
     fn draw(&self, g: &mut GfxCtx, app: &App) {
         self.panel.draw(g);
@@ -44,7 +70,7 @@ impl Elevation {
             max = max.max(pct);
 
             let color = app.cs.good_to_bad_red.eval(
-                // TODO Rescale based on a reasonable steepest grade, once the data doesn't suck
+                // TODO Rescale based on a reasonable steepest grade
                 pct.max(0.0).min(1.0),
             );
             colorer.add_r(r.id, color);
@@ -93,12 +119,14 @@ impl Elevation {
                 "Elevation change".draw_text(ctx),
                 Btn::close(ctx),
             ]),
-            Text::from_multiline(vec![
-                Line(format!("Steepest road: {:.0}% grade", max * 100.0)),
-                Line("Note: elevation data is currently wrong!").secondary(),
-            ])
-            .draw(ctx),
+            Text::from(Line(format!("Steepest road: {:.0}% grade", max * 100.0))).draw(ctx),
             ColorLegend::gradient(ctx, &app.cs.good_to_bad_red, vec!["flat", "steep"]),
+            Checkbox::switch(
+                ctx,
+                "Cyclists avoid hills",
+                None,
+                app.primary.map.get_bike_preference() == BikePreference::AvoidHills,
+            ),
         ]))
         .aligned(HorizontalAlignment::Right, VerticalAlignment::Center)
         .build(ctx);
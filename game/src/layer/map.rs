@@ -1,12 +1,12 @@
 use abstutil::{prettyprint_usize, Counter};
-use geom::{Distance, Time};
+use geom::{Circle, Distance, Time};
 use map_gui::tools::{amenity_type, ColorDiscrete, ColorLegend, ColorNetwork};
 use map_gui::ID;
-use map_model::{LaneType, PathConstraints};
+use map_model::{LaneType, LevelOfTrafficStress, PathConstraints};
 use sim::AgentType;
 use widgetry::{
-    Btn, Color, Drawable, EventCtx, GfxCtx, HorizontalAlignment, Line, Panel, Text, TextExt,
-    VerticalAlignment, Widget,
+    Btn, Choice, Color, Drawable, EventCtx, GfxCtx, HorizontalAlignment, Line, Outcome, Panel,
+    Text, TextExt, VerticalAlignment, Widget,
 };
 
 use crate::app::App;
@@ -151,6 +151,124 @@ impl BikeNetwork {
     }
 }
 
+pub struct TrafficStress {
+    panel: Panel,
+    unzoomed: Drawable,
+    zoomed: Drawable,
+}
+
+impl Layer for TrafficStress {
+    fn name(&self) -> Option<&'static str> {
+        Some("traffic stress")
+    }
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &mut App,
+        minimap: &Panel,
+    ) -> Option<LayerOutcome> {
+        self.panel.align_above(ctx, minimap);
+        match self.panel.event(ctx) {
+            Outcome::Clicked(x) => match x.as_ref() {
+                "close" => {
+                    return Some(LayerOutcome::Close);
+                }
+                _ => unreachable!(),
+            },
+            Outcome::Changed => {
+                let max: Option<LevelOfTrafficStress> = self.panel.dropdown_value("max LTS");
+                ctx.loading_screen("prepare bike routing", |_, timer| {
+                    app.primary.map.set_max_bike_lts(max, timer)
+                });
+            }
+            _ => {}
+        }
+        None
+    }
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        self.panel.draw(g);
+        if g.canvas.cam_zoom < app.opts.min_zoom_for_detail {
+            g.redraw(&self.unzoomed);
+        } else {
+            g.redraw(&self.zoomed);
+        }
+    }
+    fn draw_minimap(&self, g: &mut GfxCtx) {
+        g.redraw(&self.unzoomed);
+    }
+}
+
+impl TrafficStress {
+    pub fn new(ctx: &mut EventCtx, app: &App) -> TrafficStress {
+        let map = &app.primary.map;
+        let color = |lts| match lts {
+            LevelOfTrafficStress::Children => Color::hex("#1A9641"),
+            LevelOfTrafficStress::MostAdults => Color::hex("#A6D96A"),
+            LevelOfTrafficStress::Confident => Color::hex("#FDAE61"),
+            LevelOfTrafficStress::Fearless => Color::hex("#D7191C"),
+        };
+
+        let mut colorer = ColorNetwork::new(app);
+        let mut count = Counter::new();
+        for r in map.all_roads() {
+            let lts = r.level_of_traffic_stress(map);
+            colorer.add_r(r.id, color(lts));
+            count.add(lts, 1);
+        }
+        // Intersections take the worst rating of any road approaching them
+        for i in map.all_intersections() {
+            if let Some(lts) = i
+                .incoming_lanes
+                .iter()
+                .map(|l| map.get_l(*l))
+                .filter(|l| PathConstraints::Bike.can_use(l, map))
+                .map(|l| {
+                    l.get_directed_parent(map)
+                        .approach_level_of_traffic_stress(map)
+                })
+                .max()
+            {
+                colorer.add_i(i.id, color(lts));
+            }
+        }
+        let (unzoomed, zoomed) = colorer.build(ctx);
+
+        let mut col = vec![Widget::row(vec![
+            Widget::draw_svg(ctx, "system/assets/tools/layers.svg"),
+            "Level of traffic stress".draw_text(ctx),
+            Btn::close(ctx),
+        ])];
+        for lts in LevelOfTrafficStress::all() {
+            col.push(ColorLegend::row(
+                ctx,
+                color(lts),
+                format!(
+                    "{} ({}): {} roads",
+                    lts,
+                    lts.describe(),
+                    prettyprint_usize(count.get(lts))
+                ),
+            ));
+        }
+        let mut choices = vec![Choice::new("any road", None)];
+        for lts in LevelOfTrafficStress::all() {
+            choices.push(Choice::new(format!("{} or less", lts), Some(lts)));
+        }
+        col.push(Widget::row(vec![
+            "Cyclists stay on:".draw_text(ctx),
+            Widget::dropdown(ctx, "max LTS", map.get_max_bike_lts(), choices),
+        ]));
+
+        TrafficStress {
+            panel: Panel::new(Widget::col(col))
+                .aligned(HorizontalAlignment::Right, VerticalAlignment::Center)
+                .build(ctx),
+            unzoomed,
+            zoomed,
+        }
+    }
+}
+
 pub struct Static {
     panel: Panel,
     pub unzoomed: Drawable,
@@ -439,3 +557,111 @@ impl CongestionCaps {
         }
     }
 }
+
+pub struct BikeShare {
+    panel: Panel,
+    time: Time,
+    unzoomed: Drawable,
+    zoomed: Drawable,
+}
+
+impl Layer for BikeShare {
+    fn name(&self) -> Option<&'static str> {
+        Some("bike share")
+    }
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &mut App,
+        minimap: &Panel,
+    ) -> Option<LayerOutcome> {
+        if app.primary.sim.time() != self.time {
+            *self = BikeShare::new(ctx, app);
+        }
+
+        Layer::simple_event(ctx, minimap, &mut self.panel)
+    }
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        self.panel.draw(g);
+        if g.canvas.cam_zoom < app.opts.min_zoom_for_detail {
+            g.redraw(&self.unzoomed);
+        } else {
+            g.redraw(&self.zoomed);
+        }
+    }
+    fn draw_minimap(&self, g: &mut GfxCtx) {
+        g.redraw(&self.unzoomed);
+    }
+}
+
+impl BikeShare {
+    pub fn new(ctx: &mut EventCtx, app: &App) -> BikeShare {
+        let map = &app.primary.map;
+        let mut colorer = ColorNetwork::new(app);
+
+        // Color each station by how full it is
+        let docked = app.primary.sim.bike_share_docked();
+        let mut total_docked = 0;
+        let mut total_capacity = 0;
+        for s in map.all_bike_share_stations() {
+            let count = docked.get(&s.id).cloned().unwrap_or(0);
+            total_docked += count;
+            total_capacity += s.capacity;
+            let color = app
+                .cs
+                .good_to_bad_red
+                .eval(((count as f64) / (s.capacity as f64)).min(1.0));
+            colorer.unzoomed.push(
+                color,
+                Circle::new(s.point, Distance::meters(15.0)).to_polygon(),
+            );
+            colorer.zoomed.push(
+                color.alpha(0.5),
+                Circle::new(s.point, Distance::meters(5.0)).to_polygon(),
+            );
+        }
+
+        let mut total_dockless = 0;
+        for (b, count) in app.primary.sim.bike_share_dockless() {
+            total_dockless += count;
+            colorer.add_b(b, app.cs.unzoomed_bike);
+        }
+
+        let panel = Panel::new(Widget::col(vec![
+            Widget::row(vec![
+                Widget::draw_svg(ctx, "system/assets/tools/layers.svg"),
+                "Bike share".draw_text(ctx),
+                Btn::close(ctx),
+            ]),
+            Text::from_multiline(vec![
+                Line(format!(
+                    "{} stations",
+                    prettyprint_usize(map.all_bike_share_stations().len())
+                )),
+                Line(format!(
+                    "{} / {} docks in use",
+                    prettyprint_usize(total_docked),
+                    prettyprint_usize(total_capacity)
+                )),
+                Line(format!(
+                    "{} dockless vehicles parked",
+                    prettyprint_usize(total_dockless)
+                )),
+            ])
+            .draw(ctx),
+            ColorLegend::gradient(ctx, &app.cs.good_to_bad_red, vec!["empty", "full"]),
+            ColorLegend::row(ctx, app.cs.unzoomed_bike, "dockless vehicles"),
+        ]))
+        .aligned(HorizontalAlignment::Right, VerticalAlignment::Center)
+        .build(ctx);
+
+        let (unzoomed, zoomed) = colorer.build(ctx);
+
+        BikeShare {
+            panel,
+            time: app.primary.sim.time(),
+            unzoomed,
+            zoomed,
+        }
+    }
+}
@@ -113,9 +113,15 @@ impl PickLayer {
                     btn("map edits", Key::E),
                     btn("parking occupancy", Key::P),
                     btn("bike network", Key::B),
+                    if app.primary.map.all_bike_share_stations().is_empty() {
+                        Widget::nothing()
+                    } else {
+                        btn("bike share", Key::H)
+                    },
                     btn("transit network", Key::U),
                     btn("population map", Key::X),
                     btn("no sidewalks", Key::S),
+                    btn("traffic stress", Key::K),
                 ]),
             ])
             .evenly_spaced(),
@@ -171,6 +177,9 @@ impl State<App> for PickLayer {
                 "bike network" => {
                     app.primary.layer = Some(Box::new(map::BikeNetwork::new(ctx, app)));
                 }
+                "bike share" => {
+                    app.primary.layer = Some(Box::new(map::BikeShare::new(ctx, app)));
+                }
                 "delay" => {
                     app.primary.layer = Some(Box::new(traffic::Delay::new(ctx, app)));
                 }
@@ -219,6 +228,9 @@ impl State<App> for PickLayer {
                 "throughput" => {
                     app.primary.layer = Some(Box::new(traffic::Throughput::new(ctx, app)));
                 }
+                "traffic stress" => {
+                    app.primary.layer = Some(Box::new(map::TrafficStress::new(ctx, app)));
+                }
                 "traffic jams" => {
                     app.primary.layer = Some(Box::new(traffic::TrafficJams::new(ctx, app)));
                 }
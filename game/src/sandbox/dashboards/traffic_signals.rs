@@ -28,6 +28,9 @@ impl TrafficSignalDemand {
             Demand::all_demand(app, timer)
         });
 
+        // Summarize this before swapping out the live simulation
+        let transit_priority = transit_priority_summary(ctx, app);
+
         app.primary.current_selection = None;
         assert!(app.primary.suspended_sim.is_none());
         app.primary.suspended_sim = Some(app.primary.clear_sim());
@@ -58,6 +61,7 @@ impl TrafficSignalDemand {
                     "Hour:".draw_text(ctx),
                     Spinner::new(ctx, (0, 24), 7).named("hour"),
                 ]),
+                transit_priority,
             ]))
             .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
             .build(ctx),
@@ -147,6 +151,82 @@ impl State<App> for TrafficSignalDemand {
     }
 }
 
+/// How much transit signal priority has helped buses and trains so far, compared to the baseline
+/// without edits.
+fn transit_priority_summary(ctx: &mut EventCtx, app: &App) -> Widget {
+    let map = &app.primary.map;
+    let now = app.primary.sim.time();
+    let signals: Vec<IntersectionID> = map
+        .all_intersections()
+        .iter()
+        .filter(|i| {
+            i.is_traffic_signal() && map.get_traffic_signal(i.id).transit_priority.is_some()
+        })
+        .map(|i| i.id)
+        .collect();
+    if signals.is_empty() {
+        return Widget::nothing();
+    }
+
+    let analytics = app.primary.sim.get_analytics();
+    let mut activations = 0;
+    let mut after = (Duration::ZERO, 0);
+    let mut before = (Duration::ZERO, 0);
+    for i in &signals {
+        activations += analytics
+            .transit_signal_priority
+            .get(i)
+            .map(|list| list.len())
+            .unwrap_or(0);
+        let (dt, cnt) = analytics.transit_delay(*i, now);
+        after.0 += dt;
+        after.1 += cnt;
+        if app.has_prebaked().is_some() {
+            let (dt, cnt) = app.prebaked().transit_delay(*i, now);
+            before.0 += dt;
+            before.1 += cnt;
+        }
+    }
+
+    let mut txt = Text::from(Line("Transit signal priority").small_heading());
+    txt.add(Line(format!(
+        "{} signals have priority, used {} times so far",
+        prettyprint_usize(signals.len()),
+        prettyprint_usize(activations)
+    )));
+    if after.1 > 0 {
+        let avg_after = after.0 / (after.1 as f64);
+        txt.add(Line(format!(
+            "Buses and trains wait {} per signal on average",
+            avg_after.to_string(&app.opts.units)
+        )));
+        if before.1 > 0 {
+            let avg_before = before.0 / (before.1 as f64);
+            txt.add(
+                Line(format!(
+                    "Before any changes, they waited {} ({})",
+                    avg_before.to_string(&app.opts.units),
+                    if avg_before >= avg_after {
+                        format!(
+                            "{} saved",
+                            (avg_before - avg_after).to_string(&app.opts.units)
+                        )
+                    } else {
+                        format!(
+                            "{} worse",
+                            (avg_after - avg_before).to_string(&app.opts.units)
+                        )
+                    }
+                ))
+                .secondary(),
+            );
+        }
+    } else {
+        txt.add(Line("No buses or trains have passed through yet").secondary());
+    }
+    txt.draw(ctx)
+}
+
 struct Demand {
     // Unsorted
     raw: Vec<(Time, MovementID)>,
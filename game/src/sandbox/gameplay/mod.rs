@@ -139,7 +139,7 @@ impl GameplayMode {
     }
 
     pub fn allows(&self, edits: &MapEdits) -> bool {
-        for cmd in &edits.commands {
+        for cmd in edits.commands.iter().flat_map(|cmd| cmd.flatten()) {
             match cmd {
                 EditCmd::ChangeRoad { .. } => {
                     if !self.can_edit_lanes() {
@@ -155,7 +155,8 @@ impl GameplayMode {
                     }
                     _ => {}
                 },
-                EditCmd::ChangeRouteSchedule { .. } => {}
+                EditCmd::ChangeRouteSchedule { .. } | EditCmd::ChangeRouteVehicles { .. } => {}
+                EditCmd::Batch(_) => unreachable!(),
             }
         }
         true
@@ -354,11 +354,16 @@ impl AgentMeter {
                 "system/assets/meters/bike.svg",
                 Text::from_multiline(vec![
                     Line("Cyclists"),
-                    Line(prettyprint_usize(counts.cyclists)).secondary(),
+                    Line(format!("Bikes: {}", prettyprint_usize(counts.cyclists))).secondary(),
+                    Line(format!(
+                        "E-scooters: {}",
+                        prettyprint_usize(counts.scooter_riders)
+                    ))
+                    .secondary(),
                 ]),
             )
             .margin_right(5),
-            prettyprint_usize(counts.cyclists).draw_text(ctx),
+            prettyprint_usize(counts.cyclists + counts.scooter_riders).draw_text(ctx),
         ]));
 
         row.push(Widget::custom_row(vec![
@@ -372,10 +377,32 @@ impl AgentMeter {
                         prettyprint_usize(counts.sov_drivers)
                     ))
                     .secondary(),
+                    Line(format!(
+                        "Trucks: {}",
+                        prettyprint_usize(counts.truck_drivers)
+                    ))
+                    .secondary(),
+                    Line(format!(
+                        "Motorcycles: {}",
+                        prettyprint_usize(counts.motorcyclists)
+                    ))
+                    .secondary(),
+                    Line(format!(
+                        "{} passengers in {} ride-hailing vehicles",
+                        prettyprint_usize(counts.ride_hail_passengers),
+                        prettyprint_usize(counts.ride_hail_vehicles)
+                    ))
+                    .secondary(),
                 ]),
             )
             .margin_right(5),
-            prettyprint_usize(counts.sov_drivers).draw_text(ctx),
+            prettyprint_usize(
+                counts.sov_drivers
+                    + counts.truck_drivers
+                    + counts.motorcyclists
+                    + counts.ride_hail_passengers,
+            )
+            .draw_text(ctx),
         ]));
 
         row.push(Widget::custom_row(vec![
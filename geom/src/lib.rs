@@ -1,3 +1,4 @@
+pub use crate::acceleration::Acceleration;
 pub use crate::angle::Angle;
 pub use crate::bounds::{Bounds, GPSBounds};
 pub use crate::circle::Circle;
@@ -15,6 +16,7 @@ pub use crate::speed::Speed;
 pub use crate::stats::{HgramValue, Histogram, Statistic};
 pub use crate::time::Time;
 
+mod acceleration;
 mod angle;
 mod bounds;
 mod circle;
@@ -0,0 +1,235 @@
+//! Streams simulation events to long-lived HTTP clients as JSON lines. The sim buffers every Event
+//! it publishes to Analytics; after every command (and periodically while advancing), the server
+//! drains that buffer and broadcasts to all subscribers, who each apply their own filter.
+
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+
+use hyper::{Body, Response};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use geom::Time;
+use map_model::{IntersectionID, Traversable};
+use sim::{AgentID, AlertLocation, Event, PersonID, Sim};
+
+/// If a subscriber falls this far behind, it'll start missing events.
+const CHANNEL_CAPACITY: usize = 100_000;
+
+lazy_static::lazy_static! {
+    /// (Session name, time, event)
+    static ref EVENTS: broadcast::Sender<(String, Time, Event)> = broadcast::channel(CHANNEL_CAPACITY).0;
+}
+
+/// Send everything the sim has captured since the last call to all subscribers.
+pub fn publish(session: &str, sim: &mut Sim) {
+    for (time, ev) in sim.drain_captured_events() {
+        // This only fails when there are no subscribers, which is fine.
+        let _ = EVENTS.send((session.to_string(), time, ev));
+    }
+}
+
+/// Start a chunked response that emits one JSON object per line, for every event matching the
+/// filter. The response lasts until the client disconnects.
+pub fn stream(params: &HashMap<String, String>) -> Result<Response<Body>, Box<dyn Error>> {
+    let filter = EventFilter::parse(params)?;
+    let mut rx = EVENTS.subscribe();
+    let (mut sender, body) = Body::channel();
+
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok((session, time, ev)) => {
+                    let event_type = event_type(&ev);
+                    if !filter.matches(&session, event_type, &ev) {
+                        continue;
+                    }
+                    let mut line = abstutil::to_json_terse(&StreamedEvent {
+                        session,
+                        time,
+                        event_type,
+                        event: ev,
+                    });
+                    line.push('\n');
+                    if sender.send_data(line.into()).await.is_err() {
+                        // The client disconnected
+                        break;
+                    }
+                }
+                Err(broadcast::RecvError::Lagged(n)) => {
+                    warn!(
+                        "An event stream subscriber fell behind and missed {} events",
+                        n
+                    );
+                }
+                Err(broadcast::RecvError::Closed) => {
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(Response::builder()
+        .header("Content-Type", "application/x-ndjson")
+        .body(body)?)
+}
+
+#[derive(Serialize)]
+struct StreamedEvent {
+    session: String,
+    time: Time,
+    event_type: &'static str,
+    event: Event,
+}
+
+/// All specified conditions must match.
+struct EventFilter {
+    /// If None, events from every session are included
+    session: Option<String>,
+    /// Names of Event variants, like "TripFinished" or "Alert"
+    types: Option<BTreeSet<String>>,
+    intersection: Option<IntersectionID>,
+    car: Option<usize>,
+    ped: Option<usize>,
+    person: Option<PersonID>,
+}
+
+impl EventFilter {
+    fn parse(params: &HashMap<String, String>) -> Result<EventFilter, Box<dyn Error>> {
+        Ok(EventFilter {
+            session: params.get("session").cloned(),
+            types: params
+                .get("types")
+                .map(|x| x.split(',').map(|t| t.to_string()).collect()),
+            intersection: match params.get("intersection") {
+                Some(x) => Some(IntersectionID(x.parse::<usize>()?)),
+                None => None,
+            },
+            car: match params.get("car") {
+                Some(x) => Some(x.parse::<usize>()?),
+                None => None,
+            },
+            ped: match params.get("ped") {
+                Some(x) => Some(x.parse::<usize>()?),
+                None => None,
+            },
+            person: match params.get("person") {
+                Some(x) => Some(PersonID(x.parse::<usize>()?)),
+                None => None,
+            },
+        })
+    }
+
+    fn matches(&self, session: &str, event_type: &str, ev: &Event) -> bool {
+        if let Some(ref name) = self.session {
+            if name != session {
+                return false;
+            }
+        }
+        if let Some(ref types) = self.types {
+            if !types.contains(event_type) {
+                return false;
+            }
+        }
+        if let Some(i) = self.intersection {
+            if intersection_of(ev) != Some(i) {
+                return false;
+            }
+        }
+        if self.car.is_some() || self.ped.is_some() || self.person.is_some() {
+            let (agents, people) = agents_of(ev);
+            let agent_match = agents.into_iter().any(|a| match a {
+                AgentID::Car(c) => Some(c.0) == self.car,
+                AgentID::Pedestrian(p) => Some(p.0) == self.ped,
+                AgentID::BusPassenger(p, c) => Some(c.0) == self.car || Some(p) == self.person,
+            });
+            let person_match = people.into_iter().any(|p| Some(p) == self.person);
+            if !agent_match && !person_match {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// The name of the Event's variant, exactly as it's serialized.
+fn event_type(ev: &Event) -> &'static str {
+    match ev {
+        Event::CarReachedParkingSpot(..) => "CarReachedParkingSpot",
+        Event::CarLeftParkingSpot(..) => "CarLeftParkingSpot",
+        Event::BusArrivedAtStop(..) => "BusArrivedAtStop",
+        Event::BusDepartedFromStop(..) => "BusDepartedFromStop",
+        Event::PassengerBoardsTransit(..) => "PassengerBoardsTransit",
+        Event::PassengerAlightsTransit(..) => "PassengerAlightsTransit",
+        Event::PassengerDeniedBoarding(..) => "PassengerDeniedBoarding",
+        Event::RideHailPickup(..) => "RideHailPickup",
+        Event::RideHailLegFinished(..) => "RideHailLegFinished",
+        Event::PersonEntersBuilding(..) => "PersonEntersBuilding",
+        Event::PersonLeavesBuilding(..) => "PersonLeavesBuilding",
+        Event::PersonLeavesMap(..) => "PersonLeavesMap",
+        Event::PersonEntersMap(..) => "PersonEntersMap",
+        Event::PedReachedParkingSpot(..) => "PedReachedParkingSpot",
+        Event::BikeStoppedAtSidewalk(..) => "BikeStoppedAtSidewalk",
+        Event::BikeShareStationEmpty(..) => "BikeShareStationEmpty",
+        Event::BikeShareStationFull(..) => "BikeShareStationFull",
+        Event::AgentEntersTraversable(..) => "AgentEntersTraversable",
+        Event::IntersectionDelayMeasured(..) => "IntersectionDelayMeasured",
+        Event::TransitSignalPriority(..) => "TransitSignalPriority",
+        Event::TripFinished { .. } => "TripFinished",
+        Event::TripCancelled(..) => "TripCancelled",
+        Event::TripPhaseStarting(..) => "TripPhaseStarting",
+        Event::TripIntersectionDelay(..) => "TripIntersectionDelay",
+        Event::LaneSpeedPercentage(..) => "LaneSpeedPercentage",
+        Event::PathAmended(..) => "PathAmended",
+        Event::VehicleRerouted(..) => "VehicleRerouted",
+        Event::Alert(..) => "Alert",
+    }
+}
+
+fn intersection_of(ev: &Event) -> Option<IntersectionID> {
+    match ev {
+        Event::PersonLeavesMap(_, _, i) | Event::PersonEntersMap(_, _, i) => Some(*i),
+        Event::AgentEntersTraversable(_, Traversable::Turn(t), _) => Some(t.parent),
+        Event::IntersectionDelayMeasured(m, _, _) => Some(m.i),
+        Event::TripIntersectionDelay(_, t, _, _) => Some(t.parent),
+        Event::TransitSignalPriority(i, _) => Some(*i),
+        Event::Alert(AlertLocation::Intersection(i), _) => Some(*i),
+        _ => None,
+    }
+}
+
+/// Returns the agents and people directly involved in an event.
+fn agents_of(ev: &Event) -> (Vec<AgentID>, Vec<PersonID>) {
+    match ev {
+        Event::CarReachedParkingSpot(c, _)
+        | Event::CarLeftParkingSpot(c, _)
+        | Event::BusArrivedAtStop(c, _, _, _)
+        | Event::BusDepartedFromStop(c, _, _, _)
+        | Event::BikeStoppedAtSidewalk(c, _)
+        | Event::TransitSignalPriority(_, c)
+        | Event::VehicleRerouted(c, _, _) => (vec![AgentID::Car(*c)], Vec::new()),
+        Event::PassengerBoardsTransit(p, c, _, _, _)
+        | Event::PassengerAlightsTransit(p, c, _, _) => {
+            (vec![AgentID::BusPassenger(*p, *c)], vec![*p])
+        }
+        Event::PassengerDeniedBoarding(p, c, _, _) | Event::RideHailPickup(p, c, _) => {
+            (vec![AgentID::Car(*c)], vec![*p])
+        }
+        Event::RideHailLegFinished(c, _, p) => {
+            (vec![AgentID::Car(*c)], p.iter().cloned().collect())
+        }
+        Event::PersonEntersBuilding(p, _)
+        | Event::PersonLeavesBuilding(p, _)
+        | Event::BikeShareStationEmpty(p, _)
+        | Event::BikeShareStationFull(p, _) => (Vec::new(), vec![*p]),
+        Event::PersonLeavesMap(p, a, _) => (a.iter().cloned().collect(), vec![*p]),
+        Event::PersonEntersMap(p, a, _) => (vec![*a], vec![*p]),
+        Event::PedReachedParkingSpot(ped, _) => (vec![AgentID::Pedestrian(*ped)], Vec::new()),
+        Event::AgentEntersTraversable(a, _, _)
+        | Event::IntersectionDelayMeasured(_, _, a)
+        | Event::TripIntersectionDelay(_, _, a, _) => (vec![*a], Vec::new()),
+        Event::TripPhaseStarting(_, p, _, _) => (Vec::new(), vec![*p]),
+        Event::Alert(AlertLocation::Person(p), _) => (Vec::new(), vec![*p]),
+        _ => (Vec::new(), Vec::new()),
+    }
+}
@@ -8,14 +8,18 @@
 // it's now 01:01:00.0
 // > curl http://localhost:1234/data/get-road-thruput
 // ... huge JSON blob
+// > curl -N http://localhost:1234/events/stream?types=TripFinished,Alert
+// ... one JSON object per line, as the sim advances
 
 #[macro_use]
 extern crate log;
 
+mod events;
+
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::convert::TryFrom;
 use std::error::Error;
-use std::sync::RwLock;
+use std::sync::{Arc, Mutex, RwLock};
 
 use hyper::{Body, Request, Response, Server, StatusCode};
 use rand::SeedableRng;
@@ -26,25 +30,48 @@ use abstutil::{serialize_btreemap, CmdArgs, MapName, Timer};
 use geom::{Distance, Duration, LonLat, Time};
 use map_model::{
     CompressedMovementID, ControlTrafficSignal, EditCmd, EditIntersection, IntersectionID, Map,
-    MovementID, PermanentMapEdits, RoadID, TurnID,
+    MapEdits, MovementID, PermanentEditCmd, PermanentMapEdits, RoadID, TurnID,
 };
 use sim::{
     AgentID, AgentType, ExternalPerson, PersonID, Scenario, ScenarioModifier, Sim, SimFlags,
     SimOptions, TripID, TripMode, VehicleType,
 };
 
+/// While advancing the sim, publish captured events to streaming clients this often.
+const EVENT_PUBLISH_FREQUENCY: Duration = Duration::const_seconds(60.0);
+
+/// Commands that don't specify a session apply to this one, which always exists at startup.
+const DEFAULT_SESSION: &str = "default";
+
 lazy_static::lazy_static! {
-    static ref MAP: RwLock<Map> = RwLock::new(Map::blank());
-    static ref SIM: RwLock<Sim> = RwLock::new(Sim::new(&Map::blank(), SimOptions::new("tmp"), &mut Timer::throwaway()));
-    static ref LOAD: RwLock<LoadSim> = RwLock::new({
-        LoadSim {
-            scenario: abstutil::path_scenario(&MapName::seattle("montlake"), "weekday"),
-            modifiers: Vec::new(),
-            edits: None,
-            rng_seed: SimFlags::RNG_SEED,
-            opts: SimOptions::default(),
-        }
-    });
+    /// Each session can be locked independently, so different sessions can simulate in parallel.
+    static ref SESSIONS: RwLock<BTreeMap<String, Arc<Mutex<Session>>>> = RwLock::new(BTreeMap::new());
+    /// These're fixed from the initial command line flags, and apply to every session.
+    static ref FLAGS: RwLock<(u64, SimOptions)> = RwLock::new((SimFlags::RNG_SEED, SimOptions::default()));
+    /// Every map loaded so far, without any edits. Sessions on the same map share these.
+    static ref MAPS: Mutex<BTreeMap<MapName, Arc<Map>>> = Mutex::new(BTreeMap::new());
+}
+
+/// One independent simulation.
+struct Session {
+    name: String,
+    /// Sessions on the same map share it, until one of them applies edits and gets its own copy.
+    map: Arc<Map>,
+    sim: Sim,
+    load: LoadSim,
+}
+
+impl Session {
+    fn new(name: String, load: LoadSim, timer: &mut Timer) -> Result<Session, Box<dyn Error>> {
+        let (map, mut sim) = load.setup(timer)?;
+        sim.capture_events(true);
+        Ok(Session {
+            name,
+            map,
+            sim,
+            load,
+        })
+    }
 }
 
 #[tokio::main]
@@ -58,15 +85,21 @@ async fn main() {
     let port = args.required("--port").parse::<u16>().unwrap();
     args.done();
 
-    {
-        let mut load = LOAD.write().unwrap();
-        load.rng_seed = rng_seed;
-        load.opts = opts;
-
-        let (map, sim) = load.setup(&mut timer);
-        *MAP.write().unwrap() = map;
-        *SIM.write().unwrap() = sim;
-    }
+    *FLAGS.write().unwrap() = (rng_seed, opts);
+    let session = Session::new(
+        DEFAULT_SESSION.to_string(),
+        LoadSim::new(
+            abstutil::path_scenario(&MapName::seattle("montlake"), "weekday"),
+            Vec::new(),
+            None,
+        ),
+        &mut timer,
+    )
+    .unwrap();
+    SESSIONS
+        .write()
+        .unwrap()
+        .insert(DEFAULT_SESSION.to_string(), Arc::new(Mutex::new(session)));
 
     let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
     info!("Listening on http://{}", addr);
@@ -89,54 +122,145 @@ async fn serve_req(req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
             .collect();
     let body = hyper::body::to_bytes(req).await?.to_vec();
     info!("Handling {}", path);
-    Ok(
-        match handle_command(
-            &path,
-            &params,
-            &body,
-            &mut SIM.write().unwrap(),
-            &mut MAP.write().unwrap(),
-            &mut LOAD.write().unwrap(),
-        ) {
-            Ok(resp) => Response::new(Body::from(resp)),
-            Err(err) => {
-                error!("{}: {}", path, err);
-                Response::builder()
-                    .status(StatusCode::BAD_REQUEST)
-                    .body(Body::from(format!("Bad command {}: {}", path, err)))
-                    .unwrap()
+
+    // Streaming doesn't touch the sim directly, so don't grab any locks.
+    if path == "/events/stream" {
+        return Ok(match events::stream(&params) {
+            Ok(resp) => resp,
+            Err(err) => bad_command(&path, err.to_string()),
+        });
+    }
+
+    // Simulating can take a while, so don't tie up the async workers. This way, requests to
+    // different sessions run in parallel.
+    let result = tokio::task::spawn_blocking({
+        let path = path.clone();
+        // Box<dyn Error> can't be sent between threads
+        move || dispatch(&path, &params, &body).map_err(|err| err.to_string())
+    })
+    .await
+    .unwrap_or_else(|err| Err(err.to_string()));
+
+    Ok(match result {
+        Ok(resp) => Response::new(Body::from(resp)),
+        Err(err) => bad_command(&path, err),
+    })
+}
+
+fn dispatch(
+    path: &str,
+    params: &HashMap<String, String>,
+    body: &Vec<u8>,
+) -> Result<String, Box<dyn Error>> {
+    if path.starts_with("/sessions/") {
+        return handle_session_command(path, params, body);
+    }
+
+    let name = params
+        .get("session")
+        .cloned()
+        .unwrap_or_else(|| DEFAULT_SESSION.to_string());
+    // Only hold the lock on all sessions long enough to find this one
+    let session = SESSIONS.read().unwrap().get(&name).cloned();
+    if let Some(session) = session {
+        let mut session = session.lock().unwrap();
+        let result = handle_command(path, params, body, &mut session);
+        // Resetting, loading, spawning people, and editing the map can all produce events, so
+        // publish after every command, not just the ones that advance time.
+        let session = &mut *session;
+        events::publish(&session.name, &mut session.sim);
+        result
+    } else {
+        Err(format!("no session named {}", name).into())
+    }
+}
+
+fn bad_command(path: &str, err: String) -> Response<Body> {
+    error!("{}: {}", path, err);
+    Response::builder()
+        .status(StatusCode::BAD_REQUEST)
+        .body(Body::from(format!("Bad command {}: {}", path, err)))
+        .unwrap()
+}
+
+fn handle_session_command(
+    path: &str,
+    params: &HashMap<String, String>,
+    body: &Vec<u8>,
+) -> Result<String, Box<dyn Error>> {
+    match path {
+        "/sessions/create" => {
+            let name = params.get("name").ok_or("missing ?name")?.clone();
+            if SESSIONS.read().unwrap().contains_key(&name) {
+                return Err(format!("session {} already exists", name).into());
             }
-        },
-    )
+            let args: LoadSim = abstutil::from_json(body)?;
+            let load = LoadSim::new(args.scenario, args.modifiers, args.edits);
+            // Don't hold onto the lock while loading; other sessions can keep running.
+            let session = Session::new(name.clone(), load, &mut Timer::new("create session"))?;
+
+            let mut sessions = SESSIONS.write().unwrap();
+            if sessions.contains_key(&name) {
+                return Err(format!("session {} already exists", name).into());
+            }
+            sessions.insert(name.clone(), Arc::new(Mutex::new(session)));
+            Ok(format!("session {} created", name))
+        }
+        "/sessions/list" => {
+            let mut list = Vec::new();
+            for (name, session) in SESSIONS.read().unwrap().iter() {
+                // If this session is busy simulating, don't wait for it
+                let (map, time) = match session.try_lock() {
+                    Ok(session) => (
+                        Some(session.map.get_name().clone()),
+                        Some(session.sim.time()),
+                    ),
+                    Err(_) => (None, None),
+                };
+                list.push(SessionSummary {
+                    name: name.clone(),
+                    map,
+                    time,
+                });
+            }
+            Ok(abstutil::to_json(&list))
+        }
+        "/sessions/delete" => {
+            let name = params.get("name").ok_or("missing ?name")?;
+            if SESSIONS.write().unwrap().remove(name).is_some() {
+                Ok(format!("session {} deleted", name))
+            } else {
+                Err(format!("no session named {}", name).into())
+            }
+        }
+        _ => Err("Unknown command".into()),
+    }
 }
 
 fn handle_command(
     path: &str,
     params: &HashMap<String, String>,
     body: &Vec<u8>,
-    sim: &mut Sim,
-    map: &mut Map,
-    load: &mut LoadSim,
+    session: &mut Session,
 ) -> Result<String, Box<dyn Error>> {
+    let sim = &mut session.sim;
+    let map = &session.map;
     match path {
         // Controlling the simulation
         "/sim/reset" => {
-            let (new_map, new_sim) = load.setup(&mut Timer::new("reset sim"));
-            *map = new_map;
-            *sim = new_sim;
+            *session = Session::new(
+                session.name.clone(),
+                session.load.clone(),
+                &mut Timer::new("reset sim"),
+            )?;
             Ok(format!("sim reloaded"))
         }
         "/sim/load" => {
             let args: LoadSim = abstutil::from_json(body)?;
-
-            load.scenario = args.scenario;
-            load.modifiers = args.modifiers;
-            load.edits = args.edits;
+            let load = LoadSim::new(args.scenario, args.modifiers, args.edits);
 
             // Also reset
-            let (new_map, new_sim) = load.setup(&mut Timer::new("reset sim"));
-            *map = new_map;
-            *sim = new_sim;
+            *session = Session::new(session.name.clone(), load, &mut Timer::new("reset sim"))?;
 
             Ok(format!("flags changed and sim reloaded"))
         }
@@ -146,8 +270,13 @@ fn handle_command(
             if t <= sim.time() {
                 Err(format!("{} is in the past. call /sim/reset first?", t).into())
             } else {
-                let dt = t - sim.time();
-                sim.timed_step(map, dt, &mut None, &mut Timer::new("goto-time"));
+                // Advance in small increments, so anybody streaming events sees them promptly.
+                let mut timer = Timer::new("goto-time");
+                while sim.time() < t {
+                    let dt = std::cmp::min(t - sim.time(), EVENT_PUBLISH_FREQUENCY);
+                    sim.timed_step(map, dt, &mut None, &mut timer);
+                    events::publish(&session.name, sim);
+                }
                 Ok(format!("it's now {}", t))
             }
         }
@@ -166,13 +295,70 @@ fn handle_command(
 
             let mut scenario = Scenario::empty(map, "one-shot");
             scenario.people = ExternalPerson::import(map, vec![input])?;
-            let mut rng = XorShiftRng::seed_from_u64(load.rng_seed);
+            let mut rng = XorShiftRng::seed_from_u64(session.load.rng_seed);
             scenario.instantiate(sim, map, &mut rng, &mut Timer::throwaway());
             Ok(format!(
                 "{} created",
                 sim.get_all_people().last().unwrap().id
             ))
         }
+        "/sim/save" => {
+            let name = savestate_name(params)?;
+            sim.set_name(name.clone());
+            let path = sim.save();
+            abstutil::write_json(
+                savestate_edits_path(&path),
+                &map.get_edits().to_permanent(map),
+            );
+            // Remember where this went, in case the session's edits change before loading it
+            let index = savestate_index_path(map.get_name(), &name);
+            let mut dirs: BTreeSet<String> =
+                abstutil::maybe_read_json(index.clone(), &mut Timer::throwaway())
+                    .unwrap_or_else(|_| BTreeSet::new());
+            dirs.insert(abstutil::parent_path(&path));
+            abstutil::write_json(index, &dirs);
+            Ok(format!("saved {} at {}", name, sim.time()))
+        }
+        "/sim/list-savestates" => {
+            let name = savestate_name(params)?;
+            let times: BTreeSet<String> = all_savestates(map.get_name(), &name)
+                .into_iter()
+                .map(abstutil::basename)
+                .collect();
+            Ok(abstutil::to_json(&times))
+        }
+        "/sim/load-savestate" => {
+            let name = savestate_name(params)?;
+            let t = match params.get("t") {
+                Some(t) => Time::parse(t)?,
+                None => sim.time(),
+            };
+            // Don't rename this session's run; the restored sim already has the savestate's name.
+            // Savestates from before the session's edits changed count too.
+            let before = t.as_filename();
+            let path = all_savestates(map.get_name(), &name)
+                .into_iter()
+                .filter(|path| abstutil::basename(path) < before)
+                .max_by_key(|path| abstutil::basename(path))
+                .ok_or_else(|| format!("no savestate {} before {}", name, t))?;
+
+            let mut timer = Timer::new("load savestate");
+            let perma: PermanentMapEdits =
+                abstutil::maybe_read_json(savestate_edits_path(&path), &mut timer)?;
+            // Later resets go back to this savestate, instead of the start of the old scenario.
+            let load = LoadSim {
+                edits: Some(perma),
+                savestate: Some(path),
+                ..session.load.clone()
+            };
+            // If anything fails, the session is left untouched.
+            *session = Session::new(session.name.clone(), load, &mut timer)?;
+            Ok(format!(
+                "restored {}; it's now {}",
+                name,
+                session.sim.time()
+            ))
+        }
         // Traffic signals
         "/traffic-signals/get" => {
             let i = IntersectionID(params["id"].parse::<usize>()?);
@@ -185,6 +371,7 @@ fn handle_command(
         "/traffic-signals/set" => {
             let ts: ControlTrafficSignal = abstutil::from_json(body)?;
             let id = ts.id;
+            let map = Arc::make_mut(&mut session.map);
 
             // incremental_edit_traffic_signal is the cheap option, but since we may need to call
             // get-edits later, go through the proper flow.
@@ -192,7 +379,7 @@ fn handle_command(
             edits.commands.push(EditCmd::ChangeIntersection {
                 i: id,
                 old: map.get_i_edit(id),
-                new: EditIntersection::TrafficSignal(ts.export(map)),
+                new: EditIntersection::TrafficSignal(ts.to_raw(map)),
             });
             map.must_apply_edits(edits, &mut Timer::throwaway());
             map.recalculate_pathfinding_after_edits(&mut Timer::throwaway());
@@ -325,6 +512,19 @@ fn handle_command(
             edits.compress(map);
             Ok(abstutil::to_json(&edits.to_permanent(map)))
         }
+        "/map/apply-edits" => {
+            let perma: PermanentMapEdits = abstutil::from_json(body)?;
+            let edits = perma.to_edits(map)?;
+            Ok(abstutil::to_json(&apply_live_edits(session, edits)))
+        }
+        "/map/apply-edit-commands" => {
+            let cmds: Vec<PermanentEditCmd> = abstutil::from_json(body)?;
+            let mut edits = map.get_edits().clone();
+            for cmd in cmds {
+                edits.commands.push(cmd.to_cmd(map)?);
+            }
+            Ok(abstutil::to_json(&apply_live_edits(session, edits)))
+        }
         "/map/get-edit-road-command" => {
             let r = RoadID(params["id"].parse::<usize>()?);
             Ok(abstutil::to_json(
@@ -340,6 +540,24 @@ fn handle_command(
     }
 }
 
+/// Apply edits to the map without resetting the simulation. Any trips affected are cancelled.
+fn apply_live_edits(session: &mut Session, edits: MapEdits) -> LiveEditResults {
+    let mut timer = Timer::new("apply live edits");
+    let map = Arc::make_mut(&mut session.map);
+    map.must_apply_edits(edits, &mut timer);
+    map.recalculate_pathfinding_after_edits(&mut timer);
+
+    session.sim.handle_live_edited_traffic_signals(&session.map);
+    let (vehicles_displaced, pedestrians_displaced, parked_cars_displaced) =
+        session.sim.handle_live_edits(&session.map);
+    LiveEditResults {
+        trips_cancelled: vehicles_displaced + pedestrians_displaced,
+        vehicles_displaced,
+        pedestrians_displaced,
+        parked_cars_displaced,
+    }
+}
+
 // TODO I think specifying the API with protobufs or similar will be a better idea.
 
 #[derive(Serialize)]
@@ -393,6 +611,21 @@ struct RoadThroughput {
     counts: Vec<(RoadID, AgentType, usize, usize)>,
 }
 
+#[derive(Serialize)]
+struct LiveEditResults {
+    /// Every displaced vehicle or pedestrian was in the middle of a trip, which is cancelled.
+    trips_cancelled: usize,
+    /// Moving cars, bikes, and other vehicles removed from the map, because their path crossed
+    /// something edited or their destination parking spot is gone
+    vehicles_displaced: usize,
+    /// Pedestrians removed from the map, because their path crossed something edited or they
+    /// were walking to a car that got displaced
+    pedestrians_displaced: usize,
+    /// Parked cars whose spot was removed. They're deleted, but no trip is cancelled unless
+    /// somebody was walking to them.
+    parked_cars_displaced: usize,
+}
+
 #[derive(Serialize)]
 struct TrafficSignalState {
     current_stage_idx: usize,
@@ -402,11 +635,23 @@ struct TrafficSignalState {
     waiting: Vec<(AgentID, TurnID, Time)>,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize)]
+struct SessionSummary {
+    name: String,
+    /// None if the session is busy
+    map: Option<MapName>,
+    time: Option<Time>,
+}
+
+#[derive(Clone, Deserialize)]
 struct LoadSim {
     scenario: String,
     modifiers: Vec<ScenarioModifier>,
     edits: Option<PermanentMapEdits>,
+    /// Start from this savestate instead of the beginning of the scenario. The edits in effect
+    /// when it was written are always set too.
+    #[serde(skip_deserializing)]
+    savestate: Option<String>,
     // These are fixed from the initial command line flags
     #[serde(skip_deserializing)]
     rng_seed: u64,
@@ -415,16 +660,39 @@ struct LoadSim {
 }
 
 impl LoadSim {
-    fn setup(&self, timer: &mut Timer) -> (Map, Sim) {
-        let mut scenario: Scenario = abstutil::read_binary(self.scenario.clone(), timer);
+    fn new(
+        scenario: String,
+        modifiers: Vec<ScenarioModifier>,
+        edits: Option<PermanentMapEdits>,
+    ) -> LoadSim {
+        let (rng_seed, opts) = FLAGS.read().unwrap().clone();
+        LoadSim {
+            scenario,
+            modifiers,
+            edits,
+            savestate: None,
+            rng_seed,
+            opts,
+        }
+    }
 
-        let mut map = Map::new(scenario.map_name.path(), timer);
-        if let Some(perma) = self.edits.clone() {
-            let edits = perma.to_edits(&map).unwrap();
-            map.must_apply_edits(edits, timer);
-            map.recalculate_pathfinding_after_edits(timer);
+    fn setup(&self, timer: &mut Timer) -> Result<(Arc<Map>, Sim), Box<dyn Error>> {
+        if let Some(ref path) = self.savestate {
+            let perma = self
+                .edits
+                .clone()
+                .ok_or("savestate is missing its map edits")?;
+            let mut map = load_map(&perma.map_name.clone(), Some(perma), timer)?;
+            self.apply_travel_times(&mut map, timer);
+            let sim = Sim::load_savestate(path.clone(), timer)?;
+            return Ok((map, sim));
         }
 
+        let mut scenario: Scenario = abstutil::read_binary(self.scenario.clone(), timer);
+
+        let mut map = load_map(&scenario.map_name, self.edits.clone(), timer)?;
+        self.apply_travel_times(&mut map, timer);
+
         for m in &self.modifiers {
             scenario = m.apply(&map, scenario);
         }
@@ -433,10 +701,87 @@ impl LoadSim {
         let mut sim = Sim::new(&map, self.opts.clone(), timer);
         scenario.instantiate(&mut sim, &map, &mut rng, timer);
 
-        (map, sim)
+        Ok((map, sim))
+    }
+
+    /// Travel times from a previous run change pathfinding, so the session needs its own copy of
+    /// the map.
+    fn apply_travel_times(&self, map: &mut Arc<Map>, timer: &mut Timer) {
+        if self.opts.travel_times.is_some() {
+            self.opts.apply_travel_times(Arc::make_mut(map), timer);
+        }
     }
 }
 
+/// Returns the map shared by every session using it, unless there are edits to apply. Then this
+/// session gets its own copy.
+fn load_map(
+    name: &MapName,
+    edits: Option<PermanentMapEdits>,
+    timer: &mut Timer,
+) -> Result<Arc<Map>, Box<dyn Error>> {
+    let cached = MAPS.lock().unwrap().get(name).cloned();
+    let mut map = if let Some(map) = cached {
+        map
+    } else {
+        // Don't hold onto the lock while loading; other sessions might need different maps.
+        let map = Arc::new(Map::new(name.path(), timer));
+        MAPS.lock()
+            .unwrap()
+            .entry(name.clone())
+            .or_insert(map)
+            .clone()
+    };
+
+    if let Some(perma) = edits {
+        let edits = perma.to_edits(&map)?;
+        // Savestates always record the edits in effect, even if there weren't any.
+        if !edits.commands.is_empty() {
+            let map = Arc::make_mut(&mut map);
+            map.must_apply_edits(edits, timer);
+            map.recalculate_pathfinding_after_edits(timer);
+        }
+    }
+    Ok(map)
+}
+
+/// Savestate names become part of a path, so only allow a single path component.
+fn savestate_name(params: &HashMap<String, String>) -> Result<String, Box<dyn Error>> {
+    let name = params.get("name").ok_or("missing ?name")?;
+    if name.is_empty() || name.contains('/') || name.contains('\\') || name.contains("..") {
+        return Err(format!("bad savestate name {:?}", name).into());
+    }
+    Ok(name.clone())
+}
+
+/// The map edits in effect when a savestate was written. These live in a sibling directory, so
+/// they don't show up when listing or searching through savestates.
+fn savestate_edits_path(savestate: &str) -> String {
+    format!(
+        "{}_edits/{}.json",
+        abstutil::parent_path(savestate),
+        abstutil::basename(savestate)
+    )
+}
+
+/// Every directory that a savestate has been written to. Savestates are stored by the map edits in
+/// effect when saving, so this finds them again after the session's edits change. This directory's
+/// name has no `_`, so it can't clash with any savestate directory.
+fn savestate_index_path(map: &MapName, name: &str) -> String {
+    abstutil::path(format!(
+        "player/saves/{}/{}/headless-savestates/{}.json",
+        map.city, map.map, name
+    ))
+}
+
+/// Full paths to every savestate with this name for the map, no matter which edits were in effect.
+fn all_savestates(map: &MapName, name: &str) -> Vec<String> {
+    let dirs: BTreeSet<String> =
+        abstutil::maybe_read_json(savestate_index_path(map, name), &mut Timer::throwaway())
+            .unwrap_or_else(|_| BTreeSet::new());
+    dirs.into_iter().flat_map(abstutil::list_dir).collect()
+}
+
 fn export_geometry(map: &Map, i: IntersectionID) -> geojson::GeoJson {
     use geojson::{Feature, FeatureCollection, GeoJson};
 
@@ -20,11 +20,15 @@ pub struct GenericCityImporter {
     pub onstreet_parking: convert_osm::OnstreetParking,
     pub public_offstreet_parking: convert_osm::PublicOffstreetParking,
     pub private_offstreet_parking: convert_osm::PrivateOffstreetParking,
-    /// If provided, pull elevation data from this SRTM file. The SRTM parser is incorrect, so the
-    /// results will be nonsense.
+    /// If provided, pull elevation data from this SRTM .hgt file, GeoTIFF in WGS84, or a
+    /// directory containing several of them.
     pub elevation: Option<String>,
     /// OSM railway=rail will be included as light rail if so. Cosmetic only.
     pub include_railroads: bool,
+    /// If provided, download a zipped GTFS feed from this URL. Bus routes and stops still come
+    /// from OSM, but their schedules will come from GTFS.
+    #[serde(default)]
+    pub gtfs_url: Option<String>,
 }
 
 impl GenericCityImporter {
@@ -45,6 +49,9 @@ impl GenericCityImporter {
                 .unwrap()
         );
         download(config, &local_osm_file, &self.osm_url);
+        if let Some(ref url) = self.gtfs_url {
+            download(config, &format!("input/{}/gtfs/", name.city), url);
+        }
 
         osmconvert(
             &local_osm_file,
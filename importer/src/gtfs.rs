@@ -0,0 +1,371 @@
+//! Import transit schedules from any city's GTFS feed
+//! (https://developers.google.com/transit/gtfs/reference). The routes and stops themselves come
+//! from OSM, so this matches GTFS stops to `BusStop`s, figures out which `BusRoute` each GTFS trip
+//! runs along, and replaces the default spawn times of each route with the real schedule.
+//!
+//! shapes.txt isn't read. A `BusRoute`'s path is calculated between its OSM stops, so a GTFS shape
+//! wouldn't change where vehicles drive. The only use of `shape_id` is matching the few OSM route
+//! relations tagged with it.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use abstutil::{prettyprint_usize, MultiMap, Timer};
+use geom::{Distance, Duration, FindClosest, LonLat, Time};
+use map_model::{BusRouteID, BusStopID, Map, PathConstraints};
+
+/// How far away can a GTFS stop be from a `BusStop` and still match it?
+const MAX_STOP_DIST: Distance = Distance::const_meters(30.0);
+/// What fraction of a route's stops must a trip serve, in order, to count as running that route?
+/// Some stops won't match, due to imprecise stop locations in OSM or GTFS.
+const MIN_FRACTION_STOPS_MATCHED: f64 = 0.8;
+
+/// `dir` must contain the unzipped GTFS files. Routes with no matching trips keep their default
+/// spawn times.
+pub fn import(map: &mut Map, dir: &str, timer: &mut Timer) -> Result<(), String> {
+    timer.start("read GTFS");
+    let services = active_services(dir)?;
+    let mut route_types: HashMap<String, Option<PathConstraints>> = HashMap::new();
+    for rec in read_csv::<RouteRecord>(dir, "routes.txt")? {
+        route_types.insert(rec.route_id, route_type(rec.route_type));
+    }
+    let mut trips: HashMap<String, TripRecord> = HashMap::new();
+    for rec in read_csv::<TripRecord>(dir, "trips.txt")? {
+        if services
+            .as_ref()
+            .map(|s| s.contains(&rec.service_id))
+            .unwrap_or(true)
+        {
+            trips.insert(rec.trip_id.clone(), rec);
+        }
+    }
+    let stops = match_stops(map, dir)?;
+    // For every trip, the matched stops it serves, in order
+    let mut trip_stops: BTreeMap<String, Vec<(usize, BusStopID, Time)>> = BTreeMap::new();
+    let mut bad_times = 0;
+    for rec in read_csv::<StopTimeRecord>(dir, "stop_times.txt")? {
+        if !trips.contains_key(&rec.trip_id) {
+            continue;
+        }
+        if let Some(bs) = stops.get(&rec.stop_id) {
+            // Not every stop has a time; some are only timepoints
+            let time = if !rec.arrival_time.is_empty() {
+                &rec.arrival_time
+            } else if !rec.departure_time.is_empty() {
+                &rec.departure_time
+            } else {
+                continue;
+            };
+            let time = match Time::parse(time) {
+                Ok(t) => t,
+                Err(_) => {
+                    bad_times += 1;
+                    continue;
+                }
+            };
+            trip_stops
+                .entry(rec.trip_id)
+                .or_insert_with(Vec::new)
+                .push((rec.stop_sequence, *bs, time));
+        }
+    }
+    if bad_times > 0 {
+        timer.warn(format!(
+            "Skipped {} stop times with a malformed arrival or departure time",
+            prettyprint_usize(bad_times)
+        ));
+    }
+    timer.stop("read GTFS");
+
+    let mut routes_per_stop: MultiMap<BusStopID, BusRouteID> = MultiMap::new();
+    for br in map.all_bus_routes() {
+        for bs in &br.stops {
+            routes_per_stop.insert(*bs, br.id);
+        }
+    }
+
+    // Many trips follow exactly the same stops, so only match each pattern once.
+    let mut cache: HashMap<Vec<BusStopID>, Option<(BusRouteID, usize, usize)>> = HashMap::new();
+    let mut travel_times: HashMap<(BusRouteID, usize), Duration> = HashMap::new();
+    let mut spawn_times: MultiMap<BusRouteID, Time> = MultiMap::new();
+    let mut unmatched_trips = 0;
+    timer.start_iter("match GTFS trips to routes", trip_stops.len());
+    for (trip_id, mut list) in trip_stops {
+        timer.next();
+        list.sort_by_key(|(seq, _, _)| *seq);
+        let trip = &trips[&trip_id];
+        let pattern: Vec<BusStopID> = list.iter().map(|(_, bs, _)| *bs).collect();
+        let matched = match trip.shape_id.as_ref().and_then(|s| match_by_marker(map, s)) {
+            Some(br) => first_stop_idx(&map.get_br(br).stops, &pattern)
+                .map(|(trip_idx, route_idx)| (br, trip_idx, route_idx)),
+            None => cache
+                .entry(pattern.clone())
+                .or_insert_with(|| {
+                    match_by_stops(
+                        map,
+                        &pattern,
+                        &routes_per_stop,
+                        route_types.get(&trip.route_id).cloned().flatten(),
+                    )
+                })
+                .clone(),
+        };
+        if let Some((br, trip_idx, route_idx)) = matched {
+            // The trip might not serve the route's first few stops, or they didn't match. Either
+            // way, the vehicle has to spawn at the start of the route earlier.
+            let travel = *travel_times
+                .entry((br, route_idx))
+                .or_insert_with(|| time_to_stop(map, br, route_idx));
+            let arrival = list[trip_idx].2;
+            let mut time = if arrival - Time::START_OF_DAY > travel {
+                arrival - travel
+            } else {
+                Time::START_OF_DAY
+            };
+            // Trips running past midnight belong to the previous service day. Maybe we should
+            // duplicate these to handle the beginning and end of the simulation.
+            if time >= Time::START_OF_DAY + Duration::hours(24) {
+                time = time - Duration::hours(24);
+            }
+            spawn_times.insert(br, time);
+        } else {
+            unmatched_trips += 1;
+        }
+    }
+
+    let mut matched_routes = 0;
+    for (br, times) in spawn_times.consume() {
+        matched_routes += 1;
+        map.hack_override_orig_spawn_times(br, times.into_iter().collect());
+    }
+    timer.note(format!(
+        "GTFS schedules found for {} of {} routes. {} trips touching the map didn't match any \
+         route.",
+        prettyprint_usize(matched_routes),
+        prettyprint_usize(map.all_bus_routes().len()),
+        prettyprint_usize(unmatched_trips)
+    ));
+    Ok(())
+}
+
+/// If calendar.txt exists, only keep services running on any weekday during the most recent period
+/// described. Some feeds split weekday service into separate services for different days. Returns
+/// None if every service should be used.
+fn active_services(dir: &str) -> Result<Option<BTreeSet<String>>, String> {
+    if !abstutil::file_exists(format!("{}/calendar.txt", dir)) {
+        return Ok(None);
+    }
+    let weekday: Vec<CalendarRecord> = read_csv::<CalendarRecord>(dir, "calendar.txt")?
+        .into_iter()
+        .filter(|rec| {
+            rec.monday == 1
+                || rec.tuesday == 1
+                || rec.wednesday == 1
+                || rec.thursday == 1
+                || rec.friday == 1
+        })
+        .collect();
+    // Dates are YYYYMMDD, so comparing the strings works.
+    let date = match weekday.iter().map(|rec| rec.start_date.clone()).max() {
+        Some(date) => date,
+        None => {
+            return Err("calendar.txt doesn't have any service on weekdays".to_string());
+        }
+    };
+    Ok(Some(
+        weekday
+            .into_iter()
+            .filter(|rec| rec.start_date <= date && date <= rec.end_date)
+            .map(|rec| rec.service_id)
+            .collect(),
+    ))
+}
+
+/// Map each GTFS stop to the closest BusStop.
+fn match_stops(map: &Map, dir: &str) -> Result<HashMap<String, BusStopID>, String> {
+    let mut closest: FindClosest<BusStopID> = FindClosest::new(map.get_bounds());
+    for bs in map.all_bus_stops().values() {
+        closest.add(
+            bs.id,
+            &vec![bs.sidewalk_pos.pt(map), bs.driving_pos.pt(map)],
+        );
+    }
+
+    let mut stops = HashMap::new();
+    for rec in read_csv::<StopRecord>(dir, "stops.txt")? {
+        let gps = match (rec.stop_lon, rec.stop_lat) {
+            (Some(lon), Some(lat)) => LonLat::new(lon, lat),
+            _ => continue,
+        };
+        if !map.get_gps_bounds().contains(gps) {
+            continue;
+        }
+        if let Some((bs, _)) = closest.closest_pt(gps.to_pt(map.get_gps_bounds()), MAX_STOP_DIST) {
+            stops.insert(rec.stop_id, bs);
+        }
+    }
+    Ok(stops)
+}
+
+/// Some OSM route relations are tagged with the GTFS shape they follow.
+fn match_by_marker(map: &Map, shape_id: &str) -> Option<BusRouteID> {
+    map.all_bus_routes()
+        .iter()
+        .find(|br| {
+            br.gtfs_trip_marker
+                .as_ref()
+                // Dunno what the :0 thing is
+                .map(|m| m.split(':').next().unwrap() == shape_id)
+                .unwrap_or(false)
+        })
+        .map(|br| br.id)
+}
+
+/// Find the route whose stops the trip serves most completely, in order. Returns the route, the
+/// index into the trip's stops of the first matched stop, and the index of the same stop along
+/// the route.
+fn match_by_stops(
+    map: &Map,
+    pattern: &Vec<BusStopID>,
+    routes_per_stop: &MultiMap<BusStopID, BusRouteID>,
+    route_type: Option<PathConstraints>,
+) -> Option<(BusRouteID, usize, usize)> {
+    let mut candidates: BTreeSet<BusRouteID> = BTreeSet::new();
+    for bs in pattern {
+        candidates.extend(routes_per_stop.get(*bs).iter().cloned());
+    }
+
+    let mut best: Option<(BusRouteID, (usize, usize), f64)> = None;
+    for br in candidates {
+        let route = map.get_br(br);
+        if route_type.map(|t| t != route.route_type).unwrap_or(false) {
+            continue;
+        }
+
+        // Greedily walk through the trip, looking for the route's stops in order
+        let mut matched = 0;
+        let mut trip_idx = 0;
+        let mut start_idx = None;
+        for (route_idx, bs) in route.stops.iter().enumerate() {
+            if let Some(offset) = pattern[trip_idx..].iter().position(|x| x == bs) {
+                if start_idx.is_none() {
+                    start_idx = Some((trip_idx + offset, route_idx));
+                }
+                matched += 1;
+                trip_idx += offset + 1;
+            }
+        }
+        let fraction = (matched as f64) / (route.stops.len() as f64);
+        if fraction < MIN_FRACTION_STOPS_MATCHED {
+            continue;
+        }
+        if best.map(|(_, _, f)| fraction > f).unwrap_or(true) {
+            best = Some((br, start_idx.unwrap(), fraction));
+        }
+    }
+    best.map(|(br, (trip_idx, route_idx), _)| (br, trip_idx, route_idx))
+}
+
+/// Interpret the route_type from https://developers.google.com/transit/gtfs/reference#routestxt,
+/// including the extended types that some feeds use. None means something like a ferry or
+/// gondola, which won't match anything in the map.
+fn route_type(x: usize) -> Option<PathConstraints> {
+    match x {
+        3 | 11 | 200..=299 | 700..=799 | 800..=899 => Some(PathConstraints::Bus),
+        0 | 1 | 2 | 12 | 100..=199 | 400..=499 | 900..=999 => Some(PathConstraints::Train),
+        _ => None,
+    }
+}
+
+/// Returns the index into the trip's stops and the route's stops of the first route stop that the
+/// trip serves.
+fn first_stop_idx(
+    route_stops: &Vec<BusStopID>,
+    pattern: &Vec<BusStopID>,
+) -> Option<(usize, usize)> {
+    route_stops.iter().enumerate().find_map(|(route_idx, bs)| {
+        pattern
+            .iter()
+            .position(|x| x == bs)
+            .map(|trip_idx| (trip_idx, route_idx))
+    })
+}
+
+/// Roughly how long a vehicle takes to drive from the start of the route to one of its stops,
+/// going the speed limit and not counting time spent at earlier stops.
+fn time_to_stop(map: &Map, br: BusRouteID, stop_idx: usize) -> Duration {
+    let mut total = Duration::ZERO;
+    for req in map.get_br(br).all_steps(map).into_iter().take(stop_idx + 1) {
+        if let Some(path) = map.pathfind(req) {
+            for step in path.get_steps() {
+                let t = step.as_traversable();
+                total += t.length(map) / t.speed_limit(map);
+            }
+        }
+    }
+    total
+}
+
+fn read_csv<T: DeserializeOwned>(dir: &str, file: &str) -> Result<Vec<T>, String> {
+    let path = format!("{}/{}", dir, file);
+    let mut results = Vec::new();
+    for rec in csv::Reader::from_reader(
+        File::open(&path).map_err(|err| format!("Can't open {}: {}", path, err))?,
+    )
+    .deserialize()
+    {
+        results.push(rec.map_err(|err| format!("Bad record in {}: {}", path, err))?);
+    }
+    Ok(results)
+}
+
+#[derive(Deserialize)]
+struct RouteRecord {
+    route_id: String,
+    route_type: usize,
+}
+
+#[derive(Deserialize)]
+struct TripRecord {
+    route_id: String,
+    service_id: String,
+    trip_id: String,
+    #[serde(default)]
+    shape_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StopRecord {
+    stop_id: String,
+    // Some kinds of entrances and nodes inside stations don't have a location
+    #[serde(default)]
+    stop_lat: Option<f64>,
+    #[serde(default)]
+    stop_lon: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct StopTimeRecord {
+    trip_id: String,
+    #[serde(default)]
+    arrival_time: String,
+    #[serde(default)]
+    departure_time: String,
+    stop_id: String,
+    stop_sequence: usize,
+}
+
+#[derive(Deserialize)]
+struct CalendarRecord {
+    service_id: String,
+    monday: usize,
+    tuesday: usize,
+    wednesday: usize,
+    thursday: usize,
+    friday: usize,
+    start_date: String,
+    end_date: String,
+}
@@ -10,6 +10,7 @@ mod berlin;
 mod configuration;
 mod dependencies;
 mod generic;
+mod gtfs;
 mod leeds;
 mod london;
 mod seattle;
@@ -173,9 +174,24 @@ fn main() {
                     "distribute residents from planning areas for {}",
                     name.describe()
                 ));
-            } else if name.city == "seattle" {
+            }
+
+            // If the city has a GTFS feed, use the real transit schedules.
+            let gtfs_dir = if name.city == "seattle" {
+                abstutil::path("input/seattle/google_transit")
+            } else {
+                abstutil::path(format!("input/{}/gtfs", name.city))
+            };
+            if abstutil::file_exists(format!("{}/stop_times.txt", gtfs_dir)) {
                 timer.start(format!("add GTFS schedules for {}", name.describe()));
-                seattle::add_gtfs_schedules(&mut map);
+                match gtfs::import(&mut map, &gtfs_dir, &mut timer) {
+                    Ok(()) => {
+                        map.save();
+                    }
+                    Err(err) => {
+                        timer.error(format!("Couldn't import GTFS from {}: {}", gtfs_dir, err));
+                    }
+                }
                 timer.stop(format!("add GTFS schedules for {}", name.describe()));
             }
 
@@ -193,13 +193,21 @@ fn clip_trips(map: &Map, popdat: &PopDat, huge_map: &Map, timer: &mut Timer) ->
                     TripMode::Walk | TripMode::Transit => {
                         (&incoming_borders_walking, &outgoing_borders_walking)
                     }
-                    TripMode::Drive => (&incoming_borders_driving, &outgoing_borders_driving),
-                    TripMode::Bike => (&incoming_borders_biking, &outgoing_borders_biking),
+                    TripMode::Drive
+                    | TripMode::Truck
+                    | TripMode::Motorcycle
+                    | TripMode::RideHail
+                    | TripMode::ParkAndRide => {
+                        (&incoming_borders_driving, &outgoing_borders_driving)
+                    }
+                    TripMode::Bike
+                    | TripMode::Scooter
+                    | TripMode::BikeShare
+                    | TripMode::BikeAndRide => (&incoming_borders_biking, &outgoing_borders_biking),
                 },
                 match orig.mode {
                     TripMode::Walk | TripMode::Transit => PathConstraints::Pedestrian,
-                    TripMode::Drive => PathConstraints::Car,
-                    TripMode::Bike => PathConstraints::Bike,
+                    mode => mode.to_constraints(),
                 },
                 maybe_huge_map.as_ref(),
             )?;
@@ -290,6 +298,8 @@ pub fn make_weekday_scenario(
         map_name: map.get_name().clone(),
         people,
         only_seed_buses: None,
+        ride_hail: None,
+        bike_share: None,
     }
     .remove_weird_schedules()
 }
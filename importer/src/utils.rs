@@ -1,3 +1,5 @@
+use std::fs::File;
+use std::io::Read;
 use std::path::Path;
 use std::process::Command;
 
@@ -6,7 +8,8 @@ use abstutil::{must_run_cmd, MapName, Timer};
 use crate::configuration::ImporterConfiguration;
 
 // If the output file doesn't already exist, downloads the URL into that location. Automatically
-// uncompresses .zip and .gz files.
+// uncompresses .zip and .gz files. Zip files are detected by their contents, since URLs like
+// GTFS feeds often don't end in .zip.
 pub fn download(config: &ImporterConfiguration, output: &str, url: &str) {
     let output = abstutil::path(output);
     if Path::new(&output).exists() {
@@ -28,8 +31,7 @@ pub fn download(config: &ImporterConfiguration, output: &str, url: &str) {
             .arg(url),
     );
 
-    // Argh the Dropbox URL is .zip?dl=0
-    if url.contains(".zip") {
+    if is_zip(tmp) {
         let unzip_to = if output.ends_with("/") {
             output
         } else {
@@ -52,6 +54,14 @@ pub fn download(config: &ImporterConfiguration, output: &str, url: &str) {
     }
 }
 
+fn is_zip(path: &str) -> bool {
+    let mut magic = [0; 4];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .is_ok()
+        && &magic == b"PK\x03\x04"
+}
+
 // If the output file doesn't already exist, downloads the URL into that location. Clips .kml
 // files and converts to a .bin.
 pub fn download_kml(
@@ -293,6 +293,7 @@ impl Model {
                 osm_tags,
                 turn_restrictions: Vec::new(),
                 complicated_turn_restrictions: Vec::new(),
+                elevation: Vec::new(),
             },
         );
         self.road_added(id, ctx);
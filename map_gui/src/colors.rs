@@ -120,6 +120,9 @@ pub struct ColorScheme {
     pub unzoomed_bike: Color,
     pub unzoomed_bus: Color,
     pub unzoomed_pedestrian: Color,
+    pub unzoomed_truck: Color,
+    pub unzoomed_scooter: Color,
+    pub unzoomed_motorcycle: Color,
 
     // Agents
     agent_colors: Vec<Color>,
@@ -129,6 +132,7 @@ pub struct ColorScheme {
     pub bus_body: Color,
     pub bus_label: Color,
     pub train_body: Color,
+    pub truck_body: Color,
     pub ped_head: Color,
     pub ped_foot: Color,
     pub ped_preparing_bike_body: Color,
@@ -241,6 +245,9 @@ impl ColorScheme {
             unzoomed_bike: hex("#5D9630"),
             unzoomed_bus: hex("#12409D"),
             unzoomed_pedestrian: hex("#DF8C3D"),
+            unzoomed_truck: hex("#6B4E2E"),
+            unzoomed_scooter: hex("#2DB6A3"),
+            unzoomed_motorcycle: hex("#C2458A"),
 
             // Agents
             agent_colors: vec![
@@ -256,6 +263,7 @@ impl ColorScheme {
             bus_body: Color::rgb(50, 133, 117),
             bus_label: Color::rgb(249, 206, 24),
             train_body: hex("#42B6E9"),
+            truck_body: hex("#8C6D46"),
             ped_head: Color::rgb(139, 69, 19),
             ped_foot: Color::BLACK,
             ped_preparing_bike_body: Color::rgb(255, 0, 144),
@@ -152,11 +152,17 @@ pub struct UnzoomedAgents {
     bikes: bool,
     buses_and_trains: bool,
     peds: bool,
+    trucks: bool,
+    scooters: bool,
+    motorcycles: bool,
 
     car_color: Color,
     bike_color: Color,
     bus_color: Color,
     ped_color: Color,
+    truck_color: Color,
+    scooter_color: Color,
+    motorcycle_color: Color,
 }
 
 impl UnzoomedAgents {
@@ -166,11 +172,17 @@ impl UnzoomedAgents {
             bikes: true,
             buses_and_trains: true,
             peds: true,
+            trucks: true,
+            scooters: true,
+            motorcycles: true,
 
             car_color: cs.unzoomed_car.alpha(0.8),
             bike_color: cs.unzoomed_bike.alpha(0.8),
             bus_color: cs.unzoomed_bus.alpha(0.8),
             ped_color: cs.unzoomed_pedestrian.alpha(0.8),
+            truck_color: cs.unzoomed_truck.alpha(0.8),
+            scooter_color: cs.unzoomed_scooter.alpha(0.8),
+            motorcycle_color: cs.unzoomed_motorcycle.alpha(0.8),
         }
     }
 
@@ -197,6 +209,27 @@ impl UnzoomedAgents {
                     None
                 }
             }
+            Some(VehicleType::Truck) => {
+                if self.trucks {
+                    Some(self.truck_color)
+                } else {
+                    None
+                }
+            }
+            Some(VehicleType::Scooter) => {
+                if self.scooters {
+                    Some(self.scooter_color)
+                } else {
+                    None
+                }
+            }
+            Some(VehicleType::Motorcycle) => {
+                if self.motorcycles {
+                    Some(self.motorcycle_color)
+                } else {
+                    None
+                }
+            }
             None => {
                 if self.peds {
                     Some(self.ped_color)
@@ -212,7 +245,11 @@ impl UnzoomedAgents {
             Checkbox::colored(ctx, "Car", self.car_color, self.cars).margin_right(24),
             Checkbox::colored(ctx, "Bike", self.bike_color, self.bikes).margin_right(24),
             Checkbox::colored(ctx, "Bus", self.bus_color, self.buses_and_trains).margin_right(24),
-            Checkbox::colored(ctx, "Pedestrian", self.ped_color, self.peds).margin_right(8),
+            Checkbox::colored(ctx, "Pedestrian", self.ped_color, self.peds).margin_right(24),
+            Checkbox::colored(ctx, "Truck", self.truck_color, self.trucks).margin_right(24),
+            Checkbox::colored(ctx, "Scooter", self.scooter_color, self.scooters).margin_right(24),
+            Checkbox::colored(ctx, "Motorcycle", self.motorcycle_color, self.motorcycles)
+                .margin_right(8),
         ])
     }
 
@@ -222,6 +259,9 @@ impl UnzoomedAgents {
             Checkbox::colored(ctx, "Bike", self.bike_color, self.bikes),
             Checkbox::colored(ctx, "Bus", self.bus_color, self.buses_and_trains),
             Checkbox::colored(ctx, "Pedestrian", self.ped_color, self.peds),
+            Checkbox::colored(ctx, "Truck", self.truck_color, self.trucks),
+            Checkbox::colored(ctx, "Scooter", self.scooter_color, self.scooters),
+            Checkbox::colored(ctx, "Motorcycle", self.motorcycle_color, self.motorcycles),
         ])
     }
 
@@ -230,5 +270,8 @@ impl UnzoomedAgents {
         self.bikes = panel.is_checked("Bike");
         self.buses_and_trains = panel.is_checked("Bus");
         self.peds = panel.is_checked("Pedestrian");
+        self.trucks = panel.is_checked("Truck");
+        self.scooters = panel.is_checked("Scooter");
+        self.motorcycles = panel.is_checked("Motorcycle");
     }
 }
@@ -8,10 +8,13 @@ use crate::render::{DrawOptions, Renderable, OUTLINE_THICKNESS};
 use crate::{AppLike, ID};
 
 const CAR_WIDTH: Distance = Distance::const_meters(1.75);
+const TRUCK_WIDTH: Distance = Distance::const_meters(2.4);
+const MOTORCYCLE_WIDTH: Distance = Distance::const_meters(0.8);
 
 pub struct DrawCar {
     pub id: CarID,
     body: PolyLine,
+    width: Distance,
     body_polygon: Polygon,
     zorder: isize,
 
@@ -21,11 +24,16 @@ pub struct DrawCar {
 impl DrawCar {
     pub fn new(input: DrawCarInput, map: &Map, prerender: &Prerender, cs: &ColorScheme) -> DrawCar {
         let mut draw_default = GeomBatch::new();
+        let width = match input.id.1 {
+            VehicleType::Truck => TRUCK_WIDTH,
+            VehicleType::Motorcycle => MOTORCYCLE_WIDTH,
+            _ => CAR_WIDTH,
+        };
 
         // Wheels
         for side in vec![
-            input.body.shift_right(CAR_WIDTH / 2.0),
-            input.body.shift_left(CAR_WIDTH / 2.0),
+            input.body.shift_right(width / 2.0),
+            input.body.shift_left(width / 2.0),
         ]
         .into_iter()
         .flatten()
@@ -51,23 +59,23 @@ impl DrawCar {
 
         let body_polygon = if input.body.length() < Distance::meters(1.1) {
             // Simpler shape while appearing from a border
-            input.body.make_polygons(CAR_WIDTH)
+            input.body.make_polygons(width)
         } else {
             let front_corner = input.body.length() - Distance::meters(1.0);
             let thick_line = input
                 .body
                 .exact_slice(Distance::ZERO, front_corner)
-                .make_polygons(CAR_WIDTH);
+                .make_polygons(width);
 
             let (corner_pt, corner_angle) = input.body.must_dist_along(front_corner);
             let tip_pt = input.body.last_pt();
             let tip_angle = input.body.last_line().angle();
             let front = Ring::must_new(vec![
-                corner_pt.project_away(CAR_WIDTH / 2.0, corner_angle.rotate_degs(90.0)),
-                corner_pt.project_away(CAR_WIDTH / 2.0, corner_angle.rotate_degs(-90.0)),
-                tip_pt.project_away(CAR_WIDTH / 4.0, tip_angle.rotate_degs(-90.0)),
-                tip_pt.project_away(CAR_WIDTH / 4.0, tip_angle.rotate_degs(90.0)),
-                corner_pt.project_away(CAR_WIDTH / 2.0, corner_angle.rotate_degs(90.0)),
+                corner_pt.project_away(width / 2.0, corner_angle.rotate_degs(90.0)),
+                corner_pt.project_away(width / 2.0, corner_angle.rotate_degs(-90.0)),
+                tip_pt.project_away(width / 4.0, tip_angle.rotate_degs(-90.0)),
+                tip_pt.project_away(width / 4.0, tip_angle.rotate_degs(90.0)),
+                corner_pt.project_away(width / 2.0, corner_angle.rotate_degs(90.0)),
             ])
             .to_polygon();
             front.union(thick_line)
@@ -105,7 +113,7 @@ impl DrawCar {
 
         // If the vehicle is temporarily too short for anything, just omit.
         if input.body.length() >= Distance::meters(2.5) {
-            let arrow_len = 0.8 * CAR_WIDTH;
+            let arrow_len = 0.8 * width;
             let arrow_thickness = Distance::meters(0.5);
 
             if let Some(t) = input.waiting_for_turn {
@@ -151,11 +159,8 @@ impl DrawCar {
                     cs.brake_light,
                     thick_line_from_angle(
                         window_thickness,
-                        CAR_WIDTH - window_length_gap * 2.0,
-                        pos.project_away(
-                            CAR_WIDTH / 2.0 - window_length_gap,
-                            angle.rotate_degs(-90.0),
-                        ),
+                        width - window_length_gap * 2.0,
+                        pos.project_away(width / 2.0 - window_length_gap, angle.rotate_degs(-90.0)),
                         angle.rotate_degs(90.0),
                     ),
                 );
@@ -190,6 +195,7 @@ impl DrawCar {
         DrawCar {
             id: input.id,
             body: input.body,
+            width,
             body_polygon,
             zorder,
             draw_default: prerender.upload(draw_default),
@@ -208,7 +214,7 @@ impl Renderable for DrawCar {
 
     fn get_outline(&self, _: &Map) -> Polygon {
         self.body
-            .to_thick_boundary(CAR_WIDTH, OUTLINE_THICKNESS)
+            .to_thick_boundary(self.width, OUTLINE_THICKNESS)
             .unwrap_or_else(|| self.body_polygon.clone())
     }
 
@@ -237,6 +243,8 @@ fn zoomed_color_car(input: &DrawCarInput, cs: &ColorScheme) -> Color {
         cs.bus_body
     } else if input.id.1 == VehicleType::Train {
         cs.train_body
+    } else if input.id.1 == VehicleType::Truck && input.status == CarStatus::Moving {
+        cs.truck_body
     } else {
         match input.status {
             CarStatus::Moving => cs.rotating_color_agents(input.id.0),
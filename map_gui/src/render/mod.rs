@@ -64,7 +64,7 @@ fn draw_vehicle(
     prerender: &Prerender,
     cs: &ColorScheme,
 ) -> Box<dyn Renderable> {
-    if input.id.1 == VehicleType::Bike {
+    if input.id.1.is_bike_like() {
         Box::new(DrawBike::new(input, map, prerender, cs))
     } else {
         Box::new(DrawCar::new(input, map, prerender, cs))
@@ -84,7 +84,7 @@ pub fn all_costs_from(
                 if let Some((pos, _)) = b.driving_connection(map) {
                     bldg_to_lane.insert(b.id, pos.lane());
                 }
-            } else if constraints == PathConstraints::Bike {
+            } else if constraints.is_bike_like() {
                 if let Some((pos, _)) = b.biking_connection(map) {
                     bldg_to_lane.insert(b.id, pos.lane());
                 }
@@ -60,6 +60,13 @@ pub fn upgrade(mut value: Value, map: &Map) -> Result<PermanentMapEdits, String>
             .unwrap()
             .insert("version".to_string(), Value::Number(4.into()));
     }
+    if value["version"] == Value::Number(4.into()) {
+        fix_conditional_rules(&mut value, map);
+        value
+            .as_object_mut()
+            .unwrap()
+            .insert("version".to_string(), Value::Number(5.into()));
+    }
 
     abstutil::from_json(&value.to_string().into_bytes()).map_err(|x| x.to_string())
 }
@@ -189,7 +196,8 @@ fn fix_old_lane_cmds(value: &mut Value, map: &Map) -> Result<(), String> {
                 return Err(format!("{:?} speed limit has changed", obj));
             }
             road.speed_limit = obj.new;
-        } else if let Some(obj) = cmd.remove("ChangeAccessRestrictions") {
+        } else if let Some(mut obj) = cmd.remove("ChangeAccessRestrictions") {
+            fix_conditional_rules(&mut obj, map);
             let obj: ChangeAccessRestrictions = serde_json::from_value(obj).unwrap();
             let r = map.find_r_by_osm_id(obj.id)?;
             let road = modified.entry(r).or_insert_with(|| map.get_r_edit(r));
@@ -232,6 +240,39 @@ fn fix_map_name(value: &mut Value) {
     }
 }
 
+// Time-of-day lane types and access restrictions added conditional_lane_types to EditRoad and
+// conditional to AccessRestrictions. Roads edited before this keep whatever conditional lane types
+// OSM describes, as long as the number of lanes still matches.
+fn fix_conditional_rules(value: &mut Value, map: &Map) {
+    walk(value, &|obj| {
+        if let Some(Value::Object(cmd)) = obj.get_mut("ChangeRoad") {
+            let orig = serde_json::from_value::<OriginalRoad>(cmd["r"].clone())
+                .ok()
+                .and_then(|r| map.find_r_by_osm_id(r).ok())
+                .map(|r| EditRoad::get_orig_from_osm(map.get_r(r), map.get_config()));
+            for key in vec!["old", "new"] {
+                if let Some(Value::Object(edit)) = cmd.get_mut(key) {
+                    if edit.contains_key("conditional_lane_types") {
+                        continue;
+                    }
+                    let num_lanes = edit["lanes_ltr"].as_array().map(|x| x.len()).unwrap_or(0);
+                    let conditional = match orig {
+                        Some(ref orig) if orig.lanes_ltr.len() == num_lanes => {
+                            serde_json::to_value(&orig.conditional_lane_types).unwrap()
+                        }
+                        _ => Value::Array(vec![Value::Null; num_lanes]),
+                    };
+                    edit.insert("conditional_lane_types".to_string(), conditional);
+                }
+            }
+        }
+        if obj.contains_key("allow_through_traffic") && !obj.contains_key("conditional") {
+            obj.insert("conditional".to_string(), Value::Array(Vec::new()));
+        }
+        false
+    });
+}
+
 // These're old structs used in fix_old_lane_cmds.
 #[derive(Debug, Deserialize)]
 struct OriginalLane {
@@ -4,7 +4,7 @@
 
 use std::collections::{BTreeMap, BTreeSet};
 
-pub use perma::PermanentMapEdits;
+pub use perma::{PermanentEditCmd, PermanentMapEdits};
 use serde::{Deserialize, Serialize};
 
 use abstutil::{retain_btreemap, retain_btreeset, Timer};
@@ -12,9 +12,10 @@ use geom::{Speed, Time};
 
 use crate::make::initial::lane_specs::get_lane_specs_ltr;
 use crate::{
-    connectivity, AccessRestrictions, BusRouteID, ControlStopSign, ControlTrafficSignal, Direction,
-    IntersectionID, IntersectionType, LaneID, LaneType, Map, MapConfig, PathConstraints,
-    Pathfinder, Road, RoadID, TurnID, Zone,
+    connectivity, AccessRestrictions, BusRouteID, ConditionalLaneType, ControlStopSign,
+    ControlTrafficSignal, Direction, IntersectionID, IntersectionType, LaneID, LaneType, Map,
+    MapConfig, PathConstraints, Pathfinder, RawTrafficSignal, Road, RoadID, RouteVehicles, TurnID,
+    Zone,
 };
 
 mod compat;
@@ -49,24 +50,25 @@ pub enum EditIntersection {
     StopSign(ControlStopSign),
     // Don't keep ControlTrafficSignal here, because it contains movements that should be
     // generated after all lane edits are applied.
-    TrafficSignal(seattle_traffic_signals::TrafficSignal),
+    TrafficSignal(RawTrafficSignal),
     Closed,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EditRoad {
     pub lanes_ltr: Vec<(LaneType, Direction)>,
+    /// Parallel to lanes_ltr
+    pub conditional_lane_types: Vec<Option<ConditionalLaneType>>,
     pub speed_limit: Speed,
     pub access_restrictions: AccessRestrictions,
 }
 
 impl EditRoad {
     pub fn get_orig_from_osm(r: &Road, cfg: &MapConfig) -> EditRoad {
+        let specs = get_lane_specs_ltr(&r.osm_tags, cfg);
         EditRoad {
-            lanes_ltr: get_lane_specs_ltr(&r.osm_tags, cfg)
-                .into_iter()
-                .map(|spec| (spec.lt, spec.dir))
-                .collect(),
+            lanes_ltr: specs.iter().map(|spec| (spec.lt, spec.dir)).collect(),
+            conditional_lane_types: specs.into_iter().map(|spec| spec.conditional).collect(),
             speed_limit: r.speed_limit_from_osm(),
             access_restrictions: r.access_restrictions_from_osm(),
         }
@@ -84,6 +86,13 @@ impl EditRoad {
             }
         }
 
+        let conditional = self
+            .conditional_lane_types
+            .iter()
+            .zip(other.conditional_lane_types.iter())
+            .filter(|(c1, c2)| c1 != c2)
+            .count();
+
         let mut changes = Vec::new();
         if lt == 1 {
             changes.push(format!("1 lane type"));
@@ -95,6 +104,11 @@ impl EditRoad {
         } else if dir > 1 {
             changes.push(format!("{} lane reversal", dir));
         }
+        if conditional == 1 {
+            changes.push(format!("1 time-of-day lane type"));
+        } else if conditional > 1 {
+            changes.push(format!("{} time-of-day lane types", conditional));
+        }
         if self.speed_limit != other.speed_limit {
             changes.push(format!("speed limit"));
         }
@@ -122,6 +136,14 @@ pub enum EditCmd {
         old: Vec<Time>,
         new: Vec<Time>,
     },
+    ChangeRouteVehicles {
+        id: BusRouteID,
+        old: RouteVehicles,
+        new: RouteVehicles,
+    },
+    /// Several commands that should be applied and undone together, like coordinating the timing
+    /// of many traffic signals.
+    Batch(Vec<EditCmd>),
 }
 
 pub struct EditEffects {
@@ -177,7 +199,7 @@ impl MapEdits {
         self.original_intersections.clear();
         self.changed_routes.clear();
 
-        for cmd in &self.commands {
+        for cmd in self.commands.iter().flat_map(|cmd| cmd.flatten()) {
             match cmd {
                 EditCmd::ChangeRoad { r, .. } => {
                     self.changed_roads.insert(*r);
@@ -187,9 +209,11 @@ impl MapEdits {
                         self.original_intersections.insert(*i, old.clone());
                     }
                 }
-                EditCmd::ChangeRouteSchedule { id, .. } => {
+                EditCmd::ChangeRouteSchedule { id, .. }
+                | EditCmd::ChangeRouteVehicles { id, .. } => {
                     self.changed_routes.insert(*id);
                 }
+                EditCmd::Batch(_) => unreachable!(),
             }
         }
 
@@ -201,7 +225,7 @@ impl MapEdits {
         });
         retain_btreeset(&mut self.changed_routes, |br| {
             let r = map.get_br(*br);
-            r.spawn_times != r.orig_spawn_times
+            r.spawn_times != r.orig_spawn_times || r.vehicles() != RouteVehicles::default()
         });
     }
 
@@ -223,11 +247,20 @@ impl MapEdits {
         }
         for r in &self.changed_routes {
             let r = map.get_br(*r);
-            self.commands.push(EditCmd::ChangeRouteSchedule {
-                id: r.id,
-                new: r.spawn_times.clone(),
-                old: r.orig_spawn_times.clone(),
-            });
+            if r.spawn_times != r.orig_spawn_times {
+                self.commands.push(EditCmd::ChangeRouteSchedule {
+                    id: r.id,
+                    new: r.spawn_times.clone(),
+                    old: r.orig_spawn_times.clone(),
+                });
+            }
+            if r.vehicles() != RouteVehicles::default() {
+                self.commands.push(EditCmd::ChangeRouteVehicles {
+                    id: r.id,
+                    new: r.vehicles(),
+                    old: RouteVehicles::default(),
+                });
+            }
         }
     }
 
@@ -245,8 +278,16 @@ impl MapEdits {
                 roads.insert(r.id);
             } else {
                 let lanes_ltr = r.lanes_ltr();
-                for (idx, (lt, dir)) in orig.lanes_ltr.into_iter().enumerate() {
-                    if lanes_ltr[idx].1 != dir || lanes_ltr[idx].2 != lt {
+                for (idx, ((lt, dir), cond)) in orig
+                    .lanes_ltr
+                    .into_iter()
+                    .zip(orig.conditional_lane_types.into_iter())
+                    .enumerate()
+                {
+                    if lanes_ltr[idx].1 != dir
+                        || lanes_ltr[idx].2 != lt
+                        || map.get_l(lanes_ltr[idx].0).conditional_type != cond
+                    {
                         lanes.insert(lanes_ltr[idx].0);
                     }
                 }
@@ -291,10 +332,25 @@ impl EditCmd {
             EditCmd::ChangeRouteSchedule { id, .. } => {
                 format!("reschedule route {}", map.get_br(*id).short_name)
             }
+            EditCmd::ChangeRouteVehicles { id, .. } => {
+                format!("change vehicles on route {}", map.get_br(*id).short_name)
+            }
+            EditCmd::Batch(cmds) => {
+                details = cmds.iter().map(|cmd| cmd.describe(map).0).collect();
+                format!("{} changes", cmds.len())
+            }
         };
         (summary, details)
     }
 
+    /// Returns this command, or all of the commands in a batch, recursively.
+    pub fn flatten(&self) -> Vec<&EditCmd> {
+        match self {
+            EditCmd::Batch(cmds) => cmds.iter().flat_map(|cmd| cmd.flatten()).collect(),
+            _ => vec![self],
+        }
+    }
+
     // Must be idempotent
     fn apply(&self, effects: &mut EditEffects, map: &mut Map, timer: &mut Timer) {
         match self {
@@ -311,6 +367,7 @@ impl EditCmd {
                     let lane = &mut map.lanes[(road.lanes_ltr[idx].0).0];
                     road.lanes_ltr[idx].2 = lt;
                     lane.lane_type = lt;
+                    lane.conditional_type = new.conditional_lane_types[idx].clone();
 
                     // Direction change?
                     if road.lanes_ltr[idx].1 != dir {
@@ -379,6 +436,17 @@ impl EditCmd {
             EditCmd::ChangeRouteSchedule { id, new, .. } => {
                 map.bus_routes[id.0].spawn_times = new.clone();
             }
+            EditCmd::ChangeRouteVehicles { id, new, .. } => {
+                let route = &mut map.bus_routes[id.0];
+                route.vehicle_capacity = new.capacity;
+                route.vehicle_doors = new.doors;
+                route.fare_payment = new.fare_payment;
+            }
+            EditCmd::Batch(cmds) => {
+                for cmd in cmds {
+                    cmd.apply(effects, map, timer);
+                }
+            }
         }
     }
 
@@ -399,6 +467,14 @@ impl EditCmd {
                 old: new,
                 new: old,
             },
+            EditCmd::ChangeRouteVehicles { id, old, new } => EditCmd::ChangeRouteVehicles {
+                id,
+                old: new,
+                new: old,
+            },
+            EditCmd::Batch(cmds) => {
+                EditCmd::Batch(cmds.into_iter().rev().map(|cmd| cmd.undo()).collect())
+            }
         }
     }
 }
@@ -489,6 +565,11 @@ impl Map {
                 .into_iter()
                 .map(|(_, dir, lt)| (lt, dir))
                 .collect(),
+            conditional_lane_types: r
+                .lanes_ltr()
+                .into_iter()
+                .map(|(l, _, _)| self.get_l(l).conditional_type.clone())
+                .collect(),
             speed_limit: r.speed_limit,
             access_restrictions: r.access_restrictions.clone(),
         }
@@ -506,7 +587,7 @@ impl Map {
         match self.get_i(i).intersection_type {
             IntersectionType::StopSign => EditIntersection::StopSign(self.get_stop_sign(i).clone()),
             IntersectionType::TrafficSignal => {
-                EditIntersection::TrafficSignal(self.get_traffic_signal(i).export(self))
+                EditIntersection::TrafficSignal(self.get_traffic_signal(i).to_raw(self))
             }
             IntersectionType::Construction => EditIntersection::Closed,
             IntersectionType::Border => unreachable!(),
@@ -604,7 +685,7 @@ impl Map {
                         .get_r(*id)
                         .find_closest_lane(
                             sidewalk_pos.lane(),
-                            |l| PathConstraints::Bus.can_use(l, self),
+                            |l| PathConstraints::Bus.can_use_during(l, None, self),
                             self,
                         )
                         .unwrap();
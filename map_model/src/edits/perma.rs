@@ -7,7 +7,7 @@ use geom::Time;
 
 use crate::edits::{EditCmd, EditIntersection, EditRoad, MapEdits};
 use crate::raw::OriginalRoad;
-use crate::{osm, ControlStopSign, IntersectionID, Map};
+use crate::{osm, ControlStopSign, IntersectionID, Map, RawTrafficSignal, RouteVehicles};
 
 /// MapEdits are converted to this before serializing. Referencing things like LaneID in a Map won't
 /// work if the basemap is rebuilt from new OSM data, so instead we use stabler OSM IDs that're less
@@ -38,7 +38,7 @@ pub enum PermanentEditIntersection {
         )]
         must_stop: BTreeMap<OriginalRoad, bool>,
     },
-    TrafficSignal(seattle_traffic_signals::TrafficSignal),
+    TrafficSignal(RawTrafficSignal),
     Closed,
 }
 
@@ -59,6 +59,12 @@ pub enum PermanentEditCmd {
         old: Vec<Time>,
         new: Vec<Time>,
     },
+    ChangeRouteVehicles {
+        osm_rel_id: osm::RelationID,
+        old: RouteVehicles,
+        new: RouteVehicles,
+    },
+    Batch(Vec<PermanentEditCmd>),
 }
 
 impl EditCmd {
@@ -81,6 +87,16 @@ impl EditCmd {
                     new: new.clone(),
                 }
             }
+            EditCmd::ChangeRouteVehicles { id, old, new } => {
+                PermanentEditCmd::ChangeRouteVehicles {
+                    osm_rel_id: map.get_br(*id).osm_rel_id,
+                    old: old.clone(),
+                    new: new.clone(),
+                }
+            }
+            EditCmd::Batch(cmds) => {
+                PermanentEditCmd::Batch(cmds.iter().map(|cmd| cmd.to_perma(map)).collect())
+            }
         }
     }
 }
@@ -101,6 +117,11 @@ impl PermanentEditCmd {
                         new.lanes_ltr.len()
                     ));
                 }
+                if new.conditional_lane_types.len() != new.lanes_ltr.len()
+                    || old.conditional_lane_types.len() != old.lanes_ltr.len()
+                {
+                    return Err(format!("conditional lane types in {} are malformed", r));
+                }
                 Ok(EditCmd::ChangeRoad { r: id, new, old })
             }
             PermanentEditCmd::ChangeIntersection { i, new, old } => {
@@ -125,6 +146,21 @@ impl PermanentEditCmd {
                     .ok_or(format!("can't find {}", osm_rel_id))?;
                 Ok(EditCmd::ChangeRouteSchedule { id, old, new })
             }
+            PermanentEditCmd::ChangeRouteVehicles {
+                osm_rel_id,
+                old,
+                new,
+            } => {
+                let id = map
+                    .find_br(osm_rel_id)
+                    .ok_or(format!("can't find {}", osm_rel_id))?;
+                Ok(EditCmd::ChangeRouteVehicles { id, old, new })
+            }
+            PermanentEditCmd::Batch(cmds) => Ok(EditCmd::Batch(
+                cmds.into_iter()
+                    .map(|cmd| cmd.to_cmd(map))
+                    .collect::<Result<Vec<_>, String>>()?,
+            )),
         }
     }
 }
@@ -136,7 +172,7 @@ impl MapEdits {
             map_name: map.get_name().clone(),
             edits_name: self.edits_name.clone(),
             // Increase this every time there's a schema change
-            version: 4,
+            version: 5,
             proposal_description: self.proposal_description.clone(),
             proposal_link: self.proposal_link.clone(),
             commands: self.commands.iter().map(|cmd| cmd.to_perma(map)).collect(),
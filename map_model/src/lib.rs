@@ -11,6 +11,7 @@
 //! Map objects are usually abbreviated in method names:
 //! - a = area
 //! - b = building
+//! - bss = bike share station
 //! - br = bus route
 //! - bs = bus stop
 //! - i = intersection
@@ -33,40 +34,52 @@ use geom::{Bounds, Distance, GPSBounds, Polygon};
 
 pub use crate::city::City;
 pub use crate::edits::{
-    EditCmd, EditEffects, EditIntersection, EditRoad, MapEdits, PermanentMapEdits,
+    EditCmd, EditEffects, EditIntersection, EditRoad, MapEdits, PermanentEditCmd, PermanentMapEdits,
 };
+pub use crate::lts::LevelOfTrafficStress;
 pub use crate::map::{DrivingSide, MapConfig};
 pub use crate::objects::area::{Area, AreaID, AreaType};
+pub use crate::objects::bike_share::{BikeShareStation, BikeShareStationID};
 pub use crate::objects::building::{
     Amenity, Building, BuildingID, BuildingType, NamePerLanguage, OffstreetParking,
 };
-pub use crate::objects::bus_stop::{BusRoute, BusRouteID, BusStop, BusStopID};
+pub use crate::objects::bus_stop::{
+    BusRoute, BusRouteID, BusStop, BusStopID, FarePayment, RouteVehicles,
+};
 pub use crate::objects::intersection::{Intersection, IntersectionID, IntersectionType};
 pub use crate::objects::lane::{
-    Lane, LaneID, LaneType, PARKING_LOT_SPOT_LENGTH, PARKING_SPOT_LENGTH,
+    ConditionalLaneType, Lane, LaneID, LaneType, PARKING_LOT_SPOT_LENGTH, PARKING_SPOT_LENGTH,
 };
 pub use crate::objects::parking_lot::{ParkingLot, ParkingLotID};
 pub use crate::objects::road::{DirectedRoadID, Direction, Road, RoadID};
 pub use crate::objects::stop_signs::{ControlStopSign, RoadWithStopSign};
-pub use crate::objects::traffic_signals::{ControlTrafficSignal, PhaseType, Stage};
+pub use crate::objects::traffic_signals::{
+    ControlTrafficSignal, PhaseType, RawPhase, RawPhaseType, RawTrafficSignal, RawTransitPriority,
+    Stage, TransitPriority,
+};
 pub use crate::objects::turn::{
     CompressedMovementID, Movement, MovementID, Turn, TurnID, TurnPriority, TurnType,
 };
-pub use crate::objects::zone::{AccessRestrictions, Zone};
+pub use crate::objects::zone::{AccessRestrictions, ConditionalAccess, Zone};
 pub use crate::pathfind::uber_turns::{IntersectionCluster, UberTurn, UberTurnGroup};
 use crate::pathfind::Pathfinder;
-pub use crate::pathfind::{Path, PathConstraints, PathRequest, PathStep};
+pub use crate::pathfind::{
+    BikePreference, Path, PathConstraints, PathRequest, PathStep, TravelTimes,
+};
+pub use crate::time_windows::TimeWindows;
 pub use crate::traversable::{Position, Traversable};
 
 mod city;
 pub mod connectivity;
 mod edits;
+mod lts;
 mod make;
 mod map;
 mod objects;
 pub mod osm;
 mod pathfind;
 pub mod raw;
+mod time_windows;
 mod traversable;
 
 // TODO Minimize uses of these!
@@ -96,6 +109,7 @@ pub struct Map {
     bus_routes: Vec<BusRoute>,
     areas: Vec<Area>,
     parking_lots: Vec<ParkingLot>,
+    bike_share_stations: Vec<BikeShareStation>,
     boundary_polygon: Polygon,
 
     // Note that border nodes belong in neither!
@@ -0,0 +1,232 @@
+//! Level of traffic stress (LTS) rates how comfortable a road is to bike along. This is a
+//! simplification of the criteria from "Low-Stress Bicycling and Network Connectivity" by Mekuria,
+//! Furth, and Nixon. See
+//! <https://transweb.sjsu.edu/research/Low-Stress-Bicycling-and-Network-Connectivity>.
+//! Everything is calculated from the current lanes, speed limits, and OSM tags, so the ratings
+//! always reflect map edits.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use geom::Speed;
+
+use crate::{osm, DirectedRoadID, Direction, DrivingSide, LaneType, Map, Road, TurnType};
+
+/// From least to most stressful
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum LevelOfTrafficStress {
+    /// LTS 1: Suitable for children
+    Children,
+    /// LTS 2: Tolerable for most adults
+    MostAdults,
+    /// LTS 3: Only for enthused and confident cyclists
+    Confident,
+    /// LTS 4: Only for strong and fearless cyclists
+    Fearless,
+}
+
+impl LevelOfTrafficStress {
+    pub fn all() -> Vec<LevelOfTrafficStress> {
+        vec![
+            LevelOfTrafficStress::Children,
+            LevelOfTrafficStress::MostAdults,
+            LevelOfTrafficStress::Confident,
+            LevelOfTrafficStress::Fearless,
+        ]
+    }
+
+    /// 1 to 4
+    pub fn number(self) -> usize {
+        match self {
+            LevelOfTrafficStress::Children => 1,
+            LevelOfTrafficStress::MostAdults => 2,
+            LevelOfTrafficStress::Confident => 3,
+            LevelOfTrafficStress::Fearless => 4,
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            LevelOfTrafficStress::Children => "suitable for children",
+            LevelOfTrafficStress::MostAdults => "tolerable for most adults",
+            LevelOfTrafficStress::Confident => "for confident cyclists",
+            LevelOfTrafficStress::Fearless => "for fearless cyclists",
+        }
+    }
+
+    // Speed limit thresholds used by all of the criteria
+    fn by_speed(speed: Speed, thresholds: [LevelOfTrafficStress; 4]) -> LevelOfTrafficStress {
+        if speed <= Speed::miles_per_hour(25.0) {
+            thresholds[0]
+        } else if speed <= Speed::miles_per_hour(30.0) {
+            thresholds[1]
+        } else if speed <= Speed::miles_per_hour(35.0) {
+            thresholds[2]
+        } else {
+            thresholds[3]
+        }
+    }
+}
+
+impl fmt::Display for LevelOfTrafficStress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LTS {}", self.number())
+    }
+}
+
+impl Road {
+    /// The worse of the ratings for biking in either direction along this road.
+    pub fn level_of_traffic_stress(&self, map: &Map) -> LevelOfTrafficStress {
+        vec![Direction::Fwd, Direction::Back]
+            .into_iter()
+            .filter(|dir| self.lanes_ltr.iter().any(|(_, d, _)| d == dir))
+            .map(|dir| DirectedRoadID { id: self.id, dir }.level_of_traffic_stress(map))
+            .max()
+            .unwrap_or(LevelOfTrafficStress::Children)
+    }
+
+    fn num_motor_vehicle_lanes(&self) -> usize {
+        self.lanes_ltr
+            .iter()
+            .filter(|(_, _, lt)| *lt == LaneType::Driving || *lt == LaneType::Bus)
+            .count()
+    }
+}
+
+impl DirectedRoadID {
+    /// Rates biking along this road in one direction, away from intersections.
+    pub fn level_of_traffic_stress(self, map: &Map) -> LevelOfTrafficStress {
+        use LevelOfTrafficStress::*;
+
+        let r = map.get_r(self.id);
+        let total_lanes = r.num_motor_vehicle_lanes();
+        // Car-free paths, and bike lanes physically separated from traffic
+        if total_lanes == 0
+            || r.osm_tags.is_any("cycleway", vec!["track"])
+            || r.osm_tags.is_any("cycleway:both", vec!["track"])
+            || r.osm_tags.is_any("cycleway:left", vec!["track"])
+            || r.osm_tags.is_any("cycleway:right", vec!["track"])
+        {
+            return Children;
+        }
+
+        let lanes = r.lanes_ltr();
+        let lanes_this_way = lanes
+            .iter()
+            .filter(|(_, dir, lt)| {
+                *dir == self.dir && (*lt == LaneType::Driving || *lt == LaneType::Bus)
+            })
+            .count();
+        if let Some(idx) = lanes
+            .iter()
+            .position(|(_, dir, lt)| *dir == self.dir && *lt == LaneType::Biking)
+        {
+            // Riding next to parked cars risks getting doored
+            let next_to_parking = (idx > 0 && lanes[idx - 1].2 == LaneType::Parking)
+                || lanes
+                    .get(idx + 1)
+                    .map(|(_, _, lt)| *lt == LaneType::Parking)
+                    .unwrap_or(false);
+            let thresholds = match (lanes_this_way, next_to_parking) {
+                (0..=1, false) => [Children, Children, Confident, Fearless],
+                (0..=1, true) => [Children, MostAdults, Confident, Fearless],
+                (2, false) => [MostAdults, MostAdults, Confident, Fearless],
+                _ => [Confident, Confident, Confident, Fearless],
+            };
+            return LevelOfTrafficStress::by_speed(r.speed_limit, thresholds);
+        }
+
+        // Mixed traffic. Local streets are usually narrow without a centerline, so cars go slowly
+        // and can pass easily.
+        let local = r.get_rank() == osm::RoadRank::Local;
+        let thresholds = if total_lanes <= 3 {
+            if local {
+                [Children, MostAdults, Fearless, Fearless]
+            } else {
+                [MostAdults, Confident, Fearless, Fearless]
+            }
+        } else if total_lanes <= 5 {
+            [Confident, Fearless, Fearless, Fearless]
+        } else {
+            [Fearless, Fearless, Fearless, Fearless]
+        };
+        LevelOfTrafficStress::by_speed(r.speed_limit, thresholds)
+    }
+
+    /// Rates biking along this road and through the intersection at its end. Turn lanes at the
+    /// curb force cyclists to mix with turning traffic, and at unsignalized intersections,
+    /// cyclists who must stop have to find a gap to cross busy roads.
+    pub fn approach_level_of_traffic_stress(self, map: &Map) -> LevelOfTrafficStress {
+        let mut stress = self.level_of_traffic_stress(map);
+        let r = map.get_r(self.id);
+        let i = map.get_i(self.dst_i(map));
+
+        // Lanes going this way, starting from the curb
+        let lanes = r.lanes_ltr();
+        let mut curb_first: Vec<_> = lanes
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, dir, _))| *dir == self.dir)
+            .map(|(idx, (l, _, lt))| (idx, *l, *lt))
+            .collect();
+        let mean_idx = curb_first.iter().map(|(idx, _, _)| *idx).sum::<usize>() as f64
+            / (curb_first.len().max(1) as f64);
+        if mean_idx > ((lanes.len() - 1) as f64) / 2.0 {
+            curb_first.reverse();
+        }
+        let curb_turn = match map.get_config().driving_side {
+            DrivingSide::Right => TurnType::Right,
+            DrivingSide::Left => TurnType::Left,
+        };
+        if let Some((_, l, _)) = curb_first
+            .iter()
+            .find(|(_, _, lt)| *lt == LaneType::Driving || *lt == LaneType::Bus)
+        {
+            let turns = map.get_turns_from_lane(*l);
+            let turn_lane = curb_first
+                .iter()
+                .filter(|(_, _, lt)| *lt == LaneType::Driving || *lt == LaneType::Bus)
+                .count()
+                > 1
+                && !turns.is_empty()
+                && turns.iter().all(|t| t.turn_type == curb_turn);
+            let bike_lane_further_in = curb_first
+                .iter()
+                .skip_while(|(_, lane, _)| lane != l)
+                .any(|(_, _, lt)| *lt == LaneType::Biking);
+            if turn_lane || bike_lane_further_in {
+                stress = stress.max(LevelOfTrafficStress::Confident);
+            }
+        }
+
+        if !i.is_traffic_signal() {
+            if let Some(ss) = map.maybe_get_stop_sign(i.id) {
+                if ss.roads.get(&r.id).map(|x| x.must_stop).unwrap_or(false) {
+                    for (other, other_ss) in &ss.roads {
+                        if *other != r.id && !other_ss.must_stop {
+                            stress = stress.max(crossing_stress(map.get_r(*other)));
+                        }
+                    }
+                }
+            }
+        }
+
+        stress
+    }
+}
+
+/// How stressful it is to cross a road that doesn't have to stop.
+fn crossing_stress(r: &Road) -> LevelOfTrafficStress {
+    use LevelOfTrafficStress::*;
+
+    let total_lanes = r.num_motor_vehicle_lanes();
+    let thresholds = if total_lanes <= 3 {
+        [Children, MostAdults, Confident, Fearless]
+    } else if total_lanes <= 5 {
+        [MostAdults, Confident, Fearless, Fearless]
+    } else {
+        [Confident, Fearless, Fearless, Fearless]
+    };
+    LevelOfTrafficStress::by_speed(r.speed_limit, thresholds)
+}
@@ -0,0 +1,64 @@
+use std::collections::HashSet;
+
+use abstutil::Timer;
+use geom::{Distance, HashablePt2D};
+
+use crate::make::match_points_to_lanes;
+use crate::objects::building::sidewalk_to_bike;
+use crate::raw::RawBikeShareStation;
+use crate::{BikeShareStation, BikeShareStationID, Map, NamePerLanguage};
+
+/// When OSM doesn't say how many docks a station has, assume this many.
+const DEFAULT_CAPACITY: usize = 10;
+
+/// Match each station from OSM to the nearest sidewalk that's next to a lane bikes can use.
+pub fn make_all_bike_share_stations(
+    input: &Vec<RawBikeShareStation>,
+    map: &Map,
+    timer: &mut Timer,
+) -> Vec<BikeShareStation> {
+    timer.start("convert bike share stations");
+    let query: HashSet<HashablePt2D> = input.iter().map(|s| s.point.to_hashable()).collect();
+    let sidewalk_pts = match_points_to_lanes(
+        map.get_bounds(),
+        query,
+        map.all_lanes(),
+        |l| l.is_walkable(),
+        // Same as buildings, so bikes don't spawn right at the end of a lane
+        Distance::meters(7.5),
+        Distance::meters(100.0),
+        timer,
+    );
+
+    let mut results = Vec::new();
+    for orig in input {
+        let (biking_pos, sidewalk_pos) = match sidewalk_pts
+            .get(&orig.point.to_hashable())
+            .and_then(|pos| sidewalk_to_bike(*pos, map))
+        {
+            Some(pair) => pair,
+            None => {
+                timer.warn(format!(
+                    "Skipping bike share station {}; it's not near a sidewalk next to a bike lane",
+                    orig.osm_id
+                ));
+                continue;
+            }
+        };
+        let capacity = match orig.osm_tags.get("capacity").map(|x| x.parse::<usize>()) {
+            Some(Ok(n)) if n > 0 => n,
+            _ => DEFAULT_CAPACITY,
+        };
+        results.push(BikeShareStation {
+            id: BikeShareStationID(results.len()),
+            osm_id: orig.osm_id,
+            name: NamePerLanguage::new(&orig.osm_tags),
+            point: orig.point,
+            capacity,
+            sidewalk_pos,
+            biking_pos,
+        });
+    }
+    timer.stop("convert bike share stations");
+    results
+}
@@ -0,0 +1,270 @@
+//! Coordinates the offsets of traffic signals along a corridor, so that a platoon of vehicles
+//! moving at a steady speed keeps hitting green lights -- a "green wave." Since one offset per
+//! signal can't perfectly serve both directions, this picks a compromise between them.
+
+use geom::{Distance, Duration, Speed};
+
+use crate::{ControlTrafficSignal, IntersectionID, Map, RoadID};
+
+/// See `ControlTrafficSignal::make_green_wave`.
+pub fn make_green_wave(
+    map: &Map,
+    corridor: &[IntersectionID],
+    speed: Speed,
+    cycle_length: Option<Duration>,
+) -> Result<Vec<ControlTrafficSignal>, String> {
+    if corridor.len() < 2 {
+        return Err("A corridor needs at least two traffic signals".to_string());
+    }
+    if speed <= Speed::ZERO {
+        return Err("The progression speed must be positive".to_string());
+    }
+    let mut signals = Vec::new();
+    for i in corridor {
+        if signals.iter().any(|ts: &ControlTrafficSignal| ts.id == *i) {
+            return Err(format!("{} is in the corridor twice", i));
+        }
+        signals.push(
+            map.maybe_get_traffic_signal(*i)
+                .ok_or_else(|| format!("{} isn't a traffic signal", i))?
+                .clone(),
+        );
+    }
+
+    if let Some(cycle) = cycle_length {
+        for ts in &mut signals {
+            retime(ts, cycle)?;
+        }
+    }
+    let cycle = cycle_length_of(&signals[0]);
+    for ts in &signals {
+        if cycle_length_of(ts) != cycle {
+            return Err(format!(
+                "{} has a cycle length of {}, but {} has {}. Pick a shared cycle length.",
+                ts.id,
+                cycle_length_of(ts),
+                signals[0].id,
+                cycle
+            ));
+        }
+    }
+    let cycle = cycle.inner_seconds();
+
+    // How long does it take to reach each signal from the first one?
+    let mut paths: Vec<Vec<RoadID>> = Vec::new();
+    let mut arrivals = vec![0.0];
+    for pair in corridor.windows(2) {
+        let path = map
+            .simple_path_btwn(pair[0], pair[1])
+            .ok_or_else(|| format!("No path between {} and {}", pair[0], pair[1]))?;
+        let mut dist = Distance::ZERO;
+        for r in &path {
+            dist += map.get_r(*r).center_pts.length();
+        }
+        arrivals.push(arrivals.last().unwrap() + (dist / speed).inner_seconds());
+        paths.push(path);
+    }
+
+    let mut fwd_starts = Vec::new();
+    let mut back_starts = Vec::new();
+    for (idx, ts) in signals.iter().enumerate() {
+        let from = if idx == 0 {
+            None
+        } else {
+            paths[idx - 1].last().cloned()
+        };
+        let to = paths.get(idx).and_then(|path| path.first().cloned());
+        fwd_starts.push(stage_start(ts, corridor_stage(ts, from, to)));
+        back_starts.push(stage_start(ts, corridor_stage(ts, to, from)));
+    }
+
+    let offsets = ideal_offsets(&arrivals, &fwd_starts, &back_starts, cycle);
+    // Only relative offsets matter, so leave the first signal alone.
+    let shift = signals[0].offset.inner_seconds() - offsets[0];
+    for (ts, offset) in signals.iter_mut().zip(offsets) {
+        // Round, because offsets are exported as whole seconds
+        ts.offset = Duration::seconds(modulo(offset + shift, cycle).round() % cycle);
+    }
+    Ok(signals)
+}
+
+/// Given when a platoon leaving the first signal reaches each signal, and how far into the cycle
+/// the stage serving the corridor starts in each direction, find the offset for each signal.
+fn ideal_offsets(
+    arrivals: &[f64],
+    fwd_starts: &[f64],
+    back_starts: &[f64],
+    cycle: f64,
+) -> Vec<f64> {
+    let total = *arrivals.last().unwrap();
+
+    // Where in the cycle should each signal be, to catch each platoon exactly when the stage
+    // serving it turns green? A signal's offset is how far into its cycle it is at midnight, so
+    // the stage starting at `start` turns green at time t when (t + offset) % cycle == start.
+    let mut ideal_fwd = Vec::new();
+    let mut ideal_back = Vec::new();
+    for idx in 0..arrivals.len() {
+        ideal_fwd.push(modulo(fwd_starts[idx] - arrivals[idx], cycle));
+        // The platoon in the other direction leaves the last signal at some time, which is picked
+        // below.
+        ideal_back.push(modulo(back_starts[idx] - (total - arrivals[idx]), cycle));
+    }
+
+    // Try every departure time for the returning platoon, and keep the one that's closest to the
+    // forward wave everywhere.
+    let mut best = (0.0, f64::MAX);
+    let mut departure = 0.0;
+    while departure < cycle {
+        let cost: f64 = ideal_fwd
+            .iter()
+            .zip(ideal_back.iter())
+            .map(|(fwd, back)| circular_distance(*fwd, modulo(back - departure, cycle), cycle))
+            .sum();
+        if cost < best.1 {
+            best = (departure, cost);
+        }
+        departure += 1.0;
+    }
+
+    ideal_fwd
+        .iter()
+        .zip(ideal_back.iter())
+        .map(|(fwd, back)| circular_midpoint(*fwd, modulo(back - best.0, cycle), cycle))
+        .collect()
+}
+
+fn cycle_length_of(ts: &ControlTrafficSignal) -> Duration {
+    ts.stages
+        .iter()
+        .map(|s| s.phase_type.simple_duration())
+        .sum()
+}
+
+/// Proportionally scale all stages to fit the cycle length, but respect minimum crosswalk times.
+fn retime(ts: &mut ControlTrafficSignal, cycle: Duration) -> Result<(), String> {
+    let current = cycle_length_of(ts);
+    let mut durations = Vec::new();
+    for (idx, stage) in ts.stages.iter().enumerate() {
+        let scaled = (stage.phase_type.simple_duration() * (cycle / current))
+            .inner_seconds()
+            .round();
+        durations.push(Duration::seconds(scaled).max(ts.get_min_crossing_time(idx)));
+    }
+
+    // Rounding and minimum times may throw off the total. Make up the difference with the longest
+    // stage.
+    let total: Duration = durations.iter().cloned().sum();
+    let longest = (0..durations.len())
+        .max_by_key(|idx| durations[*idx])
+        .unwrap();
+    let adjusted = durations[longest] + cycle - total;
+    if adjusted < ts.get_min_crossing_time(longest) || adjusted <= Duration::ZERO {
+        return Err(format!(
+            "A cycle length of {} is too short for {}; the crosswalks need more time",
+            cycle, ts.id
+        ));
+    }
+    durations[longest] = adjusted;
+
+    for (stage, dt) in ts.stages.iter_mut().zip(durations) {
+        stage.phase_type.set_min_duration(dt);
+    }
+    Ok(())
+}
+
+/// Which stage serves traffic traveling along the corridor, entering from one road and leaving by
+/// another? At the ends of the corridor, only one of the roads is known.
+fn corridor_stage(ts: &ControlTrafficSignal, from: Option<RoadID>, to: Option<RoadID>) -> usize {
+    let candidates: Vec<_> = ts
+        .movements
+        .keys()
+        .filter(|m| {
+            !m.crosswalk
+                && from.map(|r| m.from.id == r).unwrap_or(true)
+                && to.map(|r| m.to.id == r).unwrap_or(true)
+        })
+        .collect();
+    let mut best = (0, 0);
+    for (idx, stage) in ts.stages.iter().enumerate() {
+        let mut score = 0;
+        for m in &candidates {
+            if stage.protected_movements.contains(*m) {
+                score += 2;
+            } else if stage.yield_movements.contains(*m) {
+                score += 1;
+            }
+        }
+        if score > best.1 {
+            best = (idx, score);
+        }
+    }
+    best.0
+}
+
+/// Seconds into the cycle when the stage begins
+fn stage_start(ts: &ControlTrafficSignal, stage: usize) -> f64 {
+    ts.stages[0..stage]
+        .iter()
+        .map(|s| s.phase_type.simple_duration().inner_seconds())
+        .sum()
+}
+
+fn modulo(x: f64, cycle: f64) -> f64 {
+    x.rem_euclid(cycle)
+}
+
+fn circular_distance(a: f64, b: f64, cycle: f64) -> f64 {
+    let d = modulo(b - a, cycle);
+    d.min(cycle - d)
+}
+
+fn circular_midpoint(a: f64, b: f64, cycle: f64) -> f64 {
+    let d = modulo(b - a, cycle);
+    if d <= cycle / 2.0 {
+        modulo(a + d / 2.0, cycle)
+    } else {
+        modulo(a - (cycle - d) / 2.0, cycle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "got {}, expected {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn two_signals_half_a_cycle_apart() {
+        // 300m at 10m/s takes 30s, exactly half of a 60s cycle. Both directions get a perfect
+        // green wave if the second signal is 30s ahead of the first.
+        let travel = (Distance::meters(300.0) / Speed::meters_per_second(10.0)).inner_seconds();
+        let offsets = ideal_offsets(&[0.0, travel], &[0.0, 0.0], &[0.0, 0.0], 60.0);
+        assert_close(modulo(offsets[1] - offsets[0], 60.0), 30.0);
+    }
+
+    #[test]
+    fn two_signals_with_different_corridor_stages() {
+        // The corridor is served by the second stage at the second signal, which starts 20s into
+        // the cycle. A platoon leaving the first signal arrives 30s later, so the second signal
+        // should start its cycle 10s behind the first.
+        let offsets = ideal_offsets(&[0.0, 30.0], &[0.0, 20.0], &[0.0, 20.0], 60.0);
+        assert_close(modulo(offsets[1] - offsets[0], 60.0), 50.0);
+    }
+
+    #[test]
+    fn midpoint_wraps_around() {
+        assert_close(circular_midpoint(10.0, 20.0, 60.0), 15.0);
+        // The short way between 50 and 10 crosses the end of the cycle
+        assert_close(circular_midpoint(50.0, 10.0, 60.0), 0.0);
+        assert_close(circular_midpoint(10.0, 50.0, 60.0), 0.0);
+        assert_close(circular_midpoint(55.0, 15.0, 60.0), 5.0);
+        assert_close(circular_distance(55.0, 5.0, 60.0), 10.0);
+    }
+}
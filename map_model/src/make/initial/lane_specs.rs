@@ -5,8 +5,8 @@ use abstutil::Tags;
 use geom::Distance;
 
 use crate::{
-    osm, Direction, DrivingSide, LaneType, MapConfig, NORMAL_LANE_THICKNESS,
-    SERVICE_ROAD_LANE_THICKNESS, SHOULDER_THICKNESS, SIDEWALK_THICKNESS,
+    osm, ConditionalLaneType, Direction, DrivingSide, LaneType, MapConfig, TimeWindows,
+    NORMAL_LANE_THICKNESS, SERVICE_ROAD_LANE_THICKNESS, SHOULDER_THICKNESS, SIDEWALK_THICKNESS,
 };
 
 #[derive(PartialEq)]
@@ -14,6 +14,7 @@ pub struct LaneSpec {
     pub lt: LaneType,
     pub dir: Direction,
     pub width: Distance,
+    pub conditional: Option<ConditionalLaneType>,
 }
 
 fn fwd(lt: LaneType) -> LaneSpec {
@@ -25,6 +26,7 @@ fn fwd(lt: LaneType) -> LaneSpec {
             LaneType::Shoulder => SHOULDER_THICKNESS,
             _ => NORMAL_LANE_THICKNESS,
         },
+        conditional: None,
     }
 }
 
@@ -37,6 +39,7 @@ fn back(lt: LaneType) -> LaneSpec {
             LaneType::Shoulder => SHOULDER_THICKNESS,
             _ => NORMAL_LANE_THICKNESS,
         },
+        conditional: None,
     }
 }
 
@@ -124,6 +127,21 @@ pub fn get_lane_specs_ltr(tags: &Tags, cfg: &MapConfig) -> Vec<LaneSpec> {
     let mut back_side: Vec<LaneSpec> = iter::repeat_with(|| back(driving_lane))
         .take(num_driving_back)
         .collect();
+    // 3rd Ave is only bus-only during the day
+    if driving_lane == LaneType::Bus && !tags.is("access", "no") {
+        if let Some(during) =
+            conditional_windows(tags, vec!["motor_vehicle:conditional".to_string()], &["no"])
+                .map(|windows| windows.complement())
+                .filter(|windows| !windows.is_empty())
+        {
+            for spec in fwd_side.iter_mut().chain(back_side.iter_mut()) {
+                spec.conditional = Some(ConditionalLaneType {
+                    lt: LaneType::Driving,
+                    during: during.clone(),
+                });
+            }
+        }
+    }
     // TODO Fix upstream. https://wiki.openstreetmap.org/wiki/Key:centre_turn_lane
     if tags.is("lanes:both_ways", "1") || tags.is("centre_turn_lane", "yes") {
         fwd_side.insert(0, fwd(LaneType::SharedLeftTurn));
@@ -176,6 +194,24 @@ pub fn get_lane_specs_ltr(tags: &Tags, cfg: &MapConfig) -> Vec<LaneSpec> {
             }
         }
     }
+    // Peak-hour bus lanes
+    let fwd_conditional_bus_spec = tags
+        .get("bus:lanes:forward:conditional")
+        .or_else(|| tags.get("psv:lanes:forward:conditional"))
+        .or_else(|| {
+            if oneway {
+                tags.get("bus:lanes:conditional")
+                    .or_else(|| tags.get("psv:lanes:conditional"))
+            } else {
+                None
+            }
+        });
+    add_conditional_bus_lanes(fwd_conditional_bus_spec, &mut fwd_side);
+    add_conditional_bus_lanes(
+        tags.get("bus:lanes:backward:conditional")
+            .or_else(|| tags.get("psv:lanes:backward:conditional")),
+        &mut back_side,
+    );
 
     if tags.is_any("cycleway", vec!["lane", "track"]) {
         fwd_side.push(fwd(LaneType::Biking));
@@ -214,10 +250,10 @@ pub fn get_lane_specs_ltr(tags: &Tags, cfg: &MapConfig) -> Vec<LaneSpec> {
         let parking_lane_back = tags.is_any(osm::PARKING_LEFT, has_parking.clone())
             || tags.is_any(osm::PARKING_BOTH, has_parking);
         if parking_lane_fwd {
-            fwd_side.push(fwd(LaneType::Parking));
+            fwd_side.push(parking_lane(tags, "right", fwd));
         }
         if parking_lane_back {
-            back_side.push(back(LaneType::Parking));
+            back_side.push(parking_lane(tags, "left", back));
         }
     }
 
@@ -287,6 +323,91 @@ pub fn get_lane_specs_ltr(tags: &Tags, cfg: &MapConfig) -> Vec<LaneSpec> {
     assemble_ltr(fwd_side, back_side, cfg.driving_side)
 }
 
+/// When do any of these conditional tags have one of these values?
+fn conditional_windows(tags: &Tags, keys: Vec<String>, values: &[&str]) -> Option<TimeWindows> {
+    let mut conditions = Vec::new();
+    for key in keys {
+        if let Some(tag) = tags.get(&key) {
+            for (value, condition) in TimeWindows::parse_conditional_tag(tag) {
+                if values.contains(&value.as_str()) {
+                    conditions.push(condition);
+                }
+            }
+        }
+    }
+    if conditions.is_empty() {
+        return None;
+    }
+    TimeWindows::parse(&conditions.join("; "))
+        .ok()
+        .filter(|windows| !windows.is_empty())
+}
+
+/// Tagged like "bus:lanes:conditional=|designated @ (Mo-Fr 07:00-09:00)". The spec must cover all
+/// of the driving lanes on one side of the road.
+fn add_conditional_bus_lanes(spec: Option<&String>, side: &mut Vec<LaneSpec>) {
+    let spec = if let Some(spec) = spec {
+        spec
+    } else {
+        return;
+    };
+    let offset = if side
+        .get(0)
+        .map(|lane| lane.lt == LaneType::SharedLeftTurn)
+        .unwrap_or(false)
+    {
+        1
+    } else {
+        0
+    };
+    for (value, condition) in TimeWindows::parse_conditional_tag(spec) {
+        let during = match TimeWindows::parse(&condition) {
+            Ok(during) if !during.is_empty() => during,
+            _ => continue,
+        };
+        let parts: Vec<&str> = value.split('|').collect();
+        if parts.len() != side.len() - offset {
+            continue;
+        }
+        for (idx, part) in parts.into_iter().enumerate() {
+            let lane = &mut side[idx + offset];
+            if part == "designated" && lane.lt == LaneType::Driving {
+                lane.conditional = Some(ConditionalLaneType {
+                    lt: LaneType::Bus,
+                    during: during.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// Parking lanes that become travel lanes at rush hour are tagged like
+/// "parking:lane:right:conditional=no_stopping @ (Mo-Fr 16:00-18:00)". These're modeled as travel
+/// lanes that allow parking the rest of the time.
+fn parking_lane(tags: &Tags, side: &str, make: fn(LaneType) -> LaneSpec) -> LaneSpec {
+    let keys = vec![
+        format!("parking:lane:{}:conditional", side),
+        "parking:lane:both:conditional".to_string(),
+        format!("parking:condition:{}:conditional", side),
+        "parking:condition:both:conditional".to_string(),
+    ];
+    let no_parking = ["no_parking", "no_stopping", "no_standing", "no"];
+    match conditional_windows(tags, keys, &no_parking) {
+        Some(travel) => {
+            let mut spec = make(LaneType::Driving);
+            let parking = travel.complement();
+            if !parking.is_empty() {
+                spec.conditional = Some(ConditionalLaneType {
+                    lt: LaneType::Parking,
+                    during: parking,
+                });
+            }
+            spec
+        }
+        None => make(LaneType::Parking),
+    }
+}
+
 fn assemble_ltr(
     mut fwd_side: Vec<LaneSpec>,
     mut back_side: Vec<LaneSpec>,
@@ -466,4 +587,40 @@ mod tests {
         }
         assert!(ok);
     }
+
+    #[test]
+    fn test_conditional_lanes() {
+        let cfg = MapConfig {
+            driving_side: DrivingSide::Right,
+            bikes_can_use_bus_lanes: true,
+            inferred_sidewalks: true,
+        };
+        let actual = get_lane_specs_ltr(
+            &tags(vec![
+                "lanes=2",
+                "oneway=yes",
+                "sidewalk=none",
+                "parking:lane:right=parallel",
+                "parking:lane:right:conditional=no_stopping @ (Mo-Fr 16:00-18:00)",
+                "bus:lanes:conditional=|designated @ (Mo-Fr 07:00-09:00)",
+            ]),
+            &cfg,
+        );
+        let conditional = actual
+            .iter()
+            .map(|s| {
+                s.conditional
+                    .as_ref()
+                    .map(|c| format!("{} {}", lt_to_char(c.lt), c.during.describe()))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            conditional,
+            vec![
+                None,
+                Some("B 07:00-09:00".to_string()),
+                Some("p 00:00-16:00, 18:00-24:00".to_string()),
+            ]
+        );
+    }
 }
@@ -4,18 +4,20 @@
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 use abstutil::{Parallelism, Tags, Timer};
-use geom::{Bounds, Distance, FindClosest, HashablePt2D, Speed, EPSILON_DIST};
+use geom::{Bounds, Distance, FindClosest, HashablePt2D, PolyLine, Speed, EPSILON_DIST};
 
 use crate::pathfind::Pathfinder;
-use crate::raw::{OriginalRoad, RawMap};
+use crate::raw::{OriginalRoad, RawMap, RawRoad};
 use crate::{
     connectivity, osm, AccessRestrictions, Area, AreaID, AreaType, ControlStopSign,
     ControlTrafficSignal, Direction, Intersection, IntersectionID, IntersectionType, Lane, LaneID,
     Map, MapEdits, Movement, PathConstraints, Position, Road, RoadID, Zone,
 };
 
+mod bike_share;
 mod bridges;
 mod buildings;
+pub mod green_wave;
 pub mod initial;
 mod medians;
 mod parking_lots;
@@ -51,6 +53,7 @@ impl Map {
             bus_routes: Vec::new(),
             areas: Vec::new(),
             parking_lots: Vec::new(),
+            bike_share_stations: Vec::new(),
             zones: Vec::new(),
             boundary_polygon: raw.boundary_polygon.clone(),
             stop_signs: BTreeMap::new(),
@@ -103,22 +106,25 @@ impl Map {
                 turn_restrictions: raw.roads[&r.id]
                     .turn_restrictions
                     .iter()
-                    .filter_map(|(rt, to)| {
+                    .filter_map(|(rt, to, scope)| {
                         // Missing roads are filtered (like service roads) or clipped out
-                        road_id_mapping.get(to).map(|to| (*rt, *to))
+                        road_id_mapping.get(to).map(|to| (*rt, *to, scope.clone()))
                     })
                     .collect(),
                 complicated_turn_restrictions: raw.roads[&r.id]
                     .complicated_turn_restrictions
                     .iter()
-                    .filter_map(|(via, to)| {
-                        if let (Some(via), Some(to)) =
-                            (road_id_mapping.get(via), road_id_mapping.get(to))
-                        {
-                            Some((*via, *to))
+                    .filter_map(|(rt, via, to, scope)| {
+                        let via_ids: Option<Vec<RoadID>> = via
+                            .iter()
+                            .map(|id| road_id_mapping.get(id).cloned())
+                            .collect();
+                        if let (Some(via), Some(to)) = (via_ids, road_id_mapping.get(to)) {
+                            Some((*rt, via, *to, scope.clone()))
                         } else {
                             timer.warn(format!(
-                                "Complicated turn restriction from {} has invalid via {} or dst {}",
+                                "Complicated turn restriction from {} has invalid via {:?} or dst \
+                                 {}",
                                 r.id, via, to
                             ));
                             None
@@ -128,6 +134,12 @@ impl Map {
                 orig_id: r.id,
                 lanes_ltr: Vec::new(),
                 center_pts: r.trimmed_center_pts.clone(),
+                elevation_profile: elevation_profile(
+                    &raw.roads[&r.id],
+                    &r.trimmed_center_pts,
+                    raw.intersections[&r.src_i].elevation,
+                    raw.intersections[&r.dst_i].elevation,
+                ),
                 src_i: i1,
                 dst_i: i2,
                 speed_limit: Speed::ZERO,
@@ -196,6 +208,7 @@ impl Map {
                     src_i,
                     dst_i,
                     lane_type: lane.lt,
+                    conditional_type: lane.conditional.clone(),
                     parent: road_id,
                     bus_stops: BTreeSet::new(),
                     driving_blackhole: false,
@@ -271,6 +284,9 @@ impl Map {
             timer,
         );
 
+        map.bike_share_stations =
+            bike_share::make_all_bike_share_stations(&raw.bike_share_stations, &map, timer);
+
         map.zones = Zone::make_all(&map);
 
         // Create medians first, so they wind up rendering underneath areas from OSM. Sometimes
@@ -402,3 +418,29 @@ fn match_points_to_lanes<F: Fn(&Lane) -> bool>(
         .flatten()
         .collect()
 }
+
+/// Matches elevation sampled along the original road to its trimmed center line. Samples are
+/// placed by how far along the original road they are, which is close enough, since trimming only
+/// shortens each end a bit.
+fn elevation_profile(
+    raw_road: &RawRoad,
+    center_pts: &PolyLine,
+    src_elevation: Distance,
+    dst_elevation: Distance,
+) -> Vec<(Distance, Distance)> {
+    let length = center_pts.length();
+    let mut profile = vec![(Distance::ZERO, src_elevation)];
+    if let Ok(orig) = PolyLine::new(raw_road.center_points.clone()) {
+        for (pt, elevation) in &raw_road.elevation {
+            if let Some((dist, _)) = orig.dist_along_of_point(*pt) {
+                let dist = (dist / orig.length()) * length;
+                if dist > Distance::ZERO && dist < length {
+                    profile.push((dist, *elevation));
+                }
+            }
+        }
+    }
+    profile.push((length, dst_elevation));
+    profile.sort_by_key(|(dist, _)| *dist);
+    profile
+}
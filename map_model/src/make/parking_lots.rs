@@ -62,7 +62,11 @@ pub fn make_all_parking_lots(
             let sidewalk_lane = sidewalk_pos.lane();
             if let Some(driving_pos) = map
                 .get_parent(sidewalk_lane)
-                .find_closest_lane(sidewalk_lane, |l| PathConstraints::Car.can_use(l, map), map)
+                .find_closest_lane(
+                    sidewalk_lane,
+                    |l| PathConstraints::Car.can_use_during(l, None, map),
+                    map,
+                )
                 .and_then(|l| {
                     sidewalk_pos
                         .equiv_pos(l, map)
@@ -24,7 +24,7 @@ pub fn get_possible_policies(
         .unwrap()
         .remove(&map.get_i(id).orig_id.0)
     {
-        match ControlTrafficSignal::import(raw, id, map) {
+        match ControlTrafficSignal::import(raw.into(), id, map) {
             Ok(ts) => {
                 results.push(("hand-mapped current real settings".to_string(), ts));
             }
@@ -95,6 +95,7 @@ fn new(id: IntersectionID, map: &Map) -> ControlTrafficSignal {
         stages: Vec::new(),
         offset: Duration::ZERO,
         movements: Movement::for_i(id, map).unwrap(),
+        transit_priority: None,
     }
 }
 
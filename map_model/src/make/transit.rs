@@ -139,6 +139,9 @@ fn make_route(
         end_border,
         spawn_times: default_spawn_times(),
         orig_spawn_times: default_spawn_times(),
+        vehicle_capacity: None,
+        vehicle_doors: None,
+        fare_payment: None,
     };
 
     let mut debug_route = format!("All parts of the route:");
@@ -260,7 +263,7 @@ impl Matcher {
             r.children(dir)
                 .iter()
                 .rev()
-                .find(|(l, _)| route_type.can_use(map.get_l(*l), map))
+                .find(|(l, _)| route_type.can_use_during(map.get_l(*l), None, map))
                 .ok_or_else(|| format!("{} {}, doesn't have a bus or driving lane", r.id, dir))?
                 .0,
         );
@@ -5,7 +5,6 @@ use nbez::{Bez3o, BezCurve, Point2d};
 use abstutil::Timer;
 use geom::{Distance, PolyLine, Pt2D};
 
-use crate::raw::RestrictionType;
 use crate::{Intersection, Lane, LaneID, Map, RoadID, Turn, TurnID, TurnType};
 
 /// Generate all driving and walking turns at an intersection, accounting for OSM turn restrictions.
@@ -20,7 +19,9 @@ pub fn make_all_turns(map: &Map, i: &Intersection, timer: &mut Timer) -> Vec<Tur
     let mut final_turns: Vec<Turn> = Vec::new();
     let mut filtered_turns: HashMap<LaneID, Vec<Turn>> = HashMap::new();
     for turn in unique_turns {
-        if !does_turn_pass_restrictions(&turn, i, map) {
+        // Restrictions that only apply to some vehicles or at some times are handled during
+        // pathfinding.
+        if turn.is_restricted(map, |scope| scope.is_unconditional()) {
             continue;
         }
 
@@ -141,36 +142,6 @@ fn is_turn_allowed(turn: &Turn, map: &Map) -> bool {
     }
 }
 
-fn does_turn_pass_restrictions(turn: &Turn, i: &Intersection, map: &Map) -> bool {
-    if turn.between_sidewalks() {
-        return true;
-    }
-
-    let src = map.get_parent(turn.id.src);
-    let dst = map.get_l(turn.id.dst).parent;
-
-    for (restriction, to) in &src.turn_restrictions {
-        // The restriction only applies to one direction of the road.
-        if !i.roads.contains(to) {
-            continue;
-        }
-        match restriction {
-            RestrictionType::BanTurns => {
-                if dst == *to {
-                    return false;
-                }
-            }
-            RestrictionType::OnlyAllowTurns => {
-                if dst != *to {
-                    return false;
-                }
-            }
-        }
-    }
-
-    true
-}
-
 fn make_vehicle_turns(i: &Intersection, map: &Map, timer: &mut Timer) -> Vec<Turn> {
     let mut turns = Vec::new();
 
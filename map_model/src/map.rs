@@ -10,10 +10,11 @@ use geom::{Bounds, Distance, GPSBounds, Polygon, Pt2D, Ring, Time};
 
 use crate::raw::{OriginalRoad, RawMap};
 use crate::{
-    osm, Area, AreaID, Building, BuildingID, BuildingType, BusRoute, BusRouteID, BusStop,
-    BusStopID, ControlStopSign, ControlTrafficSignal, Intersection, IntersectionID, Lane, LaneID,
-    LaneType, Map, MapEdits, MovementID, OffstreetParking, ParkingLot, ParkingLotID, Path,
-    PathConstraints, PathRequest, Pathfinder, Position, Road, RoadID, Turn, TurnID, TurnType, Zone,
+    osm, Area, AreaID, BikePreference, BikeShareStation, BikeShareStationID, Building, BuildingID,
+    BuildingType, BusRoute, BusRouteID, BusStop, BusStopID, ControlStopSign, ControlTrafficSignal,
+    Intersection, IntersectionID, Lane, LaneID, LevelOfTrafficStress, Map, MapEdits, MovementID,
+    OffstreetParking, ParkingLot, ParkingLotID, Path, PathConstraints, PathRequest, Pathfinder,
+    Position, Road, RoadID, TravelTimes, Turn, TurnID, TurnType, Zone,
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -83,6 +84,11 @@ impl Map {
                             prettyprint_usize(map.parking_lots.len()),
                             prettyprint_usize(serialized_size_bytes(&map.parking_lots))
                         );
+                        info!(
+                            "- {} bike share stations: {} bytes",
+                            prettyprint_usize(map.bike_share_stations.len()),
+                            prettyprint_usize(serialized_size_bytes(&map.bike_share_stations))
+                        );
                         info!(
                             "- {} zones: {} bytes",
                             prettyprint_usize(map.zones.len()),
@@ -147,6 +153,7 @@ impl Map {
             bus_routes: Vec::new(),
             areas: Vec::new(),
             parking_lots: Vec::new(),
+            bike_share_stations: Vec::new(),
             zones: Vec::new(),
             boundary_polygon: Ring::must_new(vec![
                 Pt2D::new(0.0, 0.0),
@@ -202,6 +209,10 @@ impl Map {
         &self.parking_lots
     }
 
+    pub fn all_bike_share_stations(&self) -> &Vec<BikeShareStation> {
+        &self.bike_share_stations
+    }
+
     pub fn all_zones(&self) -> &Vec<Zone> {
         &self.zones
     }
@@ -230,6 +241,10 @@ impl Map {
         self.parking_lots.get(id.0)
     }
 
+    pub fn maybe_get_bss(&self, id: BikeShareStationID) -> Option<&BikeShareStation> {
+        self.bike_share_stations.get(id.0)
+    }
+
     pub fn maybe_get_a(&self, id: AreaID) -> Option<&Area> {
         self.areas.get(id.0)
     }
@@ -283,6 +298,10 @@ impl Map {
         &self.parking_lots[id.0]
     }
 
+    pub fn get_bss(&self, id: BikeShareStationID) -> &BikeShareStation {
+        &self.bike_share_stations[id.0]
+    }
+
     pub fn get_stop_sign(&self, id: IntersectionID) -> &ControlStopSign {
         &self.stop_signs[&id]
     }
@@ -368,11 +387,27 @@ impl Map {
             .collect()
     }
 
+    /// The turns from a lane that some vehicle can use, obeying OSM turn restrictions. Turns
+    /// restricted only at certain times of day are always excluded.
     pub fn get_turns_for(&self, from: LaneID, constraints: PathConstraints) -> Vec<&Turn> {
+        self.get_turns_for_during(from, constraints, None)
+    }
+
+    /// Like `get_turns_for`, but if a time range is specified, turns restricted only at certain
+    /// times of day are excluded just when the restriction is in effect sometime during the range.
+    pub fn get_turns_for_during(
+        &self,
+        from: LaneID,
+        constraints: PathConstraints,
+        during: Option<(Time, Time)>,
+    ) -> Vec<&Turn> {
         let mut turns: Vec<&Turn> = self
             .get_next_turns_and_lanes(from, self.get_l(from).dst_i)
             .into_iter()
-            .filter(|(_, l)| constraints.can_use(l, self))
+            .filter(|(t, l)| {
+                constraints.can_use_during(l, during, self)
+                    && !t.is_restricted_for(constraints, during, self)
+            })
             .map(|(t, _)| t)
             .collect();
         // Sidewalks are bidirectional
@@ -485,10 +520,16 @@ impl Map {
     /// border.
     // TODO Making driving_connection do this.
     pub fn find_driving_lane_near_building(&self, b: BuildingID) -> LaneID {
+        self.find_lane_near_building(b, PathConstraints::Car)
+    }
+
+    /// Like `find_driving_lane_near_building`, but for other vehicles that can drive on some
+    /// subset of driving lanes.
+    pub fn find_lane_near_building(&self, b: BuildingID, constraints: PathConstraints) -> LaneID {
         let sidewalk = self.get_b(b).sidewalk();
         if let Some(l) = self.get_parent(sidewalk).find_closest_lane(
             sidewalk,
-            |l| PathConstraints::Car.can_use(l, self),
+            |l| constraints.can_use_during(l, None, self),
             self,
         ) {
             if !self.get_l(l).driving_blackhole {
@@ -515,12 +556,12 @@ impl Map {
             }
             let r = self.get_r(roads_queue.pop_front().unwrap());
 
-            for (l, lt) in r
+            for (l, _) in r
                 .children_forwards()
                 .into_iter()
                 .chain(r.children_backwards().into_iter())
             {
-                if lt == LaneType::Driving {
+                if constraints.can_use_during(self.get_l(l), None, self) {
                     if !self.get_l(l).driving_blackhole {
                         return l;
                     }
@@ -544,6 +585,12 @@ impl Map {
         assert!(!self.pathfinder_dirty);
         self.pathfinder.pathfind(req, self)
     }
+    /// Like `pathfind`, but for a vehicle departing at some time. If `set_travel_times` has been
+    /// called, this prices driving by the congestion observed around that time.
+    pub fn pathfind_at(&self, req: PathRequest, time: Time) -> Option<Path> {
+        assert!(!self.pathfinder_dirty);
+        self.pathfinder.pathfind_at(req, time, self)
+    }
     pub fn pathfind_avoiding_lanes(
         &self,
         req: PathRequest,
@@ -553,6 +600,37 @@ impl Map {
         self.pathfinder.pathfind_avoiding_lanes(req, avoid, self)
     }
 
+    /// Price driving by travel times observed in some simulation, instead of free-flow speeds. Only
+    /// `pathfind_at` uses these. Pass in `None` to go back to free-flow speeds.
+    pub fn set_travel_times(&mut self, travel_times: Option<TravelTimes>, timer: &mut Timer) {
+        let mut pathfinder = std::mem::replace(&mut self.pathfinder, Pathfinder::Dijkstra);
+        pathfinder.set_travel_times(self, travel_times, timer);
+        self.pathfinder = pathfinder;
+    }
+
+    /// Changes how cyclists choose routes. Affects all bike paths calculated afterwards.
+    pub fn set_bike_preference(&mut self, preference: BikePreference, timer: &mut Timer) {
+        let mut pathfinder = std::mem::replace(&mut self.pathfinder, Pathfinder::Dijkstra);
+        pathfinder.set_bike_preference(self, preference, timer);
+        self.pathfinder = pathfinder;
+    }
+
+    pub fn get_bike_preference(&self) -> BikePreference {
+        self.pathfinder.get_bike_preference()
+    }
+
+    /// Cyclists will avoid roads above this level of traffic stress, unless there's no other way.
+    /// Affects all bike paths calculated afterwards.
+    pub fn set_max_bike_lts(&mut self, max_lts: Option<LevelOfTrafficStress>, timer: &mut Timer) {
+        let mut pathfinder = std::mem::replace(&mut self.pathfinder, Pathfinder::Dijkstra);
+        pathfinder.set_max_bike_lts(self, max_lts, timer);
+        self.pathfinder = pathfinder;
+    }
+
+    pub fn get_max_bike_lts(&self) -> Option<LevelOfTrafficStress> {
+        self.pathfinder.get_max_bike_lts()
+    }
+
     pub fn should_use_transit(
         &self,
         start: Position,
@@ -561,6 +639,27 @@ impl Map {
         self.pathfinder.should_use_transit(self, start, end)
     }
 
+    /// Should somebody driving from `start` park in a lot and take transit the rest of the way to
+    /// `end`, a sidewalk position? If so, returns the lot and how to use transit from there.
+    pub fn should_park_and_ride(
+        &self,
+        start: Position,
+        end: Position,
+    ) -> Option<(ParkingLotID, (BusStopID, Option<BusStopID>, BusRouteID))> {
+        self.pathfinder.should_park_and_ride(self, start, end)
+    }
+
+    /// Should somebody biking from `start` lock up their bike at a stop and take transit the rest
+    /// of the way to `end`, a sidewalk position? If so, returns that stop and how to use transit
+    /// from there.
+    pub fn should_bike_and_ride(
+        &self,
+        start: Position,
+        end: Position,
+    ) -> Option<(BusStopID, (BusStopID, Option<BusStopID>, BusRouteID))> {
+        self.pathfinder.should_bike_and_ride(self, start, end)
+    }
+
     // None for SharedSidewalkCorners
     pub fn get_movement(&self, t: TurnID) -> Option<MovementID> {
         if let Some(ref ts) = self.maybe_get_traffic_signal(t.parent) {
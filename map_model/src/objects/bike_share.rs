@@ -0,0 +1,40 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_usize, serialize_usize};
+use geom::Pt2D;
+
+use crate::{osm, NamePerLanguage, Position};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BikeShareStationID(
+    #[serde(
+        serialize_with = "serialize_usize",
+        deserialize_with = "deserialize_usize"
+    )]
+    pub usize,
+);
+
+impl fmt::Display for BikeShareStationID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bike share station #{}", self.0)
+    }
+}
+
+/// A place to pick up and return shared bikes. Each station has a fixed number of docks, and a bike
+/// can't be returned to a station with all of its docks in use.
+#[derive(Serialize, Deserialize)]
+pub struct BikeShareStation {
+    pub id: BikeShareStationID,
+    pub osm_id: osm::OsmID,
+    pub name: Option<NamePerLanguage>,
+    pub point: Pt2D,
+    /// The number of docks
+    pub capacity: usize,
+
+    pub sidewalk_pos: Position,
+    /// Where bikes join and leave the closest lane they can use. It's on the same road as
+    /// sidewalk_pos.
+    pub biking_pos: Position,
+}
@@ -147,7 +147,7 @@ impl Building {
     pub fn driving_connection(&self, map: &Map) -> Option<(Position, PolyLine)> {
         let lane = map.get_parent(self.sidewalk()).find_closest_lane(
             self.sidewalk(),
-            |l| PathConstraints::Car.can_use(l, map),
+            |l| PathConstraints::Car.can_use_during(l, None, map),
             map,
         )?;
         // TODO Do we need to insist on this buffer, now that we can make cars gradually appear?
@@ -201,10 +201,10 @@ impl Building {
     }
 }
 
-fn sidewalk_to_bike(sidewalk_pos: Position, map: &Map) -> Option<(Position, Position)> {
+pub(crate) fn sidewalk_to_bike(sidewalk_pos: Position, map: &Map) -> Option<(Position, Position)> {
     let lane = map.get_parent(sidewalk_pos.lane()).find_closest_lane(
         sidewalk_pos.lane(),
-        |l| !l.biking_blackhole && PathConstraints::Bike.can_use(l, map),
+        |l| !l.biking_blackhole && PathConstraints::Bike.can_use_during(l, None, map),
         map,
     )?;
     // No buffer needed
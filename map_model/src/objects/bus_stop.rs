@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 use abstutil::{deserialize_usize, serialize_usize};
 use geom::Time;
 
+use crate::objects::building::sidewalk_to_bike;
 use crate::{osm, LaneID, Map, PathConstraints, PathRequest, Position};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
@@ -67,6 +68,43 @@ pub struct BusRoute {
     /// Explicitly store whatever the original was, since this can't be reconstructed without side
     /// input.
     pub orig_spawn_times: Vec<Time>,
+    /// How many passengers fit in each vehicle serving this route. Only set by map edits; if None,
+    /// the simulation uses a default for buses or trains.
+    pub vehicle_capacity: Option<usize>,
+    /// How many doors passengers can use on each vehicle. Only set by map edits; if None, the
+    /// simulation uses a default for buses or trains.
+    pub vehicle_doors: Option<usize>,
+    /// How passengers pay, which limits which doors they can board through. Only set by map
+    /// edits; if None, buses use front-door boarding and trains use all-door boarding.
+    pub fare_payment: Option<FarePayment>,
+}
+
+/// The editable properties of the vehicles serving one route. OSM and GTFS don't describe these,
+/// so every route starts with the defaults.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct RouteVehicles {
+    pub capacity: Option<usize>,
+    pub doors: Option<usize>,
+    pub fare_payment: Option<FarePayment>,
+}
+
+/// How passengers pay their fare when boarding transit. This affects how long vehicles have to
+/// dwell at stops.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum FarePayment {
+    /// Everybody boards through the front door and pays the operator.
+    FrontDoor,
+    /// Passengers pay before boarding or tap a card at any door, so they can board through every
+    /// door.
+    AllDoor,
+}
+
+impl BusStop {
+    /// Where somebody can lock up a bike before catching transit here. Returns (biking position,
+    /// sidewalk position). None if there's no lane for bikes on the same road.
+    pub fn biking_connection(&self, map: &Map) -> Option<(Position, Position)> {
+        sidewalk_to_bike(self.sidewalk_pos, map)
+    }
 }
 
 impl BusRoute {
@@ -94,6 +132,14 @@ impl BusRoute {
         steps
     }
 
+    pub fn vehicles(&self) -> RouteVehicles {
+        RouteVehicles {
+            capacity: self.vehicle_capacity,
+            doors: self.vehicle_doors,
+            fare_payment: self.fare_payment,
+        }
+    }
+
     pub fn plural_noun(&self) -> &'static str {
         if self.route_type == PathConstraints::Bus {
             "buses"
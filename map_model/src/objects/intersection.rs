@@ -94,7 +94,7 @@ impl Intersection {
     pub fn get_incoming_lanes(&self, map: &Map, constraints: PathConstraints) -> Vec<LaneID> {
         self.incoming_lanes
             .iter()
-            .filter(move |l| constraints.can_use(map.get_l(**l), map))
+            .filter(move |l| constraints.can_use_during(map.get_l(**l), None, map))
             .cloned()
             .collect()
     }
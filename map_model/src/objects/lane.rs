@@ -4,10 +4,11 @@ use std::fmt;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_usize, serialize_usize, wraparound_get};
-use geom::{Distance, Line, PolyLine, Polygon, Pt2D, Ring};
+use geom::{Distance, Line, PolyLine, Polygon, Pt2D, Ring, Time};
 
 use crate::{
-    osm, BusStopID, DirectedRoadID, Direction, IntersectionID, Map, Road, RoadID, TurnType,
+    osm, BusStopID, DirectedRoadID, Direction, IntersectionID, Map, Road, RoadID, TimeWindows,
+    TurnType,
 };
 
 /// Bit longer than the longest car.
@@ -104,12 +105,33 @@ impl LaneType {
     }
 }
 
+/// Some lanes are used differently at certain times of day, like a peak-hour bus lane, or a
+/// parking lane that becomes a travel lane at rush hour.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConditionalLaneType {
+    /// The lane's type during these windows. The rest of the time, it's the lane's usual type.
+    pub lt: LaneType,
+    pub during: TimeWindows,
+}
+
+impl ConditionalLaneType {
+    /// Turns and traffic queues are built from a lane's usual type, so a lane must usually carry
+    /// moving vehicles to switch to any type. Parking lanes that become travel lanes are modeled
+    /// as travel lanes that allow parking outside of rush hour.
+    pub fn is_allowed(usual: LaneType, lt: LaneType) -> bool {
+        let moving = vec![LaneType::Driving, LaneType::Bus, LaneType::Biking];
+        usual != lt && moving.contains(&usual) && (moving.contains(&lt) || lt == LaneType::Parking)
+    }
+}
+
 /// A road segment is broken down into individual lanes, which have a LaneType.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Lane {
     pub id: LaneID,
     pub parent: RoadID,
     pub lane_type: LaneType,
+    /// If present, the lane is a different type at certain times of day
+    pub conditional_type: Option<ConditionalLaneType>,
     pub lane_center_pts: PolyLine,
     pub width: Distance,
 
@@ -179,7 +201,7 @@ impl Lane {
     // TODO different types for each lane type might be reasonable
 
     pub fn number_parking_spots(&self) -> usize {
-        assert_eq!(self.lane_type, LaneType::Parking);
+        assert!(self.lane_types_during(None).contains(&LaneType::Parking));
         // No spots next to intersections
         let spots = (self.length() / PARKING_SPOT_LENGTH).floor() - 2.0;
         if spots >= 1.0 {
@@ -189,6 +211,37 @@ impl Lane {
         }
     }
 
+    /// The types this lane has sometime during a range of time, or at any time of day if the range
+    /// is None. Usually this is just `lane_type`.
+    pub fn lane_types_during(&self, during: Option<(Time, Time)>) -> Vec<LaneType> {
+        let cond = if let Some(ref cond) = self.conditional_type {
+            cond
+        } else {
+            return vec![self.lane_type];
+        };
+        match during {
+            Some((start, end)) => {
+                let mut types = Vec::new();
+                if !cond.during.covers(start, end) {
+                    types.push(self.lane_type);
+                }
+                if cond.during.overlaps(start, end) {
+                    types.push(cond.lt);
+                }
+                types
+            }
+            None => vec![self.lane_type, cond.lt],
+        }
+    }
+
+    /// The lane's type at some moment
+    pub fn lane_type_at(&self, time: Time) -> LaneType {
+        match self.conditional_type {
+            Some(ref cond) if cond.during.contains(time) => cond.lt,
+            _ => self.lane_type,
+        }
+    }
+
     pub fn is_driving(&self) -> bool {
         self.lane_type == LaneType::Driving
     }
@@ -230,6 +283,50 @@ impl Lane {
         }
     }
 
+    /// (distance along the lane, elevation), following the lane from src_i to dst_i.
+    pub fn elevation_profile(&self, map: &Map) -> Vec<(Distance, Distance)> {
+        let r = map.get_r(self.parent);
+        let road_length = r.center_pts.length();
+        let mut profile: Vec<(Distance, Distance)> = r
+            .elevation_profile
+            .iter()
+            .map(|(dist, elevation)| ((*dist / road_length) * self.length(), *elevation))
+            .collect();
+        if r.dir(self.id) == Direction::Back {
+            profile.reverse();
+            for (dist, _) in &mut profile {
+                *dist = self.length() - *dist;
+            }
+        }
+        profile
+    }
+
+    /// Returns the total (climb, descent) following the lane from src_i to dst_i.
+    pub fn elevation_change(&self, map: &Map) -> (Distance, Distance) {
+        let (climb, descent) = map.get_r(self.parent).elevation_change();
+        if map.get_r(self.parent).dir(self.id) == Direction::Fwd {
+            (climb, descent)
+        } else {
+            (descent, climb)
+        }
+    }
+
+    /// How much longer a cyclist takes to cross this lane than if it were flat. This is less than 1
+    /// for lanes that are mostly downhill.
+    pub fn bike_time_factor(&self, map: &Map) -> f64 {
+        if self.length() == Distance::ZERO {
+            return 1.0;
+        }
+        let mut total = Distance::ZERO;
+        for pair in self.elevation_profile(map).windows(2) {
+            let run = pair[1].0 - pair[0].0;
+            if run > Distance::ZERO {
+                total += run / bike_speed_factor((pair[1].1 - pair[0].1) / run);
+            }
+        }
+        total / self.length()
+    }
+
     pub fn get_turn_restrictions(&self, road: &Road) -> Option<BTreeSet<TurnType>> {
         if !self.is_driving() {
             return None;
@@ -365,3 +462,14 @@ impl Lane {
         Some((Ring::new(pts).ok()?.to_polygon(), visited))
     }
 }
+
+/// How fast a cyclist rides on some grade, relative to flat ground. Uphill, they slow down quickly;
+/// downhill, they coast somewhat faster.
+fn bike_speed_factor(percent_grade: f64) -> f64 {
+    if percent_grade >= 0.0 {
+        // Half speed on a 10% grade
+        1.0 / (1.0 + 10.0 * percent_grade)
+    } else {
+        (1.0 - 3.0 * percent_grade).min(1.5)
+    }
+}
@@ -6,10 +6,10 @@ use serde::{Deserialize, Serialize};
 use abstutil::{deserialize_usize, serialize_usize, Tags};
 use geom::{Distance, PolyLine, Polygon, Speed};
 
-use crate::raw::{OriginalRoad, RestrictionType};
+use crate::raw::{OriginalRoad, RestrictionScope, RestrictionType};
 use crate::{
-    osm, AccessRestrictions, BusStopID, IntersectionID, Lane, LaneID, LaneType, Map,
-    PathConstraints, Zone,
+    osm, AccessRestrictions, BusStopID, ConditionalAccess, IntersectionID, Lane, LaneID, LaneType,
+    Map, PathConstraints, TimeWindows, Zone,
 };
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -95,9 +95,11 @@ pub struct Road {
     pub id: RoadID,
     pub osm_tags: Tags,
     /// self is 'from'
-    pub turn_restrictions: Vec<(RestrictionType, RoadID)>,
-    /// self is 'from'. (via, to). Only BanTurns.
-    pub complicated_turn_restrictions: Vec<(RoadID, RoadID)>,
+    pub turn_restrictions: Vec<(RestrictionType, RoadID, RestrictionScope)>,
+    /// self is 'from'. (restriction, via, to, scope). The via roads are in order, starting from
+    /// self.
+    pub complicated_turn_restrictions:
+        Vec<(RestrictionType, Vec<RoadID>, RoadID, RestrictionScope)>,
     pub orig_id: OriginalRoad,
     pub speed_limit: Speed,
     pub access_restrictions: AccessRestrictions,
@@ -110,6 +112,8 @@ pub struct Road {
     /// road orientation. No edits ever change this.
     // TODO Maybe deprecated in favor of get_left_side?
     pub center_pts: PolyLine,
+    /// (distance along center_pts, elevation), sorted by distance. Always covers both ends.
+    pub elevation_profile: Vec<(Distance, Distance)>,
     pub src_i: IntersectionID,
     pub dst_i: IntersectionID,
 }
@@ -146,7 +150,11 @@ impl Road {
     }
 
     pub fn parking_to_driving(&self, parking: LaneID, map: &Map) -> Option<LaneID> {
-        self.find_closest_lane(parking, |l| l.is_driving(), map)
+        self.find_closest_lane(
+            parking,
+            |l| PathConstraints::Car.can_use_during(l, None, map),
+            map,
+        )
     }
 
     pub(crate) fn speed_limit_from_osm(&self) -> Speed {
@@ -306,8 +314,8 @@ impl Road {
         stops
     }
 
-    /// Returns [-1.0, 1.0]. 0 is flat, positive is uphill, negative is downhill.
-    // TODO Or do we care about the total up/down along the possibly long road?
+    /// Returns [-1.0, 1.0]. 0 is flat, positive is uphill, negative is downhill. This only looks at
+    /// the endpoints; see elevation_change for the total up and down along the road.
     pub fn percent_grade(&self, map: &Map) -> f64 {
         let rise = map.get_i(self.dst_i).elevation - map.get_i(self.src_i).elevation;
         let run = self.center_pts.length();
@@ -324,6 +332,36 @@ impl Road {
         grade
     }
 
+    /// The elevation at some distance along center_pts, interpolated between samples.
+    pub fn elevation_at(&self, dist: Distance) -> Distance {
+        let mut prev = self.elevation_profile[0];
+        for (d, e) in &self.elevation_profile {
+            if *d >= dist {
+                if *d == prev.0 {
+                    return *e;
+                }
+                return prev.1 + (*e - prev.1) * ((dist - prev.0) / (*d - prev.0));
+            }
+            prev = (*d, *e);
+        }
+        prev.1
+    }
+
+    /// Returns the total (climb, descent) following center_pts from src_i to dst_i.
+    pub fn elevation_change(&self) -> (Distance, Distance) {
+        let mut climb = Distance::ZERO;
+        let mut descent = Distance::ZERO;
+        for pair in self.elevation_profile.windows(2) {
+            let rise = pair[1].1 - pair[0].1;
+            if rise > Distance::ZERO {
+                climb += rise;
+            } else {
+                descent -= rise;
+            }
+        }
+        (climb, descent)
+    }
+
     pub fn is_light_rail(&self) -> bool {
         self.lanes_ltr().len() == 1 && self.lanes_ltr()[0].2 == LaneType::LightRail
     }
@@ -354,7 +392,8 @@ impl Road {
         let allow_through_traffic = if self.osm_tags.is("access", "private") {
             EnumSet::new()
         } else if self.osm_tags.is(osm::HIGHWAY, "living_street") {
-            let mut allow = PathConstraints::Pedestrian | PathConstraints::Bike;
+            let mut allow =
+                PathConstraints::Pedestrian | PathConstraints::Bike | PathConstraints::Scooter;
             if self.osm_tags.is("psv", "yes") || self.osm_tags.is("bus", "yes") {
                 allow |= PathConstraints::Bus;
             }
@@ -362,9 +401,45 @@ impl Road {
         } else {
             EnumSet::all()
         };
+
+        // School streets and the like are tagged "motor_vehicle:conditional=no @ (08:00-09:00)".
+        // Vehicles explicitly allowed, like "bus=yes", are exempt.
+        let mut exempt = EnumSet::new();
+        for (key, value) in self.osm_tags.inner() {
+            if value == "yes" || value == "designated" {
+                exempt |= PathConstraints::from_osm_vehicle(key);
+            }
+        }
+        let mut conditional = Vec::new();
+        for (key, value) in self.osm_tags.inner() {
+            let vehicles = match key.strip_suffix(":conditional") {
+                Some("access") => RestrictionScope::all_vehicles(),
+                Some(vehicle) => PathConstraints::from_osm_vehicle(vehicle),
+                None => continue,
+            } - exempt;
+            if vehicles.is_empty() {
+                continue;
+            }
+            for (restriction, condition) in TimeWindows::parse_conditional_tag(value) {
+                if !vec!["no", "private", "destination"].contains(&restriction.as_str()) {
+                    continue;
+                }
+                if let Ok(during) = TimeWindows::parse(&condition) {
+                    if !during.is_empty() {
+                        conditional.push(ConditionalAccess {
+                            during,
+                            allow_through_traffic: allow_through_traffic - vehicles,
+                            cap_vehicles_per_hour: None,
+                        });
+                    }
+                }
+            }
+        }
+
         AccessRestrictions {
             allow_through_traffic,
             cap_vehicles_per_hour: None,
+            conditional,
         }
     }
 
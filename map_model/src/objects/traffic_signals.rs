@@ -6,8 +6,8 @@ use serde::{Deserialize, Serialize};
 use abstutil::{deserialize_btreemap, retain_btreeset, serialize_btreemap, Timer};
 use geom::{Distance, Duration, Speed};
 
+use crate::make::green_wave::make_green_wave;
 use crate::make::traffic_signals::{brute_force, get_possible_policies};
-use crate::objects::traffic_signals::PhaseType::{Adaptive, Fixed};
 use crate::raw::OriginalRoad;
 use crate::{
     osm, CompressedMovementID, DirectedRoadID, Direction, IntersectionID, Map, Movement,
@@ -32,6 +32,21 @@ pub struct ControlTrafficSignal {
         deserialize_with = "deserialize_btreemap"
     )]
     pub movements: BTreeMap<MovementID, Movement>,
+    /// If set, buses and trains get priority on some movements.
+    #[serde(default)]
+    pub transit_priority: Option<TransitPriority>,
+}
+
+/// Transit signal priority: when a bus or train is detected approaching on one of `movements`,
+/// the signal can hold the stage serving it green a little longer, or cut a conflicting stage
+/// short so the vehicle gets a green sooner.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TransitPriority {
+    pub movements: BTreeSet<MovementID>,
+    /// The most that one stage can be extended past its normal end.
+    pub max_green_extension: Duration,
+    /// The most that one stage can be shortened. Crosswalks still get their minimum time.
+    pub max_early_green: Duration,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -41,6 +56,21 @@ pub struct Stage {
     // TODO Not renaming this, because this is going to change radically in
     // https://github.com/dabreegster/abstreet/pull/298 anyway
     pub phase_type: PhaseType,
+    // The pedestrian settings are missing from older clients and saved signals, so they default
+    // to off.
+    /// At the start of the stage, vehicles are held for this long, giving pedestrians in the
+    /// crosswalks a head start. Zero means there's no leading pedestrian interval.
+    #[serde(default)]
+    pub leading_pedestrian_interval: Duration,
+    /// For this long at the end of the stage, the "don't walk" sign flashes. Pedestrians already
+    /// in the crosswalk finish crossing, but nobody new starts.
+    #[serde(default)]
+    pub flashing_dont_walk: Duration,
+    /// If true, the crosswalks in this stage only get a walk signal when a pedestrian has pushed
+    /// the button before the stage begins. A stage with nothing but crosswalks is skipped entirely
+    /// when nobody is waiting.
+    #[serde(default)]
+    pub ped_push_button: bool,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -50,15 +80,126 @@ pub enum PhaseType {
     /// repeat the stage entirely.
     // TODO This is a silly policy, but a start towards variable timers.
     Adaptive(Duration),
+    /// Run for at least `min_green`. After that, keep extending the stage by `gap` as long as
+    /// vehicles are still approaching or agents are waiting to make a protected movement, but
+    /// never past `max_green`. When no demand is detected, the stage "gaps out" and ends early.
+    Actuated {
+        min_green: Duration,
+        max_green: Duration,
+        gap: Duration,
+    },
+    /// Run for at least `min_green`. Then pick the stage with the highest pressure to run next --
+    /// the number of agents waiting to use its protected movements, minus the number of vehicles
+    /// already on the lanes those movements lead to. This may repeat the same stage in increments
+    /// of `min_green`, but a stage can't run continuously for longer than `max_green`.
+    MaxPressure {
+        min_green: Duration,
+        max_green: Duration,
+    },
 }
 
 impl PhaseType {
+    /// How long the stage lasts. For policies with a variable length, this is the minimum time.
     // TODO Maybe don't have this; force callers to acknowledge different policies
     pub fn simple_duration(&self) -> Duration {
         match self {
             PhaseType::Fixed(d) | PhaseType::Adaptive(d) => *d,
+            PhaseType::Actuated { min_green, .. } | PhaseType::MaxPressure { min_green, .. } => {
+                *min_green
+            }
+        }
+    }
+
+    /// The longest the stage could possibly last before switching to a different stage.
+    pub fn max_duration(&self) -> Duration {
+        match self {
+            PhaseType::Fixed(d) | PhaseType::Adaptive(d) => *d,
+            PhaseType::Actuated { max_green, .. } | PhaseType::MaxPressure { max_green, .. } => {
+                *max_green
+            }
+        }
+    }
+
+    /// Changes the minimum duration of the stage, preserving the rest of the policy. The maximum
+    /// is raised if needed.
+    pub fn set_min_duration(&mut self, time: Duration) {
+        match self {
+            PhaseType::Fixed(d) | PhaseType::Adaptive(d) => {
+                *d = time;
+            }
+            PhaseType::Actuated {
+                min_green,
+                max_green,
+                ..
+            }
+            | PhaseType::MaxPressure {
+                min_green,
+                max_green,
+            } => {
+                *min_green = time;
+                *max_green = (*max_green).max(time);
+            }
+        }
+    }
+
+    /// A short description, like "30s (actuated up to 1m)"
+    pub fn describe(&self) -> String {
+        match self {
+            PhaseType::Fixed(d) => d.to_string(),
+            PhaseType::Adaptive(d) => format!("{} (adaptive)", d),
+            PhaseType::Actuated {
+                min_green,
+                max_green,
+                gap,
+            } => format!("{} (actuated up to {}, {} gap)", min_green, max_green, gap),
+            PhaseType::MaxPressure {
+                min_green,
+                max_green,
+            } => format!("{} (max-pressure up to {})", min_green, max_green),
         }
     }
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        match self {
+            PhaseType::Fixed(_) | PhaseType::Adaptive(_) => {}
+            PhaseType::Actuated {
+                min_green,
+                max_green,
+                gap,
+            } => {
+                if *min_green <= Duration::ZERO || *gap <= Duration::ZERO {
+                    return Err(format!(
+                        "Actuated stage needs a positive min_green and gap, not {} and {}",
+                        min_green, gap
+                    ));
+                }
+                if max_green < min_green {
+                    return Err(format!(
+                        "Actuated stage has max_green {} less than min_green {}",
+                        max_green, min_green
+                    ));
+                }
+            }
+            PhaseType::MaxPressure {
+                min_green,
+                max_green,
+            } => {
+                if *min_green <= Duration::ZERO {
+                    return Err(format!(
+                        "Max-pressure stage needs a positive min_green, not {}",
+                        min_green
+                    ));
+                }
+                if max_green < min_green {
+                    return Err(format!(
+                        "Max-pressure stage has max_green {} less than min_green {}",
+                        max_green, min_green
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl ControlTrafficSignal {
@@ -77,6 +218,21 @@ impl ControlTrafficSignal {
     ) -> Vec<(String, ControlTrafficSignal)> {
         get_possible_policies(map, id, timer)
     }
+
+    /// Given an ordered list of traffic signals along a corridor, calculate new offsets so that
+    /// vehicles traveling at `speed` in either direction hit as many green lights as possible. If
+    /// `cycle_length` is specified, the stages of every signal are first rescaled to match it;
+    /// otherwise, all of the signals must already share a cycle length. Returns the modified
+    /// signals, in the same order, without changing the map.
+    pub fn make_green_wave(
+        map: &Map,
+        corridor: &[IntersectionID],
+        speed: Speed,
+        cycle_length: Option<Duration>,
+    ) -> Result<Vec<ControlTrafficSignal>, String> {
+        make_green_wave(map, corridor, speed, cycle_length)
+    }
+
     // TODO tmp
     pub fn brute_force(map: &Map, id: IntersectionID) {
         brute_force(map, id)
@@ -120,6 +276,21 @@ impl ControlTrafficSignal {
         }
         let mut stage_index = 0;
         for stage in &self.stages {
+            stage.phase_type.validate()?;
+            let ped_intervals = stage.leading_pedestrian_interval + stage.flashing_dont_walk;
+            if ped_intervals > Duration::ZERO && ped_intervals >= stage.phase_type.simple_duration()
+            {
+                return Err(format!(
+                    "Stage {} of {} lasts {}, which doesn't leave time after the leading \
+                     pedestrian interval ({}) and flashing don't walk ({})",
+                    stage_index,
+                    self.id,
+                    stage.phase_type.simple_duration(),
+                    stage.leading_pedestrian_interval,
+                    stage.flashing_dont_walk
+                ));
+            }
+
             // Do any of the priority movements in one stage conflict?
             for m1 in stage.protected_movements.iter().map(|m| &self.movements[m]) {
                 for m2 in stage.protected_movements.iter().map(|m| &self.movements[m]) {
@@ -151,9 +322,40 @@ impl ControlTrafficSignal {
             }
             stage_index += 1;
         }
+
+        if let Some(ref tsp) = self.transit_priority {
+            if tsp.movements.is_empty() {
+                return Err(format!(
+                    "Transit signal priority at {} doesn't apply to any movements",
+                    self.id
+                ));
+            }
+            for m in &tsp.movements {
+                if !self.movements.contains_key(m) {
+                    return Err(format!(
+                        "Transit signal priority at {} refers to {:?}, which doesn't exist",
+                        self.id, m
+                    ));
+                }
+                if m.crosswalk {
+                    return Err(format!(
+                        "Transit signal priority at {} can't apply to a crosswalk",
+                        self.id
+                    ));
+                }
+            }
+        }
         Ok(())
     }
 
+    /// Does this signal give buses and trains priority on this movement?
+    pub fn has_transit_priority(&self, m: MovementID) -> bool {
+        self.transit_priority
+            .as_ref()
+            .map(|tsp| tsp.movements.contains(&m))
+            .unwrap_or(false)
+    }
+
     /// Returns true if this did anything
     pub fn convert_to_ped_scramble(&mut self) -> bool {
         let orig = self.clone();
@@ -243,9 +445,19 @@ impl Stage {
             yield_movements: BTreeSet::new(),
             // TODO Set a default
             phase_type: PhaseType::Fixed(Duration::seconds(30.0)),
+            leading_pedestrian_interval: Duration::ZERO,
+            flashing_dont_walk: Duration::ZERO,
+            ped_push_button: false,
         }
     }
 
+    /// Does this stage only serve pedestrians?
+    pub fn only_crosswalks(&self) -> bool {
+        !self.protected_movements.is_empty()
+            && self.yield_movements.is_empty()
+            && self.protected_movements.iter().all(|m| m.crosswalk)
+    }
+
     pub fn could_be_protected(
         &self,
         m1: MovementID,
@@ -303,22 +515,96 @@ impl Stage {
                 .ceil(),
         );
         if time > self.phase_type.simple_duration() {
-            self.phase_type = match self.phase_type {
-                PhaseType::Adaptive(_) => Adaptive(time),
-                PhaseType::Fixed(_) => Fixed(time),
-            };
+            self.phase_type.set_min_duration(time);
+        }
+    }
+}
+
+/// How traffic signals are stored in map edits. This is a superset of the seattle_traffic_signals
+/// schema, which only describes fixed and adaptive stages, so the hand-mapped data and edits saved
+/// before other policies existed read the same way.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RawTrafficSignal {
+    pub intersection_osm_node_id: i64,
+    pub phases: Vec<RawPhase>,
+    pub offset_seconds: usize,
+    // Missing from seattle_traffic_signals and older edits
+    #[serde(default)]
+    pub transit_priority: Option<RawTransitPriority>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RawPhase {
+    pub protected_turns: Vec<seattle_traffic_signals::Turn>,
+    pub permitted_turns: Vec<seattle_traffic_signals::Turn>,
+    pub phase_type: RawPhaseType,
+    // Missing from seattle_traffic_signals and older edits
+    #[serde(default)]
+    pub leading_pedestrian_interval_seconds: usize,
+    #[serde(default)]
+    pub flashing_dont_walk_seconds: usize,
+    #[serde(default)]
+    pub ped_push_button: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RawTransitPriority {
+    pub turns: Vec<seattle_traffic_signals::Turn>,
+    pub max_green_extension_seconds: usize,
+    pub max_early_green_seconds: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RawPhaseType {
+    Fixed(usize),
+    Adaptive(usize),
+    Actuated {
+        min_green_seconds: usize,
+        max_green_seconds: usize,
+        gap_seconds: usize,
+    },
+    MaxPressure {
+        min_green_seconds: usize,
+        max_green_seconds: usize,
+    },
+}
+
+impl From<seattle_traffic_signals::TrafficSignal> for RawTrafficSignal {
+    fn from(ts: seattle_traffic_signals::TrafficSignal) -> RawTrafficSignal {
+        RawTrafficSignal {
+            intersection_osm_node_id: ts.intersection_osm_node_id,
+            phases: ts
+                .phases
+                .into_iter()
+                .map(|p| RawPhase {
+                    protected_turns: p.protected_turns.into_iter().collect(),
+                    permitted_turns: p.permitted_turns.into_iter().collect(),
+                    phase_type: match p.phase_type {
+                        seattle_traffic_signals::PhaseType::Fixed(d) => RawPhaseType::Fixed(d),
+                        seattle_traffic_signals::PhaseType::Adaptive(d) => {
+                            RawPhaseType::Adaptive(d)
+                        }
+                    },
+                    leading_pedestrian_interval_seconds: 0,
+                    flashing_dont_walk_seconds: 0,
+                    ped_push_button: false,
+                })
+                .collect(),
+            offset_seconds: ts.offset_seconds,
+            transit_priority: None,
         }
     }
 }
 
 impl ControlTrafficSignal {
-    pub fn export(&self, map: &Map) -> seattle_traffic_signals::TrafficSignal {
-        seattle_traffic_signals::TrafficSignal {
+    /// Converts to the format stored in map edits.
+    pub fn to_raw(&self, map: &Map) -> RawTrafficSignal {
+        RawTrafficSignal {
             intersection_osm_node_id: map.get_i(self.id).orig_id.0,
             phases: self
                 .stages
                 .iter()
-                .map(|s| seattle_traffic_signals::Phase {
+                .map(|s| RawPhase {
                     protected_turns: s
                         .protected_movements
                         .iter()
@@ -330,21 +616,101 @@ impl ControlTrafficSignal {
                         .map(|t| export_movement(t, map))
                         .collect(),
                     phase_type: match s.phase_type {
-                        PhaseType::Fixed(d) => {
-                            seattle_traffic_signals::PhaseType::Fixed(d.inner_seconds() as usize)
-                        }
+                        PhaseType::Fixed(d) => RawPhaseType::Fixed(d.inner_seconds() as usize),
                         PhaseType::Adaptive(d) => {
-                            seattle_traffic_signals::PhaseType::Adaptive(d.inner_seconds() as usize)
+                            RawPhaseType::Adaptive(d.inner_seconds() as usize)
                         }
+                        PhaseType::Actuated {
+                            min_green,
+                            max_green,
+                            gap,
+                        } => RawPhaseType::Actuated {
+                            min_green_seconds: min_green.inner_seconds() as usize,
+                            max_green_seconds: max_green.inner_seconds() as usize,
+                            gap_seconds: gap.inner_seconds() as usize,
+                        },
+                        PhaseType::MaxPressure {
+                            min_green,
+                            max_green,
+                        } => RawPhaseType::MaxPressure {
+                            min_green_seconds: min_green.inner_seconds() as usize,
+                            max_green_seconds: max_green.inner_seconds() as usize,
+                        },
                     },
+                    leading_pedestrian_interval_seconds: s
+                        .leading_pedestrian_interval
+                        .inner_seconds()
+                        as usize,
+                    flashing_dont_walk_seconds: s.flashing_dont_walk.inner_seconds() as usize,
+                    ped_push_button: s.ped_push_button,
                 })
                 .collect(),
             offset_seconds: self.offset.inner_seconds() as usize,
+            transit_priority: self
+                .transit_priority
+                .as_ref()
+                .map(|tsp| RawTransitPriority {
+                    turns: tsp
+                        .movements
+                        .iter()
+                        .map(|m| export_movement(m, map))
+                        .collect(),
+                    max_green_extension_seconds: tsp.max_green_extension.inner_seconds() as usize,
+                    max_early_green_seconds: tsp.max_early_green.inner_seconds() as usize,
+                }),
         }
     }
 
+    /// Converts to the seattle_traffic_signals format, for contributing to that repo. Fails for
+    /// signals using features the schema can't describe.
+    pub fn export(&self, map: &Map) -> Result<seattle_traffic_signals::TrafficSignal, String> {
+        let raw = self.to_raw(map);
+        if raw.transit_priority.is_some() {
+            return Err(format!(
+                "{} has transit signal priority, which seattle_traffic_signals can't describe",
+                self.id
+            ));
+        }
+        let mut phases = Vec::new();
+        for (idx, p) in raw.phases.into_iter().enumerate() {
+            let phase_type = match p.phase_type {
+                RawPhaseType::Fixed(d) => seattle_traffic_signals::PhaseType::Fixed(d),
+                RawPhaseType::Adaptive(d) => seattle_traffic_signals::PhaseType::Adaptive(d),
+                RawPhaseType::Actuated { .. } | RawPhaseType::MaxPressure { .. } => {
+                    return Err(format!(
+                        "Stage {} of {} uses {}, which seattle_traffic_signals can't describe",
+                        idx + 1,
+                        self.id,
+                        self.stages[idx].phase_type.describe()
+                    ));
+                }
+            };
+            if p.leading_pedestrian_interval_seconds != 0
+                || p.flashing_dont_walk_seconds != 0
+                || p.ped_push_button
+            {
+                return Err(format!(
+                    "Stage {} of {} has pedestrian timing that seattle_traffic_signals can't \
+                     describe",
+                    idx + 1,
+                    self.id
+                ));
+            }
+            phases.push(seattle_traffic_signals::Phase {
+                protected_turns: p.protected_turns.into_iter().collect(),
+                permitted_turns: p.permitted_turns.into_iter().collect(),
+                phase_type,
+            });
+        }
+        Ok(seattle_traffic_signals::TrafficSignal {
+            intersection_osm_node_id: raw.intersection_osm_node_id,
+            phases,
+            offset_seconds: raw.offset_seconds,
+        })
+    }
+
     pub(crate) fn import(
-        raw: seattle_traffic_signals::TrafficSignal,
+        raw: RawTrafficSignal,
         id: IntersectionID,
         map: &Map,
     ) -> Result<ControlTrafficSignal, String> {
@@ -378,23 +744,57 @@ impl ControlTrafficSignal {
                     protected_movements,
                     yield_movements: permitted_movements,
                     phase_type: match s.phase_type {
-                        seattle_traffic_signals::PhaseType::Fixed(d) => {
-                            PhaseType::Fixed(Duration::seconds(d as f64))
-                        }
-                        seattle_traffic_signals::PhaseType::Adaptive(d) => {
+                        RawPhaseType::Fixed(d) => PhaseType::Fixed(Duration::seconds(d as f64)),
+                        RawPhaseType::Adaptive(d) => {
                             PhaseType::Adaptive(Duration::seconds(d as f64))
                         }
+                        RawPhaseType::Actuated {
+                            min_green_seconds,
+                            max_green_seconds,
+                            gap_seconds,
+                        } => PhaseType::Actuated {
+                            min_green: Duration::seconds(min_green_seconds as f64),
+                            max_green: Duration::seconds(max_green_seconds as f64),
+                            gap: Duration::seconds(gap_seconds as f64),
+                        },
+                        RawPhaseType::MaxPressure {
+                            min_green_seconds,
+                            max_green_seconds,
+                        } => PhaseType::MaxPressure {
+                            min_green: Duration::seconds(min_green_seconds as f64),
+                            max_green: Duration::seconds(max_green_seconds as f64),
+                        },
                     },
+                    leading_pedestrian_interval: Duration::seconds(
+                        s.leading_pedestrian_interval_seconds as f64,
+                    ),
+                    flashing_dont_walk: Duration::seconds(s.flashing_dont_walk_seconds as f64),
+                    ped_push_button: s.ped_push_button,
                 });
             } else {
                 return Err(errors.join("; "));
             }
         }
+        let transit_priority = match raw.transit_priority {
+            Some(tsp) => {
+                let mut movements = BTreeSet::new();
+                for t in tsp.turns {
+                    movements.insert(import_movement(t, map)?);
+                }
+                Some(TransitPriority {
+                    movements,
+                    max_green_extension: Duration::seconds(tsp.max_green_extension_seconds as f64),
+                    max_early_green: Duration::seconds(tsp.max_early_green_seconds as f64),
+                })
+            }
+            None => None,
+        };
         let ts = ControlTrafficSignal {
             id,
             stages,
             offset: Duration::seconds(raw.offset_seconds as f64),
             movements: Movement::for_i(id, map).unwrap(),
+            transit_priority,
         };
         ts.validate()?;
         Ok(ts)
@@ -4,9 +4,10 @@ use std::fmt;
 use serde::{Deserialize, Serialize};
 
 use abstutil::MultiMap;
-use geom::{Angle, Distance, PolyLine, Pt2D};
+use geom::{Angle, Distance, PolyLine, Pt2D, Time};
 
-use crate::{DirectedRoadID, Direction, IntersectionID, LaneID, Map};
+use crate::raw::{RestrictionScope, RestrictionType};
+use crate::{DirectedRoadID, Direction, IntersectionID, LaneID, Map, PathConstraints};
 
 /// Turns are uniquely identified by their (src, dst) lanes and their parent intersection.
 /// Intersection is needed to distinguish crosswalks that exist at two ends of a sidewalk.
@@ -114,6 +115,57 @@ impl Turn {
         self.turn_type == TurnType::SharedSidewalkCorner || self.turn_type == TurnType::Crosswalk
     }
 
+    /// Does an OSM turn restriction (via this intersection) forbid some vehicle from doing this
+    /// turn sometime in a time range? Without a time range, time-conditional restrictions always
+    /// count. Restrictions via entire roads are handled by `UberTurn`.
+    pub fn is_restricted_for(
+        &self,
+        constraints: PathConstraints,
+        during: Option<(Time, Time)>,
+        map: &Map,
+    ) -> bool {
+        self.is_restricted(map, |scope| scope.applies(constraints, during))
+    }
+
+    /// Checks the turn restrictions that `applies` says are in effect.
+    pub(crate) fn is_restricted<F: Fn(&RestrictionScope) -> bool>(
+        &self,
+        map: &Map,
+        applies: F,
+    ) -> bool {
+        if self.between_sidewalks() {
+            return false;
+        }
+
+        let src = map.get_parent(self.id.src);
+        let dst = map.get_l(self.id.dst).parent;
+        let i = map.get_i(self.id.parent);
+
+        let mut only_allow = false;
+        let mut allowed = false;
+        for (restriction, to, scope) in &src.turn_restrictions {
+            // The restriction only applies to one direction of the road.
+            if !i.roads.contains(to) || !applies(scope) {
+                continue;
+            }
+            match restriction {
+                RestrictionType::BanTurns => {
+                    if dst == *to {
+                        return true;
+                    }
+                }
+                // If there are several of these, any of them is allowed
+                RestrictionType::OnlyAllowTurns => {
+                    only_allow = true;
+                    if dst == *to {
+                        allowed = true;
+                    }
+                }
+            }
+        }
+        only_allow && !allowed
+    }
+
     // TODO Maybe precompute this.
     /// penalties for (lane types, lane-changing, slow lane)
     pub fn penalty(&self, map: &Map) -> (usize, usize, usize) {
@@ -4,6 +4,7 @@
 //! 2) Stay Healthy Streets, where most car traffic is banned, except for trips beginning/ending in
 //!    the zone
 //! 3) Congestion capping, where only so many cars per hour can enter the zone
+//! 4) School streets, closed to through traffic only while children arrive and leave
 
 use std::collections::BTreeSet;
 
@@ -11,15 +12,29 @@ use enumset::EnumSet;
 use petgraph::graphmap::DiGraphMap;
 use serde::{Deserialize, Serialize};
 
+use geom::Time;
+
 use crate::pathfind::{driving_cost, walking_cost, WalkingNode};
 use crate::{
-    IntersectionID, LaneID, Map, Path, PathConstraints, PathRequest, PathStep, RoadID, TurnID,
+    IntersectionID, LaneID, Map, Path, PathConstraints, PathRequest, PathStep, RoadID, TimeWindows,
+    TurnID,
 };
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct AccessRestrictions {
     pub allow_through_traffic: EnumSet<PathConstraints>,
     pub cap_vehicles_per_hour: Option<usize>,
+    /// Rules that replace the ones above at certain times of day
+    pub conditional: Vec<ConditionalAccess>,
+}
+
+/// Access restrictions in effect only at certain times of day. If several overlap, the most
+/// restrictive combination applies.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ConditionalAccess {
+    pub during: TimeWindows,
+    pub allow_through_traffic: EnumSet<PathConstraints>,
+    pub cap_vehicles_per_hour: Option<usize>,
 }
 
 impl AccessRestrictions {
@@ -27,7 +42,53 @@ impl AccessRestrictions {
         AccessRestrictions {
             allow_through_traffic: EnumSet::all(),
             cap_vehicles_per_hour: None,
+            conditional: Vec::new(),
+        }
+    }
+
+    /// The vehicles allowed through for an entire time range. If the range is None, the vehicles
+    /// allowed through at any time of day.
+    pub fn allow_through_traffic_during(
+        &self,
+        during: Option<(Time, Time)>,
+    ) -> EnumSet<PathConstraints> {
+        let mut allow = EnumSet::all();
+        let mut covered = false;
+        for cond in &self.conditional {
+            let applies = match during {
+                Some((start, end)) => cond.during.overlaps(start, end),
+                None => true,
+            };
+            if applies {
+                allow &= cond.allow_through_traffic;
+            }
+            if let Some((start, end)) = during {
+                covered |= cond.during.covers(start, end);
+            }
+        }
+        if !covered {
+            allow &= self.allow_through_traffic;
         }
+        allow
+    }
+
+    /// The cap in effect at some moment
+    pub fn cap_vehicles_per_hour_at(&self, time: Time) -> Option<usize> {
+        self.conditional
+            .iter()
+            .filter(|cond| cond.during.contains(time))
+            .filter_map(|cond| cond.cap_vehicles_per_hour)
+            .chain(self.cap_vehicles_per_hour)
+            .min()
+    }
+
+    /// Is there a cap on vehicles at some time of day?
+    pub fn ever_capped(&self) -> bool {
+        self.cap_vehicles_per_hour.is_some()
+            || self
+                .conditional
+                .iter()
+                .any(|cond| cond.cap_vehicles_per_hour.is_some())
     }
 }
 
@@ -71,7 +132,7 @@ impl Zone {
         let mut graph: DiGraphMap<LaneID, TurnID> = DiGraphMap::new();
         for r in &self.members {
             for l in map.get_r(*r).all_lanes() {
-                if req.constraints.can_use(map.get_l(l), map) {
+                if req.constraints.can_use_during(map.get_l(l), None, map) {
                     for turn in map.get_turns_for(l, req.constraints) {
                         if !self.borders.contains(&turn.id.parent) {
                             graph.add_edge(turn.id.src, turn.id.dst, turn.id);
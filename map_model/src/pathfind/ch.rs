@@ -1,27 +1,65 @@
 //! Uses https://github.com/easbar/fast_paths. Slower creation during map importing, but very fast
 //! queries.
 
+use std::collections::{BTreeMap, BTreeSet};
+
+use enumset::EnumSet;
 use serde::{Deserialize, Serialize};
 
 use abstutil::Timer;
+use geom::{Distance, Duration, Pt2D, Time};
 
 use crate::pathfind::driving::VehiclePathfinder;
+use crate::pathfind::travel_times::TravelTimes;
 use crate::pathfind::walking::{
     one_step_walking_path, walking_path_to_steps, SidewalkPathfinder, WalkingNode,
 };
+use crate::raw::RestrictionScope;
 use crate::{
-    BusRouteID, BusStopID, Intersection, Map, Path, PathConstraints, PathRequest, Position, TurnID,
-    Zone,
+    BikePreference, BusRouteID, BusStopID, Intersection, LevelOfTrafficStress, Map, ParkingLotID,
+    Path, PathConstraints, PathRequest, Position, TurnID, Zone,
 };
 
+/// When driving or biking to transit, only consider this many places to switch.
+const MAX_TRANSFER_CANDIDATES: usize = 10;
+/// Don't go more than this much further than the straight-line distance to switch to transit.
+const MAX_DETOUR_FACTOR: f64 = 1.5;
+/// Parking lots further than this from any stop aren't useful for park-and-ride.
+const MAX_WALK_FROM_LOT: Distance = Distance::const_meters(500.0);
+
 #[derive(Serialize, Deserialize)]
 pub struct ContractionHierarchyPathfinder {
     car_graph: VehiclePathfinder,
     bike_graph: VehiclePathfinder,
     bus_graph: VehiclePathfinder,
     train_graph: VehiclePathfinder,
+    truck_graph: VehiclePathfinder,
+    scooter_graph: VehiclePathfinder,
+    motorcycle_graph: VehiclePathfinder,
     walking_graph: SidewalkPathfinder,
     walking_with_transit_graph: SidewalkPathfinder,
+
+    /// Optionally price driving by observed congestion. These are per (constraints, hour), and
+    /// only for hours with some observations.
+    #[serde(skip_serializing, skip_deserializing)]
+    congested_graphs: BTreeMap<(PathConstraints, usize), VehiclePathfinder>,
+    #[serde(skip_serializing, skip_deserializing)]
+    travel_times: Option<TravelTimes>,
+    /// Some turn restrictions, lane types, and access restrictions only apply at certain times of
+    /// day. The graphs above are conservative about all of them, so they never produce illegal
+    /// paths. For hours when some of these are lifted, `pathfind_at` uses these graphs instead.
+    /// Hours with the same rules in effect share one graph, keyed by (constraints, the first such
+    /// hour).
+    conditional_graphs: BTreeMap<(PathConstraints, usize), VehiclePathfinder>,
+    /// For each hour of the day with conditional graphs, the hour they're keyed by
+    conditional_hours: BTreeMap<usize, usize>,
+    /// If cyclists avoid hills or stressful roads, a copy of bike_graph that penalizes these
+    #[serde(skip_serializing, skip_deserializing)]
+    custom_bike_graph: Option<(
+        BikePreference,
+        Option<LevelOfTrafficStress>,
+        VehiclePathfinder,
+    )>,
 }
 
 impl ContractionHierarchyPathfinder {
@@ -44,6 +82,22 @@ impl ContractionHierarchyPathfinder {
         let train_graph = VehiclePathfinder::new(map, PathConstraints::Train, None);
         timer.stop("prepare pathfinding for trains");
 
+        // These vehicles are restricted from a few more roads than cars or bikes, but otherwise
+        // have the same costs, so the node ordering is worth reusing.
+        timer.start("prepare pathfinding for trucks");
+        let truck_graph = VehiclePathfinder::new(map, PathConstraints::Truck, Some(&car_graph));
+        timer.stop("prepare pathfinding for trucks");
+
+        timer.start("prepare pathfinding for scooters");
+        let scooter_graph =
+            VehiclePathfinder::new(map, PathConstraints::Scooter, Some(&bike_graph));
+        timer.stop("prepare pathfinding for scooters");
+
+        timer.start("prepare pathfinding for motorcycles");
+        let motorcycle_graph =
+            VehiclePathfinder::new(map, PathConstraints::Motorcycle, Some(&car_graph));
+        timer.stop("prepare pathfinding for motorcycles");
+
         timer.start("prepare pathfinding for pedestrians");
         let walking_graph = SidewalkPathfinder::new(map, false, &bus_graph, &train_graph);
         timer.stop("prepare pathfinding for pedestrians");
@@ -53,14 +107,226 @@ impl ContractionHierarchyPathfinder {
             SidewalkPathfinder::new(map, true, &bus_graph, &train_graph);
         timer.stop("prepare pathfinding for pedestrians using transit");
 
-        ContractionHierarchyPathfinder {
+        let mut pathfinder = ContractionHierarchyPathfinder {
             car_graph,
             bike_graph,
             bus_graph,
             train_graph,
+            truck_graph,
+            scooter_graph,
+            motorcycle_graph,
             walking_graph,
             walking_with_transit_graph,
+            congested_graphs: BTreeMap::new(),
+            travel_times: None,
+            conditional_graphs: BTreeMap::new(),
+            conditional_hours: BTreeMap::new(),
+            custom_bike_graph: None,
+        };
+        pathfinder.prepare_conditional_graphs(map, timer);
+        pathfinder
+    }
+
+    /// Like `pathfind`, but for a vehicle departing at some time. If there are observed travel
+    /// times for that hour, use them. Rules that don't apply at that time are ignored.
+    pub fn pathfind_at(&self, req: PathRequest, time: Time, map: &Map) -> Option<Path> {
+        let hour = time.get_parts().0;
+        let hour_of_day = hour % 24;
+        let graph = self
+            .congested_graphs
+            .get(&(req.constraints, hour))
+            .or_else(|| {
+                // Cyclists with custom preferences stick to their own graph, which obeys all
+                // conditional restrictions
+                if req.constraints == PathConstraints::Bike && self.custom_bike_graph.is_some() {
+                    return None;
+                }
+                let hour = self.conditional_hours.get(&hour_of_day)?;
+                self.conditional_graphs.get(&(req.constraints, *hour))
+            });
+        if let Some(graph) = graph {
+            // TODO Handle zones. For now, just fall back to free-flow costs.
+            if map.get_parent(req.start.lane()).get_zone(map).is_none()
+                && map.get_parent(req.end.lane()).get_zone(map).is_none()
+            {
+                return graph.pathfind(&req, map).map(|(p, _)| p);
+            }
         }
+        self.pathfind(req, map)
+    }
+
+    /// Price cars, trucks, and motorcycles by travel times observed in some simulation, instead of
+    /// free-flow speeds. Each hour with observations gets its own graph. These reuse the node
+    /// ordering from the free-flow graph, so only the edge weights have to be recalculated. Pass
+    /// in `None` to go back to only using free-flow speeds.
+    pub fn set_travel_times(
+        &mut self,
+        map: &Map,
+        travel_times: Option<TravelTimes>,
+        timer: &mut Timer,
+    ) {
+        self.congested_graphs.clear();
+        if let Some(ref times) = travel_times {
+            let hours = times.hours();
+            timer.start_iter("prepare pathfinding with travel times", 3 * hours.len());
+            for hour in hours {
+                for (constraints, graph) in vec![
+                    (PathConstraints::Car, &self.car_graph),
+                    (PathConstraints::Truck, &self.truck_graph),
+                    (PathConstraints::Motorcycle, &self.motorcycle_graph),
+                ] {
+                    timer.next();
+                    self.congested_graphs.insert(
+                        (constraints, hour),
+                        graph.with_travel_times(map, times, hour),
+                    );
+                }
+            }
+        }
+        self.travel_times = travel_times;
+    }
+
+    pub fn set_bike_preference(
+        &mut self,
+        map: &Map,
+        preference: BikePreference,
+        timer: &mut Timer,
+    ) {
+        let max_lts = self.get_max_bike_lts();
+        self.set_bike_preferences(map, preference, max_lts, timer);
+    }
+
+    pub fn get_bike_preference(&self) -> BikePreference {
+        self.custom_bike_graph
+            .as_ref()
+            .map(|(preference, _, _)| *preference)
+            .unwrap_or(BikePreference::Fastest)
+    }
+
+    /// Cyclists will avoid roads above this level of traffic stress, unless there's no other way.
+    pub fn set_max_bike_lts(
+        &mut self,
+        map: &Map,
+        max_lts: Option<LevelOfTrafficStress>,
+        timer: &mut Timer,
+    ) {
+        let preference = self.get_bike_preference();
+        self.set_bike_preferences(map, preference, max_lts, timer);
+    }
+
+    pub fn get_max_bike_lts(&self) -> Option<LevelOfTrafficStress> {
+        self.custom_bike_graph
+            .as_ref()
+            .and_then(|(_, max_lts, _)| *max_lts)
+    }
+
+    fn set_bike_preferences(
+        &mut self,
+        map: &Map,
+        preference: BikePreference,
+        max_lts: Option<LevelOfTrafficStress>,
+        timer: &mut Timer,
+    ) {
+        self.custom_bike_graph = if preference == BikePreference::Fastest && max_lts.is_none() {
+            None
+        } else {
+            timer.start("prepare pathfinding for bikes with custom preferences");
+            let graph = self
+                .bike_graph
+                .with_bike_preferences(map, preference, max_lts);
+            timer.stop("prepare pathfinding for bikes with custom preferences");
+            Some((preference, max_lts, graph))
+        };
+    }
+
+    /// Groups the hours of the day by which time-conditional rules are in effect, and builds
+    /// graphs for the hours when the conservative graphs might be wrong.
+    fn prepare_conditional_graphs(&mut self, map: &Map, timer: &mut Timer) {
+        self.conditional_graphs.clear();
+        self.conditional_hours.clear();
+
+        // The affected vehicles and when the rule applies
+        let mut conditional = Vec::new();
+        for r in map.all_roads() {
+            for (_, _, scope) in &r.turn_restrictions {
+                conditional.extend(scope.during.as_ref().map(|during| (scope.vehicles, during)));
+            }
+            for (_, _, _, scope) in &r.complicated_turn_restrictions {
+                conditional.extend(scope.during.as_ref().map(|during| (scope.vehicles, during)));
+            }
+        }
+        // The main graphs obey every turn restriction, so they're correct for the hours when all
+        // of them are in effect. That's not true for lanes and zones, which are only used if
+        // they're open to a vehicle all day.
+        let num_turn_restrictions = conditional.len();
+        for r in map.all_roads() {
+            for cond in &r.access_restrictions.conditional {
+                conditional.push((RestrictionScope::all_vehicles(), &cond.during));
+            }
+            for l in r.all_lanes() {
+                if let Some(ref cond) = map.get_l(l).conditional_type {
+                    conditional.push((RestrictionScope::all_vehicles(), &cond.during));
+                }
+            }
+        }
+        let only_turn_restrictions = conditional.len() == num_turn_restrictions;
+        if conditional.is_empty() {
+            return;
+        }
+
+        let mut first_hour_with: BTreeMap<Vec<bool>, usize> = BTreeMap::new();
+        for hour in 0..24 {
+            let start = Time::START_OF_DAY + Duration::hours(hour);
+            let in_effect: Vec<bool> = conditional
+                .iter()
+                .map(|(_, during)| during.overlaps(start, start + Duration::hours(1)))
+                .collect();
+            if only_turn_restrictions && in_effect.iter().all(|x| *x) {
+                continue;
+            }
+            let key = *first_hour_with.entry(in_effect).or_insert(hour);
+            self.conditional_hours.insert(hour, key);
+        }
+
+        let mut vehicles = EnumSet::new();
+        for (v, _) in &conditional {
+            vehicles |= *v;
+        }
+        let hours: BTreeSet<usize> = self.conditional_hours.values().cloned().collect();
+        let mut graphs = BTreeMap::new();
+        timer.start_iter(
+            "prepare pathfinding for time-conditional rules",
+            hours.len() * vehicles.len(),
+        );
+        for hour in hours {
+            for constraints in vehicles.iter() {
+                timer.next();
+                if let Some(graph) = self.vehicle_graph(constraints) {
+                    graphs.insert((constraints, hour), graph.during_hour(map, hour));
+                }
+            }
+        }
+        self.conditional_graphs = graphs;
+    }
+
+    fn vehicle_graph(&self, constraints: PathConstraints) -> Option<&VehiclePathfinder> {
+        match constraints {
+            PathConstraints::Pedestrian => None,
+            PathConstraints::Car => Some(&self.car_graph),
+            PathConstraints::Bike => Some(&self.bike_graph),
+            PathConstraints::Bus => Some(&self.bus_graph),
+            PathConstraints::Train => Some(&self.train_graph),
+            PathConstraints::Truck => Some(&self.truck_graph),
+            PathConstraints::Scooter => Some(&self.scooter_graph),
+            PathConstraints::Motorcycle => Some(&self.motorcycle_graph),
+        }
+    }
+
+    fn biking_graph(&self) -> &VehiclePathfinder {
+        self.custom_bike_graph
+            .as_ref()
+            .map(|(_, _, graph)| graph)
+            .unwrap_or(&self.bike_graph)
     }
 
     pub fn pathfind(&self, req: PathRequest, map: &Map) -> Option<Path> {
@@ -78,7 +344,7 @@ impl ContractionHierarchyPathfinder {
                 if z1 == z2 {
                     if !z1
                         .restrictions
-                        .allow_through_traffic
+                        .allow_through_traffic_during(None)
                         .contains(req.constraints)
                     {
                         if req.constraints == PathConstraints::Pedestrian {
@@ -96,7 +362,7 @@ impl ContractionHierarchyPathfinder {
             (Some(zone), None) => {
                 if !zone
                     .restrictions
-                    .allow_through_traffic
+                    .allow_through_traffic_during(None)
                     .contains(req.constraints)
                 {
                     let mut borders: Vec<&Intersection> =
@@ -116,7 +382,7 @@ impl ContractionHierarchyPathfinder {
             (None, Some(zone)) => {
                 if !zone
                     .restrictions
-                    .allow_through_traffic
+                    .allow_through_traffic_during(None)
                     .contains(req.constraints)
                 {
                     let mut borders: Vec<&Intersection> =
@@ -141,9 +407,14 @@ impl ContractionHierarchyPathfinder {
                 Some(Path::new(map, steps, req.end.dist_along(), Vec::new()))
             }
             PathConstraints::Car => self.car_graph.pathfind(&req, map).map(|(p, _)| p),
-            PathConstraints::Bike => self.bike_graph.pathfind(&req, map).map(|(p, _)| p),
+            PathConstraints::Bike => self.biking_graph().pathfind(&req, map).map(|(p, _)| p),
             PathConstraints::Bus => self.bus_graph.pathfind(&req, map).map(|(p, _)| p),
             PathConstraints::Train => self.train_graph.pathfind(&req, map).map(|(p, _)| p),
+            PathConstraints::Truck => self.truck_graph.pathfind(&req, map).map(|(p, _)| p),
+            PathConstraints::Scooter => self.scooter_graph.pathfind(&req, map).map(|(p, _)| p),
+            PathConstraints::Motorcycle => {
+                self.motorcycle_graph.pathfind(&req, map).map(|(p, _)| p)
+            }
         }
     }
 
@@ -224,9 +495,14 @@ impl ContractionHierarchyPathfinder {
         let main_path = match req.constraints {
             PathConstraints::Pedestrian => unreachable!(),
             PathConstraints::Car => self.car_graph.pathfind(&req, map).map(|(p, _)| p),
-            PathConstraints::Bike => self.bike_graph.pathfind(&req, map).map(|(p, _)| p),
+            PathConstraints::Bike => self.biking_graph().pathfind(&req, map).map(|(p, _)| p),
             PathConstraints::Bus => self.bus_graph.pathfind(&req, map).map(|(p, _)| p),
             PathConstraints::Train => self.train_graph.pathfind(&req, map).map(|(p, _)| p),
+            PathConstraints::Truck => self.truck_graph.pathfind(&req, map).map(|(p, _)| p),
+            PathConstraints::Scooter => self.scooter_graph.pathfind(&req, map).map(|(p, _)| p),
+            PathConstraints::Motorcycle => {
+                self.motorcycle_graph.pathfind(&req, map).map(|(p, _)| p)
+            }
         }?;
         interior_path.append(main_path, map);
         Some(interior_path)
@@ -310,9 +586,14 @@ impl ContractionHierarchyPathfinder {
         let mut main_path = match req.constraints {
             PathConstraints::Pedestrian => unreachable!(),
             PathConstraints::Car => self.car_graph.pathfind(&req, map).map(|(p, _)| p),
-            PathConstraints::Bike => self.bike_graph.pathfind(&req, map).map(|(p, _)| p),
+            PathConstraints::Bike => self.biking_graph().pathfind(&req, map).map(|(p, _)| p),
             PathConstraints::Bus => self.bus_graph.pathfind(&req, map).map(|(p, _)| p),
             PathConstraints::Train => self.train_graph.pathfind(&req, map).map(|(p, _)| p),
+            PathConstraints::Truck => self.truck_graph.pathfind(&req, map).map(|(p, _)| p),
+            PathConstraints::Scooter => self.scooter_graph.pathfind(&req, map).map(|(p, _)| p),
+            PathConstraints::Motorcycle => {
+                self.motorcycle_graph.pathfind(&req, map).map(|(p, _)| p)
+            }
         }?;
         main_path.append(interior_path, map);
         main_path.end_dist = orig_end_dist;
@@ -327,6 +608,129 @@ impl ContractionHierarchyPathfinder {
     ) -> Option<(BusStopID, Option<BusStopID>, BusRouteID)> {
         self.walking_with_transit_graph
             .should_use_transit(map, start, end)
+            .map(|(plan, _)| plan)
+    }
+
+    /// Pick a parking lot near transit for somebody driving from `start` (a driving position) to
+    /// `end` (a sidewalk position). Returns the lot and how to ride transit from there.
+    pub fn should_park_and_ride(
+        &self,
+        map: &Map,
+        start: Position,
+        end: Position,
+    ) -> Option<(ParkingLotID, (BusStopID, Option<BusStopID>, BusRouteID))> {
+        let stops: Vec<Pt2D> = map
+            .all_bus_stops()
+            .values()
+            .map(|bs| bs.sidewalk_pos.pt(map))
+            .collect();
+        let candidates = map
+            .all_parking_lots()
+            .iter()
+            .filter(|pl| {
+                let pt = pl.sidewalk_pos.pt(map);
+                stops
+                    .iter()
+                    .any(|stop| stop.dist_to(pt) <= MAX_WALK_FROM_LOT)
+            })
+            .map(|pl| (pl.id, pl.driving_pos, pl.sidewalk_pos))
+            .collect();
+        // Car costs are already in seconds
+        self.cheapest_transfer(map, PathConstraints::Car, 1.0, start, end, candidates)
+    }
+
+    /// Pick a bus stop for somebody biking from `start` (a biking position) to `end` (a sidewalk
+    /// position) to lock up their bike. Returns that stop and how to ride transit from there.
+    pub fn should_bike_and_ride(
+        &self,
+        map: &Map,
+        start: Position,
+        end: Position,
+    ) -> Option<(BusStopID, (BusStopID, Option<BusStopID>, BusRouteID))> {
+        let candidates = map
+            .all_bus_stops()
+            .values()
+            .filter_map(|bs| {
+                let (biking_pos, sidewalk_pos) = bs.biking_connection(map)?;
+                Some((bs.id, biking_pos, sidewalk_pos))
+            })
+            .collect();
+        // Bike costs are roughly in meters. Assume about 4 m/s.
+        self.cheapest_transfer(map, PathConstraints::Bike, 0.25, start, end, candidates)
+    }
+
+    /// Try switching from a vehicle to transit at a few candidates, given as (ID, vehicle
+    /// position, sidewalk position). Only the ones with the smallest detour are considered.
+    fn cheapest_transfer<T: Copy>(
+        &self,
+        map: &Map,
+        constraints: PathConstraints,
+        cost_to_seconds: f64,
+        start: Position,
+        end: Position,
+        candidates: Vec<(T, Position, Position)>,
+    ) -> Option<(T, (BusStopID, Option<BusStopID>, BusRouteID))> {
+        let graph = match constraints {
+            PathConstraints::Car => &self.car_graph,
+            PathConstraints::Bike => self.biking_graph(),
+            _ => unreachable!(),
+        };
+        let start_pt = start.pt(map);
+        let end_pt = end.pt(map);
+        let direct = start_pt.dist_to(end_pt);
+
+        // Only switch to transit in the first half of the journey, without going far out of the
+        // way. Otherwise, the vehicle may as well go the whole way.
+        let mut candidates: Vec<(Distance, T, Position, Position)> = candidates
+            .into_iter()
+            .filter_map(|(id, vehicle_pos, sidewalk_pos)| {
+                let pt = sidewalk_pos.pt(map);
+                let detour = start_pt.dist_to(pt) + pt.dist_to(end_pt);
+                if vehicle_pos.lane() != start.lane()
+                    && start_pt.dist_to(pt) < pt.dist_to(end_pt)
+                    && detour <= direct * MAX_DETOUR_FACTOR
+                {
+                    Some((detour, id, vehicle_pos, sidewalk_pos))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        candidates.sort_by_key(|(detour, _, _, _)| *detour);
+
+        let mut best: Option<(f64, T, (BusStopID, Option<BusStopID>, BusRouteID))> = None;
+        for (_, id, vehicle_pos, sidewalk_pos) in
+            candidates.into_iter().take(MAX_TRANSFER_CANDIDATES)
+        {
+            let vehicle_cost = match graph.pathfind(
+                &PathRequest {
+                    start,
+                    end: vehicle_pos,
+                    constraints,
+                },
+                map,
+            ) {
+                Some((_, cost)) => (cost as f64) * cost_to_seconds,
+                None => continue,
+            };
+            let (plan, transit_cost) =
+                match self
+                    .walking_with_transit_graph
+                    .should_use_transit(map, sidewalk_pos, end)
+                {
+                    Some(pair) => pair,
+                    None => continue,
+                };
+            let total = vehicle_cost + (transit_cost as f64);
+            if best
+                .as_ref()
+                .map(|(cost, _, _)| total < *cost)
+                .unwrap_or(true)
+            {
+                best = Some((total, id, plan));
+            }
+        }
+        best.map(|(_, id, plan)| (id, plan))
     }
 
     pub fn apply_edits(&mut self, map: &Map, timer: &mut Timer) {
@@ -342,6 +746,18 @@ impl ContractionHierarchyPathfinder {
         self.bus_graph.apply_edits(map);
         timer.stop("apply edits to bus pathfinding");
 
+        timer.start("apply edits to truck pathfinding");
+        self.truck_graph.apply_edits(map);
+        timer.stop("apply edits to truck pathfinding");
+
+        timer.start("apply edits to scooter pathfinding");
+        self.scooter_graph.apply_edits(map);
+        timer.stop("apply edits to scooter pathfinding");
+
+        timer.start("apply edits to motorcycle pathfinding");
+        self.motorcycle_graph.apply_edits(map);
+        timer.stop("apply edits to motorcycle pathfinding");
+
         // Can't edit anything related to trains
 
         timer.start("apply edits to pedestrian pathfinding");
@@ -353,5 +769,13 @@ impl ContractionHierarchyPathfinder {
         self.walking_with_transit_graph
             .apply_edits(map, &self.bus_graph, &self.train_graph);
         timer.stop("apply edits to pedestrian using transit pathfinding");
+
+        self.prepare_conditional_graphs(map, timer);
+        if let Some(times) = self.travel_times.take() {
+            self.set_travel_times(map, Some(times), timer);
+        }
+        if let Some((preference, max_lts, _)) = self.custom_bike_graph.take() {
+            self.set_bike_preferences(map, preference, max_lts, timer);
+        }
     }
 }
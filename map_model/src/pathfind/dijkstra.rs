@@ -32,7 +32,7 @@ pub fn build_graph_for_vehicles(
     // TODO Handle zones.
     let mut graph: DiGraphMap<LaneID, TurnID> = DiGraphMap::new();
     for l in map.all_lanes() {
-        if constraints.can_use(l, map) {
+        if constraints.can_use_during(l, None, map) {
             for turn in map.get_turns_for(l.id, constraints) {
                 graph.add_edge(turn.id.src, turn.id.dst, turn.id);
             }
@@ -46,10 +46,10 @@ pub fn pathfind_avoiding_lanes(
     avoid: BTreeSet<LaneID>,
     map: &Map,
 ) -> Option<Path> {
-    assert_eq!(req.constraints, PathConstraints::Car);
+    assert_ne!(req.constraints, PathConstraints::Pedestrian);
     let mut graph: DiGraphMap<LaneID, TurnID> = DiGraphMap::new();
     for l in map.all_lanes() {
-        if req.constraints.can_use(l, map) && !avoid.contains(&l.id) {
+        if req.constraints.can_use_during(l, None, map) && !avoid.contains(&l.id) {
             for turn in map.get_turns_for(l.id, req.constraints) {
                 graph.add_edge(turn.id.src, turn.id.dst, turn.id);
             }
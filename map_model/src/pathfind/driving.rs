@@ -7,10 +7,15 @@ use serde::{Deserialize, Serialize};
 use thread_local::ThreadLocal;
 
 use abstutil::MultiMap;
+use geom::{Duration, Time};
 
 use crate::pathfind::node_map::{deserialize_nodemap, NodeMap};
+use crate::pathfind::travel_times::TravelTimes;
 use crate::pathfind::uber_turns::{IntersectionCluster, UberTurn};
-use crate::{Lane, LaneID, Map, Path, PathConstraints, PathRequest, PathStep, Turn, TurnID};
+use crate::{
+    BikePreference, Lane, LaneID, LevelOfTrafficStress, Map, Path, PathConstraints, PathRequest,
+    PathStep, Turn, TurnID,
+};
 
 #[derive(Serialize, Deserialize)]
 pub struct VehiclePathfinder {
@@ -54,7 +59,7 @@ impl VehiclePathfinder {
             }
         }
 
-        let input_graph = make_input_graph(map, &nodes, &uber_turns, constraints);
+        let input_graph = make_input_graph(map, &nodes, &uber_turns, constraints, None, None, None);
 
         // All VehiclePathfinders have the same nodes (lanes), so if we're not the first being
         // built, seed from the node ordering.
@@ -126,10 +131,105 @@ impl VehiclePathfinder {
         // the node ordering.
         // TODO Make sure the result of this is deterministic and equivalent to computing from
         // scratch.
-        let input_graph = make_input_graph(map, &self.nodes, &self.uber_turns, self.constraints);
+        let input_graph = make_input_graph(
+            map,
+            &self.nodes,
+            &self.uber_turns,
+            self.constraints,
+            None,
+            None,
+            None,
+        );
         let node_ordering = self.graph.get_node_ordering();
         self.graph = fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap();
     }
+
+    /// Makes a copy of this pathfinder, pricing lanes by the travel times observed during one hour
+    /// instead of free-flow speeds. Only the edge weights change, so the node ordering is reused,
+    /// and this is much faster than building from scratch.
+    pub fn with_travel_times(
+        &self,
+        map: &Map,
+        travel_times: &TravelTimes,
+        hour: usize,
+    ) -> VehiclePathfinder {
+        let input_graph = make_input_graph(
+            map,
+            &self.nodes,
+            &self.uber_turns,
+            self.constraints,
+            // Observations for an hour come from vehicles entering lanes sometime during the
+            // previous hour
+            Some((
+                Time::START_OF_DAY + Duration::hours(hour.saturating_sub(1)),
+                Time::START_OF_DAY + Duration::hours(hour),
+            )),
+            Some((travel_times, hour)),
+            None,
+        );
+        let node_ordering = self.graph.get_node_ordering();
+        VehiclePathfinder {
+            graph: fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap(),
+            nodes: self.nodes.clone(),
+            uber_turns: self.uber_turns.clone(),
+            constraints: self.constraints,
+            path_calc: ThreadLocal::new(),
+        }
+    }
+
+    /// Makes a copy of this pathfinder that only obeys the time-conditional turn restrictions in
+    /// effect sometime during one hour of the day, instead of all of them. Like
+    /// `with_travel_times`, the node ordering is reused.
+    pub fn during_hour(&self, map: &Map, hour: usize) -> VehiclePathfinder {
+        let input_graph = make_input_graph(
+            map,
+            &self.nodes,
+            &self.uber_turns,
+            self.constraints,
+            Some((
+                Time::START_OF_DAY + Duration::hours(hour),
+                Time::START_OF_DAY + Duration::hours(hour + 1),
+            )),
+            None,
+            None,
+        );
+        let node_ordering = self.graph.get_node_ordering();
+        VehiclePathfinder {
+            graph: fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap(),
+            nodes: self.nodes.clone(),
+            uber_turns: self.uber_turns.clone(),
+            constraints: self.constraints,
+            path_calc: ThreadLocal::new(),
+        }
+    }
+
+    /// Makes a copy of this pathfinder for cyclists with different preferences. Climbing can be
+    /// penalized, and so can riding on roads above some level of traffic stress. Like
+    /// `with_travel_times`, the node ordering is reused.
+    pub fn with_bike_preferences(
+        &self,
+        map: &Map,
+        preference: BikePreference,
+        max_lts: Option<LevelOfTrafficStress>,
+    ) -> VehiclePathfinder {
+        let input_graph = make_input_graph(
+            map,
+            &self.nodes,
+            &self.uber_turns,
+            self.constraints,
+            None,
+            None,
+            Some((preference, max_lts)),
+        );
+        let node_ordering = self.graph.get_node_ordering();
+        VehiclePathfinder {
+            graph: fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap(),
+            nodes: self.nodes.clone(),
+            uber_turns: self.uber_turns.clone(),
+            constraints: self.constraints,
+            path_calc: ThreadLocal::new(),
+        }
+    }
 }
 
 fn make_input_graph(
@@ -137,6 +237,12 @@ fn make_input_graph(
     nodes: &NodeMap<Node>,
     uber_turns: &Vec<UberTurn>,
     constraints: PathConstraints,
+    // Only obey time-conditional turn restrictions in effect sometime during this range, and only
+    // use lanes and zones open to this vehicle the entire time. If None, be conservative about all
+    // of these.
+    during: Option<(Time, Time)>,
+    travel_times: Option<(&TravelTimes, usize)>,
+    bike_preferences: Option<(BikePreference, Option<LevelOfTrafficStress>)>,
 ) -> InputGraph {
     let mut input_graph = InputGraph::new();
 
@@ -144,13 +250,13 @@ fn make_input_graph(
     let mut uber_turn_entrances: MultiMap<LaneID, usize> = MultiMap::new();
     for (idx, ut) in uber_turns.iter().enumerate() {
         // But actually, make sure this uber-turn only contains lanes that can be used by this
-        // vehicle.
+        // vehicle, and that no turn restrictions forbid it.
         // TODO Need to test editing lanes inside an IntersectionCluster very carefully. See Mercer
         // and Dexter.
-        if ut
-            .path
-            .iter()
-            .all(|t| constraints.can_use(map.get_l(t.dst), map))
+        if ut.path.iter().all(|t| {
+            constraints.can_use_during(map.get_l(t.dst), during, map)
+                && !map.get_t(*t).is_restricted_for(constraints, during, map)
+        }) && !ut.is_restricted_for(constraints, during, map)
         {
             uber_turn_entrances.insert(ut.entry(), idx);
         } else {
@@ -169,21 +275,25 @@ fn make_input_graph(
     for l in map.all_lanes() {
         let from = nodes.get(Node::Lane(l.id));
         let mut any = false;
-        if constraints.can_use(l, map)
+        if constraints.can_use_during(l, during, map)
             && map
                 .get_r(l.parent)
                 .access_restrictions
-                .allow_through_traffic
+                .allow_through_traffic_during(during)
                 .contains(constraints)
         {
             let indices = uber_turn_entrances.get(l.id);
             if indices.is_empty() {
-                for turn in map.get_turns_for(l.id, constraints) {
+                for turn in map.get_turns_for_during(l.id, constraints, during) {
                     any = true;
                     input_graph.add_edge(
                         from,
                         nodes.get(Node::Lane(turn.id.dst)),
-                        round(driving_cost(l, turn, constraints, map)),
+                        round(
+                            driving_cost(l, turn, constraints, map)
+                                + congestion_delay(l, travel_times, map)
+                                + bike_penalty(l, bike_preferences, map),
+                        ),
                     );
                 }
             } else {
@@ -193,7 +303,10 @@ fn make_input_graph(
 
                     let mut sum_cost = 0.0;
                     for t in &ut.path {
-                        sum_cost += driving_cost(map.get_l(t.src), map.get_t(*t), constraints, map);
+                        let src = map.get_l(t.src);
+                        sum_cost += driving_cost(src, map.get_t(*t), constraints, map)
+                            + congestion_delay(src, travel_times, map)
+                            + bike_penalty(src, bike_preferences, map);
                     }
                     input_graph.add_edge(from, nodes.get(Node::UberTurn(*idx)), round(sum_cost));
                     input_graph.add_edge(
@@ -223,16 +336,25 @@ pub fn driving_cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map:
     // TODO Could cost turns differently.
 
     let base = match constraints {
-        PathConstraints::Car | PathConstraints::Train => {
+        PathConstraints::Car
+        | PathConstraints::Train
+        | PathConstraints::Truck
+        | PathConstraints::Motorcycle => {
             // Prefer slightly longer route on faster roads
             let t1 = lane.length() / map.get_r(lane.parent).speed_limit;
             let t2 = turn.geom.length() / map.get_parent(turn.id.dst).speed_limit;
             (t1 + t2).inner_seconds()
         }
-        PathConstraints::Bike => {
+        PathConstraints::Bike | PathConstraints::Scooter => {
             // Speed limits don't matter, bikes are usually constrained by their own speed limit.
-            let dist = lane.length() + turn.geom.length();
-            // TODO Elevation gain is bad, loss is good.
+            // But cyclists slow down going uphill and speed up going downhill, so stretch or
+            // shrink the distance to reflect the time taken.
+            let grade_factor = if constraints == PathConstraints::Bike {
+                lane.bike_time_factor(map)
+            } else {
+                1.0
+            };
+            let dist = lane.length() * grade_factor + turn.geom.length();
             // TODO If we're on a driving lane, higher speed limit is worse.
             // TODO Bike lanes next to parking is dangerous.
 
@@ -271,13 +393,64 @@ pub fn driving_cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map:
     // TODO Since these costs wind up mattering most for particular lane choice, I guess just
     // adding is reasonable?
     let mut extra_penalty = lt + lc;
-    if constraints == PathConstraints::Bike {
+    if constraints.is_bike_like() {
         extra_penalty = slow_lane;
     }
 
     base + (extra_penalty as f64)
 }
 
+/// If vehicles were observed taking longer to cross a lane than its speed limit allows, returns
+/// the extra seconds. Only makes sense for constraints whose cost is in seconds.
+fn congestion_delay(lane: &Lane, travel_times: Option<(&TravelTimes, usize)>, map: &Map) -> f64 {
+    if let Some((travel_times, hour)) = travel_times {
+        if let Some(observed) = travel_times.get(lane.id, hour) {
+            let free_flow = lane.length() / map.get_r(lane.parent).speed_limit;
+            return (observed - free_flow).inner_seconds().max(0.0);
+        }
+    }
+    0.0
+}
+
+/// For cyclists avoiding hills, every meter of climbing costs as much as riding this many meters on
+/// flat ground.
+const CLIMB_PENALTY: f64 = 20.0;
+
+/// For cyclists avoiding stressful roads, riding on a road above their maximum level of traffic
+/// stress costs this many times more than usual. They'll still use these roads when there's no
+/// other way.
+const STRESS_PENALTY: f64 = 10.0;
+
+/// Only makes sense for bikes, whose cost is in meters.
+fn bike_penalty(
+    lane: &Lane,
+    bike_preferences: Option<(BikePreference, Option<LevelOfTrafficStress>)>,
+    map: &Map,
+) -> f64 {
+    let (preference, max_lts) = match bike_preferences {
+        Some(pair) => pair,
+        None => {
+            return 0.0;
+        }
+    };
+    let mut penalty = 0.0;
+    if preference == BikePreference::AvoidHills {
+        let (climb, _) = lane.elevation_change(map);
+        penalty += CLIMB_PENALTY * climb.inner_meters();
+    }
+    if let Some(max) = max_lts {
+        // Cyclists ride along the lane and then through the intersection at its end
+        if lane
+            .get_directed_parent(map)
+            .approach_level_of_traffic_stress(map)
+            > max
+        {
+            penalty += (STRESS_PENALTY - 1.0) * lane.length().inner_meters();
+        }
+    }
+    penalty
+}
+
 // Round up! 0 cost edges are ignored
 fn round(cost: f64) -> usize {
     (cost.round() as usize).max(1)
@@ -3,25 +3,27 @@
 use std::collections::{BTreeSet, VecDeque};
 use std::fmt;
 
-use enumset::EnumSetType;
+use enumset::{EnumSet, EnumSetType};
 use serde::{Deserialize, Serialize};
 
 use abstutil::Timer;
-use geom::{Distance, PolyLine, EPSILON_DIST};
+use geom::{Distance, PolyLine, Time, EPSILON_DIST};
 
 pub use self::ch::ContractionHierarchyPathfinder;
 pub use self::dijkstra::{build_graph_for_pedestrians, build_graph_for_vehicles};
 pub use self::driving::driving_cost;
+pub use self::travel_times::TravelTimes;
 pub use self::walking::{walking_cost, WalkingNode};
 use crate::{
-    osm, BuildingID, BusRouteID, BusStopID, Lane, LaneID, LaneType, Map, Position, Traversable,
-    TurnID, UberTurn,
+    osm, BuildingID, BusRouteID, BusStopID, Lane, LaneID, LaneType, LevelOfTrafficStress, Map,
+    ParkingLotID, Position, Traversable, TurnID, UberTurn,
 };
 
 mod ch;
 mod dijkstra;
 mod driving;
 mod node_map;
+mod travel_times;
 // TODO tmp
 pub mod uber_turns;
 mod walking;
@@ -254,6 +256,48 @@ impl Path {
         self.steps.push_back(step);
     }
 
+    /// Extends this path with another one, starting where this one ends.
+    pub fn append(&mut self, other: Path, map: &Map) {
+        assert_eq!(self.last_step(), other.current_step());
+        let mut steps = other.steps;
+        steps.pop_front();
+        for step in steps {
+            self.add(step, map);
+        }
+        self.end_dist = other.end_dist;
+        self.uber_turns.extend(other.uber_turns);
+    }
+
+    /// Replaces everything after the current step with a different path. It has to start from the
+    /// current step and end on the same lane as before. Not possible in the middle of an
+    /// uber-turn.
+    pub fn reroute(&mut self, new_path: Path, map: &Map) -> Result<(), String> {
+        if self.currently_inside_ut.is_some() {
+            return Err(format!("can't reroute in the middle of an uber-turn"));
+        }
+        if new_path.current_step() != self.current_step()
+            || new_path.last_step().as_lane() != self.last_step().as_lane()
+        {
+            return Err(format!(
+                "new path doesn't go from {:?} to {:?}",
+                self.current_step(),
+                self.last_step()
+            ));
+        }
+        for step in self.steps.drain(1..) {
+            self.total_length -= step.as_traversable().length(map);
+            match step {
+                PathStep::Lane(_) | PathStep::ContraflowLane(_) => self.total_lanes -= 1,
+                _ => {}
+            }
+        }
+        self.uber_turns = new_path.uber_turns;
+        for step in new_path.steps.into_iter().skip(1) {
+            self.add(step, map);
+        }
+        Ok(())
+    }
+
     // TODO This is a brittle, tied to exactly what opportunistically_lanechange does.
     pub fn approaching_uber_turn(&self) -> bool {
         if self.steps.len() < 5 || self.uber_turns.is_empty() {
@@ -408,6 +452,26 @@ impl Path {
         &self.steps
     }
 
+    /// Returns the total (climb, descent) along all lanes of the path, including the first and
+    /// last lanes entirely.
+    pub fn get_total_elevation_change(&self, map: &Map) -> (Distance, Distance) {
+        let mut climb = Distance::ZERO;
+        let mut descent = Distance::ZERO;
+        for step in &self.steps {
+            let (up, down) = match step {
+                PathStep::Lane(l) => map.get_l(*l).elevation_change(map),
+                PathStep::ContraflowLane(l) => {
+                    let (up, down) = map.get_l(*l).elevation_change(map);
+                    (down, up)
+                }
+                PathStep::Turn(_) => continue,
+            };
+            climb += up;
+            descent += down;
+        }
+        (climb, descent)
+    }
+
     // Not for walking paths
     fn append(&mut self, other: Path, map: &Map) {
         assert!(self.currently_inside_ut.is_none());
@@ -429,6 +493,15 @@ impl Path {
     }
 }
 
+/// How cyclists trade off hills against travel time when choosing a route.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BikePreference {
+    /// Minimize travel time, accounting for slowing down uphill and speeding up downhill.
+    Fastest,
+    /// Go out of the way to avoid climbing.
+    AvoidHills,
+}
+
 /// Who's asking for a path?
 // TODO This is an awful name.
 #[derive(Debug, Serialize, Deserialize, PartialOrd, Ord, EnumSetType)]
@@ -438,6 +511,9 @@ pub enum PathConstraints {
     Bike,
     Bus,
     Train,
+    Truck,
+    Scooter,
+    Motorcycle,
 }
 
 impl PathConstraints {
@@ -448,6 +524,9 @@ impl PathConstraints {
             PathConstraints::Bike,
             PathConstraints::Bus,
             PathConstraints::Train,
+            PathConstraints::Truck,
+            PathConstraints::Scooter,
+            PathConstraints::Motorcycle,
         ]
     }
 
@@ -465,13 +544,35 @@ impl PathConstraints {
 
     // TODO Handle private zones here?
     pub fn can_use(self, l: &Lane, map: &Map) -> bool {
+        self.can_use_lane_type(l.lane_type, l, map)
+    }
+
+    /// Like `can_use`, but for lanes that change type by time of day, the lane must be usable the
+    /// entire time range. If the range is None, it must be usable at any time.
+    pub fn can_use_during(self, l: &Lane, during: Option<(Time, Time)>, map: &Map) -> bool {
+        l.lane_types_during(during)
+            .into_iter()
+            .all(|lt| self.can_use_lane_type(lt, l, map))
+    }
+
+    /// Can this lane be used at some time of day?
+    pub fn can_ever_use(self, l: &Lane, map: &Map) -> bool {
+        l.lane_types_during(None)
+            .into_iter()
+            .any(|lt| self.can_use_lane_type(lt, l, map))
+    }
+
+    fn can_use_lane_type(self, lt: LaneType, l: &Lane, map: &Map) -> bool {
         match self {
-            PathConstraints::Pedestrian => l.is_walkable(),
-            PathConstraints::Car => l.is_driving(),
-            PathConstraints::Bike => {
-                if l.is_biking() {
+            PathConstraints::Pedestrian => lt == LaneType::Sidewalk || lt == LaneType::Shoulder,
+            PathConstraints::Car => lt == LaneType::Driving,
+            // E-scooters are generally held to the same rules as bikes
+            PathConstraints::Bike | PathConstraints::Scooter => {
+                if lt == LaneType::Biking {
                     true
-                } else if l.is_driving() || (l.is_bus() && map.config.bikes_can_use_bus_lanes) {
+                } else if lt == LaneType::Driving
+                    || (lt == LaneType::Bus && map.config.bikes_can_use_bus_lanes)
+                {
                     let road = map.get_r(l.parent);
                     !road.osm_tags.is("bicycle", "no")
                         && !road
@@ -481,15 +582,49 @@ impl PathConstraints {
                     false
                 }
             }
-            PathConstraints::Bus => l.is_driving() || l.is_bus(),
-            PathConstraints::Train => l.is_light_rail(),
+            PathConstraints::Bus => lt == LaneType::Driving || lt == LaneType::Bus,
+            PathConstraints::Train => lt == LaneType::LightRail,
+            PathConstraints::Truck => {
+                lt == LaneType::Driving && !map.get_r(l.parent).osm_tags.is("hgv", "no")
+            }
+            PathConstraints::Motorcycle => {
+                lt == LaneType::Driving && !map.get_r(l.parent).osm_tags.is("motorcycle", "no")
+            }
+        }
+    }
+
+    /// The vehicles that an OSM access key (like `hgv` or `psv`) refers to. Unknown keys don't
+    /// match anything.
+    pub fn from_osm_vehicle(key: &str) -> EnumSet<PathConstraints> {
+        match key {
+            "vehicle" => EnumSet::all() - PathConstraints::Pedestrian,
+            "motor_vehicle" => {
+                PathConstraints::Car
+                    | PathConstraints::Bus
+                    | PathConstraints::Truck
+                    | PathConstraints::Motorcycle
+            }
+            "motorcar" => EnumSet::only(PathConstraints::Car),
+            "psv" | "bus" => EnumSet::only(PathConstraints::Bus),
+            "hgv" | "goods" => EnumSet::only(PathConstraints::Truck),
+            "motorcycle" | "moped" | "mofa" => EnumSet::only(PathConstraints::Motorcycle),
+            // E-scooters are generally held to the same rules as bikes
+            "bicycle" => PathConstraints::Bike | PathConstraints::Scooter,
+            "foot" => EnumSet::only(PathConstraints::Pedestrian),
+            _ => EnumSet::new(),
         }
     }
 
-    /// Strict for bikes. If there are bike lanes, not allowed to use other lanes.
+    /// Bikes and scooters balance on two wheels and use bike lanes when possible.
+    pub fn is_bike_like(self) -> bool {
+        self == PathConstraints::Bike || self == PathConstraints::Scooter
+    }
+
+    /// Strict for bikes and scooters. If there are bike lanes, not allowed to use other lanes.
+    /// Lanes that change type by time of day are only included if they're always usable.
     pub(crate) fn filter_lanes(self, mut choices: Vec<LaneID>, map: &Map) -> Vec<LaneID> {
-        choices.retain(|l| self.can_use(map.get_l(*l), map));
-        if self == PathConstraints::Bike {
+        choices.retain(|l| self.can_use_during(map.get_l(*l), None, map));
+        if self.is_bike_like() {
             let just_bike_lanes: Vec<LaneID> = choices
                 .iter()
                 .copied()
@@ -538,8 +673,10 @@ impl PathRequest {
         let to = map.get_b(to);
         let (start, end) = match constraints {
             PathConstraints::Pedestrian => (from.sidewalk_pos, to.sidewalk_pos),
-            PathConstraints::Bike => (from.biking_connection(map)?.0, to.biking_connection(map)?.0),
-            PathConstraints::Car => (
+            PathConstraints::Bike | PathConstraints::Scooter => {
+                (from.biking_connection(map)?.0, to.biking_connection(map)?.0)
+            }
+            PathConstraints::Car | PathConstraints::Truck | PathConstraints::Motorcycle => (
                 from.driving_connection(map)?.0,
                 to.driving_connection(map)?.0,
             ),
@@ -600,24 +737,18 @@ fn validate_continuity(map: &Map, steps: &Vec<PathStep>) {
 }
 
 fn validate_restrictions(map: &Map, steps: &Vec<PathStep>) {
-    for triple in steps.windows(5) {
-        if let (PathStep::Lane(l1), PathStep::Lane(l2), PathStep::Lane(l3)) =
-            (triple[0], triple[2], triple[4])
-        {
-            let from = map.get_parent(l1);
-            let via = map.get_l(l2).parent;
-            let to = map.get_l(l3).parent;
-
-            for (dont_via, dont_to) in &from.complicated_turn_restrictions {
-                if via == *dont_via && to == *dont_to {
-                    panic!(
-                        "Some path does illegal uber-turn: {} -> {} -> {}",
-                        l1, l2, l3
-                    );
-                }
-            }
+    let mut roads = Vec::new();
+    for step in steps {
+        if let PathStep::Lane(l) = step {
+            roads.push(map.get_l(*l).parent);
         }
     }
+    if uber_turns::is_road_sequence_restricted(&roads, map, |scope| scope.is_unconditional()) {
+        panic!(
+            "Some path does an illegal uber-turn through roads {:?}",
+            roads
+        );
+    }
 }
 
 /// Most of the time, prefer using the faster contraction hierarchies. But sometimes, callers can
@@ -636,6 +767,13 @@ impl Pathfinder {
             Pathfinder::CH(ref p) => p.pathfind(req, map),
         }
     }
+    pub fn pathfind_at(&self, req: PathRequest, time: Time, map: &Map) -> Option<Path> {
+        match self {
+            // TODO Implement this
+            Pathfinder::Dijkstra => dijkstra::pathfind(req, map),
+            Pathfinder::CH(ref p) => p.pathfind_at(req, time, map),
+        }
+    }
     pub fn pathfind_avoiding_lanes(
         &self,
         req: PathRequest,
@@ -659,10 +797,89 @@ impl Pathfinder {
         }
     }
 
+    pub fn should_park_and_ride(
+        &self,
+        map: &Map,
+        start: Position,
+        end: Position,
+    ) -> Option<(ParkingLotID, (BusStopID, Option<BusStopID>, BusRouteID))> {
+        match self {
+            // TODO Implement this
+            Pathfinder::Dijkstra => None,
+            Pathfinder::CH(ref p) => p.should_park_and_ride(map, start, end),
+        }
+    }
+
+    pub fn should_bike_and_ride(
+        &self,
+        map: &Map,
+        start: Position,
+        end: Position,
+    ) -> Option<(BusStopID, (BusStopID, Option<BusStopID>, BusRouteID))> {
+        match self {
+            // TODO Implement this
+            Pathfinder::Dijkstra => None,
+            Pathfinder::CH(ref p) => p.should_bike_and_ride(map, start, end),
+        }
+    }
+
     pub fn apply_edits(&mut self, map: &Map, timer: &mut Timer) {
         match self {
             Pathfinder::Dijkstra => {}
             Pathfinder::CH(ref mut p) => p.apply_edits(map, timer),
         }
     }
+
+    pub fn set_travel_times(
+        &mut self,
+        map: &Map,
+        travel_times: Option<TravelTimes>,
+        timer: &mut Timer,
+    ) {
+        match self {
+            // TODO Implement this
+            Pathfinder::Dijkstra => {}
+            Pathfinder::CH(ref mut p) => p.set_travel_times(map, travel_times, timer),
+        }
+    }
+
+    pub fn set_bike_preference(
+        &mut self,
+        map: &Map,
+        preference: BikePreference,
+        timer: &mut Timer,
+    ) {
+        match self {
+            // TODO Implement this
+            Pathfinder::Dijkstra => {}
+            Pathfinder::CH(ref mut p) => p.set_bike_preference(map, preference, timer),
+        }
+    }
+
+    pub fn get_bike_preference(&self) -> BikePreference {
+        match self {
+            Pathfinder::Dijkstra => BikePreference::Fastest,
+            Pathfinder::CH(ref p) => p.get_bike_preference(),
+        }
+    }
+
+    pub fn set_max_bike_lts(
+        &mut self,
+        map: &Map,
+        max_lts: Option<LevelOfTrafficStress>,
+        timer: &mut Timer,
+    ) {
+        match self {
+            // TODO Implement this
+            Pathfinder::Dijkstra => {}
+            Pathfinder::CH(ref mut p) => p.set_max_bike_lts(map, max_lts, timer),
+        }
+    }
+
+    pub fn get_max_bike_lts(&self) -> Option<LevelOfTrafficStress> {
+        match self {
+            Pathfinder::Dijkstra => None,
+            Pathfinder::CH(ref p) => p.get_max_bike_lts(),
+        }
+    }
 }
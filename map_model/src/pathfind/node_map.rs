@@ -7,7 +7,7 @@ use fast_paths::{NodeId, ShortestPath};
 use serde::{Deserialize, Deserializer, Serialize};
 
 // TODO Upstream this in fast_paths when this is more solid.
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 pub struct NodeMap<T: Copy + Ord + Debug + Serialize> {
     #[serde(skip_serializing)]
     node_to_id: BTreeMap<T, NodeId>,
@@ -0,0 +1,73 @@
+//! Travel times observed in a simulation, so pathfinding can price driving by congestion instead of
+//! free-flow speeds.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::{Duration, Time};
+
+use crate::{LaneID, Map, Path, PathStep};
+
+/// How long vehicles actually took to cross lanes, bucketed by the hour they entered the lane. This
+/// includes time spent queued at the end of the lane, waiting to turn.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TravelTimes {
+    /// For each (lane, hour), the total time spent crossing it and the number of samples
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    samples: BTreeMap<(LaneID, usize), (Duration, usize)>,
+}
+
+impl TravelTimes {
+    pub fn new() -> TravelTimes {
+        TravelTimes {
+            samples: BTreeMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, l: LaneID, entered: Time, dt: Duration) {
+        let entry = self
+            .samples
+            .entry((l, entered.get_parts().0))
+            .or_insert((Duration::ZERO, 0));
+        entry.0 += dt;
+        entry.1 += 1;
+    }
+
+    /// The average time to cross a lane, for vehicles entering it during some hour.
+    pub fn get(&self, l: LaneID, hour: usize) -> Option<Duration> {
+        let (total, count) = self.samples.get(&(l, hour))?;
+        Some(*total / (*count as f64))
+    }
+
+    /// Estimates how long a vehicle departing at some time would take to follow a path. Lanes
+    /// without any observations for that hour are assumed to be crossed at the speed limit.
+    pub fn estimate(&self, path: &Path, depart: Time, map: &Map) -> Duration {
+        let hour = depart.get_parts().0;
+        let mut total = Duration::ZERO;
+        for step in path.get_steps() {
+            total += match step {
+                PathStep::Lane(l) | PathStep::ContraflowLane(l) => self
+                    .get(*l, hour)
+                    .unwrap_or_else(|| map.get_l(*l).length() / map.get_parent(*l).speed_limit),
+                PathStep::Turn(t) => {
+                    map.get_t(*t).geom.length() / map.get_parent(t.dst).speed_limit
+                }
+            };
+        }
+        total
+    }
+
+    /// All hours with any observations
+    pub fn hours(&self) -> BTreeSet<usize> {
+        self.samples.keys().map(|(_, hour)| *hour).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
@@ -7,9 +7,12 @@ use petgraph::graphmap::UnGraphMap;
 use serde::{Deserialize, Serialize};
 
 use abstutil::MultiMap;
-use geom::{Angle, Distance, PolyLine, Pt2D};
+use geom::{Angle, Distance, PolyLine, Pt2D, Time};
 
-use crate::{DirectedRoadID, Direction, IntersectionID, LaneID, Map, TurnID};
+use crate::raw::{RestrictionScope, RestrictionType};
+use crate::{
+    DirectedRoadID, Direction, IntersectionID, LaneID, Map, PathConstraints, RoadID, TurnID,
+};
 
 /// This only applies to VehiclePathfinder; walking through these intersections is nothing special.
 // TODO I haven't seen any cases yet with "interior" intersections. Some stuff might break.
@@ -42,10 +45,12 @@ impl IntersectionCluster {
         // Then look for intersections with complicated turn restrictions.
         let mut graph: UnGraphMap<IntersectionID, ()> = UnGraphMap::new();
         for from in map.all_roads() {
-            for (via, _) in &from.complicated_turn_restrictions {
-                // Each of these tells us 2 intersections to group together
-                let r = map.get_r(*via);
-                graph.add_edge(r.src_i, r.dst_i, ());
+            for (_, via, _, _) in &from.complicated_turn_restrictions {
+                // Each via road tells us 2 intersections to group together
+                for r in via {
+                    let r = map.get_r(*r);
+                    graph.add_edge(r.src_i, r.dst_i, ());
+                }
             }
         }
         for intersections in petgraph::algo::kosaraju_scc(&graph) {
@@ -114,33 +119,16 @@ impl IntersectionCluster {
             uber_turns.extend(flood(entrance, map, &exits));
         }
 
-        // Filter illegal paths
-        let mut all_restrictions = Vec::new();
-        for from in map.all_roads() {
-            for (via, to) in &from.complicated_turn_restrictions {
-                all_restrictions.push((from.id, *via, *to));
-            }
-        }
-
-        // Filter out the restricted ones!
+        // Filter out the restricted ones! Restrictions that only apply to some vehicles or at some
+        // times are handled during pathfinding.
         let mut illegal = Vec::new();
         uber_turns.retain(|ut| {
-            let mut ok = true;
-            for pair in ut.path.windows(2) {
-                let r1 = map.get_l(pair[0].src).parent;
-                let r2 = map.get_l(pair[0].dst).parent;
-                let r3 = map.get_l(pair[1].dst).parent;
-                if all_restrictions.contains(&(r1, r2, r3)) {
-                    ok = false;
-                    break;
-                }
-            }
-            if ok {
-                true
-            } else {
+            if ut.is_restricted(map, |scope| scope.is_unconditional()) {
                 // TODO There's surely a method in Vec to do partition like this
                 illegal.push(ut.clone());
                 false
+            } else {
+                true
             }
         });
 
@@ -255,6 +243,58 @@ impl UberTurn {
         }
         pl
     }
+
+    /// Does an OSM turn restriction via entire roads forbid some vehicle from doing this sequence
+    /// of turns sometime in a time range? Without a time range, time-conditional restrictions
+    /// always count.
+    pub fn is_restricted_for(
+        &self,
+        constraints: PathConstraints,
+        during: Option<(Time, Time)>,
+        map: &Map,
+    ) -> bool {
+        self.is_restricted(map, |scope| scope.applies(constraints, during))
+    }
+
+    fn is_restricted<F: Fn(&RestrictionScope) -> bool>(&self, map: &Map, applies: F) -> bool {
+        let mut roads = vec![map.get_l(self.entry()).parent];
+        for t in &self.path {
+            roads.push(map.get_l(t.dst).parent);
+        }
+        is_road_sequence_restricted(&roads, map, applies)
+    }
+}
+
+/// Checks the turn restrictions via entire roads that `applies` says are in effect against a
+/// sequence of roads.
+pub(crate) fn is_road_sequence_restricted<F: Fn(&RestrictionScope) -> bool>(
+    roads: &[RoadID],
+    map: &Map,
+    applies: F,
+) -> bool {
+    for (idx, from) in roads.iter().enumerate() {
+        for (restriction, via, to, scope) in &map.get_r(*from).complicated_turn_restrictions {
+            // Only check restrictions for this exact sequence of via roads
+            let rest = &roads[idx + 1..];
+            if rest.len() <= via.len() || rest[..via.len()] != via[..] || !applies(scope) {
+                continue;
+            }
+            let next = rest[via.len()];
+            match restriction {
+                RestrictionType::BanTurns => {
+                    if next == *to {
+                        return true;
+                    }
+                }
+                RestrictionType::OnlyAllowTurns => {
+                    if next != *to {
+                        return true;
+                    }
+                }
+            }
+        }
+    }
+    false
 }
 
 pub struct UberTurnGroup {
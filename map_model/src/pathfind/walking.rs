@@ -132,18 +132,20 @@ impl SidewalkPathfinder {
     }
 
     /// Attempt the pathfinding and see if we should ride a bus. If so, says (stop1, optional stop
-    /// 2, route). If there's no stop 2, then ride the bus off the border.
+    /// 2, route). If there's no stop 2, then ride the bus off the border. Also returns the cost of
+    /// the whole journey, roughly in seconds.
     pub fn should_use_transit(
         &self,
         map: &Map,
         start: Position,
         end: Position,
-    ) -> Option<(BusStopID, Option<BusStopID>, BusRouteID)> {
+    ) -> Option<((BusStopID, Option<BusStopID>, BusRouteID), usize)> {
         let raw_path = fast_paths::calc_path(
             &self.graph,
             self.nodes.get(WalkingNode::closest(start, map)),
             self.nodes.get(WalkingNode::end_transit(end, map)),
         )?;
+        let cost = raw_path.get_weight();
 
         let nodes = self.nodes.translate(&raw_path);
         if false {
@@ -180,11 +182,14 @@ impl SidewalkPathfinder {
                         if filtered.is_empty() {
                             // Aha, a transfer!
                             return Some((
-                                first_stop.unwrap(),
-                                // TODO I thought this should be impossible, but huge_seattle hits
-                                // it. Workaround for now by just walking.
-                                Some(last_stop?),
-                                possible_routes[0].id,
+                                (
+                                    first_stop.unwrap(),
+                                    // TODO I thought this should be impossible, but huge_seattle
+                                    // hits it. Workaround for now by just walking.
+                                    Some(last_stop?),
+                                    possible_routes[0].id,
+                                ),
+                                cost,
                             ));
                         }
                         last_stop = Some(*stop2);
@@ -198,21 +203,27 @@ impl SidewalkPathfinder {
                             .map(|l| map.get_l(l).dst_i == *i)
                             .unwrap_or(false)
                     }) {
-                        return Some((first_stop.unwrap(), None, r.id));
+                        return Some(((first_stop.unwrap(), None, r.id), cost));
                     }
                     // We can get close to the border, but should hop off at some stop.
                     return Some((
-                        first_stop.unwrap(),
-                        Some(last_stop.expect("impossible transit transfer")),
-                        possible_routes[0].id,
+                        (
+                            first_stop.unwrap(),
+                            Some(last_stop.expect("impossible transit transfer")),
+                            possible_routes[0].id,
+                        ),
+                        cost,
                     ));
                 }
                 WalkingNode::SidewalkEndpoint(_, _) => {
                     if let Some(stop1) = first_stop {
                         return Some((
-                            stop1,
-                            Some(last_stop.expect("impossible transit transfer")),
-                            possible_routes[0].id,
+                            (
+                                stop1,
+                                Some(last_stop.expect("impossible transit transfer")),
+                                possible_routes[0].id,
+                            ),
+                            cost,
                         ));
                     }
                 }
@@ -236,7 +247,7 @@ fn make_input_graph(
             && map
                 .get_r(l.parent)
                 .access_restrictions
-                .allow_through_traffic
+                .allow_through_traffic_during(None)
                 .contains(PathConstraints::Pedestrian)
         {
             let mut cost = walking_cost(l.length());
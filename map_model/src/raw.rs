@@ -5,15 +5,17 @@
 use std::collections::BTreeMap;
 use std::fmt;
 
+use enumset::EnumSet;
 use petgraph::graphmap::DiGraphMap;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_btreemap, serialize_btreemap, MapName, Tags, Timer};
-use geom::{Circle, Distance, GPSBounds, PolyLine, Polygon, Pt2D};
+use geom::{Circle, Distance, GPSBounds, PolyLine, Polygon, Pt2D, Time};
 
 use crate::make::initial::lane_specs::get_lane_specs_ltr;
 use crate::{
     osm, Amenity, AreaType, Direction, DrivingSide, IntersectionType, LaneType, MapConfig,
+    PathConstraints, TimeWindows,
 };
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -38,6 +40,7 @@ pub struct RawMap {
     pub areas: Vec<RawArea>,
     pub parking_lots: Vec<RawParkingLot>,
     pub parking_aisles: Vec<(osm::WayID, Vec<Pt2D>)>,
+    pub bike_share_stations: Vec<RawBikeShareStation>,
 
     pub boundary_polygon: Polygon,
     pub gps_bounds: GPSBounds,
@@ -94,6 +97,7 @@ impl RawMap {
             areas: Vec::new(),
             parking_lots: Vec::new(),
             parking_aisles: Vec::new(),
+            bike_share_stations: Vec::new(),
             // Some nonsense thing
             boundary_polygon: Polygon::rectangle(1.0, 1.0),
             gps_bounds: GPSBounds::new(),
@@ -248,9 +252,18 @@ pub struct RawRoad {
     /// happened.
     pub center_points: Vec<Pt2D>,
     pub osm_tags: Tags,
-    pub turn_restrictions: Vec<(RestrictionType, OriginalRoad)>,
-    /// (via, to). For turn restrictions where 'via' is an entire road. Only BanTurns.
-    pub complicated_turn_restrictions: Vec<(OriginalRoad, OriginalRoad)>,
+    pub turn_restrictions: Vec<(RestrictionType, OriginalRoad, RestrictionScope)>,
+    /// (restriction, via, to, scope). For turn restrictions where 'via' is a sequence of entire
+    /// roads, in order starting from this one.
+    pub complicated_turn_restrictions: Vec<(
+        RestrictionType,
+        Vec<OriginalRoad>,
+        OriginalRoad,
+        RestrictionScope,
+    )>,
+    /// Elevation sampled at points along center_points, if known. Points that don't lie on
+    /// center_points (because it was edited later) are ignored.
+    pub elevation: Vec<(Pt2D, Distance)>,
 }
 
 impl RawRoad {
@@ -336,6 +349,13 @@ pub struct RawParkingLot {
     pub osm_tags: Tags,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RawBikeShareStation {
+    pub osm_id: osm::OsmID,
+    pub point: Pt2D,
+    pub osm_tags: Tags,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum RestrictionType {
     BanTurns,
@@ -347,8 +367,7 @@ pub struct TurnRestriction(pub OriginalRoad, pub RestrictionType, pub OriginalRo
 
 impl RestrictionType {
     pub fn new(restriction: &str) -> Option<RestrictionType> {
-        // TODO There's a huge space of things not represented yet: time conditions, bus-only, no
-        // right turn on red...
+        // TODO There's a huge space of things not represented yet, like no right turn on red.
 
         // There are so many possibilities:
         // https://taginfo.openstreetmap.org/keys/restriction#values
@@ -361,6 +380,157 @@ impl RestrictionType {
             None
         }
     }
+
+    /// Interprets all of the restriction tags on an OSM relation. One relation can restrict
+    /// different vehicles (`restriction:hgv`), exempt some (`except=bicycle`), or only apply at
+    /// certain times (`restriction:conditional`, or the older `hour_on` and `day_on` tags).
+    pub fn parse_osm_relation(
+        id: osm::RelationID,
+        tags: &Tags,
+        timer: &mut Timer,
+    ) -> Vec<(RestrictionType, RestrictionScope)> {
+        let mut except = EnumSet::new();
+        if let Some(list) = tags.get("except") {
+            for vehicle in list.split(';') {
+                except |= PathConstraints::from_osm_vehicle(vehicle.trim());
+            }
+        }
+
+        let mut results = Vec::new();
+        for (key, value) in tags.inner() {
+            // Like restriction, restriction:hgv, or restriction:bus:conditional
+            let mut parts = key.split(':');
+            if parts.next() != Some("restriction") {
+                continue;
+            }
+            let mut vehicles = RestrictionScope::all_vehicles() - except;
+            let mut conditional = false;
+            for part in parts {
+                if part == "conditional" {
+                    conditional = true;
+                } else {
+                    vehicles = PathConstraints::from_osm_vehicle(part);
+                }
+            }
+            if vehicles.is_empty() {
+                timer.warn(format!("{} has an unknown restriction {}", id, key));
+                continue;
+            }
+
+            if conditional {
+                for (restriction, condition) in TimeWindows::parse_conditional_tag(value) {
+                    match (
+                        RestrictionType::new(&restriction),
+                        TimeWindows::parse(&condition),
+                    ) {
+                        // Only on weekends, which aren't modeled
+                        (Some(_), Ok(during)) if during.is_empty() => {}
+                        (Some(rt), Ok(during)) => {
+                            results.push((
+                                rt,
+                                RestrictionScope {
+                                    vehicles,
+                                    during: Some(during),
+                                },
+                            ));
+                        }
+                        (Some(_), Err(err)) => {
+                            timer.warn(format!("Skipping {}={} on {}: {}", key, value, id, err));
+                        }
+                        (None, _) => {}
+                    }
+                }
+            } else if let Some(rt) = RestrictionType::new(value) {
+                // The older way of tagging times, like hour_on=07:00 and day_on=Monday
+                let during = if let (Some(on), Some(off)) =
+                    (tags.get("hour_on"), tags.get("hour_off"))
+                {
+                    let mut condition = format!("{}-{}", on, off);
+                    if let (Some(first), Some(last)) = (tags.get("day_on"), tags.get("day_off")) {
+                        // Monday to Mo, and so on
+                        condition = format!(
+                            "{}-{} {}",
+                            first.get(0..2).unwrap_or(""),
+                            last.get(0..2).unwrap_or(""),
+                            condition
+                        );
+                    }
+                    match TimeWindows::parse(&condition) {
+                        Ok(during) if during.is_empty() => {
+                            continue;
+                        }
+                        Ok(during) => Some(during),
+                        Err(err) => {
+                            timer.warn(format!("Skipping {}={} on {}: {}", key, value, id, err));
+                            continue;
+                        }
+                    }
+                } else {
+                    None
+                };
+                results.push((rt, RestrictionScope { vehicles, during }));
+            }
+        }
+        results
+    }
+}
+
+/// Which vehicles have to obey a turn restriction, and when.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RestrictionScope {
+    pub vehicles: EnumSet<PathConstraints>,
+    /// If present, the restriction only applies during these times of day.
+    pub during: Option<TimeWindows>,
+}
+
+impl RestrictionScope {
+    /// Applies to every vehicle, all the time
+    pub fn always() -> RestrictionScope {
+        RestrictionScope {
+            vehicles: RestrictionScope::all_vehicles(),
+            during: None,
+        }
+    }
+
+    /// Everything that drives on roads and has to obey turn restrictions
+    pub fn all_vehicles() -> EnumSet<PathConstraints> {
+        EnumSet::all() - PathConstraints::Pedestrian - PathConstraints::Train
+    }
+
+    pub fn is_unconditional(&self) -> bool {
+        self.vehicles.is_superset(RestrictionScope::all_vehicles()) && self.during.is_none()
+    }
+
+    /// Does this apply to some vehicle sometime in a time range? Without a time range, a
+    /// time-conditional restriction is treated as always applying.
+    pub fn applies(&self, constraints: PathConstraints, during: Option<(Time, Time)>) -> bool {
+        if !self.vehicles.contains(constraints) {
+            return false;
+        }
+        match (&self.during, during) {
+            (Some(windows), Some((start, end))) => windows.overlaps(start, end),
+            _ => true,
+        }
+    }
+
+    /// Like "for truck, bus during 07:00-09:00", or empty for unconditional restrictions
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.vehicles.is_superset(RestrictionScope::all_vehicles()) {
+            parts.push(format!(
+                "for {}",
+                self.vehicles
+                    .iter()
+                    .map(|c| format!("{:?}", c).to_ascii_lowercase())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        if let Some(ref windows) = self.during {
+            parts.push(format!("during {}", windows.describe()));
+        }
+        parts.join(" ")
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
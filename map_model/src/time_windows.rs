@@ -0,0 +1,313 @@
+//! Some restrictions only apply at certain times of day, like turns banned during rush hour. OSM
+//! describes these with `:conditional` tags, using a subset of the opening_hours syntax for the
+//! condition. See <https://wiki.openstreetmap.org/wiki/Conditional_restrictions>.
+//!
+//! The simulation only models one typical weekday, so rules limited to other days are dropped.
+
+use serde::{Deserialize, Serialize};
+
+use geom::{Duration, Time};
+
+const SECONDS_PER_DAY: f64 = 24.0 * 3600.0;
+
+/// Times of the day, like "Mo-Fr 07:00-09:00,16:00-18:00".
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TimeWindows {
+    /// (start, end) since midnight. Windows crossing midnight are split in two.
+    windows: Vec<(Duration, Duration)>,
+}
+
+impl TimeWindows {
+    /// Parses the condition of a conditional tag, like "Mo-Fr 07:00-09:00; Sa 10:00-12:00".
+    /// Conditions that don't involve time, like "wet" or "weight>7.5", aren't supported.
+    pub fn parse(condition: &str) -> Result<TimeWindows, String> {
+        let mut windows = Vec::new();
+        for rule in condition.split(';') {
+            let rule = rule.trim();
+            if rule.is_empty() {
+                continue;
+            }
+            if rule == "24/7" {
+                windows.push((Duration::ZERO, Duration::hours(24)));
+                continue;
+            }
+            // An optional list of days comes first
+            let (days, times) = if rule.starts_with(|c: char| c.is_ascii_digit()) {
+                (None, rule)
+            } else {
+                match rule.find(char::is_whitespace) {
+                    Some(idx) => (Some(&rule[..idx]), rule[idx..].trim()),
+                    None => (Some(rule), ""),
+                }
+            };
+            if times == "off" {
+                // Exceptions to other rules, like "PH off". Holidays aren't modeled.
+                continue;
+            }
+            if let Some(days) = days {
+                if !includes_weekday(days)? {
+                    continue;
+                }
+            }
+
+            if times.is_empty() {
+                windows.push((Duration::ZERO, Duration::hours(24)));
+                continue;
+            }
+            for range in times.split(',') {
+                let range = range.trim();
+                let mut parts = range.split('-');
+                let (start, end) = match (parts.next(), parts.next(), parts.next()) {
+                    (Some(start), Some(end), None) => (parse_time(start)?, parse_time(end)?),
+                    _ => {
+                        return Err(format!("Can't parse time range {} in {}", range, condition));
+                    }
+                };
+                if start < end {
+                    windows.push((start, end));
+                } else {
+                    // Like 22:00-06:00
+                    windows.push((start, Duration::hours(24)));
+                    windows.push((Duration::ZERO, end));
+                }
+            }
+        }
+        Ok(TimeWindows { windows })
+    }
+
+    /// Is any part of the time range inside one of these windows? If start and end are the same,
+    /// just checks that instant. Any time past the first day wraps around.
+    pub fn overlaps(&self, start: Time, end: Time) -> bool {
+        let len = end.inner_seconds() - start.inner_seconds();
+        if len >= SECONDS_PER_DAY {
+            return !self.windows.is_empty();
+        }
+        let start = start.inner_seconds() % SECONDS_PER_DAY;
+        let end = start + len;
+        self.windows.iter().any(|(w1, w2)| {
+            // The range may wrap past midnight, so also check the windows on the next day
+            vec![0.0, SECONDS_PER_DAY].into_iter().any(|shift| {
+                let (w1, w2) = (w1.inner_seconds() + shift, w2.inner_seconds() + shift);
+                if len > 0.0 {
+                    w1 < end && start < w2
+                } else {
+                    w1 <= start && start < w2
+                }
+            })
+        })
+    }
+
+    /// Is the entire time range inside these windows? If start and end are the same, just checks
+    /// that instant.
+    pub fn covers(&self, start: Time, end: Time) -> bool {
+        !self.complement().overlaps(start, end)
+    }
+
+    /// The times of day outside these windows
+    pub fn complement(&self) -> TimeWindows {
+        let mut sorted = self.windows.clone();
+        sorted.sort();
+        let mut windows = Vec::new();
+        let mut start = Duration::ZERO;
+        for (w1, w2) in sorted {
+            if w1 > start {
+                windows.push((start, w1));
+            }
+            start = start.max(w2);
+        }
+        if start < Duration::hours(24) {
+            windows.push((start, Duration::hours(24)));
+        }
+        TimeWindows { windows }
+    }
+
+    /// True if the windows never apply, like for conditions only on weekends
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    pub fn contains(&self, time: Time) -> bool {
+        self.overlaps(time, time)
+    }
+
+    /// Like "07:00-09:00, 16:00-18:00"
+    pub fn describe(&self) -> String {
+        if self.windows.is_empty() {
+            return "never".to_string();
+        }
+        self.windows
+            .iter()
+            .map(|(start, end)| format!("{}-{}", format_time(*start), format_time(*end)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Splits a conditional tag's value, like "no @ (Mo-Fr 07:00-09:00); yes @ (Sa 10:00-12:00)",
+    /// into (value, condition) pairs.
+    pub fn parse_conditional_tag(value: &str) -> Vec<(String, String)> {
+        // Semicolons separate rules, unless they're inside a parenthesized condition
+        let mut rules = Vec::new();
+        let mut current = String::new();
+        let mut depth = 0;
+        for c in value.chars() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                ';' if depth == 0 => {
+                    rules.push(std::mem::replace(&mut current, String::new()));
+                    continue;
+                }
+                _ => {}
+            }
+            current.push(c);
+        }
+        rules.push(current);
+
+        let mut results = Vec::new();
+        for rule in rules {
+            let mut parts = rule.splitn(2, '@');
+            if let (Some(value), Some(condition)) = (parts.next(), parts.next()) {
+                let condition = condition.trim();
+                let condition = condition
+                    .strip_prefix('(')
+                    .and_then(|c| c.strip_suffix(')'))
+                    .unwrap_or(condition);
+                results.push((value.trim().to_string(), condition.trim().to_string()));
+            }
+        }
+        results
+    }
+}
+
+/// Does a list of days like "Mo-Fr" or "Mo,We,Sa" include any weekday?
+fn includes_weekday(days: &str) -> Result<bool, String> {
+    let day_idx = |day: &str| -> Result<usize, String> {
+        vec!["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"]
+            .into_iter()
+            .position(|x| x == day)
+            .ok_or_else(|| format!("Unknown day {}", day))
+    };
+    for part in days.split(',') {
+        let part = part.trim();
+        // Holidays aren't modeled
+        if part == "PH" || part == "SH" {
+            continue;
+        }
+        let mut range = part.split('-');
+        let (first, last) = match (range.next(), range.next()) {
+            (Some(first), Some(last)) => (day_idx(first)?, day_idx(last)?),
+            (Some(day), None) => (day_idx(day)?, day_idx(day)?),
+            _ => {
+                return Err(format!("Can't parse days {}", days));
+            }
+        };
+        // Ranges like Sa-Mo wrap around the week
+        if first <= 4 || last <= 4 || last < first {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Parses "07:30" or "24:00" into the time since midnight
+fn parse_time(time: &str) -> Result<Duration, String> {
+    let err = || format!("Can't parse time {}", time);
+    let mut parts = time.trim().split(':');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(hours), Some(minutes), None) => {
+            let hours = hours.parse::<usize>().map_err(|_| err())?;
+            let minutes = minutes.parse::<usize>().map_err(|_| err())?;
+            if hours > 24 || minutes >= 60 {
+                return Err(err());
+            }
+            Ok(Duration::hours(hours) + Duration::minutes(minutes))
+        }
+        _ => Err(err()),
+    }
+}
+
+fn format_time(time: Duration) -> String {
+    let minutes = (time.inner_seconds() / 60.0).round() as usize;
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn t(hours: usize, minutes: usize) -> Time {
+        Time::START_OF_DAY + Duration::hours(hours) + Duration::minutes(minutes)
+    }
+
+    #[test]
+    fn weekday_rush_hours() {
+        let w = TimeWindows::parse("Mo-Fr 07:00-09:00,16:00-18:00").unwrap();
+        assert_eq!(w.describe(), "07:00-09:00, 16:00-18:00");
+        assert!(w.contains(t(7, 0)));
+        assert!(w.contains(t(8, 30)));
+        assert!(!w.contains(t(9, 0)));
+        assert!(!w.contains(t(12, 0)));
+        assert!(w.contains(t(17, 0)));
+        assert!(!w.contains(t(18, 30)));
+    }
+
+    #[test]
+    fn crossing_midnight() {
+        let w = TimeWindows::parse("22:00-06:00").unwrap();
+        assert_eq!(w.describe(), "22:00-24:00, 00:00-06:00");
+        assert!(w.contains(t(23, 0)));
+        assert!(w.contains(t(3, 0)));
+        assert!(!w.contains(t(6, 0)));
+        assert!(!w.contains(t(12, 0)));
+    }
+
+    #[test]
+    fn weekends_only() {
+        assert!(TimeWindows::parse("Sa,Su 10:00-12:00").unwrap().is_empty());
+        assert!(TimeWindows::parse("Sa-Su").unwrap().is_empty());
+        // Wraps around to include Monday
+        assert!(!TimeWindows::parse("Sa-Mo 10:00-12:00").unwrap().is_empty());
+    }
+
+    #[test]
+    fn holidays_off() {
+        assert!(TimeWindows::parse("PH off").unwrap().is_empty());
+        assert_eq!(
+            TimeWindows::parse("Mo-Fr 07:00-09:00; PH off").unwrap(),
+            TimeWindows::parse("Mo-Fr 07:00-09:00").unwrap()
+        );
+    }
+
+    #[test]
+    fn conditional_tag_with_nested_parens() {
+        assert_eq!(
+            TimeWindows::parse_conditional_tag(
+                "no @ (Mo-Fr 07:00-09:00; Sa (10:00-12:00)); destination @ (22:00-06:00)"
+            ),
+            vec![
+                (
+                    "no".to_string(),
+                    "Mo-Fr 07:00-09:00; Sa (10:00-12:00)".to_string()
+                ),
+                ("destination".to_string(), "22:00-06:00".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ranges_across_midnight() {
+        let night = TimeWindows::parse("22:00-06:00").unwrap();
+        // 23:00 until 05:00 the next day
+        assert!(night.covers(t(23, 0), t(29, 0)));
+        assert!(!night.covers(t(21, 0), t(23, 0)));
+        assert!(night.overlaps(t(21, 0), t(23, 0)));
+
+        let morning = TimeWindows::parse("07:00-09:00").unwrap();
+        // 23:00 until 08:00 the next day overlaps the next morning, but not if it ends at 06:00
+        assert!(morning.overlaps(t(23, 0), t(32, 0)));
+        assert!(!morning.overlaps(t(23, 0), t(30, 0)));
+        assert!(!morning.covers(t(23, 0), t(32, 0)));
+        // Times past the first day wrap around
+        assert!(morning.contains(t(32, 0)));
+    }
+}
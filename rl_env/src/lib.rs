@@ -0,0 +1,511 @@
+//! A reinforcement learning environment for traffic signal control, in the style of OpenAI Gym.
+//! An agent repeatedly observes the state of some traffic signals, picks a stage for each of them
+//! to run next, and receives a reward.
+//!
+//! The binary in this crate exposes an `Environment` over a local socket, so that any language can
+//! drive it. See https://dabreegster.github.io/abstreet/dev/api.html.
+
+#[macro_use]
+extern crate log;
+
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use serde::{Deserialize, Serialize};
+
+use abstutil::Timer;
+use geom::{Duration, Time};
+use map_model::{IntersectionID, LaneID, Map, MovementID, PermanentMapEdits};
+use sim::{AlertHandler, Scenario, Sim, SimOptions};
+
+/// Describes what to simulate and how to score the agent.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EnvConfig {
+    /// Path to a scenario file
+    pub scenario: String,
+    pub edits: Option<PermanentMapEdits>,
+    /// The traffic signals the agent controls. If empty, every signal in the map is controlled.
+    pub intersections: Vec<IntersectionID>,
+    /// After resetting, simulate until this time before handing control to the agent.
+    pub start_time: Time,
+    /// The episode is done once the simulation reaches this time.
+    pub end_time: Time,
+    /// How long each chosen stage runs before the agent acts again.
+    pub step_duration: Duration,
+    /// A signal won't switch stages until the current one has run at least this long.
+    pub min_green: Duration,
+    /// When the agent switches stages, the old one keeps running this long first, so vehicles
+    /// already committed to a turn can clear. Must be shorter than `step_duration`.
+    pub yellow_time: Duration,
+    pub reward: Reward,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Reward {
+    /// The negative total delay in seconds experienced by every agent that finished crossing a
+    /// controlled intersection during the step.
+    TotalDelay,
+    /// The number of agents that passed through a controlled intersection during the step.
+    Throughput,
+}
+
+/// For each controlled signal (in the same order as `Observation::signals`), the stage to run
+/// next. None leaves the signal on its normal timing.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Action {
+    pub stages: Vec<Option<usize>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Observation {
+    pub time: Time,
+    pub signals: Vec<SignalObservation>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignalObservation {
+    pub id: IntersectionID,
+    pub num_stages: usize,
+    pub current_stage: usize,
+    pub remaining_time: Duration,
+    /// In the same order as `ControlTrafficSignal::movements`
+    pub movements: Vec<MovementObservation>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MovementObservation {
+    pub id: MovementID,
+    /// How many agents are at the intersection, waiting to start this movement
+    pub queue_length: usize,
+    /// How many vehicles are on the lanes leading to this movement. Lanes feeding multiple
+    /// movements are counted for each of them.
+    pub approaching_vehicles: usize,
+    /// Summed over everyone in the queue, how long have they waited so far?
+    pub total_delay: Duration,
+    pub max_delay: Duration,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StepResult {
+    pub observation: Observation,
+    pub reward: f64,
+    pub done: bool,
+}
+
+pub struct Environment {
+    config: EnvConfig,
+    map: Map,
+    scenario: Scenario,
+    sim: Sim,
+    signals: Vec<IntersectionID>,
+    /// Per controlled signal, the current stage and when it started
+    green_since: Vec<(usize, Time)>,
+
+    last_step: Time,
+    last_thruput: usize,
+    last_reward: f64,
+}
+
+impl Environment {
+    pub fn new(config: EnvConfig, timer: &mut Timer) -> Result<Environment, String> {
+        let scenario: Scenario = abstutil::read_binary(config.scenario.clone(), timer);
+        let mut map = Map::new(scenario.map_name.path(), timer);
+        if let Some(perma) = config.edits.clone() {
+            let edits = perma.to_edits(&map)?;
+            map.must_apply_edits(edits, timer);
+            map.recalculate_pathfinding_after_edits(timer);
+        }
+        Environment::from_scenario(config, map, scenario, timer)
+    }
+
+    /// Like `new`, but with an already loaded map and scenario. `config.scenario` and
+    /// `config.edits` are ignored.
+    pub fn from_scenario(
+        config: EnvConfig,
+        map: Map,
+        scenario: Scenario,
+        timer: &mut Timer,
+    ) -> Result<Environment, String> {
+        let signals = if config.intersections.is_empty() {
+            map.all_intersections()
+                .iter()
+                .filter(|i| i.is_traffic_signal())
+                .map(|i| i.id)
+                .collect()
+        } else {
+            for i in &config.intersections {
+                if map.maybe_get_traffic_signal(*i).is_none() {
+                    return Err(format!("{} isn't a traffic signal", i));
+                }
+            }
+            config.intersections.clone()
+        };
+        if config.step_duration <= Duration::ZERO {
+            return Err("step_duration must be positive".to_string());
+        }
+        if config.yellow_time < Duration::ZERO || config.yellow_time >= config.step_duration {
+            return Err(
+                "yellow_time must be at least 0 and shorter than step_duration".to_string(),
+            );
+        }
+        if config.min_green < Duration::ZERO {
+            return Err("min_green can't be negative".to_string());
+        }
+
+        let sim = Sim::new(&map, sim_options(), timer);
+        Ok(Environment {
+            config,
+            map,
+            scenario,
+            sim,
+            green_since: vec![(0, Time::START_OF_DAY); signals.len()],
+            signals,
+
+            last_step: Time::START_OF_DAY,
+            last_thruput: 0,
+            last_reward: 0.0,
+        })
+    }
+
+    /// Start a new episode. The seed determines the randomness in instantiating the scenario.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        let mut timer = Timer::throwaway();
+        self.sim = Sim::new(&self.map, sim_options(), &mut timer);
+        let mut rng = XorShiftRng::seed_from_u64(seed);
+        self.scenario
+            .instantiate(&mut self.sim, &self.map, &mut rng, &mut timer);
+        if self.config.start_time > Time::START_OF_DAY {
+            self.sim.timed_step(
+                &self.map,
+                self.config.start_time - Time::START_OF_DAY,
+                &mut None,
+                &mut timer,
+            );
+        }
+
+        self.last_step = self.sim.time();
+        self.last_thruput = self.current_thruput();
+        self.last_reward = 0.0;
+        for (idx, i) in self.signals.iter().enumerate() {
+            let (current, _) = self.sim.current_stage_and_remaining_time(*i);
+            self.green_since[idx] = (current, self.last_step);
+        }
+        debug!("Reset episode with seed {} at {}", seed, self.last_step);
+        self.observe()
+    }
+
+    pub fn observe(&self) -> Observation {
+        let now = self.sim.time();
+        let mut signals = Vec::new();
+        for i in &self.signals {
+            let signal = self.map.get_traffic_signal(*i);
+            let (current_stage, remaining_time) = self.sim.current_stage_and_remaining_time(*i);
+            let waiting = self.sim.get_waiting_agents(*i);
+
+            let mut movements = Vec::new();
+            for m in signal.movements.values() {
+                let mut obs = MovementObservation {
+                    id: m.id,
+                    queue_length: 0,
+                    approaching_vehicles: 0,
+                    total_delay: Duration::ZERO,
+                    max_delay: Duration::ZERO,
+                };
+                for (_, turn, since) in &waiting {
+                    if m.members.contains(turn) {
+                        let delay = now - *since;
+                        obs.queue_length += 1;
+                        obs.total_delay += delay;
+                        obs.max_delay = obs.max_delay.max(delay);
+                    }
+                }
+                let mut lanes: Vec<LaneID> = m.members.iter().map(|t| t.src).collect();
+                lanes.sort();
+                lanes.dedup();
+                for l in lanes {
+                    obs.approaching_vehicles += self.sim.target_lane_penalty(self.map.get_l(l)).0;
+                }
+                movements.push(obs);
+            }
+
+            signals.push(SignalObservation {
+                id: *i,
+                num_stages: signal.stages.len(),
+                current_stage,
+                remaining_time,
+                movements,
+            });
+        }
+        Observation { time: now, signals }
+    }
+
+    /// Run the chosen stages for one step, then score the result.
+    pub fn step(&mut self, action: &Action) -> Result<StepResult, String> {
+        if self.is_done() {
+            return Err("The episode is over; call reset".to_string());
+        }
+        if action.stages.len() != self.signals.len() {
+            return Err(format!(
+                "Action has {} stages, but there are {} controlled signals",
+                action.stages.len(),
+                self.signals.len()
+            ));
+        }
+        for (i, stage) in self.signals.iter().zip(action.stages.iter()) {
+            if let Some(stage) = stage {
+                let num_stages = self.map.get_traffic_signal(*i).stages.len();
+                if *stage >= num_stages {
+                    return Err(format!("{} only has {} stages", i, num_stages));
+                }
+            }
+        }
+
+        let now = self.sim.time();
+        let mut switching = Vec::new();
+        for (idx, (i, stage)) in self.signals.iter().zip(action.stages.iter()).enumerate() {
+            let stage = if let Some(stage) = stage {
+                *stage
+            } else {
+                continue;
+            };
+            // The signal may have changed stages on its own since the last step
+            let (current, _) = self.sim.current_stage_and_remaining_time(*i);
+            if self.green_since[idx].0 != current {
+                self.green_since[idx] = (current, self.last_step);
+            }
+
+            if stage == current || now - self.green_since[idx].1 < self.config.min_green {
+                self.sim.override_traffic_signal_stage(
+                    *i,
+                    current,
+                    self.config.step_duration,
+                    &self.map,
+                );
+            } else if self.config.yellow_time == Duration::ZERO {
+                self.sim.override_traffic_signal_stage(
+                    *i,
+                    stage,
+                    self.config.step_duration,
+                    &self.map,
+                );
+                self.green_since[idx] = (stage, now);
+            } else {
+                // Hold the old stage for the yellow. Vehicles won't start a turn they can't
+                // finish before it ends.
+                self.sim.override_traffic_signal_stage(
+                    *i,
+                    current,
+                    self.config.yellow_time,
+                    &self.map,
+                );
+                switching.push((idx, *i, stage));
+            }
+        }
+
+        let dt = std::cmp::min(
+            self.config.step_duration,
+            self.config.end_time - self.sim.time(),
+        );
+        let mut timer = Timer::throwaway();
+        if switching.is_empty() {
+            self.sim.timed_step(&self.map, dt, &mut None, &mut timer);
+        } else {
+            let yellow = std::cmp::min(self.config.yellow_time, dt);
+            self.sim
+                .timed_step(&self.map, yellow, &mut None, &mut timer);
+            for (idx, i, stage) in switching {
+                self.sim.override_traffic_signal_stage(
+                    i,
+                    stage,
+                    self.config.step_duration - self.config.yellow_time,
+                    &self.map,
+                );
+                self.green_since[idx] = (stage, self.sim.time());
+            }
+            if dt > yellow {
+                self.sim
+                    .timed_step(&self.map, dt - yellow, &mut None, &mut timer);
+            }
+        }
+
+        self.last_reward = self.calculate_reward();
+        self.last_step = self.sim.time();
+        self.last_thruput = self.current_thruput();
+
+        Ok(StepResult {
+            observation: self.observe(),
+            reward: self.last_reward,
+            done: self.is_done(),
+        })
+    }
+
+    /// The reward from the most recent step
+    pub fn reward(&self) -> f64 {
+        self.last_reward
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.sim.time() >= self.config.end_time
+    }
+
+    pub fn controlled_signals(&self) -> &Vec<IntersectionID> {
+        &self.signals
+    }
+
+    pub fn get_map(&self) -> &Map {
+        &self.map
+    }
+
+    pub fn get_sim(&self) -> &Sim {
+        &self.sim
+    }
+
+    fn calculate_reward(&self) -> f64 {
+        match self.config.reward {
+            Reward::TotalDelay => {
+                let analytics = self.sim.get_analytics();
+                let mut total = Duration::ZERO;
+                for i in &self.signals {
+                    if let Some(list) = analytics.intersection_delays.get(i) {
+                        // The list is sorted by time, so only look at the end.
+                        for (_, t, dt, _) in list.iter().rev() {
+                            if *t <= self.last_step {
+                                break;
+                            }
+                            total += *dt;
+                        }
+                    }
+                }
+                -total.inner_seconds()
+            }
+            Reward::Throughput => (self.current_thruput() - self.last_thruput) as f64,
+        }
+    }
+
+    fn current_thruput(&self) -> usize {
+        let analytics = self.sim.get_analytics();
+        self.signals
+            .iter()
+            .map(|i| analytics.intersection_thruput.total_for(*i))
+            .sum()
+    }
+}
+
+/// Every episode, including the one before the first reset, uses the same options.
+fn sim_options() -> SimOptions {
+    let mut opts = SimOptions::new("rl_env");
+    opts.alerts = AlertHandler::Silence;
+    opts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sim::{IndividTrip, PersonSpec, TripEndpoint, TripMode, TripPurpose};
+
+    /// Drive cars between every pair of borders around the signal in a small test map.
+    fn make_env(reward: Reward) -> Environment {
+        let mut timer = Timer::throwaway();
+        let path = abstutil::path("../tests/input/lane_selection.osm");
+        let raw = convert_osm::convert(
+            convert_osm::Options {
+                name: abstutil::MapName::new("oneshot", "lane_selection"),
+                osm_input: path,
+                clip: None,
+                map_config: map_model::MapConfig {
+                    driving_side: map_model::DrivingSide::Right,
+                    bikes_can_use_bus_lanes: true,
+                    inferred_sidewalks: true,
+                },
+                onstreet_parking: convert_osm::OnstreetParking::JustOSM,
+                public_offstreet_parking: convert_osm::PublicOffstreetParking::None,
+                private_offstreet_parking: convert_osm::PrivateOffstreetParking::FixedPerBldg(0),
+                elevation: None,
+                include_railroads: true,
+            },
+            &mut timer,
+        );
+        let map = Map::create_from_raw(raw, true, true, &mut timer);
+
+        let borders: Vec<IntersectionID> = map
+            .all_intersections()
+            .iter()
+            .filter(|i| i.is_border())
+            .map(|i| i.id)
+            .collect();
+        let mut scenario = Scenario::empty(&map, "rl_env_smoke_test");
+        for (idx, from) in borders.iter().enumerate() {
+            for to in &borders {
+                if from != to {
+                    scenario.people.push(PersonSpec {
+                        orig_id: None,
+                        origin: TripEndpoint::Border(*from),
+                        trips: vec![IndividTrip::new(
+                            Time::START_OF_DAY + Duration::seconds(idx as f64),
+                            TripPurpose::Shopping,
+                            TripEndpoint::Border(*to),
+                            TripMode::Drive,
+                        )],
+                    });
+                }
+            }
+        }
+
+        let config = EnvConfig {
+            scenario: String::new(),
+            edits: None,
+            intersections: Vec::new(),
+            start_time: Time::START_OF_DAY,
+            end_time: Time::START_OF_DAY + Duration::minutes(5),
+            step_duration: Duration::seconds(10.0),
+            min_green: Duration::seconds(20.0),
+            yellow_time: Duration::seconds(3.0),
+            reward,
+        };
+        Environment::from_scenario(config, map, scenario, &mut timer).unwrap()
+    }
+
+    fn check_shape(env: &Environment, obs: &Observation) {
+        assert_eq!(obs.signals.len(), env.controlled_signals().len());
+        for (signal, i) in obs.signals.iter().zip(env.controlled_signals()) {
+            let ts = env.get_map().get_traffic_signal(*i);
+            assert_eq!(signal.id, *i);
+            assert_eq!(signal.num_stages, ts.stages.len());
+            assert!(signal.current_stage < signal.num_stages);
+            assert_eq!(signal.movements.len(), ts.movements.len());
+        }
+    }
+
+    #[test]
+    fn reset_and_step() {
+        for reward in vec![Reward::TotalDelay, Reward::Throughput] {
+            let mut env = make_env(reward);
+            let obs = env.reset(42);
+            assert!(!obs.signals.is_empty());
+            check_shape(&env, &obs);
+
+            let mut steps = 0;
+            while !env.is_done() {
+                // Cycle through the stages, so the signal switches at least a few times
+                let action = Action {
+                    stages: obs
+                        .signals
+                        .iter()
+                        .map(|s| Some((steps / 3) % s.num_stages))
+                        .collect(),
+                };
+                let result = env.step(&action).unwrap();
+                check_shape(&env, &result.observation);
+                match reward {
+                    Reward::TotalDelay => assert!(result.reward <= 0.0),
+                    Reward::Throughput => assert!(result.reward >= 0.0),
+                }
+                assert_eq!(result.reward, env.reward());
+                assert_eq!(result.done, env.is_done());
+                steps += 1;
+            }
+            assert_eq!(steps, 30);
+            assert!(env.step(&Action { stages: Vec::new() }).is_err());
+        }
+    }
+}
@@ -0,0 +1,83 @@
+// This serves an rl_env::Environment over a local TCP socket, so an agent written in any language
+// can drive it. Each request and response is one line of JSON. To run this:
+//
+// > cd rl_env; cargo run -- --config=env.json --port=5555
+// > echo '{"Reset": {"seed": 42}}' | nc localhost 5555
+//
+// Requests are `{"Reset": {"seed": 42}}`, `"Observe"`, `{"Step": {"stages": [0, null, 2]}}`, and
+// `"Reward"`. Responses are `{"Ok": ...}` or `{"Err": "message"}`.
+
+#[macro_use]
+extern crate log;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+
+use serde::Deserialize;
+
+use abstutil::{CmdArgs, Timer};
+use rl_env::{Action, EnvConfig, Environment};
+
+#[derive(Deserialize)]
+enum Request {
+    Reset { seed: u64 },
+    Observe,
+    Step(Action),
+    Reward,
+}
+
+fn main() {
+    let mut args = CmdArgs::new();
+    let config: EnvConfig = abstutil::read_json(args.required("--config"), &mut Timer::throwaway());
+    let port = args.required("--port").parse::<u16>().unwrap();
+    args.done();
+
+    let mut env = match Environment::new(config, &mut Timer::new("setup rl_env")) {
+        Ok(env) => env,
+        Err(err) => panic!("Bad config: {}", err),
+    };
+
+    let listener = TcpListener::bind(("127.0.0.1", port)).unwrap();
+    info!("Listening on {}", listener.local_addr().unwrap());
+    // Only one agent can drive the environment at a time.
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(err) = serve(&mut env, stream) {
+                    error!("Connection failed: {}", err);
+                }
+            }
+            Err(err) => {
+                error!("Couldn't accept connection: {}", err);
+            }
+        }
+    }
+}
+
+fn serve(env: &mut Environment, stream: TcpStream) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut response = match handle(env, &line) {
+            Ok(value) => abstutil::to_json_terse(&Ok::<_, String>(value)),
+            Err(err) => abstutil::to_json_terse(&Err::<(), _>(err)),
+        };
+        response.push('\n');
+        writer.write_all(response.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn handle(env: &mut Environment, line: &str) -> Result<serde_json::Value, String> {
+    let req: Request = serde_json::from_str(line).map_err(|err| err.to_string())?;
+    let value = match req {
+        Request::Reset { seed } => serde_json::to_value(env.reset(seed)),
+        Request::Observe => serde_json::to_value(env.observe()),
+        Request::Step(action) => serde_json::to_value(env.step(&action)?),
+        Request::Reward => serde_json::to_value(env.reward()),
+    };
+    value.map_err(|err| err.to_string())
+}
@@ -6,11 +6,12 @@ use abstutil::Counter;
 use geom::{Distance, Duration, Time};
 use map_model::{
     BusRouteID, BusStopID, CompressedMovementID, IntersectionID, LaneID, Map, MovementID,
-    ParkingLotID, Path, PathRequest, RoadID, Traversable, TurnID,
+    ParkingLotID, Path, PathRequest, RoadID, TravelTimes, Traversable, TurnID,
 };
 
 use crate::{
-    AgentID, AgentType, AlertLocation, CarID, Event, ParkingSpot, TripID, TripMode, TripPhaseType,
+    AgentID, AgentType, AlertLocation, CarID, Event, ParkingSpot, RerouteCause, TripID, TripMode,
+    TripPhaseType, VehicleType,
 };
 
 /// As a simulation runs, different pieces emit Events. The Analytics object listens to these,
@@ -39,6 +40,17 @@ pub struct Analytics {
     /// For each passenger boarding, how long did they wait at the stop?
     pub passengers_boarding: BTreeMap<BusStopID, Vec<(Time, BusRouteID, Duration)>>,
     pub passengers_alighting: BTreeMap<BusStopID, Vec<(Time, BusRouteID)>>,
+    /// How long did each vehicle spend at the stop, letting passengers on and off?
+    pub bus_dwell_times: BTreeMap<BusStopID, Vec<(Time, BusRouteID, Duration)>>,
+    /// When did somebody have to keep waiting, because the vehicle arriving was full?
+    pub passengers_denied_boarding: BTreeMap<BusStopID, Vec<(Time, BusRouteID)>>,
+    /// For each route, how many passengers were on board each time a vehicle left a stop. This
+    /// describes the load on the segment of the route following that stop.
+    pub transit_loads: BTreeMap<BusRouteID, Vec<(Time, CarID, BusStopID, usize)>>,
+    /// How long did each ride-hailing passenger wait to be picked up?
+    pub ride_hail_waits: Vec<(Time, CarID, Duration)>,
+    /// Every distance driven by a ride-hailing vehicle, and whether a passenger was aboard
+    pub ride_hail_distance: Vec<(Time, CarID, Distance, bool)>,
 
     pub started_trips: BTreeMap<TripID, Time>,
     /// Finish time, ID, mode, trip duration if successful (or None if cancelled)
@@ -52,6 +64,13 @@ pub struct Analytics {
     /// If it is over a certain threshold (<95% of max speed)
     /// TripID, [(LaneID, Percent of maximum speed as an integer (0-100)]
     pub lane_speed_percentage: BTreeMap<TripID, BTreeMap<LaneID, u8>>,
+    /// How long cars, trucks, and motorcycles took to cross each lane, including waiting to turn
+    /// at the end. Pathfinding can use this to avoid congestion; see `Map::set_travel_times`.
+    pub lane_travel_times: TravelTimes,
+    /// When did each vehicle enter the lane it's currently on?
+    vehicles_entered_lane: BTreeMap<CarID, (LaneID, Time)>,
+    /// When did drivers change their path en route, and why?
+    pub reroutes: Vec<(Time, CarID, RerouteCause)>,
 
     // TODO This subsumes finished_trips
     pub trip_log: Vec<(Time, TripID, Option<PathRequest>, TripPhaseType)>,
@@ -59,6 +78,8 @@ pub struct Analytics {
     // TODO Transit riders aren't represented here yet, just the vehicle they're riding.
     /// Only for traffic signals. The u8 is the movement index from a CompressedMovementID.
     pub intersection_delays: BTreeMap<IntersectionID, Vec<(u8, Time, Duration, AgentType)>>,
+    /// When did a traffic signal give priority to a bus or train?
+    pub transit_signal_priority: BTreeMap<IntersectionID, Vec<(Time, CarID)>>,
 
     /// Per parking lane or lot, when does a spot become filled (true) or free (false)
     pub parking_lane_changes: BTreeMap<LaneID, Vec<(Time, bool)>>,
@@ -80,12 +101,21 @@ impl Analytics {
             bus_arrivals: Vec::new(),
             passengers_boarding: BTreeMap::new(),
             passengers_alighting: BTreeMap::new(),
+            bus_dwell_times: BTreeMap::new(),
+            passengers_denied_boarding: BTreeMap::new(),
+            transit_loads: BTreeMap::new(),
+            ride_hail_waits: Vec::new(),
+            ride_hail_distance: Vec::new(),
             started_trips: BTreeMap::new(),
             finished_trips: Vec::new(),
             trip_intersection_delays: BTreeMap::new(),
             lane_speed_percentage: BTreeMap::new(),
+            lane_travel_times: TravelTimes::new(),
+            vehicles_entered_lane: BTreeMap::new(),
+            reroutes: Vec::new(),
             trip_log: Vec::new(),
             intersection_delays: BTreeMap::new(),
+            transit_signal_priority: BTreeMap::new(),
             parking_lane_changes: BTreeMap::new(),
             parking_lot_changes: BTreeMap::new(),
             alerts: Vec::new(),
@@ -151,9 +181,35 @@ impl Analytics {
             _ => {}
         }
 
+        // Lane travel times. Buses and bikes aren't a good indication of congestion.
+        if let Event::AgentEntersTraversable(AgentID::Car(car), on, _) = ev {
+            if matches!(
+                car.1,
+                VehicleType::Car | VehicleType::Truck | VehicleType::Motorcycle
+            ) {
+                match on {
+                    Traversable::Lane(l) => {
+                        self.vehicles_entered_lane.insert(car, (l, time));
+                    }
+                    Traversable::Turn(t) => {
+                        // The vehicle might've last been seen on some lane during a previous trip
+                        if let Some((l, entered)) = self.vehicles_entered_lane.remove(&car) {
+                            if l == t.src {
+                                self.lane_travel_times.record(l, entered, time - entered);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
         // Bus arrivals
-        if let Event::BusArrivedAtStop(bus, route, stop) = ev {
+        if let Event::BusArrivedAtStop(bus, route, stop, dwell) = ev {
             self.bus_arrivals.push((time, bus, route, stop));
+            self.bus_dwell_times
+                .entry(stop)
+                .or_insert_with(Vec::new)
+                .push((time, route, dwell));
         }
 
         // Passengers boarding/alighting
@@ -169,6 +225,27 @@ impl Analytics {
                 .or_insert_with(Vec::new)
                 .push((time, route));
         }
+        if let Event::PassengerDeniedBoarding(_, _, route, stop) = ev {
+            self.passengers_denied_boarding
+                .entry(stop)
+                .or_insert_with(Vec::new)
+                .push((time, route));
+        }
+        if let Event::BusDepartedFromStop(bus, route, stop, passengers) = ev {
+            self.transit_loads
+                .entry(route)
+                .or_insert_with(Vec::new)
+                .push((time, bus, stop, passengers));
+        }
+
+        // Ride-hailing
+        if let Event::RideHailPickup(_, car, waiting) = ev {
+            self.ride_hail_waits.push((time, car, waiting));
+        }
+        if let Event::RideHailLegFinished(car, dist, passenger) = ev {
+            self.ride_hail_distance
+                .push((time, car, dist, passenger.is_some()));
+        }
 
         // Started trips
         if let Event::TripPhaseStarting(id, _, _, _) = ev {
@@ -231,6 +308,13 @@ impl Analytics {
                 .push((id.idx, time, delay, agent.to_type()));
         }
 
+        if let Event::TransitSignalPriority(i, car) = ev {
+            self.transit_signal_priority
+                .entry(i)
+                .or_insert_with(Vec::new)
+                .push((time, car));
+        }
+
         // Parking spot changes
         if let Event::CarReachedParkingSpot(_, spot) = ev {
             if let ParkingSpot::Onstreet(l, _) = spot {
@@ -275,6 +359,9 @@ impl Analytics {
             Event::PathAmended(path) => {
                 self.record_demand(&path, map);
             }
+            Event::VehicleRerouted(car, _, cause) => {
+                self.reroutes.push((time, car, cause));
+            }
             Event::Alert(loc, msg) => {
                 self.alerts.push((time, loc, msg));
             }
@@ -306,6 +393,80 @@ impl Analytics {
         None
     }
 
+    /// Up to some time, the total delay of buses and trains through an intersection, and how many
+    /// of them passed through.
+    pub fn transit_delay(&self, i: IntersectionID, now: Time) -> (Duration, usize) {
+        let mut total = Duration::ZERO;
+        let mut count = 0;
+        if let Some(list) = self.intersection_delays.get(&i) {
+            for (_, t, dt, agent_type) in list {
+                if *t > now {
+                    break;
+                }
+                if *agent_type == AgentType::Bus || *agent_type == AgentType::Train {
+                    total += *dt;
+                    count += 1;
+                }
+            }
+        }
+        (total, count)
+    }
+
+    /// The average and maximum time vehicles of one route have dwelled at a stop. None if no
+    /// vehicles have stopped there yet.
+    pub fn dwell_time_stats(
+        &self,
+        stop: BusStopID,
+        route: BusRouteID,
+    ) -> Option<(Duration, Duration)> {
+        let dwells: Vec<Duration> = self
+            .bus_dwell_times
+            .get(&stop)?
+            .iter()
+            .filter(|(_, r, _)| *r == route)
+            .map(|(_, _, dt)| *dt)
+            .collect();
+        let max = dwells.iter().max().cloned()?;
+        let avg = dwells.iter().cloned().sum::<Duration>() / (dwells.len() as f64);
+        Some((avg, max))
+    }
+
+    /// For each stop along a route, the average and maximum number of passengers on board vehicles
+    /// departing it. Stops without any departures yet are omitted.
+    pub fn transit_load_profile(&self, route: BusRouteID) -> BTreeMap<BusStopID, (f64, usize)> {
+        let mut per_stop: BTreeMap<BusStopID, Vec<usize>> = BTreeMap::new();
+        if let Some(list) = self.transit_loads.get(&route) {
+            for (_, _, stop, load) in list {
+                per_stop.entry(*stop).or_insert_with(Vec::new).push(*load);
+            }
+        }
+        per_stop
+            .into_iter()
+            .map(|(stop, loads)| {
+                let avg = (loads.iter().sum::<usize>() as f64) / (loads.len() as f64);
+                (stop, (avg, loads.into_iter().max().unwrap()))
+            })
+            .collect()
+    }
+
+    /// Up to some time, the distance driven by ride-hailing vehicles with a passenger, and the
+    /// distance driven empty (deadheading).
+    pub fn ride_hail_vmt(&self, now: Time) -> (Distance, Distance) {
+        let mut with_passenger = Distance::ZERO;
+        let mut deadheading = Distance::ZERO;
+        for (t, _, dist, passenger) in &self.ride_hail_distance {
+            if *t > now {
+                break;
+            }
+            if *passenger {
+                with_passenger += *dist;
+            } else {
+                deadheading += *dist;
+            }
+        }
+        (with_passenger, deadheading)
+    }
+
     /// Returns pairs of trip times for finished trips in both worlds. (ID, before, after, mode)
     pub fn both_finished_trips(
         &self,
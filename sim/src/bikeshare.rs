@@ -0,0 +1,301 @@
+//! Bike share: bikes that anybody can pick up at one station and return to another. Unlike bikes
+//! owned by a Person, somebody can only start riding if there's a bike docked at the station they
+//! walk to, and they can only finish riding at a station with a free dock. Optionally, there can
+//! also be dockless vehicles, like shared scooters, that're picked up and left in front of any
+//! building.
+//!
+//! Docked bikes aren't physically on the map.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::{Distance, Duration, Pt2D};
+use map_model::{BikeShareStationID, BuildingID, Map};
+
+use crate::{CarID, SidewalkSpot, Vehicle, VehicleType};
+
+/// People won't walk further than this, as the crow flies, to pick up a shared vehicle. If nothing
+/// is available that close, they'll walk the whole way.
+const MAX_WALK_TO_PICKUP: Distance = Distance::const_meters(800.0);
+
+/// Shared bikes and scooters, used by everybody taking a `TripMode::BikeShare` trip. The stations
+/// come from the map.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct BikeShareSystem {
+    /// What fraction of each station's docks have a bike at the start of the day? 0 means every
+    /// station starts empty, 1 means full.
+    pub initial_fill: f64,
+    pub dockless: Option<DocklessFleet>,
+    pub rebalancing: Option<Rebalancing>,
+}
+
+/// Vehicles that can be picked up in front of any building and left in front of the destination.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct DocklessFleet {
+    pub num_vehicles: usize,
+    /// Bike or Scooter
+    pub vehicle_type: VehicleType,
+}
+
+/// Periodically move bikes from the fullest stations to the emptiest. The trucks that do this
+/// aren't simulated; the bikes just instantly move.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Rebalancing {
+    pub every: Duration,
+    /// Move bikes until each station is as close as possible to this fraction of its capacity.
+    pub target_fill: f64,
+}
+
+/// Where a shared vehicle is picked up or returned.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BikeShareDock {
+    Station(BikeShareStationID),
+    /// In front of a building, for dockless vehicles
+    Dockless(BuildingID),
+}
+
+impl BikeShareDock {
+    /// Where a pedestrian picks up or leaves the vehicle. The connection is always a BikeRack. None
+    /// if a dockless vehicle is left somewhere bikes can't reach.
+    pub fn sidewalk_spot(self, map: &Map) -> Option<SidewalkSpot> {
+        match self {
+            BikeShareDock::Station(s) => Some(SidewalkSpot::bike_share_station(s, map)),
+            BikeShareDock::Dockless(b) => SidewalkSpot::bike_rack(b, map),
+        }
+    }
+
+    fn pt(self, map: &Map) -> Pt2D {
+        match self {
+            BikeShareDock::Station(s) => map.get_bss(s).point,
+            BikeShareDock::Dockless(b) => map.get_b(b).label_center,
+        }
+    }
+}
+
+/// Tracks where every shared vehicle is.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct BikeShareSimState {
+    system: Option<BikeShareSystem>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    docked: BTreeMap<BikeShareStationID, Vec<Vehicle>>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    dockless: BTreeMap<BuildingID, Vec<Vehicle>>,
+    /// Vehicles being ridden right now, and where they were picked up
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    in_use: BTreeMap<CarID, (Vehicle, BikeShareDock)>,
+}
+
+impl BikeShareSimState {
+    pub fn new() -> BikeShareSimState {
+        BikeShareSimState {
+            system: None,
+            docked: BTreeMap::new(),
+            dockless: BTreeMap::new(),
+            in_use: BTreeMap::new(),
+        }
+    }
+
+    pub fn set_system(&mut self, system: BikeShareSystem) {
+        self.system = Some(system);
+    }
+
+    pub fn get_system(&self) -> Option<&BikeShareSystem> {
+        self.system.as_ref()
+    }
+
+    /// Place a vehicle at the start of the day. Stations may be overfilled.
+    pub fn add_vehicle(&mut self, vehicle: Vehicle, dock: BikeShareDock) {
+        match dock {
+            BikeShareDock::Station(s) => self.docked.entry(s).or_insert_with(Vec::new),
+            BikeShareDock::Dockless(b) => self.dockless.entry(b).or_insert_with(Vec::new),
+        }
+        .push(vehicle);
+    }
+
+    /// Find the closest place to pick up a vehicle, ignoring one dock that's already been tried.
+    pub fn find_vehicle(
+        &self,
+        near: Pt2D,
+        except: Option<BikeShareDock>,
+        map: &Map,
+    ) -> Option<BikeShareDock> {
+        self.docked
+            .iter()
+            .filter(|(_, bikes)| !bikes.is_empty())
+            .map(|(s, _)| BikeShareDock::Station(*s))
+            .chain(
+                self.dockless
+                    .iter()
+                    .filter(|(_, vehicles)| !vehicles.is_empty())
+                    .map(|(b, _)| BikeShareDock::Dockless(*b)),
+            )
+            .filter(|dock| Some(*dock) != except)
+            .map(|dock| (dock, dock.pt(map).dist_to(near)))
+            .filter(|(_, dist)| *dist <= MAX_WALK_TO_PICKUP)
+            .min_by_key(|(_, dist)| *dist)
+            .map(|(dock, _)| dock)
+    }
+
+    /// Somebody takes a vehicle. None if there's nothing left there.
+    pub fn take_vehicle(&mut self, dock: BikeShareDock) -> Option<Vehicle> {
+        let vehicle = match dock {
+            BikeShareDock::Station(s) => self.docked.get_mut(&s)?.pop()?,
+            BikeShareDock::Dockless(b) => self.dockless.get_mut(&b)?.pop()?,
+        };
+        self.in_use.insert(vehicle.id, (vehicle.clone(), dock));
+        Some(vehicle)
+    }
+
+    /// Where should somebody riding this vehicle return it? Dockless vehicles are left right at
+    /// the destination. Otherwise, the station with a free dock closest to the destination. If
+    /// every station is full, just the closest one.
+    pub fn pick_dropoff(&self, id: CarID, goal: BuildingID, map: &Map) -> Option<BikeShareDock> {
+        if let BikeShareDock::Dockless(_) = self.in_use[&id].1 {
+            return Some(BikeShareDock::Dockless(goal));
+        }
+        let pt = map.get_b(goal).label_center;
+        self.free_dock_near(pt, None, map)
+            .or_else(|| {
+                map.all_bike_share_stations()
+                    .iter()
+                    .min_by_key(|s| s.point.dist_to(pt))
+                    .map(|s| s.id)
+            })
+            .map(BikeShareDock::Station)
+    }
+
+    /// The closest station with a free dock, ignoring one that's already been tried
+    pub fn free_dock_near(
+        &self,
+        pt: Pt2D,
+        except: Option<BikeShareStationID>,
+        map: &Map,
+    ) -> Option<BikeShareStationID> {
+        map.all_bike_share_stations()
+            .iter()
+            .filter(|s| Some(s.id) != except && self.has_free_dock(s.id, map))
+            .min_by_key(|s| s.point.dist_to(pt))
+            .map(|s| s.id)
+    }
+
+    /// Returns false if the station is full, in which case the rider still has the vehicle.
+    pub fn return_vehicle(&mut self, id: CarID, dock: BikeShareDock, map: &Map) -> bool {
+        if let BikeShareDock::Station(s) = dock {
+            if !self.has_free_dock(s, map) {
+                return false;
+            }
+        }
+        self.force_return_vehicle(id, dock);
+        true
+    }
+
+    /// Return a vehicle somewhere, even if the station is full. The vehicle is left beside it.
+    pub fn force_return_vehicle(&mut self, id: CarID, dock: BikeShareDock) {
+        let (vehicle, _) = self.in_use.remove(&id).unwrap();
+        self.add_vehicle(vehicle, dock);
+    }
+
+    /// If a trip using a shared vehicle is cancelled, put the vehicle back where it came from.
+    /// Returns false if this isn't a shared vehicle.
+    pub fn vehicle_abandoned(&mut self, id: CarID) -> bool {
+        if let Some((_, dock)) = self.in_use.get(&id) {
+            let dock = *dock;
+            self.force_return_vehicle(id, dock);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn get_vehicle(&self, id: CarID) -> Vehicle {
+        self.in_use[&id].0.clone()
+    }
+
+    pub fn is_shared_vehicle(&self, id: CarID) -> bool {
+        self.in_use.contains_key(&id)
+    }
+
+    /// Move docked bikes between stations, towards the target fill.
+    pub fn rebalance(&mut self, map: &Map) {
+        let target_fill = match self.system {
+            Some(BikeShareSystem {
+                rebalancing: Some(ref r),
+                ..
+            }) => r.target_fill,
+            _ => {
+                return;
+            }
+        };
+
+        // Positive means the station has too many bikes
+        let mut surplus: Vec<(BikeShareStationID, isize)> = Vec::new();
+        for s in map.all_bike_share_stations() {
+            let target = (target_fill * s.capacity as f64).round() as isize;
+            let have = self.docked.get(&s.id).map(|bikes| bikes.len()).unwrap_or(0) as isize;
+            surplus.push((s.id, have - target));
+        }
+
+        let mut spare: Vec<Vehicle> = Vec::new();
+        for (s, extra) in &surplus {
+            if *extra > 0 {
+                let bikes = self.docked.get_mut(s).unwrap();
+                let keep = bikes.len() - *extra as usize;
+                spare.extend(bikes.drain(keep..));
+            }
+        }
+        // Fill the emptiest stations first
+        surplus.sort_by_key(|(_, extra)| *extra);
+        for (s, extra) in surplus {
+            if extra >= 0 {
+                break;
+            }
+            for _ in 0..-extra {
+                if let Some(bike) = spare.pop() {
+                    self.add_vehicle(bike, BikeShareDock::Station(s));
+                }
+            }
+        }
+        // Every station is at the target, but there are bikes left over. Spread them out.
+        for bike in spare {
+            let s = self
+                .docked
+                .iter()
+                .min_by_key(|(_, bikes)| bikes.len())
+                .map(|(s, _)| *s)
+                .unwrap();
+            self.add_vehicle(bike, BikeShareDock::Station(s));
+        }
+    }
+
+    fn has_free_dock(&self, s: BikeShareStationID, map: &Map) -> bool {
+        self.docked.get(&s).map(|bikes| bikes.len()).unwrap_or(0) < map.get_bss(s).capacity
+    }
+
+    /// For each station, the number of docked bikes
+    pub fn docked_bikes(&self) -> BTreeMap<BikeShareStationID, usize> {
+        self.docked
+            .iter()
+            .map(|(s, bikes)| (*s, bikes.len()))
+            .collect()
+    }
+
+    /// For each building with dockless vehicles in front of it, how many
+    pub fn dockless_vehicles(&self) -> BTreeMap<BuildingID, usize> {
+        self.dockless
+            .iter()
+            .filter(|(_, vehicles)| !vehicles.is_empty())
+            .map(|(b, vehicles)| (*b, vehicles.len()))
+            .collect()
+    }
+}
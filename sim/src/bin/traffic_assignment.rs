@@ -0,0 +1,112 @@
+//! Iterative dynamic traffic assignment. Runs a scenario repeatedly. Between runs, some fraction of
+//! driving trips switch to the best path, given the lane travel times experienced in the previous
+//! run. This stops when the relative gap -- how much time drivers could save in total by switching
+//! paths -- is small enough. The final path for every trip is saved, and the game can replay it
+//! with `--trip_paths`.
+
+use rand::Rng;
+
+use abstutil::Timer;
+use geom::Duration;
+use map_model::Map;
+use sim::{Scenario, Sim, SimFlags, TripPaths};
+
+fn main() {
+    let mut args = abstutil::CmdArgs::new();
+    let hours = Duration::hours(
+        args.optional_parse("--hours", |s| s.parse::<usize>())
+            .unwrap_or(24),
+    );
+    let max_iterations = args
+        .optional_parse("--iterations", |s| s.parse::<usize>())
+        .unwrap_or(10);
+    let reroute_fraction = args
+        .optional_parse("--reroute_fraction", |s| s.parse::<f64>())
+        .unwrap_or(0.2);
+    let max_gap = args
+        .optional_parse("--max_gap", |s| s.parse::<f64>())
+        .unwrap_or(0.01);
+    let output = args.required("--output");
+    let flags = SimFlags::from_args(&mut args);
+    args.done();
+
+    let mut timer = Timer::new("traffic assignment");
+    if !flags.load.contains("/scenarios/") {
+        panic!("{} isn't a scenario", flags.load);
+    }
+    let mut scenario: Scenario = abstutil::read_binary(flags.load.clone(), &mut timer);
+    let mut map = Map::new(scenario.map_name.path(), &mut timer);
+    // The first run can start from travel times observed somewhere else
+    flags.opts.apply_travel_times(&mut map, &mut timer);
+    for m in &flags.modifiers {
+        scenario = m.apply(&map, scenario);
+    }
+    let mut rng = flags.make_rng();
+
+    let mut trip_paths = TripPaths::new();
+    for iteration in 1..=max_iterations {
+        // Every run has to start from the same initial state, so the trips are the same
+        let mut sim = Sim::new(&map, flags.opts.clone(), &mut timer);
+        scenario.instantiate(&mut sim, &map, &mut flags.make_rng(), &mut timer);
+        sim.set_trip_paths(trip_paths);
+        sim.timed_step(&map, hours, &mut None, &mut timer);
+
+        trip_paths = sim.get_trip_paths().unwrap().clone();
+        let travel_times = sim.get_analytics().lane_travel_times.clone();
+        // Paths for trips that didn't match anything in trip_paths will use these
+        map.set_travel_times(Some(travel_times.clone()), &mut timer);
+
+        // How much time could each driver save by switching to the best path right now?
+        let mut total_experienced = Duration::ZERO;
+        let mut total_savings = Duration::ZERO;
+        let mut better_paths = Vec::new();
+        timer.start_iter("find best paths", trip_paths.paths.len());
+        for (trip, (depart, req, path)) in &trip_paths.paths {
+            timer.next();
+            let experienced = travel_times.estimate(path, *depart, &map);
+            total_experienced += experienced;
+            if let Some(best) = map.pathfind_at(req.clone(), *depart) {
+                let best_time = travel_times.estimate(&best, *depart, &map);
+                if best_time < experienced {
+                    total_savings += experienced - best_time;
+                    better_paths.push((*trip, best));
+                }
+            }
+        }
+        let gap = if total_experienced == Duration::ZERO {
+            0.0
+        } else {
+            total_savings / total_experienced
+        };
+        println!(
+            "After iteration {}, the relative gap is {:.4}. {} of {} trips could switch to a \
+             better path",
+            iteration,
+            gap,
+            abstutil::prettyprint_usize(better_paths.len()),
+            abstutil::prettyprint_usize(trip_paths.paths.len())
+        );
+        if gap <= max_gap {
+            break;
+        }
+        if iteration == max_iterations {
+            println!("Giving up without converging");
+            break;
+        }
+
+        let mut rerouted = 0;
+        for (trip, best) in better_paths {
+            if rng.gen_bool(reroute_fraction) {
+                trip_paths.paths.get_mut(&trip).unwrap().2 = best;
+                rerouted += 1;
+            }
+        }
+        println!(
+            "Rerouting {} trips for the next run",
+            abstutil::prettyprint_usize(rerouted)
+        );
+    }
+
+    abstutil::write_binary(output.clone(), &trip_paths);
+    println!("Saved final paths to {}", output);
+}
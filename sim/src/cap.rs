@@ -3,7 +3,9 @@ use std::collections::{BTreeMap, BTreeSet};
 use serde::{Deserialize, Serialize};
 
 use geom::{Duration, Time};
-use map_model::{LaneID, Map, Path, PathConstraints, PathRequest, PathStep, TurnID};
+use map_model::{
+    AccessRestrictions, LaneID, Map, Path, PathConstraints, PathRequest, PathStep, TurnID,
+};
 
 use crate::mechanics::IntersectionSimState;
 use crate::{CarID, SimOptions, VehicleType};
@@ -12,7 +14,7 @@ use crate::{CarID, SimOptions, VehicleType};
 type ZoneIdx = usize;
 
 /// Some roads (grouped into zones) may have a cap on the number of vehicles that can enter per
-/// hour. CapSimState enforces this, just for driving trips.
+/// hour, possibly only at some times of day. CapSimState enforces this, just for driving trips.
 #[derive(Serialize, Deserialize, Clone)]
 pub(crate) struct CapSimState {
     lane_to_zone: BTreeMap<LaneID, ZoneIdx>,
@@ -22,7 +24,7 @@ pub(crate) struct CapSimState {
 
 #[derive(Serialize, Deserialize, Clone)]
 struct Zone {
-    cap: usize,
+    restrictions: AccessRestrictions,
     entered_in_last_hour: BTreeSet<CarID>,
     // TODO Maybe want sliding windows or something else
     hour_started: Time,
@@ -38,17 +40,17 @@ impl CapSimState {
                 .map(|delay_threshold| AvoidCongestion { delay_threshold }),
         };
         for z in map.all_zones() {
-            if let Some(cap) = z.restrictions.cap_vehicles_per_hour {
+            if z.restrictions.ever_capped() {
                 let idx = sim.zones.len();
                 for r in &z.members {
                     for l in map.get_r(*r).all_lanes() {
-                        if PathConstraints::Car.can_use(map.get_l(l), map) {
+                        if PathConstraints::Car.can_ever_use(map.get_l(l), map) {
                             sim.lane_to_zone.insert(l, idx);
                         }
                     }
                 }
                 sim.zones.push(Zone {
-                    cap,
+                    restrictions: z.restrictions.clone(),
                     entered_in_last_hour: BTreeSet::new(),
                     hour_started: Time::START_OF_DAY,
                 });
@@ -71,9 +73,7 @@ impl CapSimState {
                         zone.entered_in_last_hour.clear();
                     }
 
-                    if zone.entered_in_last_hour.len() >= zone.cap
-                        && !zone.entered_in_last_hour.contains(&car)
-                    {
+                    if zone.is_full(now, car) {
                         return false;
                     }
                     zone.entered_in_last_hour.insert(car);
@@ -113,10 +113,7 @@ impl CapSimState {
 
         let mut avoid_lanes: BTreeSet<LaneID> = BTreeSet::new();
         for (l, idx) in &self.lane_to_zone {
-            let zone = &self.zones[*idx];
-            if zone.entered_in_last_hour.len() >= zone.cap
-                && !zone.entered_in_last_hour.contains(&car)
-            {
+            if self.zones[*idx].is_full(now, car) {
                 avoid_lanes.insert(*l);
             }
         }
@@ -133,6 +130,18 @@ impl CapSimState {
     }
 }
 
+impl Zone {
+    /// Would this car entering exceed the cap in effect right now?
+    fn is_full(&self, now: Time, car: CarID) -> bool {
+        match self.restrictions.cap_vehicles_per_hour_at(now) {
+            Some(cap) => {
+                self.entered_in_last_hour.len() >= cap && !self.entered_in_last_hour.contains(&car)
+            }
+            None => false,
+        }
+    }
+}
+
 /// Before the driving portion of a trip begins, check that the desired path doesn't pass through
 /// any road with agents currently experiencing some delay.
 #[derive(Serialize, Deserialize, Clone)]
@@ -148,26 +157,44 @@ impl AvoidCongestion {
         intersections: &IntersectionSimState,
         map: &Map,
     ) -> Option<(TurnID, Duration)> {
-        for step in path.get_steps() {
-            if let PathStep::Lane(l) = step {
-                let lane = map.get_l(*l);
-                for (agent, turn, start) in intersections.get_waiting_agents(lane.dst_i) {
-                    if now - start < self.delay_threshold {
-                        continue;
-                    }
-                    if agent.to_vehicle_type() != Some(VehicleType::Car) {
-                        continue;
-                    }
-                    if map.get_l(turn.src).parent != lane.parent {
-                        continue;
-                    }
-                    // TODO Should we make sure the delayed agent is also trying to go the same
-                    // direction? For example, people turning left somewhere might be delayed, while
-                    // people going straight are fine. But then the presence of a turn lane matters.
-                    return Some((turn, now - start));
+        find_delay(
+            path.get_steps().iter(),
+            now,
+            self.delay_threshold,
+            intersections,
+            map,
+        )
+    }
+}
+
+/// Do any of these steps cross a road where cars have been waiting to turn for at least some
+/// threshold? If so, returns the turn they're waiting for and how long.
+pub(crate) fn find_delay<'a, I: Iterator<Item = &'a PathStep>>(
+    steps: I,
+    now: Time,
+    delay_threshold: Duration,
+    intersections: &IntersectionSimState,
+    map: &Map,
+) -> Option<(TurnID, Duration)> {
+    for step in steps {
+        if let PathStep::Lane(l) = step {
+            let lane = map.get_l(*l);
+            for (agent, turn, start) in intersections.get_waiting_agents(lane.dst_i) {
+                if now - start < delay_threshold {
+                    continue;
+                }
+                if agent.to_vehicle_type() != Some(VehicleType::Car) {
+                    continue;
+                }
+                if map.get_l(turn.src).parent != lane.parent {
+                    continue;
                 }
+                // TODO Should we make sure the delayed agent is also trying to go the same
+                // direction? For example, people turning left somewhere might be delayed, while
+                // people going straight are fine. But then the presence of a turn lane matters.
+                return Some((turn, now - start));
             }
         }
-        None
     }
+    None
 }
@@ -1,9 +1,9 @@
 use serde::{Deserialize, Serialize};
 
-use geom::{Duration, Speed};
+use geom::{Distance, Duration, Speed};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, CompressedMovementID, IntersectionID, LaneID, Map, Path,
-    PathRequest, Traversable, TurnID,
+    BikeShareStationID, BuildingID, BusRouteID, BusStopID, CompressedMovementID, IntersectionID,
+    LaneID, Map, Path, PathRequest, RoadID, Traversable, TurnID,
 };
 
 use crate::{AgentID, CarID, ParkingSpot, PedestrianID, PersonID, TripID, TripMode};
@@ -21,11 +21,21 @@ pub enum Event {
     CarReachedParkingSpot(CarID, ParkingSpot),
     CarLeftParkingSpot(CarID, ParkingSpot),
 
-    BusArrivedAtStop(CarID, BusRouteID, BusStopID),
-    BusDepartedFromStop(CarID, BusRouteID, BusStopID),
+    /// Includes how long the vehicle will dwell at the stop, based on who's boarding and
+    /// alighting.
+    BusArrivedAtStop(CarID, BusRouteID, BusStopID, Duration),
+    /// Includes the number of passengers on board, leaving the stop.
+    BusDepartedFromStop(CarID, BusRouteID, BusStopID, usize),
     /// How long waiting at the stop?
     PassengerBoardsTransit(PersonID, CarID, BusRouteID, BusStopID, Duration),
     PassengerAlightsTransit(PersonID, CarID, BusRouteID, BusStopID),
+    /// The vehicle was full, so the passenger has to keep waiting.
+    PassengerDeniedBoarding(PersonID, CarID, BusRouteID, BusStopID),
+    /// How long did the passenger wait for the ride-hailing vehicle to arrive?
+    RideHailPickup(PersonID, CarID, Duration),
+    /// A ride-hailing vehicle finished driving somewhere. If nobody was aboard, the vehicle was
+    /// deadheading.
+    RideHailLegFinished(CarID, Distance, Option<PersonID>),
 
     PersonEntersBuilding(PersonID, BuildingID),
     PersonLeavesBuilding(PersonID, BuildingID),
@@ -36,11 +46,17 @@ pub enum Event {
     PedReachedParkingSpot(PedestrianID, ParkingSpot),
 
     BikeStoppedAtSidewalk(CarID, LaneID),
+    /// Somebody reached a bike share station to pick up a bike, but there weren't any left.
+    BikeShareStationEmpty(PersonID, BikeShareStationID),
+    /// Somebody reached a bike share station to return a bike, but every dock was in use.
+    BikeShareStationFull(PersonID, BikeShareStationID),
 
     /// If the agent is a transit vehicle, then include a count of how many passengers are on
     /// board.
     AgentEntersTraversable(AgentID, Traversable, Option<usize>),
     IntersectionDelayMeasured(CompressedMovementID, Duration, AgentID),
+    /// A traffic signal held a stage green longer or ended a stage early for this bus or train.
+    TransitSignalPriority(IntersectionID, CarID),
 
     TripFinished {
         trip: TripID,
@@ -58,10 +74,21 @@ pub enum Event {
     /// Just use for parking replanning. Not happy about copying the full path in here, but the way
     /// to plumb info into Analytics is Event.
     PathAmended(Path),
+    /// A driver changed their path en route, like they were following a navigation app.
+    VehicleRerouted(CarID, Option<TripID>, RerouteCause),
 
     Alert(AlertLocation, String),
 }
 
+/// Why did a driver change their path?
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum RerouteCause {
+    /// Cars have been waiting this long to turn off of some road ahead.
+    Delay(RoadID, Duration),
+    /// A live map edit changed part of the path.
+    LiveEdit,
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum AlertLocation {
     Nil,
@@ -79,6 +106,8 @@ pub enum TripPhaseType {
     WaitingForBus(BusRouteID, BusStopID),
     /// What stop did they board at?
     RidingBus(BusRouteID, BusStopID, CarID),
+    WaitingForRideHail,
+    RidingRideHail(CarID),
     Cancelled,
     Finished,
     DelayedStart,
@@ -95,6 +124,8 @@ impl TripPhaseType {
                 format!("Waiting for bus {}", map.get_br(r).full_name)
             }
             TripPhaseType::RidingBus(r, _, _) => format!("Riding bus {}", map.get_br(r).full_name),
+            TripPhaseType::WaitingForRideHail => "Waiting for a ride-hail".to_string(),
+            TripPhaseType::RidingRideHail(_) => "Riding in a ride-hail".to_string(),
             TripPhaseType::Cancelled => "Trip was cancelled due to some bug".to_string(),
             TripPhaseType::Finished => "Trip finished".to_string(),
             TripPhaseType::DelayedStart => "Delayed by a previous trip taking too long".to_string(),
@@ -17,10 +17,10 @@ use std::fmt;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_usize, serialize_usize};
-use geom::{Distance, Speed, Time};
+use geom::{Acceleration, Distance, Speed, Time};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, DirectedRoadID, IntersectionID, LaneID, Map, ParkingLotID,
-    Path, PathConstraints, PathRequest, Position,
+    BikeShareStationID, BuildingID, BusRouteID, BusStopID, DirectedRoadID, IntersectionID, LaneID,
+    Map, ParkingLotID, Path, PathConstraints, PathRequest, Position,
 };
 
 pub use crate::render::{
@@ -29,9 +29,10 @@ pub use crate::render::{
 };
 
 pub use self::analytics::{Analytics, TripPhase};
+pub(crate) use self::bikeshare::{BikeShareDock, BikeShareSimState};
+pub use self::bikeshare::{BikeShareSystem, DocklessFleet, Rebalancing};
 pub(crate) use self::cap::CapSimState;
-pub(crate) use self::events::Event;
-pub use self::events::{AlertLocation, TripPhaseType};
+pub use self::events::{AlertLocation, Event, RerouteCause, TripPhaseType};
 pub(crate) use self::make::TripSpec;
 pub use self::make::{
     BorderSpawnOverTime, ExternalPerson, ExternalTrip, ExternalTripEndpoint, IndividTrip,
@@ -43,15 +44,18 @@ pub(crate) use self::mechanics::{
 };
 pub(crate) use self::pandemic::PandemicModel;
 pub(crate) use self::recorder::TrafficRecorder;
+pub use self::ridehail::{AfterDropoff, DispatchStrategy, RideHailFleet};
+pub(crate) use self::ridehail::{RideHailSimState, RideRequest};
 pub(crate) use self::router::{ActionAtEnd, Router};
 pub(crate) use self::scheduler::{Command, Scheduler};
 pub use self::sim::{AgentProperties, AlertHandler, DelayCause, Sim, SimCallback, SimOptions};
 pub(crate) use self::transit::TransitSimState;
 pub use self::trips::{CommutersVehiclesCounts, Person, PersonState, TripInfo, TripResult};
-pub use self::trips::{TripEndpoint, TripMode};
+pub use self::trips::{TripEndpoint, TripMode, TripPaths};
 pub(crate) use self::trips::{TripLeg, TripManager};
 
 mod analytics;
+mod bikeshare;
 mod cap;
 mod events;
 mod make;
@@ -59,6 +63,7 @@ mod mechanics;
 mod pandemic;
 mod recorder;
 mod render;
+mod ridehail;
 mod router;
 mod scheduler;
 mod sim;
@@ -73,6 +78,11 @@ pub(crate) const MAX_CAR_LENGTH: Distance = Distance::const_meters(6.5);
 // Note this is more than MAX_CAR_LENGTH
 pub(crate) const BUS_LENGTH: Distance = Distance::const_meters(12.5);
 pub(crate) const LIGHT_RAIL_LENGTH: Distance = Distance::const_meters(60.0);
+// Box trucks making deliveries. These must also be < PARKING_SPOT_LENGTH
+pub(crate) const MIN_TRUCK_LENGTH: Distance = Distance::const_meters(7.0);
+pub(crate) const MAX_TRUCK_LENGTH: Distance = Distance::const_meters(7.9);
+pub(crate) const SCOOTER_LENGTH: Distance = Distance::const_meters(1.2);
+pub(crate) const MOTORCYCLE_LENGTH: Distance = Distance::const_meters(2.2);
 
 /// At all speeds (including at rest), cars must be at least this far apart, measured from front of
 /// one car to the back of the other.
@@ -102,6 +112,9 @@ impl fmt::Display for CarID {
             VehicleType::Bus => write!(f, "Bus #{}", self.0),
             VehicleType::Train => write!(f, "Train #{}", self.0),
             VehicleType::Bike => write!(f, "Bike #{}", self.0),
+            VehicleType::Truck => write!(f, "Truck #{}", self.0),
+            VehicleType::Scooter => write!(f, "Scooter #{}", self.0),
+            VehicleType::Motorcycle => write!(f, "Motorcycle #{}", self.0),
         }
     }
 }
@@ -144,6 +157,9 @@ impl AgentID {
                 VehicleType::Bike => AgentType::Bike,
                 VehicleType::Bus => AgentType::Bus,
                 VehicleType::Train => AgentType::Train,
+                VehicleType::Truck => AgentType::Truck,
+                VehicleType::Scooter => AgentType::Scooter,
+                VehicleType::Motorcycle => AgentType::Motorcycle,
             },
             AgentID::Pedestrian(_) => AgentType::Pedestrian,
             AgentID::BusPassenger(_, _) => AgentType::TransitRider,
@@ -177,6 +193,9 @@ pub enum AgentType {
     Train,
     Pedestrian,
     TransitRider,
+    Truck,
+    Scooter,
+    Motorcycle,
 }
 
 impl AgentType {
@@ -188,6 +207,9 @@ impl AgentType {
             AgentType::Train,
             AgentType::Pedestrian,
             AgentType::TransitRider,
+            AgentType::Truck,
+            AgentType::Scooter,
+            AgentType::Motorcycle,
         ]
     }
 
@@ -199,6 +221,9 @@ impl AgentType {
             AgentType::Train => "Train",
             AgentType::Pedestrian => "Pedestrian",
             AgentType::TransitRider => "Transit rider",
+            AgentType::Truck => "Truck",
+            AgentType::Scooter => "Scooter",
+            AgentType::Motorcycle => "Motorcycle",
         }
     }
 
@@ -210,6 +235,9 @@ impl AgentType {
             AgentType::Train => "trains",
             AgentType::Pedestrian => "pedestrians",
             AgentType::TransitRider => "transit riders",
+            AgentType::Truck => "trucks",
+            AgentType::Scooter => "scooters",
+            AgentType::Motorcycle => "motorcycles",
         }
     }
 
@@ -220,6 +248,9 @@ impl AgentType {
             AgentType::Bus | AgentType::Train => unreachable!(),
             AgentType::Pedestrian => "walking",
             AgentType::TransitRider => "riding transit",
+            AgentType::Truck => "trucking",
+            AgentType::Scooter => "scooting",
+            AgentType::Motorcycle => "riding a motorcycle",
         }
     }
 }
@@ -274,6 +305,11 @@ pub enum VehicleType {
     Bus,
     Train,
     Bike,
+    /// A delivery truck, restricted from some roads
+    Truck,
+    /// An e-scooter
+    Scooter,
+    Motorcycle,
 }
 
 impl fmt::Display for VehicleType {
@@ -283,6 +319,9 @@ impl fmt::Display for VehicleType {
             VehicleType::Bus => write!(f, "bus"),
             VehicleType::Train => write!(f, "train"),
             VehicleType::Bike => write!(f, "bike"),
+            VehicleType::Truck => write!(f, "truck"),
+            VehicleType::Scooter => write!(f, "scooter"),
+            VehicleType::Motorcycle => write!(f, "motorcycle"),
         }
     }
 }
@@ -294,6 +333,9 @@ impl VehicleType {
             VehicleType::Bus => PathConstraints::Bus,
             VehicleType::Train => PathConstraints::Train,
             VehicleType::Bike => PathConstraints::Bike,
+            VehicleType::Truck => PathConstraints::Truck,
+            VehicleType::Scooter => PathConstraints::Scooter,
+            VehicleType::Motorcycle => PathConstraints::Motorcycle,
         }
     }
 
@@ -303,6 +345,69 @@ impl VehicleType {
             VehicleType::Bus => true,
             VehicleType::Train => true,
             VehicleType::Bike => false,
+            VehicleType::Truck => false,
+            VehicleType::Scooter => false,
+            VehicleType::Motorcycle => false,
+        }
+    }
+
+    /// Bikes and scooters prefer bike lanes, and they're locked up at a rack near the
+    /// destination, instead of using a parking spot.
+    pub fn is_bike_like(self) -> bool {
+        self.to_constraints().is_bike_like()
+    }
+
+    /// Can this vehicle park in this spot? Delivery trucks are too tall for garages and parking
+    /// lots, and nobody parks along a road they're not allowed to drive on.
+    pub(crate) fn can_park_at(self, spot: ParkingSpot, map: &Map) -> bool {
+        match spot {
+            ParkingSpot::Onstreet(l, _) => {
+                let constraints = self.to_constraints();
+                map.get_parent(l)
+                    .all_lanes()
+                    .into_iter()
+                    .any(|l| constraints.can_ever_use(map.get_l(l), map))
+            }
+            ParkingSpot::Offstreet(_, _) | ParkingSpot::Lot(_, _) => self != VehicleType::Truck,
+        }
+    }
+
+    /// The typical (maximum acceleration, maximum comfortable deceleration) for this type of
+    /// vehicle. Both are positive.
+    pub fn default_acceleration(self) -> (Acceleration, Acceleration) {
+        let (accel, decel) = match self {
+            VehicleType::Car => (2.5, 4.5),
+            VehicleType::Bus => (1.2, 3.0),
+            VehicleType::Train => (1.0, 1.3),
+            VehicleType::Bike => (1.0, 2.5),
+            VehicleType::Truck => (1.2, 3.5),
+            VehicleType::Scooter => (1.5, 3.0),
+            VehicleType::Motorcycle => (3.5, 5.0),
+        };
+        (
+            Acceleration::meters_per_second_squared(accel),
+            Acceleration::meters_per_second_squared(decel),
+        )
+    }
+
+    /// How many passengers, seated and standing, fit in a transit vehicle when no capacity is
+    /// specified for its route. None for everything else.
+    pub fn default_capacity(self) -> Option<usize> {
+        match self {
+            VehicleType::Bus => Some(70),
+            // Two light rail cars
+            VehicleType::Train => Some(400),
+            _ => None,
+        }
+    }
+
+    /// How many doors a transit vehicle has when no door count is specified for its route. None
+    /// for everything else.
+    pub fn default_doors(self) -> Option<usize> {
+        match self {
+            VehicleType::Bus => Some(2),
+            VehicleType::Train => Some(8),
+            _ => None,
         }
     }
 }
@@ -314,6 +419,8 @@ pub struct Vehicle {
     pub vehicle_type: VehicleType,
     pub length: Distance,
     pub max_speed: Option<Speed>,
+    pub max_accel: Acceleration,
+    pub max_decel: Acceleration,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -321,6 +428,10 @@ pub struct VehicleSpec {
     pub vehicle_type: VehicleType,
     pub length: Distance,
     pub max_speed: Option<Speed>,
+    /// How quickly the vehicle can speed up
+    pub max_accel: Acceleration,
+    /// How hard the vehicle can comfortably brake. Positive.
+    pub max_decel: Acceleration,
 }
 
 impl VehicleSpec {
@@ -332,6 +443,8 @@ impl VehicleSpec {
             vehicle_type: self.vehicle_type,
             length: self.length,
             max_speed: self.max_speed,
+            max_accel: self.max_accel,
+            max_decel: self.max_decel,
         }
     }
 }
@@ -357,6 +470,11 @@ pub struct ParkedCar {
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub(crate) enum DrivingGoal {
     ParkNear(BuildingID),
+    /// Park in this lot before switching to transit. If it's full, park as close as possible. The
+    /// building is where the trip is ultimately headed.
+    ParkInLot(ParkingLotID, BuildingID),
+    /// Lock up a bike or scooter at this stop before switching to transit
+    BikeRackAt(BusStopID),
     Border(IntersectionID, LaneID),
 }
 
@@ -378,14 +496,20 @@ impl DrivingGoal {
     pub fn goal_pos(&self, constraints: PathConstraints, map: &Map) -> Option<Position> {
         match self {
             DrivingGoal::ParkNear(b) => match constraints {
-                PathConstraints::Car => {
-                    Some(Position::start(map.find_driving_lane_near_building(*b)))
+                PathConstraints::Car | PathConstraints::Truck | PathConstraints::Motorcycle => {
+                    Some(Position::start(
+                        map.find_lane_near_building(*b, constraints),
+                    ))
+                }
+                PathConstraints::Bike | PathConstraints::Scooter => {
+                    Some(map.get_b(*b).biking_connection(map)?.0)
                 }
-                PathConstraints::Bike => Some(map.get_b(*b).biking_connection(map)?.0),
                 PathConstraints::Bus | PathConstraints::Train | PathConstraints::Pedestrian => {
                     unreachable!()
                 }
             },
+            DrivingGoal::ParkInLot(pl, _) => Some(map.get_pl(*pl).driving_pos),
+            DrivingGoal::BikeRackAt(stop) => Some(map.get_bs(*stop).biking_connection(map)?.0),
             DrivingGoal::Border(_, l) => Some(Position::end(*l, map)),
         }
     }
@@ -393,12 +517,18 @@ impl DrivingGoal {
     pub(crate) fn make_router(&self, owner: CarID, path: Path, map: &Map) -> Router {
         match self {
             DrivingGoal::ParkNear(b) => {
-                if owner.1 == VehicleType::Bike {
+                if owner.1.is_bike_like() {
                     Router::bike_then_stop(owner, path, SidewalkSpot::bike_rack(*b, map).unwrap())
                 } else {
                     Router::park_near(owner, path, *b)
                 }
             }
+            DrivingGoal::ParkInLot(pl, b) => Router::park_in_lot(owner, path, *pl, *b),
+            DrivingGoal::BikeRackAt(stop) => Router::bike_then_stop(
+                owner,
+                path,
+                SidewalkSpot::bike_rack_at_stop(*stop, map).unwrap(),
+            ),
             DrivingGoal::Border(i, last_lane) => {
                 Router::end_at_border(owner, path, map.get_l(*last_lane).length(), *i)
             }
@@ -465,6 +595,24 @@ impl SidewalkSpot {
         })
     }
 
+    /// Somebody leaves their bike here before catching transit at the stop.
+    pub fn bike_rack_at_stop(stop: BusStopID, map: &Map) -> Option<SidewalkSpot> {
+        let (bike_pos, sidewalk_pos) = map.get_bs(stop).biking_connection(map)?;
+        Some(SidewalkSpot {
+            connection: SidewalkPOI::BikeRack(bike_pos),
+            sidewalk_pos,
+        })
+    }
+
+    /// Shared bikes are picked up and returned here.
+    pub fn bike_share_station(id: BikeShareStationID, map: &Map) -> SidewalkSpot {
+        let station = map.get_bss(id);
+        SidewalkSpot {
+            connection: SidewalkPOI::BikeRack(station.biking_pos),
+            sidewalk_pos: station.sidewalk_pos,
+        }
+    }
+
     pub fn bus_stop(stop: BusStopID, map: &Map) -> SidewalkSpot {
         SidewalkSpot {
             sidewalk_pos: map.get_bs(stop).sidewalk_pos,
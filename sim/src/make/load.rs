@@ -79,6 +79,7 @@ impl SimFlags {
                     panic!("Couldn't load edits \"{}\": {}", sim.edits_name, err);
                 }
             }
+            opts.apply_travel_times(&mut map, timer);
 
             (map, sim, rng)
         } else if self.load.contains("/scenarios/") {
@@ -89,7 +90,8 @@ impl SimFlags {
 
             let mut scenario: Scenario = abstutil::read_binary(self.load.clone(), timer);
 
-            let map = Map::new(scenario.map_name.path(), timer);
+            let mut map = Map::new(scenario.map_name.path(), timer);
+            opts.apply_travel_times(&mut map, timer);
 
             for m in &self.modifiers {
                 scenario = m.apply(&map, scenario);
@@ -105,7 +107,8 @@ impl SimFlags {
         } else if self.load.contains("/raw_maps/") || self.load.contains("/maps/") {
             timer.note(format!("Loading map {}", self.load));
 
-            let map = Map::new(self.load.clone(), timer);
+            let mut map = Map::new(self.load.clone(), timer);
+            opts.apply_travel_times(&mut map, timer);
 
             timer.start("create sim");
             let sim = Sim::new(&map, opts, timer);
@@ -12,8 +12,9 @@ use map_model::{BuildingID, Map, OffstreetParking, RoadID};
 
 use crate::make::fork_rng;
 use crate::{
-    OrigPersonID, ParkingSpot, Sim, TripEndpoint, TripInfo, TripMode, TripSpec, Vehicle,
-    VehicleSpec, VehicleType, BIKE_LENGTH, MAX_CAR_LENGTH, MIN_CAR_LENGTH,
+    BikeShareDock, BikeShareSystem, OrigPersonID, ParkingSpot, RideHailFleet, Sim, TripEndpoint,
+    TripInfo, TripMode, TripSpec, Vehicle, VehicleSpec, VehicleType, BIKE_LENGTH, MAX_CAR_LENGTH,
+    MAX_TRUCK_LENGTH, MIN_CAR_LENGTH, MIN_TRUCK_LENGTH, MOTORCYCLE_LENGTH, SCOOTER_LENGTH,
 };
 
 /// A Scenario describes all the input to a simulation. Usually a scenario covers one day.
@@ -25,6 +26,12 @@ pub struct Scenario {
     pub people: Vec<PersonSpec>,
     /// None means seed all buses. Otherwise the route name must be present here.
     pub only_seed_buses: Option<BTreeSet<String>>,
+    /// Vehicles serving `TripMode::RideHail` trips. If there's no fleet, those trips are
+    /// cancelled.
+    pub ride_hail: Option<RideHailFleet>,
+    /// Shared vehicles for `TripMode::BikeShare` trips. If there aren't any, those trips just
+    /// walk.
+    pub bike_share: Option<BikeShareSystem>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -207,6 +214,58 @@ impl Scenario {
         parked_cars.shuffle(rng);
         seed_parked_cars(parked_cars, sim, map, rng, timer);
 
+        if let Some(ref fleet) = self.ride_hail {
+            // The number of buildings changes with map edits
+            let mut tmp_rng = fork_rng(rng);
+            let depots = if fleet.depots.is_empty() {
+                map.all_buildings()
+                    .choose_multiple(&mut tmp_rng, fleet.num_vehicles)
+                    .map(|b| b.id)
+                    .collect()
+            } else {
+                fleet.depots.clone()
+            };
+            if !depots.is_empty() {
+                let vehicles = (0..fleet.num_vehicles)
+                    .map(|idx| (Scenario::rand_car(&mut tmp_rng), depots[idx % depots.len()]))
+                    .collect();
+                let failed = sim.seed_ride_hail_fleet(fleet.clone(), vehicles, map);
+                if failed > 0 {
+                    timer.warn(format!(
+                        "{} ride-hailing vehicles don't have a depot connected to a road",
+                        prettyprint_usize(failed)
+                    ));
+                }
+            }
+        }
+
+        if let Some(ref system) = self.bike_share {
+            // The stations and buildings change with map edits
+            let mut tmp_rng = fork_rng(rng);
+            let mut vehicles = Vec::new();
+            for station in map.all_bike_share_stations() {
+                let num_bikes = (system.initial_fill * station.capacity as f64).round() as usize;
+                for _ in 0..num_bikes {
+                    vehicles.push((
+                        Scenario::rand_bike(&mut tmp_rng),
+                        BikeShareDock::Station(station.id),
+                    ));
+                }
+            }
+            if let Some(ref fleet) = system.dockless {
+                for b in map
+                    .all_buildings()
+                    .choose_multiple(&mut tmp_rng, fleet.num_vehicles)
+                {
+                    vehicles.push((
+                        Scenario::rand_vehicle(fleet.vehicle_type, &mut tmp_rng),
+                        BikeShareDock::Dockless(b.id),
+                    ));
+                }
+            }
+            sim.seed_bike_share(system.clone(), vehicles);
+        }
+
         sim.spawn_trips(results, map, timer);
         timer.stop(format!("Instantiating {}", self.scenario_name));
     }
@@ -224,16 +283,25 @@ impl Scenario {
             map_name: map.get_name().clone(),
             people: Vec::new(),
             only_seed_buses: Some(BTreeSet::new()),
+            ride_hail: None,
+            bike_share: None,
+        }
+    }
+
+    fn rand_vehicle(vehicle_type: VehicleType, rng: &mut XorShiftRng) -> VehicleSpec {
+        match vehicle_type {
+            VehicleType::Car => Scenario::rand_car(rng),
+            VehicleType::Bike => Scenario::rand_bike(rng),
+            VehicleType::Truck => Scenario::rand_truck(rng),
+            VehicleType::Scooter => Scenario::rand_scooter(rng),
+            VehicleType::Motorcycle => Scenario::rand_motorcycle(rng),
+            VehicleType::Bus | VehicleType::Train => unreachable!(),
         }
     }
 
     fn rand_car(rng: &mut XorShiftRng) -> VehicleSpec {
         let length = Scenario::rand_dist(rng, MIN_CAR_LENGTH, MAX_CAR_LENGTH);
-        VehicleSpec {
-            vehicle_type: VehicleType::Car,
-            length,
-            max_speed: None,
-        }
+        Scenario::spec(VehicleType::Car, length, None)
     }
 
     fn rand_bike(rng: &mut XorShiftRng) -> VehicleSpec {
@@ -242,16 +310,42 @@ impl Scenario {
             Speed::miles_per_hour(8.0),
             Scenario::max_bike_speed(),
         ));
-        VehicleSpec {
-            vehicle_type: VehicleType::Bike,
-            length: BIKE_LENGTH,
-            max_speed,
-        }
+        Scenario::spec(VehicleType::Bike, BIKE_LENGTH, max_speed)
     }
     pub fn max_bike_speed() -> Speed {
         Speed::miles_per_hour(10.0)
     }
 
+    fn rand_truck(rng: &mut XorShiftRng) -> VehicleSpec {
+        let length = Scenario::rand_dist(rng, MIN_TRUCK_LENGTH, MAX_TRUCK_LENGTH);
+        Scenario::spec(VehicleType::Truck, length, None)
+    }
+
+    fn rand_scooter(rng: &mut XorShiftRng) -> VehicleSpec {
+        // Most e-scooters are limited to 15mph
+        let max_speed = Some(Scenario::rand_speed(
+            rng,
+            Speed::miles_per_hour(10.0),
+            Speed::miles_per_hour(15.0),
+        ));
+        Scenario::spec(VehicleType::Scooter, SCOOTER_LENGTH, max_speed)
+    }
+
+    fn rand_motorcycle(_: &mut XorShiftRng) -> VehicleSpec {
+        Scenario::spec(VehicleType::Motorcycle, MOTORCYCLE_LENGTH, None)
+    }
+
+    fn spec(vehicle_type: VehicleType, length: Distance, max_speed: Option<Speed>) -> VehicleSpec {
+        let (max_accel, max_decel) = vehicle_type.default_acceleration();
+        VehicleSpec {
+            vehicle_type,
+            length,
+            max_speed,
+            max_accel,
+            max_decel,
+        }
+    }
+
     pub fn rand_dist(rng: &mut XorShiftRng, low: Distance, high: Distance) -> Distance {
         assert!(high > low);
         Distance::meters(rng.gen_range(low.inner_meters(), high.inner_meters()))
@@ -364,7 +458,7 @@ fn seed_parked_cars(
         if !ok {
             continue;
         }
-        if let Some(spot) = find_spot_near_building(b, &mut open_spots_per_road, map) {
+        if let Some(spot) = find_spot_near_building(b, &vehicle, &mut open_spots_per_road, map) {
             seeded += 1;
             sim.seed_parked_car(vehicle, spot);
         } else {
@@ -380,9 +474,10 @@ fn seed_parked_cars(
 
 // Pick a parking spot for this building. If the building's road has a free spot, use it. If not,
 // start BFSing out from the road in a deterministic way until finding a nearby road with an open
-// spot.
+// spot that fits the vehicle.
 fn find_spot_near_building(
     b: BuildingID,
+    vehicle: &Vehicle,
     open_spots_per_road: &mut BTreeMap<RoadID, Vec<(ParkingSpot, Option<BuildingID>)>>,
     map: &Map,
 ) -> Option<ParkingSpot> {
@@ -399,16 +494,14 @@ fn find_spot_near_building(
         if let Some(spots) = open_spots_per_road.get_mut(&r) {
             // Fill in all private parking first before
             // TODO With some probability, skip this available spot and park farther away
-            if let Some(idx) = spots
-                .iter()
-                .position(|(_, restriction)| restriction == &Some(b))
-            {
+            if let Some(idx) = spots.iter().position(|(spot, restriction)| {
+                restriction == &Some(b) && vehicle.vehicle_type.can_park_at(*spot, map)
+            }) {
                 return Some(spots.remove(idx).0);
             }
-            if let Some(idx) = spots
-                .iter()
-                .position(|(_, restriction)| restriction.is_none())
-            {
+            if let Some(idx) = spots.iter().position(|(spot, restriction)| {
+                restriction.is_none() && vehicle.vehicle_type.can_park_at(*spot, map)
+            }) {
                 return Some(spots.remove(idx).0);
             }
         }
@@ -462,53 +555,63 @@ impl PersonSpec {
         let mut cars_initially_parked_at = Vec::new();
         let mut vehicle_foreach_trip = Vec::new();
 
-        let mut bike_idx = None;
-        // For each indexed car, is it parked somewhere, or off-map?
-        let mut car_locations: Vec<(usize, Option<BuildingID>)> = Vec::new();
+        // Bikes and scooters can be carried along by walking, so a person only needs one of each.
+        let mut bike_like_idx: BTreeMap<VehicleType, usize> = BTreeMap::new();
+        // For each indexed car, truck, or motorcycle, is it parked somewhere, or off-map?
+        let mut car_locations: Vec<(usize, VehicleType, Option<BuildingID>)> = Vec::new();
+        // Cars left at a park-and-ride lot. The next park-and-ride trip picks them up, wherever it
+        // starts.
+        let mut cars_at_lots: Vec<usize> = Vec::new();
 
         // TODO If the trip is cancelled, this should be affected...
         let mut from = self.origin.clone();
         for trip in &self.trips {
-            let use_for_trip = match trip.mode {
-                TripMode::Walk | TripMode::Transit => None,
-                TripMode::Bike => {
-                    if bike_idx.is_none() {
-                        bike_idx = Some(vehicle_specs.len());
-                        vehicle_specs.push(Scenario::rand_bike(rng));
-                    }
-                    bike_idx
+            let use_for_trip = match trip.mode.vehicle_type() {
+                None => None,
+                Some(vt) if vt.is_bike_like() => {
+                    Some(*bike_like_idx.entry(vt).or_insert_with(|| {
+                        vehicle_specs.push(Scenario::rand_vehicle(vt, rng));
+                        vehicle_specs.len() - 1
+                    }))
                 }
-                TripMode::Drive => {
+                Some(vt) => {
                     let need_parked_at = match from {
                         TripEndpoint::Bldg(b) => Some(b),
                         _ => None,
                     };
 
-                    // Any available cars in the right spot?
-                    let idx = if let Some(idx) = car_locations
+                    // Any available vehicles of this type in the right spot?
+                    let from_lot = trip.mode == TripMode::ParkAndRide && !cars_at_lots.is_empty();
+                    let idx = if from_lot {
+                        cars_at_lots.remove(0)
+                    } else if let Some(idx) = car_locations
                         .iter()
-                        .find(|(_, parked_at)| *parked_at == need_parked_at)
-                        .map(|(idx, _)| *idx)
+                        .find(|(_, vt2, parked_at)| *vt2 == vt && *parked_at == need_parked_at)
+                        .map(|(idx, _, _)| *idx)
                     {
                         idx
                     } else {
-                        // Need a new car, starting in the right spot
+                        // Need a new vehicle, starting in the right spot
                         let idx = vehicle_specs.len();
-                        vehicle_specs.push(Scenario::rand_car(rng));
+                        vehicle_specs.push(Scenario::rand_vehicle(vt, rng));
                         if let Some(b) = need_parked_at {
                             cars_initially_parked_at.push((idx, b));
                         }
                         idx
                     };
 
-                    // Where does this car wind up?
-                    car_locations.retain(|(i, _)| idx != *i);
-                    match trip.destination {
-                        TripEndpoint::Bldg(b) => {
-                            car_locations.push((idx, Some(b)));
-                        }
-                        TripEndpoint::Border(_) | TripEndpoint::SuddenlyAppear(_) => {
-                            car_locations.push((idx, None));
+                    // Where does this vehicle wind up?
+                    car_locations.retain(|(i, _, _)| idx != *i);
+                    if trip.mode == TripMode::ParkAndRide && !from_lot {
+                        cars_at_lots.push(idx);
+                    } else {
+                        match trip.destination {
+                            TripEndpoint::Bldg(b) => {
+                                car_locations.push((idx, vt, Some(b)));
+                            }
+                            TripEndpoint::Border(_) | TripEndpoint::SuddenlyAppear(_) => {
+                                car_locations.push((idx, vt, None));
+                            }
                         }
                     }
 
@@ -521,10 +624,7 @@ impl PersonSpec {
 
         // For debugging
         if false {
-            let mut n = vehicle_specs.len();
-            if bike_idx.is_some() {
-                n -= 1;
-            }
+            let n = vehicle_specs.len() - bike_like_idx.len();
             if n > 1 {
                 println!("Someone needs {} cars", n);
             }
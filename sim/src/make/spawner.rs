@@ -9,7 +9,7 @@ use map_model::{BuildingID, BusRouteID, BusStopID, Map, PathConstraints, PathReq
 
 use crate::{
     CarID, DrivingGoal, PersonID, SidewalkSpot, TripEndpoint, TripInfo, TripLeg, TripMode,
-    VehicleType, SPAWN_DIST,
+    SPAWN_DIST,
 };
 
 // TODO Some of these fields are unused now that we separately pass TripEndpoint
@@ -50,6 +50,33 @@ pub(crate) enum TripSpec {
         stop1: BusStopID,
         maybe_stop2: Option<BusStopID>,
     },
+    UsingRideHail {
+        start: BuildingID,
+        goal: BuildingID,
+    },
+    UsingBikeShare {
+        start: BuildingID,
+        goal: BuildingID,
+    },
+    /// Drive to a lot and take transit from there, or take transit back to a car left at a lot and
+    /// drive the rest of the way. Which one happens depends on where the car is when the trip
+    /// starts.
+    UsingParkAndRide {
+        /// This must be a currently parked vehicle owned by the person.
+        car: CarID,
+        start_bldg: BuildingID,
+        goal: BuildingID,
+    },
+    UsingBikeAndRide {
+        bike: CarID,
+        start: BuildingID,
+        /// Where to leave the bike
+        lock_up_at: BusStopID,
+        goal: SidewalkSpot,
+        route: BusRouteID,
+        stop1: BusStopID,
+        maybe_stop2: Option<BusStopID>,
+    },
 }
 
 impl TripSpec {
@@ -82,11 +109,7 @@ impl TripSpec {
                     }
                 }
 
-                let constraints = if use_vehicle.1 == VehicleType::Bike {
-                    PathConstraints::Bike
-                } else {
-                    PathConstraints::Car
-                };
+                let constraints = use_vehicle.1.to_constraints();
 
                 legs.push(TripLeg::Drive(*use_vehicle, goal.clone()));
                 if let DrivingGoal::ParkNear(b) = goal {
@@ -115,6 +138,8 @@ impl TripSpec {
                         legs.push(TripLeg::Walk(SidewalkSpot::building(*b, map)));
                     }
                     DrivingGoal::Border(_, _) => {}
+                    // Park-and-ride and bike-and-ride trips are planned differently
+                    DrivingGoal::ParkInLot(_, _) | DrivingGoal::BikeRackAt(_) => unreachable!(),
                 }
             }
             TripSpec::JustWalking { start, goal, .. } => {
@@ -139,6 +164,7 @@ impl TripSpec {
                             goal,
                         })
                     }
+                    DrivingGoal::ParkInLot(_, _) | DrivingGoal::BikeRackAt(_) => unreachable!(),
                 };
 
                 if let Some(start_spot) = SidewalkSpot::bike_rack(*start, map) {
@@ -168,6 +194,9 @@ impl TripSpec {
                             legs.push(TripLeg::Walk(SidewalkSpot::building(*b, map)));
                         }
                         DrivingGoal::Border(_, _) => {}
+                        DrivingGoal::ParkInLot(_, _) | DrivingGoal::BikeRackAt(_) => {
+                            unreachable!()
+                        }
                     }
                 } else if backup_plan.is_some() {
                     info!("Can't start biking from {}. Walking instead", start);
@@ -204,6 +233,46 @@ impl TripSpec {
                     ];
                 }
             }
+            TripSpec::UsingRideHail { goal, .. } => {
+                legs.push(TripLeg::RideHail(*goal));
+            }
+            TripSpec::UsingBikeShare { goal, .. } => {
+                // Where to pick up a vehicle depends on what's available when the trip starts, so
+                // those legs get added then.
+                legs.push(TripLeg::Walk(SidewalkSpot::building(*goal, map)));
+            }
+            TripSpec::UsingParkAndRide { car, goal, .. } => {
+                // Just drive. Whether to use transit depends on where the car is when the trip
+                // starts, so the legs might change then.
+                legs.push(TripLeg::Walk(SidewalkSpot::deferred_parking_spot()));
+                legs.push(TripLeg::Drive(*car, DrivingGoal::ParkNear(*goal)));
+                legs.push(TripLeg::Walk(SidewalkSpot::building(*goal, map)));
+            }
+            TripSpec::UsingBikeAndRide {
+                bike,
+                start,
+                lock_up_at,
+                goal,
+                route,
+                stop1,
+                maybe_stop2,
+            } => {
+                if let Some(start_spot) = SidewalkSpot::bike_rack(*start, map) {
+                    legs.push(TripLeg::Walk(start_spot));
+                    legs.push(TripLeg::Drive(*bike, DrivingGoal::BikeRackAt(*lock_up_at)));
+                    legs.push(TripLeg::Walk(SidewalkSpot::bus_stop(*stop1, map)));
+                    legs.push(TripLeg::RideBus(*route, *maybe_stop2));
+                    if maybe_stop2.is_some() {
+                        legs.push(TripLeg::Walk(goal.clone()));
+                    }
+                } else {
+                    return TripSpec::SpawningFailure {
+                        use_vehicle: Some(*bike),
+                        error: format!("Can't start biking from {}", start),
+                    }
+                    .to_plan(person, info, map);
+                }
+            }
         };
 
         (person, info, self, legs)
@@ -217,11 +286,7 @@ impl TripSpec {
                 use_vehicle,
                 ..
             } => {
-                let constraints = if use_vehicle.1 == VehicleType::Bike {
-                    PathConstraints::Bike
-                } else {
-                    PathConstraints::Car
-                };
+                let constraints = use_vehicle.1.to_constraints();
                 Some(PathRequest {
                     start: *start_pos,
                     end: goal.goal_pos(constraints, map).unwrap(),
@@ -236,16 +301,24 @@ impl TripSpec {
                 end: goal.sidewalk_pos,
                 constraints: PathConstraints::Pedestrian,
             }),
-            TripSpec::UsingBike { start, .. } => Some(PathRequest {
-                start: map.get_b(*start).sidewalk_pos,
-                end: SidewalkSpot::bike_rack(*start, map).unwrap().sidewalk_pos,
-                constraints: PathConstraints::Pedestrian,
-            }),
+            TripSpec::UsingBike { start, .. } | TripSpec::UsingBikeAndRide { start, .. } => {
+                Some(PathRequest {
+                    start: map.get_b(*start).sidewalk_pos,
+                    end: SidewalkSpot::bike_rack(*start, map).unwrap().sidewalk_pos,
+                    constraints: PathConstraints::Pedestrian,
+                })
+            }
             TripSpec::UsingTransit { start, stop1, .. } => Some(PathRequest {
                 start: start.sidewalk_pos,
                 end: SidewalkSpot::bus_stop(*stop1, map).sidewalk_pos,
                 constraints: PathConstraints::Pedestrian,
             }),
+            // We don't know which vehicle will show up
+            TripSpec::UsingRideHail { .. } => None,
+            // We don't know where a vehicle will be available
+            TripSpec::UsingBikeShare { .. } => None,
+            // We don't know where the parked car will be
+            TripSpec::UsingParkAndRide { .. } => None,
         }
     }
 
@@ -261,27 +334,28 @@ impl TripSpec {
         map: &Map,
     ) -> Result<TripSpec, String> {
         Ok(match mode {
-            TripMode::Drive | TripMode::Bike => {
-                let constraints = if mode == TripMode::Drive {
-                    PathConstraints::Car
-                } else {
-                    PathConstraints::Bike
-                };
+            TripMode::Drive
+            | TripMode::Bike
+            | TripMode::Truck
+            | TripMode::Scooter
+            | TripMode::Motorcycle => {
+                let vehicle_type = mode.vehicle_type().unwrap();
+                let constraints = vehicle_type.to_constraints();
                 let goal = to.driving_goal(constraints, map)?;
                 match from {
                     TripEndpoint::Bldg(start_bldg) => {
-                        if mode == TripMode::Drive {
-                            TripSpec::UsingParkedCar {
-                                start_bldg,
-                                goal,
-                                car: use_vehicle.unwrap(),
-                            }
-                        } else {
+                        if vehicle_type.is_bike_like() {
                             TripSpec::UsingBike {
                                 start: start_bldg,
                                 goal,
                                 bike: use_vehicle.unwrap(),
                             }
+                        } else {
+                            TripSpec::UsingParkedCar {
+                                start_bldg,
+                                goal,
+                                car: use_vehicle.unwrap(),
+                            }
                         }
                     }
                     TripEndpoint::Border(i) => {
@@ -330,6 +404,104 @@ impl TripSpec {
                     TripSpec::JustWalking { start, goal }
                 }
             }
+            TripMode::RideHail => match (&from, &to) {
+                (TripEndpoint::Bldg(start), TripEndpoint::Bldg(goal)) => {
+                    let pickup = map
+                        .get_b(*start)
+                        .driving_connection(map)
+                        .ok_or_else(|| format!("{} isn't connected to a road", start))?
+                        .0;
+                    let dropoff = map
+                        .get_b(*goal)
+                        .driving_connection(map)
+                        .ok_or_else(|| format!("{} isn't connected to a road", goal))?
+                        .0;
+                    // The vehicle can't back up to reach somewhere just behind the pickup
+                    if pickup.lane() == dropoff.lane()
+                        && pickup.dist_along() >= dropoff.dist_along()
+                    {
+                        TripSpec::JustWalking {
+                            start: from.start_sidewalk_spot(map)?,
+                            goal: to.end_sidewalk_spot(map)?,
+                        }
+                    } else {
+                        TripSpec::UsingRideHail {
+                            start: *start,
+                            goal: *goal,
+                        }
+                    }
+                }
+                _ => {
+                    return Err(format!(
+                        "ride-hailing trips must go between buildings, not {:?} to {:?}",
+                        from, to
+                    ));
+                }
+            },
+            TripMode::BikeShare => match (&from, &to) {
+                (TripEndpoint::Bldg(start), TripEndpoint::Bldg(goal)) => TripSpec::UsingBikeShare {
+                    start: *start,
+                    goal: *goal,
+                },
+                _ => {
+                    return Err(format!(
+                        "bike share trips must go between buildings, not {:?} to {:?}",
+                        from, to
+                    ));
+                }
+            },
+            TripMode::ParkAndRide => match (&from, &to) {
+                (TripEndpoint::Bldg(start), TripEndpoint::Bldg(goal)) => {
+                    TripSpec::UsingParkAndRide {
+                        car: use_vehicle.unwrap(),
+                        start_bldg: *start,
+                        goal: *goal,
+                    }
+                }
+                _ => {
+                    return Err(format!(
+                        "park-and-ride trips must go between buildings, not {:?} to {:?}",
+                        from, to
+                    ));
+                }
+            },
+            TripMode::BikeAndRide => {
+                let start = match from {
+                    TripEndpoint::Bldg(b) => b,
+                    _ => {
+                        return Err(format!(
+                            "bike-and-ride trips must start at a building, not {:?}",
+                            from
+                        ));
+                    }
+                };
+                let bike = use_vehicle.unwrap();
+                let goal = to.end_sidewalk_spot(map)?;
+                if let Some((lock_up_at, (stop1, maybe_stop2, route))) = map
+                    .get_b(start)
+                    .biking_connection(map)
+                    .and_then(|(biking_pos, _)| {
+                        map.should_bike_and_ride(biking_pos, goal.sidewalk_pos)
+                    })
+                {
+                    TripSpec::UsingBikeAndRide {
+                        bike,
+                        start,
+                        lock_up_at,
+                        goal,
+                        route,
+                        stop1,
+                        maybe_stop2,
+                    }
+                } else {
+                    // Transit doesn't help, so just bike the whole way
+                    TripSpec::UsingBike {
+                        bike,
+                        start,
+                        goal: to.driving_goal(PathConstraints::Bike, map)?,
+                    }
+                }
+            }
         })
     }
 }
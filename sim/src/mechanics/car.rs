@@ -2,9 +2,10 @@ use std::collections::VecDeque;
 
 use serde::{Deserialize, Serialize};
 
-use geom::{Distance, Duration, PolyLine, Time};
-use map_model::{Direction, Map, Traversable};
+use geom::{Distance, Duration, PolyLine, Speed, Time};
+use map_model::{Direction, Map, RoadID, Traversable};
 
+use crate::mechanics::kinematics::SpeedProfile;
 use crate::{
     CarStatus, DistanceInterval, DrawCarInput, ParkingSpot, PersonID, Router, TimeInterval,
     TransitSimState, TripID, Vehicle, VehicleType,
@@ -21,6 +22,12 @@ pub(crate) struct Car {
     pub trip_and_person: Option<(TripID, PersonID)>,
     pub started_at: Time,
     pub total_blocked_time: Duration,
+    /// Only used in kinematic mode. How fast the vehicle was going when it finished its most
+    /// recent Crossing state.
+    pub last_speed: Speed,
+    /// The most recent delayed road this car tried to reroute around, whether or not that worked.
+    /// Don't pathfind around the same delay again on every lane.
+    pub last_reroute_road: Option<RoadID>,
 
     /// In reverse order -- most recently left is first. The sum length of these must be >=
     /// vehicle.length.
@@ -29,7 +36,13 @@ pub(crate) struct Car {
 
 impl Car {
     /// Assumes the current head of the path is the thing to cross.
-    pub fn crossing_state(&self, start_dist: Distance, start_time: Time, map: &Map) -> CarState {
+    pub fn crossing_state(
+        &self,
+        start_dist: Distance,
+        start_time: Time,
+        map: &Map,
+        kinematic: bool,
+    ) -> CarState {
         let dist_int = DistanceInterval::new_driving(
             start_dist,
             if self.router.last_step() {
@@ -38,7 +51,7 @@ impl Car {
                 self.router.head().length(map)
             },
         );
-        self.crossing_state_with_end_dist(dist_int, start_time, map)
+        self.crossing_state_with_end_dist(dist_int, start_time, map, kinematic)
     }
 
     pub fn crossing_state_with_end_dist(
@@ -46,14 +59,102 @@ impl Car {
         dist_int: DistanceInterval,
         start_time: Time,
         map: &Map,
+        kinematic: bool,
     ) -> CarState {
         let on = self.router.head();
+        let speed = self.max_speed_on(on, map);
+        if kinematic {
+            // The driving sim decides whether the vehicle has to stop before the next step, and
+            // replans the profile if so.
+            let profile = SpeedProfile::new(
+                dist_int.length(),
+                self.current_speed(start_time),
+                speed,
+                self.exit_speed(map),
+                self.vehicle.max_accel,
+                self.vehicle.max_decel,
+            );
+            let dt = profile.total_time();
+            return CarState::Crossing(
+                TimeInterval::new(start_time, start_time + dt),
+                dist_int,
+                Some(profile),
+            );
+        }
+        let dt = (dist_int.end - dist_int.start) / speed;
+        CarState::Crossing(
+            TimeInterval::new(start_time, start_time + dt),
+            dist_int,
+            None,
+        )
+    }
+
+    /// Only in kinematic mode, partway through a Crossing state. Keep what's been driven so far,
+    /// but replan the rest of the way, either braking to a stop at the end or slowing down just
+    /// enough to enter the next step.
+    pub fn replan_crossing(&self, now: Time, map: &Map, stop_at_end: bool) -> CarState {
+        match self.state {
+            CarState::Crossing(time_int, dist_int, Some(ref profile)) => {
+                let elapsed = now - time_int.start;
+                let dist = (dist_int.start + profile.dist_at(elapsed)).min(dist_int.end);
+                let rest = SpeedProfile::new(
+                    dist_int.end - dist,
+                    profile.speed_at(elapsed),
+                    self.max_speed_on(self.router.head(), map),
+                    if stop_at_end {
+                        Speed::ZERO
+                    } else {
+                        self.exit_speed(map)
+                    },
+                    self.vehicle.max_accel,
+                    self.vehicle.max_decel,
+                );
+                let profile = profile.splice(elapsed, rest);
+                CarState::Crossing(
+                    TimeInterval::new(time_int.start, time_int.start + profile.total_time()),
+                    dist_int,
+                    Some(profile),
+                )
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Come to a stop at the end of the trip. Otherwise, slow down enough to enter the next step.
+    fn exit_speed(&self, map: &Map) -> Speed {
+        match self.router.maybe_next() {
+            Some(next) if !self.router.last_step() => self.max_speed_on(next, map),
+            _ => Speed::ZERO,
+        }
+    }
+
+    pub fn max_speed_on(&self, on: Traversable, map: &Map) -> Speed {
         let mut speed = on.speed_limit(map);
-        if let Some(s) = self.vehicle.max_speed {
+        if let Some(mut s) = self.vehicle.max_speed {
+            // Cyclists slow down uphill and speed up downhill, but still obey the speed limit
+            if let (VehicleType::Bike, Traversable::Lane(l)) = (self.vehicle.vehicle_type, on) {
+                s = s * (1.0 / map.get_l(l).bike_time_factor(map));
+            }
             speed = speed.min(s);
         }
-        let dt = (dist_int.end - dist_int.start) / speed;
-        CarState::Crossing(TimeInterval::new(start_time, start_time + dt), dist_int)
+        speed
+    }
+
+    /// Only meaningful in kinematic mode; otherwise vehicles are either stopped or moving at the
+    /// speed limit.
+    pub fn current_speed(&self, now: Time) -> Speed {
+        match self.state {
+            CarState::Crossing(ref time_int, _, Some(ref profile)) => {
+                profile.speed_at(now - time_int.start)
+            }
+            // If the vehicle finished crossing something just now, it hasn't stopped yet.
+            CarState::Queued { blocked_since } | CarState::WaitingToAdvance { blocked_since }
+                if blocked_since == now =>
+            {
+                self.last_speed
+            }
+            _ => Speed::ZERO,
+        }
     }
 
     pub fn get_draw_car(
@@ -214,7 +315,7 @@ impl Car {
             status: match self.state {
                 CarState::Queued { .. } => CarStatus::Moving,
                 CarState::WaitingToAdvance { .. } => CarStatus::Moving,
-                CarState::Crossing(_, _) => CarStatus::Moving,
+                CarState::Crossing(_, _, _) => CarStatus::Moving,
                 CarState::Unparking(_, _, _) => CarStatus::Moving,
                 CarState::Parking(_, _, _) => CarStatus::Moving,
                 // Changing color for idling buses is helpful
@@ -253,7 +354,9 @@ impl Car {
 /// state machine encoded here.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub(crate) enum CarState {
-    Crossing(TimeInterval, DistanceInterval),
+    /// The speed profile is only present in kinematic mode. Otherwise, the vehicle moves at a
+    /// constant speed.
+    Crossing(TimeInterval, DistanceInterval, Option<SpeedProfile>),
     Queued {
         blocked_since: Time,
     },
@@ -269,7 +372,7 @@ pub(crate) enum CarState {
 impl CarState {
     pub fn get_end_time(&self) -> Time {
         match self {
-            CarState::Crossing(ref time_int, _) => time_int.end,
+            CarState::Crossing(ref time_int, _, _) => time_int.end,
             CarState::Queued { .. } => unreachable!(),
             CarState::WaitingToAdvance { .. } => unreachable!(),
             CarState::Unparking(_, _, ref time_int) => time_int.end,
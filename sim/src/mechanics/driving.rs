@@ -4,23 +4,25 @@ use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_hashmap, serialize_hashmap, FixedMap, IndexableKey};
 use geom::{Distance, Duration, PolyLine, Speed, Time};
-use map_model::{IntersectionID, LaneID, Map, Path, PathStep, Traversable};
+use map_model::{IntersectionID, LaneID, Map, Path, PathStep, Traversable, TurnID};
 
+use crate::cap::find_delay;
 use crate::mechanics::car::{Car, CarState};
 use crate::mechanics::Queue;
 use crate::sim::Ctx;
 use crate::{
     ActionAtEnd, AgentID, AgentProperties, CarID, Command, CreateCar, DelayCause, DistanceInterval,
     DrawCarInput, Event, IntersectionSimState, ParkedCar, ParkingSim, ParkingSpot, PersonID,
-    SimOptions, TimeInterval, TransitSimState, TripID, TripManager, UnzoomedAgent, Vehicle,
-    WalkingSimState, FOLLOWING_DISTANCE,
+    RerouteCause, RideHailSimState, SimOptions, TimeInterval, TransitSimState, TripID, TripManager,
+    UnzoomedAgent, Vehicle, VehicleType, WalkingSimState, FOLLOWING_DISTANCE,
 };
 
-const TIME_TO_WAIT_AT_BUS_STOP: Duration = Duration::const_seconds(10.0);
-
 // TODO Do something else.
 pub const BLIND_RETRY_TO_CREEP_FORWARDS: Duration = Duration::const_seconds(0.1);
 pub const BLIND_RETRY_TO_REACH_END_DIST: Duration = Duration::const_seconds(5.0);
+/// In kinematic mode, how often a vehicle braking for a red light or a full lane checks if it can
+/// keep going after all. Within this long of needing to brake, a moving vehicle is committed.
+const RECHECK_PLANNED_STOP: Duration = Duration::const_seconds(1.0);
 
 /// Simulates vehicles!
 #[derive(Serialize, Deserialize, Clone)]
@@ -42,6 +44,8 @@ pub(crate) struct DrivingSimState {
 
     recalc_lanechanging: bool,
     handle_uber_turns: bool,
+    kinematic: bool,
+    reroute_delay_threshold: Option<Duration>,
 
     time_to_unpark_onstreet: Duration,
     time_to_park_onstreet: Duration,
@@ -58,6 +62,8 @@ impl DrivingSimState {
             events: Vec::new(),
             recalc_lanechanging: opts.recalc_lanechanging,
             handle_uber_turns: opts.handle_uber_turns,
+            kinematic: opts.kinematic_driving,
+            reroute_delay_threshold: opts.reroute_drivers_delay_threshold,
 
             time_to_unpark_onstreet: Duration::seconds(10.0),
             time_to_park_onstreet: Duration::seconds(15.0),
@@ -115,6 +121,8 @@ impl DrivingSimState {
                 last_steps: VecDeque::new(),
                 started_at: now,
                 total_blocked_time: Duration::ZERO,
+                last_speed: Speed::ZERO,
+                last_reroute_road: None,
                 trip_and_person: params.trip_and_person,
             };
             if let Some(p) = params.maybe_parked_car {
@@ -136,6 +144,7 @@ impl DrivingSimState {
                         params.start_dist,
                         &car.vehicle,
                         ctx.parking,
+                        now,
                         ctx.map,
                         car.trip_and_person,
                         &mut self.events,
@@ -166,10 +175,12 @@ impl DrivingSimState {
                     }
                 }
 
-                car.state = car.crossing_state(params.start_dist, now, ctx.map);
+                car.state = car.crossing_state(params.start_dist, now, ctx.map, self.kinematic);
             }
-            ctx.scheduler
-                .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+            ctx.scheduler.push(
+                plan_lane_end(&mut car, now, &self.queues, ctx),
+                Command::UpdateCar(car.vehicle.id),
+            );
             {
                 let queue = self.queues.get_mut(&Traversable::Lane(first_lane)).unwrap();
                 queue.cars.insert(idx, car.vehicle.id);
@@ -210,6 +221,7 @@ impl DrivingSimState {
         ctx: &mut Ctx,
         trips: &mut TripManager,
         transit: &mut TransitSimState,
+        ridehail: &mut RideHailSimState,
         walking: &mut WalkingSimState,
     ) {
         let mut need_distances = {
@@ -226,7 +238,8 @@ impl DrivingSimState {
             // checker, temporarily move one of them out of the map.
             let mut car = self.cars.remove(&id).unwrap();
             // Responsibility of update_car to manage scheduling stuff!
-            need_distances = self.update_car_without_distances(&mut car, now, ctx, transit);
+            need_distances =
+                self.update_car_without_distances(&mut car, now, ctx, transit, ridehail);
             self.cars.insert(id, car);
         }
 
@@ -243,9 +256,9 @@ impl DrivingSimState {
             // checker, temporarily move one of them out of the map.
             let mut car = self.cars.remove(&id).unwrap();
             // Responsibility of update_car_with_distances to manage scheduling stuff!
-            if self
-                .update_car_with_distances(&mut car, &dists, idx, now, ctx, trips, transit, walking)
-            {
+            if self.update_car_with_distances(
+                &mut car, &dists, idx, now, ctx, trips, transit, ridehail, walking,
+            ) {
                 self.cars.insert(id, car);
             } else {
                 self.delete_car_internal(&mut car, dists, idx, now, ctx);
@@ -262,19 +275,27 @@ impl DrivingSimState {
         now: Time,
         ctx: &mut Ctx,
         transit: &mut TransitSimState,
+        ridehail: &mut RideHailSimState,
     ) -> bool {
         match car.state {
-            CarState::Crossing(time_int, dist_int) => {
+            // A kinematic vehicle is partway along a lane, deciding whether to stop at the end
+            CarState::Crossing(time_int, _, Some(_)) if now < time_int.end => {
+                ctx.scheduler.push(
+                    plan_lane_end(car, now, &self.queues, ctx),
+                    Command::UpdateCar(car.vehicle.id),
+                );
+            }
+            CarState::Crossing(time_int, dist_int, ref profile) => {
+                car.last_speed = profile
+                    .as_ref()
+                    .map(|p| p.exit_speed())
+                    .unwrap_or(Speed::ZERO);
                 let time_cross = now - time_int.start;
                 if time_cross > Duration::ZERO {
                     let avg_speed = Speed::from_dist_time(dist_int.length(), time_cross);
 
                     let route = car.router.head();
-                    let max_speed = route.speed_limit(ctx.map).min(
-                        car.vehicle
-                            .max_speed
-                            .unwrap_or(Speed::meters_per_second(100.0)),
-                    );
+                    let max_speed = car.max_speed_on(route, ctx.map);
 
                     if let Some((trip, _)) = car.trip_and_person {
                         if let Traversable::Lane(lane) = route {
@@ -313,22 +334,35 @@ impl DrivingSimState {
                         front,
                         &car.vehicle,
                         ctx.parking,
+                        now,
                         ctx.map,
                         car.trip_and_person,
                         &mut self.events,
                     );
                 }
-                car.state = car.crossing_state(front, now, ctx.map);
-                ctx.scheduler
-                    .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                car.state = car.crossing_state(front, now, ctx.map, self.kinematic);
+                ctx.scheduler.push(
+                    plan_lane_end(car, now, &self.queues, ctx),
+                    Command::UpdateCar(car.vehicle.id),
+                );
             }
             CarState::IdlingAtStop(dist, _) => {
-                car.router = transit.bus_departed_from_stop(car.vehicle.id, ctx.map);
+                if car.vehicle.vehicle_type.is_transit() {
+                    car.router = transit.bus_departed_from_stop(car.vehicle.id, ctx.map);
+                } else {
+                    let (router, passenger) = ridehail.vehicle_departed(car.vehicle.id, ctx.map);
+                    car.router = router;
+                    car.trip_and_person = passenger;
+                    // Each leg of a ride-hailing vehicle's day is measured separately
+                    car.total_blocked_time = Duration::ZERO;
+                }
                 self.events
                     .push(Event::PathAmended(car.router.get_path().clone()));
-                car.state = car.crossing_state(dist, now, ctx.map);
-                ctx.scheduler
-                    .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                car.state = car.crossing_state(dist, now, ctx.map, self.kinematic);
+                ctx.scheduler.push(
+                    plan_lane_end(car, now, &self.queues, ctx),
+                    Command::UpdateCar(car.vehicle.id),
+                );
 
                 // Update our follower, so they know we stopped idling.
                 let queue = &self.queues[&car.router.head()];
@@ -350,9 +384,10 @@ impl DrivingSimState {
                                     dist - car.vehicle.length - FOLLOWING_DISTANCE,
                                     now,
                                     ctx.map,
+                                    self.kinematic,
                                 );
                                 ctx.scheduler.update(
-                                    follower.state.get_end_time(),
+                                    plan_lane_end(follower, now, &self.queues, ctx),
                                     Command::UpdateCar(follower.vehicle.id),
                                 );
                             }
@@ -360,7 +395,7 @@ impl DrivingSimState {
                         CarState::WaitingToAdvance { .. } => unreachable!(),
                         // They weren't blocked. Note that there's no way the Crossing state could
                         // jump forwards here; the leader is still in front of them.
-                        CarState::Crossing(_, _)
+                        CarState::Crossing(_, _, _)
                         | CarState::Unparking(_, _, _)
                         | CarState::Parking(_, _, _)
                         | CarState::IdlingAtStop(_, _) => {}
@@ -413,14 +448,13 @@ impl DrivingSimState {
                 let last_step = car.router.advance(
                     &car.vehicle,
                     ctx.parking,
+                    now,
                     ctx.map,
                     car.trip_and_person,
                     &mut self.events,
                 );
                 car.total_blocked_time += now - blocked_since;
-                car.state = car.crossing_state(Distance::ZERO, now, ctx.map);
-                ctx.scheduler
-                    .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                car.state = car.crossing_state(Distance::ZERO, now, ctx.map, self.kinematic);
                 self.events.push(Event::AgentEntersTraversable(
                     AgentID::Car(car.vehicle.id),
                     goto,
@@ -431,6 +465,15 @@ impl DrivingSimState {
                     },
                 ));
 
+                if let Traversable::Lane(_) = goto {
+                    self.maybe_reroute_around_delay(car, now, ctx);
+                }
+                // Only plan for the end of the lane after picking the turn there.
+                ctx.scheduler.push(
+                    plan_lane_end(car, now, &self.queues, ctx),
+                    Command::UpdateCar(car.vehicle.id),
+                );
+
                 // Don't mark turn_finished until our back is out of the turn.
                 car.last_steps.push_front(last_step);
 
@@ -445,6 +488,7 @@ impl DrivingSimState {
                         ),
                         now,
                         ctx.map,
+                        self.kinematic,
                     )
                     .get_end_time(),
                     Command::UpdateLaggyHead(car.vehicle.id),
@@ -471,12 +515,13 @@ impl DrivingSimState {
         ctx: &mut Ctx,
         trips: &mut TripManager,
         transit: &mut TransitSimState,
+        ridehail: &mut RideHailSimState,
         walking: &mut WalkingSimState,
     ) -> bool {
         let our_dist = dists[idx].1;
 
         match car.state {
-            CarState::Crossing(_, _)
+            CarState::Crossing(_, _, _)
             | CarState::Unparking(_, _, _)
             | CarState::IdlingAtStop(_, _)
             | CarState::WaitingToAdvance { .. } => unreachable!(),
@@ -485,6 +530,7 @@ impl DrivingSimState {
                     our_dist,
                     &car.vehicle,
                     ctx.parking,
+                    now,
                     ctx.map,
                     car.trip_and_person,
                     &mut self.events,
@@ -537,7 +583,7 @@ impl DrivingSimState {
                     }
                     Some(ActionAtEnd::GotoLaneEnd) => {
                         car.total_blocked_time += now - blocked_since;
-                        car.state = car.crossing_state(our_dist, now, ctx.map);
+                        car.state = car.crossing_state(our_dist, now, ctx.map, self.kinematic);
                         ctx.scheduler
                             .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
                         true
@@ -557,10 +603,12 @@ impl DrivingSimState {
                     }
                     Some(ActionAtEnd::BusAtStop) => {
                         car.total_blocked_time += now - blocked_since;
-                        if transit.bus_arrived_at_stop(now, car.vehicle.id, trips, walking, ctx) {
+                        if let Some(dwell) =
+                            transit.bus_arrived_at_stop(now, car.vehicle.id, trips, walking, ctx)
+                        {
                             car.state = CarState::IdlingAtStop(
                                 our_dist,
-                                TimeInterval::new(now, now + TIME_TO_WAIT_AT_BUS_STOP),
+                                TimeInterval::new(now, now + dwell),
                             );
                             ctx.scheduler
                                 .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
@@ -570,6 +618,30 @@ impl DrivingSimState {
                             false
                         }
                     }
+                    Some(ActionAtEnd::RideHailAtStop) => {
+                        car.total_blocked_time += now - blocked_since;
+                        let dwell = ridehail.vehicle_arrived(
+                            now,
+                            car.vehicle.id,
+                            car.total_blocked_time,
+                            car.router.get_path().total_length(),
+                            trips,
+                            ctx,
+                        );
+                        car.trip_and_person = None;
+                        if let Some(dwell) = dwell {
+                            car.state = CarState::IdlingAtStop(
+                                our_dist,
+                                TimeInterval::new(now, now + dwell),
+                            );
+                            ctx.scheduler
+                                .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                            true
+                        } else {
+                            // Idle until the next request
+                            false
+                        }
+                    }
                     None => {
                         ctx.scheduler.push(
                             now + BLIND_RETRY_TO_REACH_END_DIST,
@@ -581,7 +653,7 @@ impl DrivingSimState {
                         /*
                         // If this car wasn't blocked at all, when would it reach its goal?
                         let ideal_end_time = match car.crossing_state(our_dist, now, map) {
-                            CarState::Crossing(time_int, _) => time_int.end,
+                            CarState::Crossing(time_int, _, _) => time_int.end,
                             _ => unreachable!(),
                         };
                         if ideal_end_time == now {
@@ -688,18 +760,20 @@ impl DrivingSimState {
                 CarState::Queued { blocked_since } => {
                     // Prevent them from jumping forwards.
                     follower.total_blocked_time += now - blocked_since;
-                    follower.state = follower.crossing_state(follower_dist, now, ctx.map);
+                    follower.state =
+                        follower.crossing_state(follower_dist, now, ctx.map, self.kinematic);
                     ctx.scheduler.update(
-                        follower.state.get_end_time(),
+                        plan_lane_end(follower, now, &self.queues, ctx),
                         Command::UpdateCar(follower_id),
                     );
                 }
-                CarState::Crossing(_, _) => {
+                CarState::Crossing(_, _, _) => {
                     // If the follower was still Crossing, they might not've been blocked by leader
                     // yet. In that case, recalculating their Crossing state is a no-op.
-                    follower.state = follower.crossing_state(follower_dist, now, ctx.map);
+                    follower.state =
+                        follower.crossing_state(follower_dist, now, ctx.map, self.kinematic);
                     ctx.scheduler.update(
-                        follower.state.get_end_time(),
+                        plan_lane_end(follower, now, &self.queues, ctx),
                         Command::UpdateCar(follower_id),
                     );
                 }
@@ -759,6 +833,7 @@ impl DrivingSimState {
                     ),
                     now,
                     ctx.map,
+                    self.kinematic,
                 )
                 .get_end_time();
             // Sometimes due to rounding, retry_at will be exactly time, but we really need to
@@ -838,7 +913,7 @@ impl DrivingSimState {
                         CarState::WaitingToAdvance { .. } => unreachable!(),
                         // They weren't blocked. Note that there's no way the Crossing state could
                         // jump forwards here; the leader vanished from the end of the traversable.
-                        CarState::Crossing(_, _)
+                        CarState::Crossing(_, _, _)
                         | CarState::Unparking(_, _, _)
                         | CarState::Parking(_, _, _)
                         | CarState::IdlingAtStop(_, _) => {}
@@ -856,6 +931,90 @@ impl DrivingSimState {
         std::mem::replace(&mut self.events, Vec::new())
     }
 
+    /// Just after a car enters a lane, check if cars have been waiting too long to turn off any
+    /// road further along the path. If so, try to go around.
+    fn maybe_reroute_around_delay(&mut self, car: &mut Car, now: Time, ctx: &mut Ctx) {
+        let threshold = match self.reroute_delay_threshold {
+            Some(threshold) => threshold,
+            None => {
+                return;
+            }
+        };
+        let steps = car.router.get_path().get_steps();
+        // Nothing can be done about delays on the current or last lane
+        if steps.len() < 3 {
+            return;
+        }
+        let (turn, delay) = match find_delay(
+            steps.iter().skip(1).take(steps.len() - 2),
+            now,
+            threshold,
+            ctx.intersections,
+            ctx.map,
+        ) {
+            Some(pair) => pair,
+            None => {
+                return;
+            }
+        };
+        let delayed_road = ctx.map.get_parent(turn.src);
+        if car.last_reroute_road == Some(delayed_road.id) {
+            return;
+        }
+        car.last_reroute_road = Some(delayed_road.id);
+        let avoid = delayed_road
+            .all_lanes()
+            .into_iter()
+            .filter(|l| {
+                PathStep::Lane(*l) != car.router.get_path().current_step()
+                    && PathStep::Lane(*l) != car.router.get_path().last_step()
+            })
+            .collect();
+        if car.router.reroute(avoid, ctx.map) {
+            self.events
+                .push(Event::PathAmended(car.router.get_path().clone()));
+            self.events.push(Event::VehicleRerouted(
+                car.vehicle.id,
+                car.trip_and_person.map(|(t, _)| t),
+                RerouteCause::Delay(delayed_road.id, delay),
+            ));
+        }
+    }
+
+    /// If rerouting is enabled and the car is partway along a lane, try to find a new path that
+    /// avoids edited lanes. Returns true if the car is no longer affected by the edits.
+    pub fn reroute_around_live_edits(
+        &mut self,
+        id: CarID,
+        edited_lanes: &BTreeSet<LaneID>,
+        map: &Map,
+    ) -> bool {
+        if self.reroute_delay_threshold.is_none() {
+            return false;
+        }
+        let car = match self.cars.get_mut(&id) {
+            Some(car) => car,
+            None => {
+                return false;
+            }
+        };
+        // When a car is queued at the end of a lane, it may have already asked to start a turn.
+        if !matches!(car.state, CarState::Crossing(_, _, _)) {
+            return false;
+        }
+        if !car.router.reroute(edited_lanes.clone(), map) {
+            return false;
+        }
+        self.events
+            .push(Event::PathAmended(car.router.get_path().clone()));
+        self.events.push(Event::VehicleRerouted(
+            id,
+            car.trip_and_person.map(|(t, _)| t),
+            RerouteCause::LiveEdit,
+        ));
+        true
+    }
+
     pub fn handle_live_edits(&mut self, map: &Map) {
         // Calculate all queues that should exist now.
         let mut new_queues = HashSet::new();
@@ -1111,6 +1270,23 @@ impl DrivingSimState {
         self.queues[&Traversable::Lane(l)].target_lane_penalty()
     }
 
+    /// Finds a bus or train anywhere on the lane leading to one of these turns, planning to make
+    /// the turn next.
+    pub fn transit_approaching(&self, turns: &[TurnID]) -> Option<CarID> {
+        for t in turns {
+            if let Some(queue) = self.queues.get(&Traversable::Lane(t.src)) {
+                for car in &queue.cars {
+                    if (car.1 == VehicleType::Bus || car.1 == VehicleType::Train)
+                        && self.cars[car].router.maybe_next() == Some(Traversable::Turn(*t))
+                    {
+                        return Some(*car);
+                    }
+                }
+            }
+        }
+        None
+    }
+
     pub fn find_trips_to_edited_parking(
         &self,
         spots: BTreeSet<ParkingSpot>,
@@ -1207,6 +1383,51 @@ impl DrivingSimState {
     }
 }
 
+/// In kinematic mode, a vehicle crossing a lane brakes to a stop at the end if the turn after
+/// isn't allowed right now or the lane after that is full. Otherwise it slows down just enough to
+/// make the turn. Since lights change and queues clear, the vehicle decides again when it would
+/// have to start braking, and keeps checking while it brakes. Returns when the vehicle should be
+/// updated next, either to decide again or because it finished crossing.
+fn plan_lane_end(
+    car: &mut Car,
+    now: Time,
+    queues: &HashMap<Traversable, Queue>,
+    ctx: &Ctx,
+) -> Time {
+    let turn = match (&car.state, car.router.maybe_next()) {
+        (CarState::Crossing(_, _, Some(_)), Some(Traversable::Turn(t))) => t,
+        _ => {
+            return car.state.get_end_time();
+        }
+    };
+    let blocked = ctx.intersections.must_stop_before(turn, ctx.map)
+        || queues
+            .get(&Traversable::Lane(turn.dst))
+            .map(|q| !q.room_for_car(car))
+            .unwrap_or(false);
+
+    let stop = car.replan_crossing(now, ctx.map, true);
+    let braking_starts = match stop {
+        CarState::Crossing(time_int, _, Some(ref profile)) => {
+            time_int.start + profile.braking_starts()
+        }
+        _ => unreachable!(),
+    };
+    if blocked {
+        car.state = stop;
+        braking_starts
+            .max(now + RECHECK_PLANNED_STOP)
+            .min(car.state.get_end_time())
+    } else {
+        car.state = car.replan_crossing(now, ctx.map, false);
+        if braking_starts >= now + RECHECK_PLANNED_STOP {
+            braking_starts
+        } else {
+            car.state.get_end_time()
+        }
+    }
+}
+
 impl IndexableKey for CarID {
     fn index(&self) -> usize {
         self.0
@@ -7,18 +7,22 @@ use abstutil::{
 };
 use geom::{Duration, Time};
 use map_model::{
-    ControlStopSign, ControlTrafficSignal, Intersection, IntersectionID, LaneID, Map, PhaseType,
-    Traversable, TurnID, TurnPriority, TurnType,
+    ControlStopSign, ControlTrafficSignal, Intersection, IntersectionID, LaneID, Map, MovementID,
+    PhaseType, Stage, Traversable, TurnID, TurnPriority, TurnType,
 };
 
 use crate::mechanics::car::Car;
-use crate::mechanics::Queue;
+use crate::mechanics::{DrivingSimState, Queue};
 use crate::{
     AgentID, AlertLocation, CarID, Command, DelayCause, Event, Scheduler, SimOptions, Speed,
+    VehicleType,
 };
 
 const WAIT_AT_STOP_SIGN: Duration = Duration::const_seconds(0.5);
 const WAIT_BEFORE_YIELD_AT_TRAFFIC_SIGNAL: Duration = Duration::const_seconds(0.2);
+/// When a bus or train is still approaching at the end of a stage serving it, how much longer to
+/// hold the green before checking again
+const TRANSIT_PRIORITY_EXTENSION: Duration = Duration::const_seconds(3.0);
 
 /// Manages conflicts at intersections. When an agent has reached the end of a lane, they call
 /// maybe_start_turn to make a Request. Based on the intersection type (stop sign, traffic signal,
@@ -70,7 +74,19 @@ struct State {
 #[derive(Clone, Serialize, Deserialize)]
 struct SignalState {
     current_stage: usize,
+    // Only policies with a variable stage length and leading pedestrian intervals care about this
+    stage_started_at: Time,
     stage_ends_at: Time,
+    // Crosswalks where a pedestrian pushed the button, waiting to be served
+    ped_calls: BTreeSet<MovementID>,
+    // Crosswalks in the current stage that show "walk." On push-button stages, only the called
+    // crosswalks do.
+    walking: BTreeSet<MovementID>,
+    // How long the current stage has been held green for transit
+    transit_extension: Duration,
+    // If transit signal priority cut the current stage short, this is the next stage that serves
+    // the bus or train.
+    transit_early_green: Option<usize>,
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Debug)]
@@ -245,6 +261,7 @@ impl IntersectionSimState {
         now: Time,
         id: IntersectionID,
         map: &Map,
+        driving: &DrivingSimState,
         scheduler: &mut Scheduler,
     ) {
         let state = self.state.get_mut(&id).unwrap();
@@ -254,7 +271,27 @@ impl IntersectionSimState {
         // Switch to a new stage?
         assert_eq!(now, signal_state.stage_ends_at);
         let old_stage = &signal.stages[signal_state.current_stage];
+        let elapsed = now - signal_state.stage_started_at;
+
+        // Transit signal priority overrides the normal policy.
+        if let Some((car, dt)) = transit_extension(signal, signal_state, old_stage, driving) {
+            if signal_state.transit_extension == Duration::ZERO {
+                self.events.push(Event::TransitSignalPriority(id, car));
+            }
+            signal_state.transit_extension += dt;
+            signal_state.stage_ends_at = now + dt;
+            scheduler.push(signal_state.stage_ends_at, Command::UpdateIntersection(id));
+            self.wakeup_waiting(now, id, scheduler, map);
+            return;
+        }
+
+        // If the current stage should keep going, for how much longer?
+        let mut extend_by = None;
         match old_stage.phase_type {
+            // Don't let the policy undo cutting the stage short for transit.
+            _ if signal_state.transit_early_green.is_some() => {
+                signal_state.current_stage = signal_state.transit_early_green.unwrap();
+            }
             PhaseType::Fixed(_) => {
                 signal_state.current_stage += 1;
             }
@@ -275,15 +312,57 @@ impl IntersectionSimState {
                     ));
                 }
             }
+            PhaseType::Actuated { max_green, gap, .. } => {
+                // The detectors cover the entire incoming lane, not just the stop bar.
+                if elapsed < max_green
+                    && stage_demand(&state.waiting, signal, old_stage, map, driving) > 0
+                {
+                    extend_by = Some(gap.min(max_green - elapsed));
+                } else {
+                    signal_state.current_stage += 1;
+                }
+            }
+            PhaseType::MaxPressure {
+                min_green,
+                max_green,
+            } => {
+                let current = signal_state.current_stage;
+                let mut best: Option<(usize, isize)> = None;
+                for (idx, stage) in signal.stages.iter().enumerate() {
+                    if idx == current && elapsed >= max_green {
+                        continue;
+                    }
+                    let pressure = stage_pressure(&state.waiting, signal, stage, map, driving);
+                    // Ties go to the current stage, then to the next stage in the cycle.
+                    let rank = |i: usize| (i + signal.stages.len() - current) % signal.stages.len();
+                    if best
+                        .map(|(b, p)| pressure > p || (pressure == p && rank(idx) < rank(b)))
+                        .unwrap_or(true)
+                    {
+                        best = Some((idx, pressure));
+                    }
+                }
+                match best {
+                    Some((idx, _)) if idx == current => {
+                        extend_by = Some(min_green.min(max_green - elapsed));
+                    }
+                    Some((idx, _)) => {
+                        signal_state.current_stage = idx;
+                    }
+                    // There's only one stage, and it's been running for max_green. Start it over.
+                    None => {}
+                }
+            }
         }
         if signal_state.current_stage == signal.stages.len() {
             signal_state.current_stage = 0;
         }
 
-        signal_state.stage_ends_at = now
-            + signal.stages[signal_state.current_stage]
-                .phase_type
-                .simple_duration();
+        if let Some(dt) = extend_by {
+            signal_state.stage_ends_at = now + dt;
+        } else {
+            signal_state.start_stage(signal, now);
+        }
         scheduler.push(signal_state.stage_ends_at, Command::UpdateIntersection(id));
         self.wakeup_waiting(now, id, scheduler, map);
     }
@@ -359,6 +438,11 @@ impl IntersectionSimState {
             // If we made it this far, we don't conflict with an accepted turn
             true
         } else if let Some(ref signal) = map.maybe_get_traffic_signal(turn.parent) {
+            if let AgentID::Car(car) = agent {
+                if car.1 == VehicleType::Bus || car.1 == VehicleType::Train {
+                    self.transit_early_green(now, car, turn, map, scheduler);
+                }
+            }
             self.traffic_signal_policy(&req, map, signal, speed, now, Some(scheduler))
         } else if let Some(ref sign) = map.maybe_get_stop_sign(turn.parent) {
             self.stop_sign_policy(&req, map, sign, now, scheduler)
@@ -493,6 +577,14 @@ impl IntersectionSimState {
                             state.id
                         );
                     }
+                    // Transit priority was relative to the old stages
+                    signal_state.transit_extension = Duration::ZERO;
+                    signal_state.transit_early_green = None;
+                    // Movements might've been removed or renumbered
+                    signal_state
+                        .ped_calls
+                        .retain(|m| ts.movements.contains_key(m));
+                    signal_state.update_walk_signal(&ts.stages[signal_state.current_stage]);
                 }
                 (Some(_), None) => {
                     state.signal = Some(SignalState::new(state.id, now, map, scheduler));
@@ -506,6 +598,52 @@ impl IntersectionSimState {
         }
     }
 
+    /// Pedestrians call this when they reach a crosswalk. If the crosswalk is at a traffic signal
+    /// and isn't already showing "walk," this places a call for the next stage serving it.
+    pub fn push_ped_button(&mut self, now: Time, turn: TurnID, map: &Map) {
+        if map.get_t(turn).turn_type != TurnType::Crosswalk {
+            return;
+        }
+        if let Some(signal) = map.maybe_get_traffic_signal(turn.parent) {
+            let signal_state = self
+                .state
+                .get_mut(&turn.parent)
+                .unwrap()
+                .signal
+                .as_mut()
+                .unwrap();
+            let stage = &signal.stages[signal_state.current_stage];
+            let m = signal.turn_to_movement(turn);
+            let walking_now = signal_state.walking.contains(&m)
+                && signal_state.stage_ends_at - now > stage.flashing_dont_walk;
+            if !walking_now {
+                signal_state.ped_calls.insert(m);
+            }
+        }
+    }
+
+    pub fn override_stage(
+        &mut self,
+        now: Time,
+        id: IntersectionID,
+        stage: usize,
+        duration: Duration,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) {
+        let signal_state = self.state.get_mut(&id).unwrap().signal.as_mut().unwrap();
+        assert!(stage < map.get_traffic_signal(id).stages.len());
+        assert!(duration > Duration::ZERO);
+        signal_state.current_stage = stage;
+        signal_state.stage_started_at = now;
+        signal_state.stage_ends_at = now + duration;
+        signal_state.transit_extension = Duration::ZERO;
+        signal_state.transit_early_green = None;
+        signal_state.update_walk_signal(&map.get_traffic_signal(id).stages[stage]);
+        scheduler.update(signal_state.stage_ends_at, Command::UpdateIntersection(id));
+        self.wakeup_waiting(now, id, scheduler, map);
+    }
+
     pub fn handle_live_edits(&self, map: &Map) {
         // Just sanity check that we don't have any references to deleted turns
         let mut errors = Vec::new();
@@ -546,6 +684,24 @@ impl IntersectionSimState {
             .any(|req| req.turn.dst == lane)
     }
 
+    /// Would a vehicle arriving right now have to stop before making this turn, because of a stop
+    /// sign or a signal that doesn't currently allow it? Conflicts with other agents aren't
+    /// considered; this is just for kinematic vehicles to plan braking.
+    pub fn must_stop_before(&self, turn: TurnID, map: &Map) -> bool {
+        if self.use_freeform_policy_everywhere {
+            return false;
+        }
+        if let Some(ref signal) = map.maybe_get_traffic_signal(turn.parent) {
+            let signal_state = self.state[&turn.parent].signal.as_ref().unwrap();
+            signal.stages[signal_state.current_stage].get_priority_of_turn(turn, signal)
+                == TurnPriority::Banned
+        } else if let Some(ref sign) = map.maybe_get_stop_sign(turn.parent) {
+            sign.get_priority(turn, map) == TurnPriority::Yield
+        } else {
+            false
+        }
+    }
+
     pub fn debug(&self, id: IntersectionID, map: &Map) {
         println!("{}", abstutil::to_json(&self.state[&id]));
         if let Some(ref sign) = map.maybe_get_stop_sign(id) {
@@ -733,8 +889,17 @@ impl IntersectionSimState {
         let state = &self.state[&req.turn.parent];
         let signal_state = state.signal.as_ref().unwrap();
         let stage = &signal.stages[signal_state.current_stage];
-        let full_stage_duration = stage.phase_type.simple_duration();
-        let remaining_stage_time = signal_state.stage_ends_at - now;
+        let full_stage_duration = stage.phase_type.max_duration();
+        let remaining_stage_time = match stage.phase_type {
+            // Somebody waiting to make a protected movement will keep the stage going, so the
+            // only hard deadline is max_green.
+            PhaseType::Actuated { max_green, .. }
+                if stage.get_priority_of_turn(req.turn, signal) == TurnPriority::Protected =>
+            {
+                (signal_state.stage_started_at + max_green).max(signal_state.stage_ends_at) - now
+            }
+            _ => signal_state.stage_ends_at - now,
+        };
         let our_time = state.waiting[req];
 
         // Can't go at all this stage.
@@ -743,6 +908,32 @@ impl IntersectionSimState {
             return false;
         }
 
+        if turn.turn_type == TurnType::Crosswalk {
+            // Nobody pushed the button for this crosswalk before the stage started.
+            if !signal_state
+                .walking
+                .contains(&signal.turn_to_movement(req.turn))
+            {
+                return false;
+            }
+            // Anybody already crossing can finish, but don't start while "don't walk" flashes.
+            if remaining_stage_time <= stage.flashing_dont_walk {
+                return false;
+            }
+        } else if now < signal_state.stage_started_at + stage.leading_pedestrian_interval
+            && signal_state.conflicts_with_walking(signal, signal.turn_to_movement(req.turn))
+        {
+            // Give pedestrians a head start over vehicles crossing their path. Like yielding, we
+            // own scheduling for req.agent.
+            if let Some(s) = scheduler {
+                s.push(
+                    signal_state.stage_started_at + stage.leading_pedestrian_interval,
+                    Command::update_agent(req.agent),
+                );
+            }
+            return false;
+        }
+
         if our_priority == TurnPriority::Yield
             && now < our_time + WAIT_BEFORE_YIELD_AT_TRAFFIC_SIGNAL
         {
@@ -787,6 +978,67 @@ impl IntersectionSimState {
         true
     }
 
+    /// A bus or train is waiting to make a movement with transit priority, but the current stage
+    /// doesn't allow it. End the stage early, as long as the crosswalks still get enough time.
+    fn transit_early_green(
+        &mut self,
+        now: Time,
+        car: CarID,
+        turn: TurnID,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) {
+        let signal = map.get_traffic_signal(turn.parent);
+        let tsp = match signal.transit_priority {
+            Some(ref tsp) => tsp,
+            None => {
+                return;
+            }
+        };
+        let movement = signal.turn_to_movement(turn);
+        if !tsp.movements.contains(&movement) {
+            return;
+        }
+        let signal_state = self
+            .state
+            .get_mut(&turn.parent)
+            .unwrap()
+            .signal
+            .as_mut()
+            .unwrap();
+        let stage = &signal.stages[signal_state.current_stage];
+        if signal_state.transit_early_green.is_some()
+            || stage.get_priority_of_movement(movement) != TurnPriority::Banned
+        {
+            return;
+        }
+        // With three or more stages, the next one might not serve the vehicle either
+        let num_stages = signal.stages.len();
+        let target = match (1..num_stages)
+            .map(|i| (signal_state.current_stage + i) % num_stages)
+            .find(|idx| {
+                signal.stages[*idx].get_priority_of_movement(movement) != TurnPriority::Banned
+            }) {
+            Some(idx) => idx,
+            None => {
+                return;
+            }
+        };
+
+        let shortest = (stage.phase_type.simple_duration() - tsp.max_early_green)
+            .max(signal.get_min_crossing_time(signal_state.current_stage))
+            .max(stage.leading_pedestrian_interval);
+        let new_end = (signal_state.stage_started_at + shortest).max(now);
+        if new_end >= signal_state.stage_ends_at {
+            return;
+        }
+        signal_state.transit_early_green = Some(target);
+        signal_state.stage_ends_at = new_end;
+        scheduler.update(new_end, Command::UpdateIntersection(turn.parent));
+        self.events
+            .push(Event::TransitSignalPriority(turn.parent, car));
+    }
+
     // If true, the request can go.
     fn handle_accepted_conflicts(
         &mut self,
@@ -891,12 +1143,23 @@ impl SignalState {
     fn new(id: IntersectionID, now: Time, map: &Map, scheduler: &mut Scheduler) -> SignalState {
         let mut state = SignalState {
             current_stage: 0,
+            stage_started_at: now,
             stage_ends_at: now,
+            ped_calls: BTreeSet::new(),
+            walking: BTreeSet::new(),
+            transit_extension: Duration::ZERO,
+            transit_early_green: None,
         };
 
         let signal = map.get_traffic_signal(id);
-        // What stage are we starting with?
-        let mut offset = (now - Time::START_OF_DAY) + signal.offset;
+        // What stage are we starting with? The offset may be longer than the time elapsed so far,
+        // so only look at how far into the cycle we are.
+        let cycle_length: Duration = signal
+            .stages
+            .iter()
+            .map(|s| s.phase_type.simple_duration())
+            .sum();
+        let mut offset = ((now - Time::START_OF_DAY) + signal.offset) % cycle_length;
         loop {
             let dt = signal.stages[state.current_stage]
                 .phase_type
@@ -908,13 +1171,144 @@ impl SignalState {
                     state.current_stage = 0;
                 }
             } else {
-                state.stage_ends_at = now + dt - offset;
+                // The stage may have started before midnight
+                state.stage_started_at = if now - Time::START_OF_DAY >= offset {
+                    now - offset
+                } else {
+                    Time::START_OF_DAY
+                };
+                state.stage_ends_at = now + (dt - offset);
                 break;
             }
         }
+        state.update_walk_signal(&signal.stages[state.current_stage]);
         scheduler.push(state.stage_ends_at, Command::UpdateIntersection(id));
         state
     }
+
+    /// Begin the current stage, skipping over any stage that only serves pedestrians when nobody
+    /// has pushed the button.
+    fn start_stage(&mut self, signal: &ControlTrafficSignal, now: Time) {
+        for _ in 0..signal.stages.len() {
+            let stage = &signal.stages[self.current_stage];
+            if !stage.ped_push_button || !stage.only_crosswalks() || self.is_called(stage) {
+                break;
+            }
+            self.current_stage = (self.current_stage + 1) % signal.stages.len();
+        }
+        let stage = &signal.stages[self.current_stage];
+        self.update_walk_signal(stage);
+        self.stage_started_at = now;
+        self.stage_ends_at = now + stage.phase_type.simple_duration();
+        self.transit_extension = Duration::ZERO;
+        self.transit_early_green = None;
+    }
+
+    fn is_called(&self, stage: &Stage) -> bool {
+        crosswalks(stage).any(|m| self.ped_calls.contains(m))
+    }
+
+    /// Show "walk" on every crosswalk the stage serves, or just the called ones for push-button
+    /// stages. Calls for crosswalks that now show "walk" are served.
+    fn update_walk_signal(&mut self, stage: &Stage) {
+        self.walking = crosswalks(stage)
+            .filter(|m| !stage.ped_push_button || self.ped_calls.contains(m))
+            .cloned()
+            .collect();
+        for m in &self.walking {
+            self.ped_calls.remove(m);
+        }
+    }
+
+    /// Does a vehicle movement cross the path of any crosswalk showing "walk"?
+    fn conflicts_with_walking(&self, signal: &ControlTrafficSignal, movement: MovementID) -> bool {
+        self.walking
+            .iter()
+            .any(|m| signal.movements[m].conflicts_with(&signal.movements[&movement]))
+    }
+}
+
+/// All crosswalks that a stage serves, protected or yielding.
+fn crosswalks(stage: &Stage) -> impl Iterator<Item = &MovementID> {
+    stage
+        .protected_movements
+        .iter()
+        .chain(stage.yield_movements.iter())
+        .filter(|m| m.crosswalk)
+}
+
+/// How many agents want to make one of the stage's protected movements? This counts everyone
+/// waiting at the intersection, plus every vehicle anywhere on a lane leading to the movement.
+fn stage_demand(
+    waiting: &BTreeMap<Request, Time>,
+    signal: &ControlTrafficSignal,
+    stage: &Stage,
+    map: &Map,
+    driving: &DrivingSimState,
+) -> usize {
+    let mut demand = waiting
+        .keys()
+        .filter(|req| stage.get_priority_of_turn(req.turn, signal) == TurnPriority::Protected)
+        .count();
+    let mut lanes = BTreeSet::new();
+    for m in &stage.protected_movements {
+        lanes.extend(signal.movements[m].members.iter().map(|t| t.src));
+    }
+    for l in lanes {
+        if map.get_l(l).lane_type.is_for_moving_vehicles() {
+            demand += driving.target_lane_penalty(l).0;
+        }
+    }
+    demand
+}
+
+/// The "pressure" of a stage for max-pressure control: the demand for the stage's protected
+/// movements, minus the number of vehicles already on the lanes those movements lead to.
+fn stage_pressure(
+    waiting: &BTreeMap<Request, Time>,
+    signal: &ControlTrafficSignal,
+    stage: &Stage,
+    map: &Map,
+    driving: &DrivingSimState,
+) -> isize {
+    let mut downstream = BTreeSet::new();
+    for m in &stage.protected_movements {
+        downstream.extend(signal.movements[m].members.iter().map(|t| t.dst));
+    }
+    let mut pressure = stage_demand(waiting, signal, stage, map, driving) as isize;
+    for l in downstream {
+        if map.get_l(l).lane_type.is_for_moving_vehicles() {
+            pressure -= driving.target_lane_penalty(l).0 as isize;
+        }
+    }
+    pressure
+}
+
+/// Should the current stage be held green a little longer, because a bus or train is approaching on
+/// a movement with transit priority that the stage serves? Returns the vehicle and how much longer.
+fn transit_extension(
+    signal: &ControlTrafficSignal,
+    signal_state: &SignalState,
+    stage: &Stage,
+    driving: &DrivingSimState,
+) -> Option<(CarID, Duration)> {
+    let tsp = signal.transit_priority.as_ref()?;
+    if signal_state.transit_early_green.is_some()
+        || signal_state.transit_extension >= tsp.max_green_extension
+    {
+        return None;
+    }
+    let mut turns = Vec::new();
+    for m in &tsp.movements {
+        if stage.get_priority_of_movement(*m) != TurnPriority::Banned {
+            turns.extend(signal.movements[m].members.iter().cloned());
+        }
+    }
+    let car = driving.transit_approaching(&turns)?;
+    Some((
+        car,
+        TRANSIT_PRIORITY_EXTENSION.min(tsp.max_green_extension - signal_state.transit_extension),
+    ))
 }
 
 fn allow_block_the_box(i: &Intersection) -> bool {
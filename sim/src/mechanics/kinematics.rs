@@ -0,0 +1,386 @@
+//! In the kinematic driving mode, vehicles don't instantly change speed when they cross onto a
+//! new lane or turn. Instead, each Crossing state follows a speed profile: accelerate from the
+//! entry speed towards the speed limit, cruise, then brake to the speed required at the end. Each
+//! phase has constant acceleration, so the position and the end time can be calculated exactly,
+//! and the discrete-event scheduler only needs a few events per Crossing. When a vehicle has to
+//! stop at the end of a lane for a red light or a full lane ahead, it brakes to a stop, and it
+//! replans the rest of the profile if the way clears first.
+
+use serde::{Deserialize, Serialize};
+
+use geom::{Acceleration, Distance, Duration, Speed};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub(crate) struct SpeedProfile {
+    phases: Vec<Phase>,
+}
+
+/// A period of constant acceleration
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct Phase {
+    duration: Duration,
+    start_speed: Speed,
+    accel: Acceleration,
+}
+
+impl Phase {
+    fn new(start_speed: f64, end_speed: f64, accel: f64) -> Phase {
+        Phase {
+            duration: Duration::seconds((end_speed - start_speed) / accel),
+            start_speed: Speed::meters_per_second(start_speed),
+            accel: Acceleration::meters_per_second_squared(accel),
+        }
+    }
+
+    fn cruise(dist: f64, speed: f64) -> Phase {
+        Phase {
+            duration: Duration::seconds(dist / speed),
+            start_speed: Speed::meters_per_second(speed),
+            accel: Acceleration::ZERO,
+        }
+    }
+
+    fn speed_at(&self, t: Duration) -> Speed {
+        let t = t.inner_seconds();
+        Speed::meters_per_second(
+            (self.start_speed.inner_meters_per_second()
+                + self.accel.inner_meters_per_second_squared() * t)
+                .max(0.0),
+        )
+    }
+
+    fn dist_at(&self, t: Duration) -> Distance {
+        let t = t.inner_seconds();
+        Distance::meters(
+            (self.start_speed.inner_meters_per_second() * t
+                + 0.5 * self.accel.inner_meters_per_second_squared() * t * t)
+                .max(0.0),
+        )
+    }
+}
+
+impl SpeedProfile {
+    /// Plan how to cover some distance, starting at `entry` speed. The vehicle won't exceed
+    /// `cruise` and will try to slow down to `exit` by the end. If the distance is too short to
+    /// brake in time, it'll finish faster than `exit`. `accel` and `decel` must be positive.
+    pub fn new(
+        dist: Distance,
+        entry: Speed,
+        cruise: Speed,
+        exit: Speed,
+        accel: Acceleration,
+        decel: Acceleration,
+    ) -> SpeedProfile {
+        let mut d = dist.inner_meters();
+        let mut v0 = entry.inner_meters_per_second();
+        let vc = cruise.inner_meters_per_second();
+        let v1 = exit.inner_meters_per_second().min(vc);
+        let a = accel.inner_meters_per_second_squared();
+        let b = decel.inner_meters_per_second_squared();
+        assert!(
+            a > 0.0 && b > 0.0,
+            "Bad acceleration limits {}, {}",
+            accel,
+            decel
+        );
+
+        let mut phases = Vec::new();
+        if d <= 0.0 {
+            // Nothing to cross, so leave at the same speed
+            phases.push(Phase {
+                duration: Duration::ZERO,
+                start_speed: entry,
+                accel: Acceleration::ZERO,
+            });
+            return SpeedProfile { phases };
+        }
+
+        // Coming from somewhere faster, so slow down to the new limit first.
+        if v0 > vc {
+            let braking_dist = (v0 * v0 - vc * vc) / (2.0 * b);
+            if braking_dist >= d {
+                let v = (v0 * v0 - 2.0 * b * d).sqrt();
+                phases.push(Phase::new(v0, v, -b));
+                return SpeedProfile { phases };
+            }
+            phases.push(Phase::new(v0, vc, -b));
+            d -= braking_dist;
+            v0 = vc;
+        }
+
+        // Braking the whole way isn't enough to reach the exit speed
+        if v0 > v1 && (v0 * v0 - v1 * v1) / (2.0 * b) >= d {
+            let v = (v0 * v0 - 2.0 * b * d).max(0.0).sqrt();
+            phases.push(Phase::new(v0, v, -b));
+            return SpeedProfile { phases };
+        }
+        // Accelerating the whole way doesn't reach the exit speed
+        if v0 < v1 && v0 * v0 + 2.0 * a * d <= v1 * v1 {
+            let v = (v0 * v0 + 2.0 * a * d).sqrt();
+            phases.push(Phase::new(v0, v, a));
+            return SpeedProfile { phases };
+        }
+
+        // The highest speed that still leaves room to brake to the exit speed
+        let peak = ((2.0 * a * b * d + b * v0 * v0 + a * v1 * v1) / (a + b))
+            .sqrt()
+            .min(vc);
+        let accel_dist = (peak * peak - v0 * v0) / (2.0 * a);
+        let brake_dist = (peak * peak - v1 * v1) / (2.0 * b);
+        let cruise_dist = d - accel_dist - brake_dist;
+        if peak > v0 {
+            phases.push(Phase::new(v0, peak, a));
+        }
+        if cruise_dist > 0.0 && peak > 0.0 {
+            phases.push(Phase::cruise(cruise_dist, peak));
+        }
+        if peak > v1 {
+            phases.push(Phase::new(peak, v1, -b));
+        }
+        SpeedProfile { phases }
+    }
+
+    pub fn total_time(&self) -> Duration {
+        self.phases.iter().map(|p| p.duration).sum()
+    }
+
+    /// How far along the profile the vehicle is, some time after starting it.
+    pub fn dist_at(&self, mut t: Duration) -> Distance {
+        let mut dist = Distance::ZERO;
+        for phase in &self.phases {
+            if t <= phase.duration {
+                return dist + phase.dist_at(t.max(Duration::ZERO));
+            }
+            dist += phase.dist_at(phase.duration);
+            t -= phase.duration;
+        }
+        dist
+    }
+
+    pub fn speed_at(&self, mut t: Duration) -> Speed {
+        for phase in &self.phases {
+            if t <= phase.duration {
+                return phase.speed_at(t.max(Duration::ZERO));
+            }
+            t -= phase.duration;
+        }
+        self.exit_speed()
+    }
+
+    pub fn exit_speed(&self) -> Speed {
+        match self.phases.last() {
+            Some(phase) => phase.speed_at(phase.duration),
+            None => Speed::ZERO,
+        }
+    }
+
+    /// When the final stretch of braking begins. If the profile doesn't end by braking, this is
+    /// the total time.
+    pub fn braking_starts(&self) -> Duration {
+        match self.phases.last() {
+            Some(phase) if phase.accel < Acceleration::ZERO => self.total_time() - phase.duration,
+            _ => self.total_time(),
+        }
+    }
+
+    /// Follow this profile for `t`, then switch to a different plan for the rest of the way.
+    pub fn splice(&self, mut t: Duration, rest: SpeedProfile) -> SpeedProfile {
+        let mut phases = Vec::new();
+        for phase in &self.phases {
+            if t <= Duration::ZERO {
+                break;
+            }
+            phases.push(Phase {
+                duration: phase.duration.min(t),
+                start_speed: phase.start_speed,
+                accel: phase.accel,
+            });
+            t -= phase.duration;
+        }
+        phases.extend(rest.phases);
+        SpeedProfile { phases }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mps(x: f64) -> Speed {
+        Speed::meters_per_second(x)
+    }
+
+    fn mpss(x: f64) -> Acceleration {
+        Acceleration::meters_per_second_squared(x)
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "got {}, expected {}",
+            actual,
+            expected
+        );
+    }
+
+    /// The profile covers exactly the distance, never goes backwards, and never speeds past the
+    /// higher of the entry and cruising speeds.
+    fn check_round_trip(profile: &SpeedProfile, dist: Distance, max_speed: Speed) {
+        let total = profile.total_time();
+        assert_close(profile.dist_at(total).inner_meters(), dist.inner_meters());
+        assert_close(profile.dist_at(Duration::ZERO).inner_meters(), 0.0);
+        let mut last = Distance::ZERO;
+        for i in 0..=100 {
+            let t = total * (i as f64 / 100.0);
+            let d = profile.dist_at(t);
+            assert!(d >= last, "went backwards at {}", t);
+            assert!(profile.speed_at(t) <= max_speed + mps(1e-6));
+            last = d;
+        }
+        // Past the end, the vehicle stays at the end
+        assert_close(
+            profile
+                .dist_at(total + Duration::seconds(10.0))
+                .inner_meters(),
+            dist.inner_meters(),
+        );
+    }
+
+    #[test]
+    fn distance_and_time_round_trip() {
+        // (distance, entry, cruise, exit)
+        for (dist, entry, cruise, exit) in vec![
+            // Accelerate, cruise, brake
+            (200.0, 0.0, 15.0, 0.0),
+            // Too short to reach the cruising speed
+            (20.0, 0.0, 15.0, 0.0),
+            // Coming from a faster road
+            (200.0, 20.0, 10.0, 5.0),
+            // Accelerating the whole way
+            (10.0, 0.0, 30.0, 30.0),
+            // Already at the cruising speed
+            (100.0, 10.0, 10.0, 10.0),
+        ] {
+            let dist = Distance::meters(dist);
+            let profile = SpeedProfile::new(
+                dist,
+                mps(entry),
+                mps(cruise),
+                mps(exit),
+                mpss(2.0),
+                mpss(3.0),
+            );
+            check_round_trip(&profile, dist, mps(entry.max(cruise)));
+        }
+    }
+
+    #[test]
+    fn cruise_exactly() {
+        let profile = SpeedProfile::new(
+            Distance::meters(100.0),
+            mps(10.0),
+            mps(10.0),
+            mps(10.0),
+            mpss(2.0),
+            mpss(3.0),
+        );
+        assert_close(profile.total_time().inner_seconds(), 10.0);
+        assert_close(profile.dist_at(Duration::seconds(4.0)).inner_meters(), 40.0);
+        assert_close(profile.exit_speed().inner_meters_per_second(), 10.0);
+    }
+
+    #[test]
+    fn stop_from_cruising_speed() {
+        // At 10m/s braking at 2m/s^2, stopping takes 5s and 25m.
+        let profile = SpeedProfile::new(
+            Distance::meters(25.0),
+            mps(10.0),
+            mps(10.0),
+            Speed::ZERO,
+            mpss(1.0),
+            mpss(2.0),
+        );
+        assert_close(profile.total_time().inner_seconds(), 5.0);
+        assert_close(profile.exit_speed().inner_meters_per_second(), 0.0);
+        assert_close(
+            profile
+                .speed_at(Duration::seconds(2.5))
+                .inner_meters_per_second(),
+            5.0,
+        );
+
+        // With more room, cruise first, then brake over the last 25m
+        let profile = SpeedProfile::new(
+            Distance::meters(50.0),
+            mps(10.0),
+            mps(10.0),
+            Speed::ZERO,
+            mpss(1.0),
+            mpss(2.0),
+        );
+        assert_close(profile.total_time().inner_seconds(), 7.5);
+        assert_close(profile.dist_at(Duration::seconds(2.5)).inner_meters(), 25.0);
+        assert_close(profile.exit_speed().inner_meters_per_second(), 0.0);
+
+        // Not enough room to stop, so finish still moving
+        let profile = SpeedProfile::new(
+            Distance::meters(16.0),
+            mps(10.0),
+            mps(10.0),
+            Speed::ZERO,
+            mpss(1.0),
+            mpss(2.0),
+        );
+        assert_close(profile.exit_speed().inner_meters_per_second(), 6.0);
+        assert_close(profile.total_time().inner_seconds(), 2.0);
+    }
+
+    #[test]
+    fn brake_partway_through() {
+        // Cruising at 10m/s over 100m, planning to keep going
+        let go = SpeedProfile::new(
+            Distance::meters(100.0),
+            mps(10.0),
+            mps(10.0),
+            mps(10.0),
+            mpss(1.0),
+            mpss(2.0),
+        );
+        assert_eq!(go.braking_starts(), go.total_time());
+
+        // After 5s, the light ahead turns red. Stop over the remaining 50m.
+        let t = Duration::seconds(5.0);
+        let rest = SpeedProfile::new(
+            Distance::meters(100.0) - go.dist_at(t),
+            go.speed_at(t),
+            mps(10.0),
+            Speed::ZERO,
+            mpss(1.0),
+            mpss(2.0),
+        );
+        let stop = go.splice(t, rest);
+        check_round_trip(&stop, Distance::meters(100.0), mps(10.0));
+        // The first 5s are unchanged
+        assert_close(stop.dist_at(t).inner_meters(), 50.0);
+        // Cruise another 25m, then brake over the last 25m for 5s
+        assert_close(stop.braking_starts().inner_seconds(), 7.5);
+        assert_close(stop.total_time().inner_seconds(), 12.5);
+        assert_close(stop.exit_speed().inner_meters_per_second(), 0.0);
+    }
+
+    #[test]
+    fn zero_length_lane() {
+        let profile = SpeedProfile::new(
+            Distance::ZERO,
+            mps(10.0),
+            mps(15.0),
+            Speed::ZERO,
+            mpss(2.0),
+            mpss(3.0),
+        );
+        assert_eq!(profile.total_time(), Duration::ZERO);
+        assert_eq!(profile.dist_at(Duration::seconds(1.0)), Distance::ZERO);
+        // Crossing nothing doesn't change the vehicle's speed
+        assert_close(profile.exit_speed().inner_meters_per_second(), 10.0);
+    }
+}
@@ -7,10 +7,10 @@ use abstutil::{
     deserialize_btreemap, deserialize_multimap, retain_btreemap, serialize_btreemap,
     serialize_multimap, MultiMap, Timer,
 };
-use geom::{Distance, PolyLine, Pt2D};
+use geom::{Distance, PolyLine, Pt2D, Time};
 use map_model::{
     BuildingID, Lane, LaneID, LaneType, Map, OffstreetParking, ParkingLotID, PathConstraints,
-    PathStep, Position, Traversable, TurnID,
+    PathStep, Position, TimeWindows, Traversable, TurnID,
 };
 
 use crate::{CarID, CarStatus, DrawCarInput, Event, ParkedCar, ParkingSpot, PersonID, Vehicle};
@@ -39,7 +39,8 @@ pub trait ParkingSim {
     fn is_free(&self, spot: ParkingSpot) -> bool;
     fn get_car_at_spot(&self, spot: ParkingSpot) -> Option<&ParkedCar>;
     /// The vehicle's front is currently at the given driving_pos. Returns all valid spots and their
-    /// driving position.
+    /// driving position. Lanes that only allow parking at some times of day are skipped when
+    /// parking isn't allowed now.
     fn get_all_free_spots(
         &self,
         driving_pos: Position,
@@ -47,6 +48,7 @@ pub trait ParkingSim {
         // Either the building where a seeded car starts or the target of a trip. For filtering
         // private spots.
         target: BuildingID,
+        now: Time,
         map: &Map,
     ) -> Vec<(ParkingSpot, Position)>;
     fn spot_to_driving_pos(&self, spot: ParkingSpot, vehicle: &Vehicle, map: &Map) -> Position;
@@ -64,6 +66,7 @@ pub trait ParkingSim {
         start: LaneID,
         vehicle: &Vehicle,
         target: BuildingID,
+        now: Time,
         map: &Map,
     ) -> Option<(Vec<PathStep>, ParkingSpot, Position)>;
     fn collect_events(&mut self) -> Vec<Event>;
@@ -415,12 +418,17 @@ impl ParkingSim for NormalParkingSimState {
         // Either the building where a seeded car starts or the target of a trip. For filtering
         // private spots.
         target: BuildingID,
+        now: Time,
         map: &Map,
     ) -> Vec<(ParkingSpot, Position)> {
         let mut candidates = Vec::new();
 
         for l in self.driving_to_parking_lanes.get(driving_pos.lane()) {
-            for spot in self.onstreet_lanes[l].spots() {
+            let lane = &self.onstreet_lanes[l];
+            if !lane.is_open(now) {
+                continue;
+            }
+            for spot in lane.spots() {
                 if self.is_free(spot)
                     && driving_pos.dist_along()
                         < self.spot_to_driving_pos(spot, vehicle, map).dist_along()
@@ -439,7 +447,7 @@ impl ParkingSim for NormalParkingSimState {
             if driving_pos.dist_along() < *bldg_dist {
                 for idx in 0..self.num_spots_per_offstreet[b] {
                     let spot = ParkingSpot::Offstreet(*b, idx);
-                    if self.is_free(spot) {
+                    if self.is_free(spot) && vehicle.vehicle_type.can_park_at(spot, map) {
                         candidates.push(spot);
                     }
                 }
@@ -451,7 +459,7 @@ impl ParkingSim for NormalParkingSimState {
             if driving_pos.dist_along() < lot_dist {
                 for idx in 0..self.num_spots_per_lot[&pl] {
                     let spot = ParkingSpot::Lot(*pl, idx);
-                    if self.is_free(spot) {
+                    if self.is_free(spot) && vehicle.vehicle_type.can_park_at(spot, map) {
                         candidates.push(spot);
                     }
                 }
@@ -535,6 +543,7 @@ impl ParkingSim for NormalParkingSimState {
         start: LaneID,
         vehicle: &Vehicle,
         target: BuildingID,
+        now: Time,
         map: &Map,
     ) -> Option<(Vec<PathStep>, ParkingSpot, Position)> {
         let mut backrefs: HashMap<LaneID, TurnID> = HashMap::new();
@@ -552,7 +561,7 @@ impl ParkingSim for NormalParkingSimState {
                 // Pick the closest to the start of the lane, since that's closest to where we came
                 // from
                 if let Some((spot, pos)) = self
-                    .get_all_free_spots(Position::start(current), vehicle, target, map)
+                    .get_all_free_spots(Position::start(current), vehicle, target, now, map)
                     .into_iter()
                     .min_by_key(|(_, pos)| pos.dist_along())
                 {
@@ -572,7 +581,11 @@ impl ParkingSim for NormalParkingSimState {
                     }
                 }
             }
-            for turn in map.get_turns_for(current, PathConstraints::Car) {
+            for turn in map.get_turns_for_during(
+                current,
+                vehicle.vehicle_type.to_constraints(),
+                Some((now, now)),
+            ) {
                 if !backrefs.contains_key(&turn.id.dst) {
                     let dist_this_step = turn.geom.length() + map.get_l(current).length();
                     backrefs.insert(turn.id.dst, turn.id);
@@ -620,17 +633,35 @@ struct ParkingLane {
     sidewalk: LaneID,
     // The front of the parking spot (farthest along the lane)
     spot_dist_along: Vec<Distance>,
+    // Some lanes only allow parking at certain times of day
+    allowed: Option<TimeWindows>,
 }
 
 impl ParkingLane {
     fn new(lane: &Lane, map: &Map, timer: &mut Timer) -> Option<ParkingLane> {
-        if lane.lane_type != LaneType::Parking {
+        if !lane.lane_types_during(None).contains(&LaneType::Parking) {
             return None;
         }
+        let allowed = match lane.conditional_type {
+            Some(ref cond) if lane.lane_type != LaneType::Parking => Some(cond.during.clone()),
+            _ => None,
+        };
 
         let driving_lane = if let Some(l) = map.get_parent(lane.id).parking_to_driving(lane.id, map)
         {
             l
+        } else if map
+            .get_parent(lane.id)
+            .all_lanes()
+            .into_iter()
+            .any(|l| map.get_l(l).conditional_type.is_some())
+        {
+            // The only driving lanes might change type at some times of day
+            timer.warn(format!(
+                "Parking lane {} has no driving lane usable all day",
+                lane.id
+            ));
+            return None;
         } else {
             // Serious enough to blow up loudly.
             panic!("Parking lane {} has no driving lane!", lane.id);
@@ -655,9 +686,17 @@ impl ParkingLane {
             spot_dist_along: (0..lane.number_parking_spots())
                 .map(|idx| map_model::PARKING_SPOT_LENGTH * (2.0 + idx as f64))
                 .collect(),
+            allowed,
         })
     }
 
+    fn is_open(&self, now: Time) -> bool {
+        self.allowed
+            .as_ref()
+            .map(|windows| windows.contains(now))
+            .unwrap_or(true)
+    }
+
     fn dist_along_for_car(&self, spot_idx: usize, vehicle: &Vehicle) -> Distance {
         // Find the offset to center this particular car in the parking spot
         self.spot_dist_along[spot_idx] - (map_model::PARKING_SPOT_LENGTH - vehicle.length) / 2.0
@@ -866,6 +905,7 @@ impl ParkingSim for InfiniteParkingSimState {
         driving_pos: Position,
         vehicle: &Vehicle,
         target: BuildingID,
+        _: Time,
         map: &Map,
     ) -> Vec<(ParkingSpot, Position)> {
         // The target building may be blackholed, so fallback to a building on one of the
@@ -921,6 +961,7 @@ impl ParkingSim for InfiniteParkingSimState {
         start: LaneID,
         vehicle: &Vehicle,
         target: BuildingID,
+        now: Time,
         map: &Map,
     ) -> Option<(Vec<PathStep>, ParkingSpot, Position)> {
         // TODO This impl is copied from NormalParkingSimState. Instead, we already know the
@@ -940,7 +981,7 @@ impl ParkingSim for InfiniteParkingSimState {
                 // Pick the closest to the start of the lane, since that's closest to where we came
                 // from
                 if let Some((spot, pos)) = self
-                    .get_all_free_spots(Position::start(current), vehicle, target, map)
+                    .get_all_free_spots(Position::start(current), vehicle, target, now, map)
                     .into_iter()
                     .min_by_key(|(_, pos)| pos.dist_along())
                 {
@@ -960,7 +1001,11 @@ impl ParkingSim for InfiniteParkingSimState {
                     }
                 }
             }
-            for turn in map.get_turns_for(current, PathConstraints::Car) {
+            for turn in map.get_turns_for_during(
+                current,
+                vehicle.vehicle_type.to_constraints(),
+                Some((now, now)),
+            ) {
                 if !backrefs.contains_key(&turn.id.dst) {
                     let dist_this_step = turn.geom.length() + map.get_l(current).length();
                     backrefs.insert(turn.id.dst, turn.id);
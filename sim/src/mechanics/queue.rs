@@ -7,7 +7,7 @@ use geom::{Distance, Time};
 use map_model::{Map, Traversable};
 
 use crate::mechanics::car::{Car, CarState};
-use crate::{CarID, VehicleType, FOLLOWING_DISTANCE};
+use crate::{CarID, FOLLOWING_DISTANCE};
 
 /// A Queue of vehicles on a single lane or turn. No over-taking or lane-changing. This is where
 /// https://dabreegster.github.io/abstreet/trafficsim/discrete_event.html#exact-positions is
@@ -174,10 +174,15 @@ impl Queue {
                     assert_eq!(bound, self.geom_len);
                     self.geom_len
                 }
-                CarState::Crossing(ref time_int, ref dist_int) => {
+                CarState::Crossing(ref time_int, ref dist_int, ref profile) => {
                     // TODO Why percent_clamp_end? We process car updates in any order, so we might
                     // calculate this before moving this car from Crossing to another state.
-                    dist_int.lerp(time_int.percent_clamp_end(now)).min(bound)
+                    let front = match profile {
+                        Some(profile) => (dist_int.start + profile.dist_at(now - time_int.start))
+                            .min(dist_int.end),
+                        None => dist_int.lerp(time_int.percent_clamp_end(now)),
+                    };
+                    front.min(bound)
                 }
                 CarState::Unparking(front, _, _) => front,
                 CarState::Parking(front, _, _) => front,
@@ -267,11 +272,8 @@ impl Queue {
             num_vehicles += 1;
         }
 
-        let bike_cost = if self.cars.iter().any(|c| c.1 == VehicleType::Bike)
-            || self
-                .laggy_head
-                .map(|c| c.1 == VehicleType::Bike)
-                .unwrap_or(false)
+        let bike_cost = if self.cars.iter().any(|c| c.1.is_bike_like())
+            || self.laggy_head.map(|c| c.1.is_bike_like()).unwrap_or(false)
         {
             1
         } else {
@@ -310,7 +312,7 @@ fn dump_cars(
         let car = &cars[id];
         println!("- {} @ {} (length {})", id, dist, car.vehicle.length);
         match car.state {
-            CarState::Crossing(ref time_int, ref dist_int) => {
+            CarState::Crossing(ref time_int, ref dist_int, _) => {
                 println!(
                     "  Going {} .. {} during {} .. {}",
                     dist_int.start, dist_int.end, time_int.start, time_int.end
@@ -749,6 +749,8 @@ impl Pedestrian {
         scheduler: &mut Scheduler,
     ) -> bool {
         if let PathStep::Turn(t) = self.path.next_step() {
+            // Push the button to cross, if there is one. It's harmless to do this repeatedly.
+            intersections.push_ped_button(now, t, map);
             if !intersections.maybe_start_turn(
                 AgentID::Pedestrian(self.id),
                 t,
@@ -55,11 +55,7 @@ impl TrafficRecorder {
                                                 time,
                                                 TripPurpose::Shopping,
                                                 TripEndpoint::Border(t.parent),
-                                                if car.1 == VehicleType::Bike {
-                                                    TripMode::Bike
-                                                } else {
-                                                    TripMode::Drive
-                                                },
+                                                TripMode::from_constraints(car.1.to_constraints()),
                                             ),
                                         ));
                                         self.seen_trips.insert(trip);
@@ -92,6 +88,8 @@ impl TrafficRecorder {
             map_name: map.get_name().clone(),
             people,
             only_seed_buses: None,
+            ride_hail: None,
+            bike_share: None,
         }
         .save();
     }
@@ -0,0 +1,533 @@
+//! Ride-hailing: a shared fleet of vehicles that pick somebody up at one building, drop them off at
+//! another, and then wait for the next request. Nobody in the simulation drives these vehicles, so
+//! unlike cars owned by a Person, they spend some of their time driving around empty
+//! ("deadheading").
+//!
+//! Idle vehicles aren't physically on the map. Think of them as circling the block or pulled over
+//! somewhere out of the way. When they're dispatched, they appear at the beginning of the lane
+//! where they were waiting.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::{Distance, Duration, Time};
+use map_model::{BuildingID, Map, Path, PathConstraints, PathRequest, Position};
+
+use crate::sim::Ctx;
+use crate::{
+    CarID, Command, CreateCar, Event, PersonID, Router, Scheduler, TripID, TripManager, Vehicle,
+};
+
+/// How long does it take a passenger to get in the vehicle?
+const PICKUP_TIME: Duration = Duration::const_seconds(30.0);
+/// How long does it take a passenger to get out?
+const DROPOFF_TIME: Duration = Duration::const_seconds(15.0);
+
+/// A fleet of ride-hailing vehicles, shared by everybody taking a `TripMode::RideHail` trip.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct RideHailFleet {
+    pub num_vehicles: usize,
+    /// Vehicles start the day spread evenly between these buildings. If this is empty, each
+    /// vehicle starts near a random building.
+    pub depots: Vec<BuildingID>,
+    pub dispatch: DispatchStrategy,
+    pub after_dropoff: AfterDropoff,
+}
+
+/// Decides which idle vehicle should handle a request. Requests are handled in the order they're
+/// made; if nobody's idle, a request waits until some vehicle finishes its current ride.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum DispatchStrategy {
+    /// The vehicle closest to the pickup, as the crow flies
+    Nearest,
+    /// Among vehicles within this distance of the pickup, the one that's been waiting the longest.
+    /// If none are that close, fall back to the nearest one. This spreads rides more evenly
+    /// between drivers, at the cost of longer pickups.
+    LongestIdle(Distance),
+}
+
+/// What does a vehicle do after dropping somebody off, if no other requests are waiting?
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum AfterDropoff {
+    /// Drive to the end of the current road and wait there
+    WaitNearby,
+    /// Drive back to the depot where the vehicle started the day
+    ReturnToDepot,
+}
+
+impl DispatchStrategy {
+    /// Each idle vehicle is described by where it's waiting and since when.
+    fn pick(
+        self,
+        pickup: Position,
+        idle: &Vec<(CarID, Position, Time)>,
+        map: &Map,
+    ) -> Option<CarID> {
+        let pt = pickup.pt(map);
+        let nearest = idle
+            .iter()
+            .min_by_key(|(_, pos, _)| pos.pt(map).dist_to(pt))
+            .map(|(id, _, _)| *id);
+        match self {
+            DispatchStrategy::Nearest => nearest,
+            DispatchStrategy::LongestIdle(max_dist) => idle
+                .iter()
+                .filter(|(_, pos, _)| pos.pt(map).dist_to(pt) <= max_dist)
+                .min_by_key(|(_, _, since)| *since)
+                .map(|(id, _, _)| *id)
+                .or(nearest),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct RideRequest {
+    pub trip: TripID,
+    pub person: PersonID,
+    pub requested_at: Time,
+    pub pickup: BuildingID,
+    pub dropoff: BuildingID,
+    /// Where the vehicle stops on the road in front of each building
+    pub pickup_pos: Position,
+    pub dropoff_pos: Position,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct FleetVehicle {
+    vehicle: Vehicle,
+    depot: Position,
+    state: FleetState,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+enum FleetState {
+    /// Off the map, waiting to appear at this position. Since when?
+    Idle(Position, Time),
+    /// Driving empty to the pickup
+    ToPickup(RideRequest),
+    /// Waiting for the passenger to get in. The path to the dropoff is already calculated.
+    Boarding(RideRequest, Path),
+    Carrying(RideRequest),
+    /// Waiting for the passenger to get out at this position
+    Alighting(Position),
+    /// Driving empty somewhere, then becoming idle at this position
+    Repositioning(Position),
+}
+
+/// Manages a fleet of ride-hailing vehicles, matching requests from people to vehicles.
+//
+// TODO Live map edits cancelling a ride in progress will confuse this.
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct RideHailSimState {
+    fleet: Option<RideHailFleet>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    vehicles: BTreeMap<CarID, FleetVehicle>,
+    /// Requests that haven't been assigned a vehicle yet, oldest first
+    waiting: VecDeque<RideRequest>,
+
+    events: Vec<Event>,
+}
+
+impl RideHailSimState {
+    pub fn new() -> RideHailSimState {
+        RideHailSimState {
+            fleet: None,
+            vehicles: BTreeMap::new(),
+            waiting: VecDeque::new(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn set_fleet(&mut self, fleet: RideHailFleet) {
+        self.fleet = Some(fleet);
+    }
+
+    /// Returns false if the depot isn't connected to a road.
+    pub fn add_vehicle(
+        &mut self,
+        now: Time,
+        vehicle: Vehicle,
+        depot: BuildingID,
+        map: &Map,
+    ) -> bool {
+        if let Some((pos, _)) = map.get_b(depot).driving_connection(map) {
+            let idle_pos = idle_pos(pos, &vehicle, map);
+            self.vehicles.insert(
+                vehicle.id,
+                FleetVehicle {
+                    vehicle,
+                    depot: pos,
+                    state: FleetState::Idle(idle_pos, now),
+                },
+            );
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Somebody wants a ride. Fails if there aren't any vehicles at all; otherwise, the request
+    /// might have to wait for a vehicle to free up.
+    pub fn request_ride(
+        &mut self,
+        now: Time,
+        req: RideRequest,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) -> Result<(), String> {
+        if self.vehicles.is_empty() {
+            return Err(format!(
+                "{} requested a ride, but there aren't any ride-hailing vehicles",
+                req.person
+            ));
+        }
+        self.waiting.push_back(req);
+        self.dispatch(now, map, scheduler);
+        Ok(())
+    }
+
+    /// Match waiting requests to idle vehicles.
+    fn dispatch(&mut self, now: Time, map: &Map, scheduler: &mut Scheduler) {
+        let strategy = self.fleet.as_ref().unwrap().dispatch;
+        let mut idle: Vec<(CarID, Position, Time)> = self
+            .vehicles
+            .values()
+            .filter_map(|v| match v.state {
+                FleetState::Idle(pos, since) => Some((v.vehicle.id, pos, since)),
+                _ => None,
+            })
+            .collect();
+
+        let mut still_waiting = VecDeque::new();
+        for req in self.waiting.drain(..) {
+            let mut candidates = idle.clone();
+            let mut assigned = false;
+            while let Some(id) = strategy.pick(req.pickup_pos, &candidates, map) {
+                let start = candidates.iter().find(|(c, _, _)| *c == id).unwrap().1;
+                candidates.retain(|(c, _, _)| *c != id);
+                if let Some((path_req, path)) = path_between(start, req.pickup_pos, map) {
+                    let v = self.vehicles.get_mut(&id).unwrap();
+                    scheduler.push(
+                        now,
+                        Command::SpawnCar(
+                            CreateCar {
+                                vehicle: v.vehicle.clone(),
+                                router: Router::ride_hail(id, path, req.pickup_pos.dist_along()),
+                                req: path_req,
+                                start_dist: start.dist_along(),
+                                maybe_parked_car: None,
+                                trip_and_person: None,
+                                maybe_route: None,
+                            },
+                            true,
+                        ),
+                    );
+                    v.state = FleetState::ToPickup(req.clone());
+                    idle.retain(|(c, _, _)| *c != id);
+                    assigned = true;
+                    break;
+                }
+            }
+            if !assigned {
+                still_waiting.push_back(req);
+            }
+        }
+        self.waiting = still_waiting;
+    }
+
+    /// A vehicle reached the end of its path. If it's stopping to pick up or drop off somebody,
+    /// returns how long that'll take. Otherwise, the vehicle becomes idle and vanishes.
+    pub fn vehicle_arrived(
+        &mut self,
+        now: Time,
+        id: CarID,
+        blocked_time: Duration,
+        distance_crossed: Distance,
+        trips: &mut TripManager,
+        ctx: &mut Ctx,
+    ) -> Option<Duration> {
+        let v = self.vehicles.get_mut(&id).unwrap();
+        match v.state.clone() {
+            FleetState::ToPickup(req) => {
+                self.events
+                    .push(Event::RideHailLegFinished(id, distance_crossed, None));
+                self.events.push(Event::RideHailPickup(
+                    req.person,
+                    id,
+                    now - req.requested_at,
+                ));
+                if let Some((path_req, path)) =
+                    path_between(req.pickup_pos, req.dropoff_pos, ctx.map)
+                {
+                    trips.ride_hail_pickup(req.trip, id, req.pickup, path_req);
+                    v.state = FleetState::Boarding(req, path);
+                    Some(PICKUP_TIME)
+                } else {
+                    trips.cancel_trip(
+                        now,
+                        req.trip,
+                        format!("no path to drive from {} to {}", req.pickup, req.dropoff),
+                        None,
+                        ctx,
+                    );
+                    v.state = FleetState::Alighting(req.pickup_pos);
+                    Some(Duration::ZERO)
+                }
+            }
+            FleetState::Carrying(req) => {
+                self.events.push(Event::RideHailLegFinished(
+                    id,
+                    distance_crossed,
+                    Some(req.person),
+                ));
+                trips.ride_hail_dropoff(now, id, blocked_time, distance_crossed, ctx);
+                v.state = FleetState::Alighting(req.dropoff_pos);
+                Some(DROPOFF_TIME)
+            }
+            FleetState::Repositioning(pos) => {
+                self.events
+                    .push(Event::RideHailLegFinished(id, distance_crossed, None));
+                v.state = FleetState::Idle(pos, now);
+                // Maybe somebody's been waiting for this vehicle to free up. The vehicle will
+                // vanish before it appears again.
+                self.dispatch(now, ctx.map, ctx.scheduler);
+                None
+            }
+            FleetState::Idle(_, _) | FleetState::Boarding(_, _) | FleetState::Alighting(_) => {
+                unreachable!()
+            }
+        }
+    }
+
+    /// A vehicle finished picking up or dropping off somebody. Returns where it goes next, and the
+    /// passenger, if there is one.
+    pub fn vehicle_departed(
+        &mut self,
+        id: CarID,
+        map: &Map,
+    ) -> (Router, Option<(TripID, PersonID)>) {
+        let v = self.vehicles.get_mut(&id).unwrap();
+        match v.state.clone() {
+            FleetState::Boarding(req, path) => {
+                let router = Router::ride_hail(id, path, req.dropoff_pos.dist_along());
+                let passenger = Some((req.trip, req.person));
+                v.state = FleetState::Carrying(req);
+                (router, passenger)
+            }
+            FleetState::Alighting(pos) => {
+                // Head to whoever's been waiting the longest, if possible
+                for idx in 0..self.waiting.len() {
+                    if let Some((_, path)) = path_between(pos, self.waiting[idx].pickup_pos, map) {
+                        let req = self.waiting.remove(idx).unwrap();
+                        let router = Router::ride_hail(id, path, req.pickup_pos.dist_along());
+                        v.state = FleetState::ToPickup(req);
+                        return (router, None);
+                    }
+                }
+
+                if self.fleet.as_ref().unwrap().after_dropoff == AfterDropoff::ReturnToDepot {
+                    if let Some((_, path)) = path_between(pos, v.depot, map) {
+                        v.state = FleetState::Repositioning(idle_pos(v.depot, &v.vehicle, map));
+                        return (Router::ride_hail(id, path, v.depot.dist_along()), None);
+                    }
+                }
+
+                let lane = map.get_l(pos.lane());
+                v.state = FleetState::Repositioning(idle_pos(pos, &v.vehicle, map));
+                (
+                    Router::ride_hail(id, Path::one_step(lane.id, map), lane.length()),
+                    None,
+                )
+            }
+            FleetState::Idle(_, _)
+            | FleetState::ToPickup(_)
+            | FleetState::Carrying(_)
+            | FleetState::Repositioning(_) => unreachable!(),
+        }
+    }
+
+    pub fn collect_events(&mut self) -> Vec<Event> {
+        std::mem::replace(&mut self.events, Vec::new())
+    }
+}
+
+// Queries
+impl RideHailSimState {
+    pub fn get_fleet(&self) -> Option<&RideHailFleet> {
+        self.fleet.as_ref()
+    }
+
+    pub fn is_fleet_vehicle(&self, id: CarID) -> bool {
+        self.vehicles.contains_key(&id)
+    }
+
+    /// How many vehicles are on the map, and how many of those are driving without a passenger?
+    pub fn active_vehicles(&self) -> (usize, usize) {
+        let mut active = 0;
+        let mut empty = 0;
+        for v in self.vehicles.values() {
+            match v.state {
+                FleetState::Idle(_, _) => {}
+                FleetState::Boarding(_, _) | FleetState::Carrying(_) => {
+                    active += 1;
+                }
+                FleetState::ToPickup(_)
+                | FleetState::Alighting(_)
+                | FleetState::Repositioning(_) => {
+                    active += 1;
+                    empty += 1;
+                }
+            }
+        }
+        (active, empty)
+    }
+}
+
+/// Idle vehicles reappear at the beginning of the lane where they're waiting, with room for their
+/// length.
+fn idle_pos(pos: Position, vehicle: &Vehicle, map: &Map) -> Position {
+    Position::new(
+        pos.lane(),
+        vehicle.length.min(map.get_l(pos.lane()).length()),
+    )
+}
+
+/// If the end is behind the start on the same lane, the vehicle loops around the block.
+fn path_between(start: Position, end: Position, map: &Map) -> Option<(PathRequest, Path)> {
+    let req = PathRequest {
+        start,
+        end,
+        constraints: PathConstraints::Car,
+    };
+    if start.lane() != end.lane() || start.dist_along() < end.dist_along() {
+        let path = map.pathfind(req.clone())?;
+        return Some((req, path));
+    }
+
+    // A path staying on one lane can't go backwards, so leave the lane and loop around the block
+    let mut best: Option<Path> = None;
+    for turn in map.get_turns_from_lane(start.lane()) {
+        let next = Position::start(turn.id.dst);
+        if !PathConstraints::Car.can_use(map.get_l(next.lane()), map) {
+            continue;
+        }
+        let mut path = match map.pathfind(PathRequest {
+            start,
+            end: next,
+            constraints: PathConstraints::Car,
+        }) {
+            Some(path) => path,
+            None => continue,
+        };
+        let rest = match map.pathfind(PathRequest {
+            start: next,
+            end,
+            constraints: PathConstraints::Car,
+        }) {
+            Some(path) => path,
+            None => continue,
+        };
+        path.append(rest, map);
+        if best
+            .as_ref()
+            .map(|b| path.total_length() < b.total_length())
+            .unwrap_or(true)
+        {
+            best = Some(path);
+        }
+    }
+    Some((req, best?))
+}
+
+#[cfg(test)]
+mod tests {
+    use abstutil::Timer;
+    use map_model::PathStep;
+
+    use super::*;
+    use crate::{VehicleType, MIN_CAR_LENGTH};
+
+    fn city_block() -> Map {
+        let mut timer = Timer::throwaway();
+        let raw = convert_osm::convert(
+            convert_osm::Options {
+                name: abstutil::MapName::new("oneshot", "city_block"),
+                osm_input: abstutil::path("../tests/input/city_block.osm"),
+                clip: None,
+                map_config: map_model::MapConfig {
+                    driving_side: map_model::DrivingSide::Right,
+                    bikes_can_use_bus_lanes: true,
+                    inferred_sidewalks: true,
+                },
+                onstreet_parking: convert_osm::OnstreetParking::JustOSM,
+                public_offstreet_parking: convert_osm::PublicOffstreetParking::None,
+                private_offstreet_parking: convert_osm::PrivateOffstreetParking::FixedPerBldg(0),
+                elevation: None,
+                include_railroads: true,
+            },
+            &mut timer,
+        );
+        Map::create_from_raw(raw, true, true, &mut timer)
+    }
+
+    #[test]
+    fn only_vehicle_is_past_the_pickup() {
+        let map = city_block();
+        let lane = map.all_lanes().iter().find(|l| l.is_driving()).unwrap();
+        let pickup = Position::new(lane.id, lane.length() * 0.25);
+        let start = Position::new(lane.id, lane.length() * 0.75);
+
+        let mut state = RideHailSimState::new();
+        state.set_fleet(RideHailFleet {
+            num_vehicles: 1,
+            depots: Vec::new(),
+            dispatch: DispatchStrategy::Nearest,
+            after_dropoff: AfterDropoff::WaitNearby,
+        });
+        let id = CarID(0, VehicleType::Car);
+        let (max_accel, max_decel) = VehicleType::Car.default_acceleration();
+        state.vehicles.insert(
+            id,
+            FleetVehicle {
+                vehicle: Vehicle {
+                    id,
+                    owner: None,
+                    vehicle_type: VehicleType::Car,
+                    length: MIN_CAR_LENGTH,
+                    max_speed: None,
+                    max_accel,
+                    max_decel,
+                },
+                depot: start,
+                state: FleetState::Idle(start, Time::START_OF_DAY),
+            },
+        );
+
+        let req = RideRequest {
+            trip: TripID(0),
+            person: PersonID(0),
+            requested_at: Time::START_OF_DAY,
+            pickup: BuildingID(0),
+            dropoff: BuildingID(1),
+            pickup_pos: pickup,
+            dropoff_pos: start,
+        };
+        let mut scheduler = Scheduler::new();
+        state
+            .request_ride(Time::START_OF_DAY, req, &map, &mut scheduler)
+            .unwrap();
+        assert!(state.waiting.is_empty());
+        assert!(matches!(state.vehicles[&id].state, FleetState::ToPickup(_)));
+
+        // The vehicle has to drive around the block to come back to the pickup
+        let (_, path) = path_between(start, pickup, &map).unwrap();
+        assert_eq!(path.current_step(), PathStep::Lane(lane.id));
+        assert_eq!(path.last_step(), PathStep::Lane(lane.id));
+        assert!(path.total_lanes() > 2);
+    }
+}
@@ -1,14 +1,14 @@
 //! For vehicles only, not pedestrians. Follows a Path from map_model, but can opportunistically
 //! lane-change to avoid a slow lane, can can handle re-planning to look for available parking.
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use serde::{Deserialize, Serialize};
 
-use geom::Distance;
+use geom::{Distance, Time};
 use map_model::{
-    BuildingID, IntersectionID, LaneID, Map, Path, PathConstraints, PathRequest, PathStep,
-    Position, Traversable, TurnID,
+    BuildingID, IntersectionID, LaneID, Map, ParkingLotID, Path, PathRequest, PathStep, Position,
+    Traversable, TurnID,
 };
 
 use crate::mechanics::Queue;
@@ -33,6 +33,7 @@ pub(crate) enum ActionAtEnd {
     StopBiking(SidewalkSpot),
     BusAtStop,
     GiveUpOnParking,
+    RideHailAtStop,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -40,6 +41,8 @@ enum Goal {
     /// Spot and cached distance along the last driving lane
     ParkNearBuilding {
         target: BuildingID,
+        /// Prefer any free spot in this lot
+        lot: Option<ParkingLotID>,
         spot: Option<(ParkingSpot, Distance)>,
         /// No parking available at all!
         stuck_end_dist: Option<Distance>,
@@ -55,6 +58,10 @@ enum Goal {
     FollowBusRoute {
         end_dist: Distance,
     },
+    /// Picking up or dropping off somebody, or repositioning an empty vehicle
+    RideHail {
+        end_dist: Distance,
+    },
 }
 
 impl Router {
@@ -87,6 +94,23 @@ impl Router {
             path,
             goal: Goal::ParkNearBuilding {
                 target: bldg,
+                lot: None,
+                spot: None,
+                stuck_end_dist: None,
+                started_looking: false,
+            },
+            owner,
+        }
+    }
+
+    /// The path should end at the lot's driving position. If the lot is full, this parks as close
+    /// as possible instead.
+    pub fn park_in_lot(owner: CarID, path: Path, lot: ParkingLotID, bldg: BuildingID) -> Router {
+        Router {
+            path,
+            goal: Goal::ParkNearBuilding {
+                target: bldg,
+                lot: Some(lot),
                 spot: None,
                 stuck_end_dist: None,
                 started_looking: false,
@@ -111,6 +135,14 @@ impl Router {
         }
     }
 
+    pub fn ride_hail(owner: CarID, path: Path, end_dist: Distance) -> Router {
+        Router {
+            path,
+            goal: Goal::RideHail { end_dist },
+            owner,
+        }
+    }
+
     pub fn head(&self) -> Traversable {
         self.path.current_step().as_traversable()
     }
@@ -139,6 +171,7 @@ impl Router {
             } => stuck_end_dist.unwrap_or_else(|| spot.unwrap().1),
             Goal::BikeThenStop { ref goal } => goal.sidewalk_pos.dist_along(),
             Goal::FollowBusRoute { end_dist } => end_dist,
+            Goal::RideHail { end_dist } => end_dist,
         }
     }
 
@@ -151,6 +184,7 @@ impl Router {
         &mut self,
         vehicle: &Vehicle,
         parking: &ParkingSimState,
+        now: Time,
         map: &Map,
         trip_and_person: Option<(TripID, PersonID)>,
         events: &mut Vec<Event>,
@@ -162,6 +196,7 @@ impl Router {
                 Distance::ZERO,
                 vehicle,
                 parking,
+                now,
                 map,
                 trip_and_person,
                 events,
@@ -171,7 +206,11 @@ impl Router {
         // Sanity check laws haven't been broken
         if let Traversable::Lane(l) = self.head() {
             let lane = map.get_l(l);
-            if !vehicle.vehicle_type.to_constraints().can_use(lane, map) {
+            if !vehicle
+                .vehicle_type
+                .to_constraints()
+                .can_ever_use(lane, map)
+            {
                 panic!(
                     "{} just wound up on {}, a {:?} (check the OSM tags)",
                     vehicle.id, l, lane.lane_type
@@ -189,6 +228,7 @@ impl Router {
         front: Distance,
         vehicle: &Vehicle,
         parking: &ParkingSimState,
+        now: Time,
         map: &Map,
         // TODO Not so nice to plumb all of this here
         trip_and_person: Option<(TripID, PersonID)>,
@@ -206,6 +246,7 @@ impl Router {
                 ref mut spot,
                 ref mut stuck_end_dist,
                 target,
+                lot,
                 ref mut started_looking,
             } => {
                 if let Some(d) = stuck_end_dist {
@@ -227,8 +268,16 @@ impl Router {
                         Position::new(current_lane, front),
                         vehicle,
                         target,
+                        now,
                         map,
                     );
+                    let in_lot = candidates
+                        .iter()
+                        .find(|(spot, _)| match spot {
+                            ParkingSpot::Lot(pl, _) => Some(*pl) == lot,
+                            _ => false,
+                        })
+                        .cloned();
                     let best =
                         if let Some((driving_pos, _)) = map.get_b(target).driving_connection(map) {
                             if driving_pos.lane() == current_lane {
@@ -249,6 +298,8 @@ impl Router {
                                 .into_iter()
                                 .min_by_key(|(_, pos)| pos.dist_along())
                         };
+                    // Park-and-ride trips head for a particular lot
+                    let best = in_lot.or(best);
                     if let Some((new_spot, new_pos)) = best {
                         if let Some((t, p)) = trip_and_person {
                             events.push(Event::TripPhaseStarting(
@@ -257,7 +308,7 @@ impl Router {
                                 Some(PathRequest {
                                     start: Position::new(current_lane, front),
                                     end: new_pos,
-                                    constraints: PathConstraints::Car,
+                                    constraints: vehicle.vehicle_type.to_constraints(),
                                 }),
                                 TripPhaseType::Parking,
                             ));
@@ -266,8 +317,8 @@ impl Router {
                         assert!(new_pos.dist_along() >= front);
                         *spot = Some((new_spot, new_pos.dist_along()));
                     } else {
-                        if let Some((new_path_steps, new_spot, new_pos)) =
-                            parking.path_to_free_parking_spot(current_lane, vehicle, target, map)
+                        if let Some((new_path_steps, new_spot, new_pos)) = parking
+                            .path_to_free_parking_spot(current_lane, vehicle, target, now, map)
                         {
                             assert!(!new_path_steps.is_empty());
                             for step in new_path_steps {
@@ -283,7 +334,7 @@ impl Router {
                                     Some(PathRequest {
                                         start: Position::new(current_lane, front),
                                         end: new_pos,
-                                        constraints: PathConstraints::Car,
+                                        constraints: vehicle.vehicle_type.to_constraints(),
                                     }),
                                     TripPhaseType::Parking,
                                 ));
@@ -325,6 +376,13 @@ impl Router {
                     None
                 }
             }
+            Goal::RideHail { end_dist } => {
+                if end_dist == front {
+                    Some(ActionAtEnd::RideHailAtStop)
+                } else {
+                    None
+                }
+            }
         }
     }
 
@@ -362,7 +420,12 @@ impl Router {
         let (_, turn1, best_lane, turn2) = parent
             .lanes_ltr()
             .into_iter()
-            .filter(|(l, d, _)| dir == *d && constraints.can_use(map.get_l(*l), map))
+            // Only switch onto lanes usable at any time of day
+            .filter(|(l, d, _)| {
+                dir == *d
+                    && (*l == orig_target_lane
+                        || constraints.can_use_during(map.get_l(*l), None, map))
+            })
             .filter_map(|(l, _, _)| {
                 let t1 = TurnID {
                     parent: current_turn.parent,
@@ -402,7 +465,7 @@ impl Router {
                 // A linear combination of these penalties is hard to reason about. Instead, we
                 // make our choice based on each penalty in order, breaking ties by moving onto the
                 // next thing.
-                if self.owner.1 == VehicleType::Bike {
+                if self.owner.1.is_bike_like() {
                     bike = 0;
                 } else {
                     slow_lane = 0;
@@ -424,6 +487,42 @@ impl Router {
         self.path.modify_step(3, PathStep::Turn(turn2.id), map);
     }
 
+    /// Try to find a different path from the current lane to the same final lane, avoiding some
+    /// lanes, using the vehicle's own constraints. Only cars, trucks, and motorcycles can do this;
+    /// transit has to stick to its route. Doesn't work after a car has started looking for parking.
+    /// Returns true if the path changed.
+    pub fn reroute(&mut self, avoid: BTreeSet<LaneID>, map: &Map) -> bool {
+        match self.owner.1 {
+            VehicleType::Car | VehicleType::Truck | VehicleType::Motorcycle => {}
+            _ => {
+                return false;
+            }
+        }
+        if self.is_parking() {
+            return false;
+        }
+        let current = match self.path.current_step() {
+            PathStep::Lane(l) => l,
+            _ => {
+                return false;
+            }
+        };
+        let last = self.path.last_step().as_lane();
+        if current == last || avoid.contains(&current) || avoid.contains(&last) {
+            return false;
+        }
+        let req = PathRequest {
+            start: Position::start(current),
+            end: Position::end(last, map),
+            constraints: self.owner.1.to_constraints(),
+        };
+        if let Some(path) = map.pathfind_avoiding_lanes(req, avoid) {
+            self.path.reroute(path, map).is_ok()
+        } else {
+            false
+        }
+    }
+
     pub fn is_parking(&self) -> bool {
         match self.goal {
             Goal::ParkNearBuilding {
@@ -9,7 +9,8 @@ use geom::{Duration, Histogram, Time};
 use map_model::{BusRouteID, IntersectionID};
 
 use crate::{
-    pandemic, AgentID, CarID, CreateCar, CreatePedestrian, PedestrianID, TripID, TripSpec,
+    pandemic, AgentID, CarID, CreateCar, CreatePedestrian, PedestrianID, RideRequest, TripID,
+    TripSpec,
 };
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -27,6 +28,8 @@ pub(crate) enum Command {
     Pandemic(pandemic::Cmd),
     /// The Time is redundant, just used to dedupe commands
     StartBus(BusRouteID, Time),
+    RequestRide(RideRequest),
+    RebalanceBikeShare,
 }
 
 impl Command {
@@ -50,6 +53,8 @@ impl Command {
             Command::Callback(_) => CommandType::Callback,
             Command::Pandemic(ref p) => CommandType::Pandemic(p.clone()),
             Command::StartBus(r, t) => CommandType::StartBus(*r, *t),
+            Command::RequestRide(ref req) => CommandType::RequestRide(req.trip),
+            Command::RebalanceBikeShare => CommandType::RebalanceBikeShare,
         }
     }
 
@@ -65,6 +70,8 @@ impl Command {
             Command::Callback(_) => SimpleCommandType::Callback,
             Command::Pandemic(_) => SimpleCommandType::Pandemic,
             Command::StartBus(_, _) => SimpleCommandType::StartBus,
+            Command::RequestRide(_) => SimpleCommandType::RequestRide,
+            Command::RebalanceBikeShare => SimpleCommandType::RebalanceBikeShare,
         }
     }
 }
@@ -81,6 +88,8 @@ enum CommandType {
     Callback,
     Pandemic(pandemic::Cmd),
     StartBus(BusRouteID, Time),
+    RequestRide(TripID),
+    RebalanceBikeShare,
 }
 
 /// A more compressed form of CommandType, just used for keeping stats on event processing.
@@ -94,6 +103,8 @@ enum SimpleCommandType {
     Callback,
     Pandemic,
     StartBus,
+    RequestRide,
+    RebalanceBikeShare,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone)]
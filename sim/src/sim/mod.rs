@@ -17,11 +17,12 @@ use map_model::{
 
 pub use self::queries::{AgentProperties, DelayCause};
 use crate::{
-    AgentID, AlertLocation, Analytics, CapSimState, CarID, Command, CreateCar, DrivingSimState,
-    Event, IntersectionSimState, OrigPersonID, PandemicModel, ParkedCar, ParkingSim,
-    ParkingSimState, ParkingSpot, Person, PersonID, Router, Scheduler, SidewalkPOI, SidewalkSpot,
-    TrafficRecorder, TransitSimState, TripID, TripInfo, TripLeg, TripManager, TripPhaseType,
-    TripSpec, Vehicle, VehicleSpec, VehicleType, WalkingSimState, BUS_LENGTH, LIGHT_RAIL_LENGTH,
+    AgentID, AlertLocation, Analytics, BikeShareDock, BikeShareSystem, CapSimState, CarID, Command,
+    CreateCar, DrivingSimState, Event, IntersectionSimState, OrigPersonID, PandemicModel,
+    ParkedCar, ParkingSim, ParkingSimState, ParkingSpot, Person, PersonID, RideHailFleet,
+    RideHailSimState, Router, Scheduler, SidewalkPOI, SidewalkSpot, TrafficRecorder,
+    TransitSimState, TripID, TripInfo, TripLeg, TripManager, TripPaths, TripPhaseType, TripSpec,
+    Vehicle, VehicleSpec, VehicleType, WalkingSimState, BUS_LENGTH, LIGHT_RAIL_LENGTH,
     MIN_CAR_LENGTH, SPAWN_DIST,
 };
 
@@ -38,6 +39,7 @@ pub struct Sim {
     walking: WalkingSimState,
     intersections: IntersectionSimState,
     transit: TransitSimState,
+    ridehail: RideHailSimState,
     cap: CapSimState,
     trips: TripManager,
     #[serde(skip_serializing, skip_deserializing)]
@@ -60,6 +62,11 @@ pub struct Sim {
 
     #[serde(skip_serializing, skip_deserializing)]
     alerts: AlertHandler,
+
+    /// If present, every dispatched Event is also buffered here, until somebody outside the sim
+    /// drains them.
+    #[serde(skip_serializing, skip_deserializing)]
+    captured_events: Option<Vec<(Time, Event)>>,
 }
 
 pub(crate) struct Ctx<'a> {
@@ -108,9 +115,23 @@ pub struct SimOptions {
     /// If present, cancel any driving trips who will pass through a road currently experiencing
     /// delays beyond this threshold.
     pub cancel_drivers_delay_threshold: Option<Duration>,
+    /// If present, drivers reroute en route when cars ahead of them have been delayed beyond this
+    /// threshold, or when a live map edit affects the rest of their path.
+    pub reroute_drivers_delay_threshold: Option<Duration>,
     /// Don't collect any analytics. Only useful for benchmarking and debugging gridlock more
     /// quickly.
     pub skip_analytics: bool,
+    /// Instead of moving at a constant speed along each lane and turn, vehicles accelerate and
+    /// brake, limited by the `max_accel` and `max_decel` of their `VehicleSpec`. Travel times near
+    /// intersections are more realistic, but the simulation is slower.
+    pub kinematic_driving: bool,
+    /// If present, a file produced by the `traffic_assignment` tool. Driving trips follow the
+    /// paths recorded there, instead of pathfinding when they start.
+    pub trip_paths: Option<String>,
+    /// If present, a file with the `Analytics` saved from a previous run, like the prebaked
+    /// results. Cars, trucks, and motorcycles pathfind using the lane travel times observed there,
+    /// instead of free-flow speeds. This changes the map, so see `apply_travel_times`.
+    pub travel_times: Option<String>,
 }
 
 impl std::default::Default for SimOptions {
@@ -148,7 +169,21 @@ impl SimOptions {
             disable_turn_conflicts: args.enabled("--disable_turn_conflicts"),
             cancel_drivers_delay_threshold: args
                 .optional_parse("--cancel_drivers_delay_threshold", Duration::parse),
+            reroute_drivers_delay_threshold: args
+                .optional_parse("--reroute_drivers_delay_threshold", Duration::parse),
             skip_analytics: args.enabled("--skip_analytics"),
+            kinematic_driving: args.enabled("--kinematic_driving"),
+            trip_paths: args.optional("--trip_paths"),
+            travel_times: args.optional("--travel_times"),
+        }
+    }
+
+    /// If `travel_times` is set, price driving on the map by the travel times from that run. Call
+    /// this before creating the simulation.
+    pub fn apply_travel_times(&self, map: &mut Map, timer: &mut Timer) {
+        if let Some(ref path) = self.travel_times {
+            let prev: Analytics = abstutil::read_binary(path.clone(), timer);
+            map.set_travel_times(Some(prev.lane_travel_times), timer);
         }
     }
 }
@@ -183,7 +218,11 @@ impl SimOptions {
             infinite_parking: false,
             disable_turn_conflicts: false,
             cancel_drivers_delay_threshold: None,
+            reroute_drivers_delay_threshold: None,
             skip_analytics: false,
+            kinematic_driving: false,
+            trip_paths: None,
+            travel_times: None,
         }
     }
 }
@@ -192,14 +231,19 @@ impl SimOptions {
 impl Sim {
     pub fn new(map: &Map, opts: SimOptions, timer: &mut Timer) -> Sim {
         let mut scheduler = Scheduler::new();
+        let trip_paths = opts
+            .trip_paths
+            .as_ref()
+            .map(|path| abstutil::read_binary(path.clone(), timer));
         Sim {
             driving: DrivingSimState::new(map, &opts),
             parking: ParkingSimState::new(map, opts.infinite_parking, timer),
             walking: WalkingSimState::new(),
             intersections: IntersectionSimState::new(map, &mut scheduler, &opts),
             transit: TransitSimState::new(map),
+            ridehail: RideHailSimState::new(),
             cap: CapSimState::new(map, &opts),
-            trips: TripManager::new(),
+            trips: TripManager::new(trip_paths),
             pandemic: if let Some(rng) = opts.enable_pandemic_model {
                 Some(PandemicModel::new(rng))
             } else {
@@ -216,6 +260,7 @@ impl Sim {
 
             analytics: Analytics::new(!opts.skip_analytics),
             recorder: None,
+            captured_events: None,
         }
     }
 
@@ -272,12 +317,15 @@ impl Sim {
         map: &Map,
         b: BuildingID,
     ) -> Option<(Path, Distance)> {
+        let (max_accel, max_decel) = VehicleType::Car.default_acceleration();
         let vehicle = Vehicle {
             id: CarID(0, VehicleType::Car),
             owner: None,
             vehicle_type: VehicleType::Car,
             length: MIN_CAR_LENGTH,
             max_speed: None,
+            max_accel,
+            max_decel,
         };
         let driving_lane = map.find_driving_lane_near_building(b);
 
@@ -286,14 +334,18 @@ impl Sim {
         // TODO Refactor the logic in router
         let spot = if let Some((spot, _)) = self
             .parking
-            .get_all_free_spots(Position::start(driving_lane), &vehicle, b, map)
+            .get_all_free_spots(Position::start(driving_lane), &vehicle, b, self.time, map)
             .get(0)
         {
             spot.clone()
         } else {
-            let (_, spot, _) =
-                self.parking
-                    .path_to_free_parking_spot(driving_lane, &vehicle, b, map)?;
+            let (_, spot, _) = self.parking.path_to_free_parking_spot(
+                driving_lane,
+                &vehicle,
+                b,
+                self.time,
+                map,
+            )?;
             spot
         };
 
@@ -324,6 +376,37 @@ impl Sim {
         });
     }
 
+    /// Returns the number of vehicles that couldn't be placed, because their depot isn't connected
+    /// to a road.
+    pub(crate) fn seed_ride_hail_fleet(
+        &mut self,
+        fleet: RideHailFleet,
+        vehicles: Vec<(VehicleSpec, BuildingID)>,
+        map: &Map,
+    ) -> usize {
+        self.ridehail.set_fleet(fleet);
+        let mut failed = 0;
+        for (spec, depot) in vehicles {
+            let vehicle = spec.make(CarID(self.trips.new_car_id(), VehicleType::Car), None);
+            if !self.ridehail.add_vehicle(self.time, vehicle, depot, map) {
+                failed += 1;
+            }
+        }
+        failed
+    }
+
+    pub(crate) fn seed_bike_share(
+        &mut self,
+        system: BikeShareSystem,
+        vehicles: Vec<(VehicleSpec, BikeShareDock)>,
+    ) {
+        if let Some(ref r) = system.rebalancing {
+            self.scheduler
+                .push(self.time + r.every, Command::RebalanceBikeShare);
+        }
+        self.trips.seed_bike_share(system, vehicles);
+    }
+
     pub(crate) fn seed_bus_route(&mut self, route: &BusRoute) {
         for t in &route.spawn_times {
             self.scheduler.push(*t, Command::StartBus(route.id, *t));
@@ -341,10 +424,13 @@ impl Sim {
             PathConstraints::Train => (VehicleType::Train, LIGHT_RAIL_LENGTH),
             _ => unreachable!(),
         };
+        let (max_accel, max_decel) = vehicle_type.default_acceleration();
         let vehicle = VehicleSpec {
             vehicle_type,
             length,
             max_speed: None,
+            max_accel,
+            max_decel,
         }
         .make(CarID(self.trips.new_car_id(), vehicle_type), None);
         let start_lane = map.get_l(path.current_step().as_lane());
@@ -379,6 +465,12 @@ impl Sim {
     pub fn set_name(&mut self, name: String) {
         self.run_name = name;
     }
+
+    /// Driving trips will follow these paths, if they match the trip's request. Otherwise they'll
+    /// pathfind normally and record the result here.
+    pub fn set_trip_paths(&mut self, trip_paths: TripPaths) {
+        self.trips.set_trip_paths(trip_paths);
+    }
 }
 
 // Running
@@ -456,7 +548,7 @@ impl Sim {
                 for step in create_car.router.get_path().get_steps() {
                     match step.as_traversable() {
                         Traversable::Lane(l) => {
-                            if !constraints.can_use(ctx.map.get_l(l), ctx.map) {
+                            if !constraints.can_ever_use(ctx.map.get_l(l), ctx.map) {
                                 ok = false;
                                 break;
                             }
@@ -521,10 +613,10 @@ impl Sim {
                                 trip,
                                 person,
                                 Some(req),
-                                if id.1 == VehicleType::Car {
-                                    TripPhaseType::Driving
-                                } else {
+                                if id.1.is_bike_like() {
                                     TripPhaseType::Biking
+                                } else {
+                                    TripPhaseType::Driving
                                 },
                             ));
                         }
@@ -539,7 +631,7 @@ impl Sim {
                             self.parking.remove_parked_car(parked_car);
                         }
                         if let Some(route) = maybe_route {
-                            self.transit.bus_created(id, route);
+                            self.transit.bus_created(id, map.get_br(route));
                         }
                         self.analytics
                             .record_demand(self.driving.get_path(id).unwrap(), map);
@@ -591,6 +683,7 @@ impl Sim {
                     &mut ctx,
                     &mut self.trips,
                     &mut self.transit,
+                    &mut self.ridehail,
                     &mut self.walking,
                 );
             }
@@ -607,8 +700,13 @@ impl Sim {
                 );
             }
             Command::UpdateIntersection(i) => {
-                self.intersections
-                    .update_intersection(self.time, i, map, &mut self.scheduler);
+                self.intersections.update_intersection(
+                    self.time,
+                    i,
+                    map,
+                    &self.driving,
+                    &mut self.scheduler,
+                );
             }
             Command::Callback(frequency) => {
                 self.scheduler
@@ -626,6 +724,28 @@ impl Sim {
             Command::StartBus(r, _) => {
                 self.start_bus(map.get_br(r), map);
             }
+            Command::RequestRide(req) => {
+                let trip = req.trip;
+                if let Err(err) = self
+                    .ridehail
+                    .request_ride(self.time, req, map, ctx.scheduler)
+                {
+                    self.trips.cancel_trip(self.time, trip, err, None, &mut ctx);
+                }
+            }
+            Command::RebalanceBikeShare => {
+                self.trips.bike_share_mut().rebalance(map);
+                if let Some(every) = self
+                    .trips
+                    .bike_share()
+                    .get_system()
+                    .and_then(|s| s.rebalancing.as_ref())
+                    .map(|r| r.every)
+                {
+                    ctx.scheduler
+                        .push(self.time + every, Command::RebalanceBikeShare);
+                }
+            }
         }
 
         // Record events at precisely the time they occur.
@@ -637,6 +757,7 @@ impl Sim {
     fn dispatch_events(&mut self, mut events: Vec<Event>, map: &Map) {
         events.extend(self.trips.collect_events());
         events.extend(self.transit.collect_events());
+        events.extend(self.ridehail.collect_events());
         events.extend(self.driving.collect_events());
         events.extend(self.walking.collect_events());
         events.extend(self.intersections.collect_events());
@@ -648,6 +769,9 @@ impl Sim {
             if let Some(ref mut r) = self.recorder {
                 r.handle_event(self.time, &ev, map, &self.driving, &self.trips);
             }
+            if let Some(ref mut list) = self.captured_events {
+                list.push((self.time, ev.clone()));
+            }
 
             self.analytics.event(ev, self.time, map);
         }
@@ -792,6 +916,10 @@ impl Sim {
                 "- transit: {} bytes",
                 prettyprint_usize(serialized_size_bytes(&self.transit))
             );
+            println!(
+                "- ridehail: {} bytes",
+                prettyprint_usize(serialized_size_bytes(&self.ridehail))
+            );
             println!(
                 "- cap: {} bytes",
                 prettyprint_usize(serialized_size_bytes(&self.cap))
@@ -835,13 +963,48 @@ impl Sim {
             .handle_live_edited_traffic_signals(self.time, map, &mut self.scheduler)
     }
 
-    /// Respond to arbitrary map edits without resetting the simulation. Returns the number of
-    /// (trips cancelled, parked cars displaced).
-    pub fn handle_live_edits(&mut self, map: &Map) -> (usize, usize) {
+    /// Immediately switch a traffic signal to a particular stage and hold it there for some
+    /// duration, ignoring the normal timing. Afterwards, the signal advances to the next stage as
+    /// usual.
+    pub fn override_traffic_signal_stage(
+        &mut self,
+        i: IntersectionID,
+        stage: usize,
+        duration: Duration,
+        map: &Map,
+    ) {
+        self.intersections
+            .override_stage(self.time, i, stage, duration, map, &mut self.scheduler);
+    }
+
+    /// Respond to arbitrary map edits without resetting the simulation. Every moving agent removed
+    /// has its trip cancelled. Returns the number of (vehicles removed, pedestrians removed,
+    /// parked cars displaced).
+    pub fn handle_live_edits(&mut self, map: &Map) -> (usize, usize, usize) {
         self.edits_name = map.get_edits().edits_name.clone();
 
-        let (affected, num_parked_cars) = self.find_trips_affected_by_live_edits(map);
-        let num_trips_cancelled = affected.len();
+        let (mut affected, crosses_edits, num_parked_cars) =
+            self.find_trips_affected_by_live_edits(map);
+        // Some drivers might be able to avoid the edits. Only try once it's certain they won't be
+        // cancelled for another reason.
+        let (edited_lanes, _) = map.get_edits().changed_lanes(map);
+        for (agent, trip) in crosses_edits {
+            if affected.contains(&(agent, trip)) {
+                continue;
+            }
+            let rerouted = match agent {
+                AgentID::Car(car) => {
+                    self.driving
+                        .reroute_around_live_edits(car, &edited_lanes, map)
+                }
+                _ => false,
+            };
+            if !rerouted {
+                affected.insert((agent, trip));
+            }
+        }
+        let mut num_vehicles = 0;
+        let mut num_peds = 0;
 
         // V1: Just cancel every trip crossing an affected area.
         // (V2 is probably rerouting everyone, only cancelling when that fails)
@@ -867,6 +1030,7 @@ impl Sim {
                         &mut ctx,
                     );
                     self.trips.trip_abruptly_cancelled(trip, AgentID::Car(car));
+                    num_vehicles += 1;
                 }
                 AgentID::Pedestrian(ped) => {
                     self.walking.delete_ped(ped, &mut ctx);
@@ -879,6 +1043,7 @@ impl Sim {
                     );
                     self.trips
                         .trip_abruptly_cancelled(trip, AgentID::Pedestrian(ped));
+                    num_peds += 1;
                 }
                 AgentID::BusPassenger(_, _) => unreachable!(),
             }
@@ -887,15 +1052,21 @@ impl Sim {
         self.driving.handle_live_edits(map);
         self.intersections.handle_live_edits(map);
 
-        (num_trips_cancelled, num_parked_cars)
+        (num_vehicles, num_peds, num_parked_cars)
     }
 
-    /// Returns (trips affected, number of parked cars displaced)
+    /// Returns (trips affected, trips whose remaining path crosses the edits, number of parked cars
+    /// displaced). Drivers in the second group might be able to reroute.
     fn find_trips_affected_by_live_edits(
         &mut self,
         map: &Map,
-    ) -> (BTreeSet<(AgentID, TripID)>, usize) {
+    ) -> (
+        BTreeSet<(AgentID, TripID)>,
+        BTreeSet<(AgentID, TripID)>,
+        usize,
+    ) {
         let mut affected: BTreeSet<(AgentID, TripID)> = BTreeSet::new();
+        let mut crosses_edits: BTreeSet<(AgentID, TripID)> = BTreeSet::new();
 
         // TODO Handle changes to access restrictions
 
@@ -922,7 +1093,7 @@ impl Sim {
                             }
                         })
                     {
-                        affected.insert((*a, *trip));
+                        crosses_edits.insert((*a, *trip));
                     }
                 }
             }
@@ -953,7 +1124,7 @@ impl Sim {
             num_evicted
         };
 
-        (affected, num_evicted)
+        (affected, crosses_edits, num_evicted)
     }
 }
 
@@ -1008,6 +1179,31 @@ impl Sim {
     }
 }
 
+// Streaming events
+impl Sim {
+    /// Start or stop buffering every Event that the simulation publishes, so that something
+    /// outside the sim can observe exactly what Analytics sees. Call `drain_captured_events`
+    /// regularly, or the buffer will grow without bound.
+    pub fn capture_events(&mut self, enabled: bool) {
+        if enabled {
+            if self.captured_events.is_none() {
+                self.captured_events = Some(Vec::new());
+            }
+        } else {
+            self.captured_events = None;
+        }
+    }
+
+    /// Returns every Event captured since the last call, in the order they happened.
+    pub fn drain_captured_events(&mut self) -> Vec<(Time, Event)> {
+        if let Some(ref mut list) = self.captured_events {
+            std::mem::replace(list, Vec::new())
+        } else {
+            Vec::new()
+        }
+    }
+}
+
 // Recording traffic
 impl Sim {
     pub fn record_traffic_for(&mut self, intersections: BTreeSet<IntersectionID>) {
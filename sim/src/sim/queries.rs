@@ -5,15 +5,16 @@ use std::collections::{BTreeMap, HashSet};
 use abstutil::Counter;
 use geom::{Distance, Duration, PolyLine, Pt2D, Time};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, IntersectionID, Lane, LaneID, Map, Path, Position,
-    Traversable, TurnID,
+    BikeShareStationID, BuildingID, BusRouteID, BusStopID, IntersectionID, Lane, LaneID, Map, Path,
+    Position, Traversable, TurnID,
 };
 
 use crate::analytics::Window;
 use crate::{
     AgentID, AgentType, Analytics, CarID, CommutersVehiclesCounts, DrawCarInput, DrawPedCrowdInput,
     DrawPedestrianInput, OrigPersonID, PandemicModel, ParkedCar, ParkingSim, PedestrianID, Person,
-    PersonID, PersonState, Scenario, Sim, TripID, TripInfo, TripResult, UnzoomedAgent, VehicleType,
+    PersonID, PersonState, Scenario, Sim, TripID, TripInfo, TripPaths, TripResult, UnzoomedAgent,
+    VehicleType,
 };
 
 // TODO Many of these just delegate to an inner piece. This is unorganized and hard to maintain.
@@ -35,11 +36,11 @@ impl Sim {
         self.trips.num_trips()
     }
     pub fn num_agents(&self) -> Counter<AgentType> {
-        self.trips.num_agents(&self.transit)
+        self.trips.num_agents(&self.transit, &self.ridehail)
     }
     pub fn num_commuters_vehicles(&self) -> CommutersVehiclesCounts {
         self.trips
-            .num_commuters_vehicles(&self.transit, &self.walking)
+            .num_commuters_vehicles(&self.transit, &self.ridehail, &self.walking)
     }
     /// (total number of people, just in buildings, just off map)
     pub fn num_ppl(&self) -> (usize, usize, usize) {
@@ -84,6 +85,10 @@ impl Sim {
         self.transit.get_passengers(car).len()
     }
 
+    pub fn transit_capacity(&self, car: CarID) -> usize {
+        self.transit.get_capacity(car)
+    }
+
     pub fn bus_route_id(&self, maybe_bus: CarID) -> Option<BusRouteID> {
         if maybe_bus.1 == VehicleType::Bus || maybe_bus.1 == VehicleType::Train {
             Some(self.transit.bus_route(maybe_bus))
@@ -185,6 +190,9 @@ impl Sim {
             VehicleType::Bike,
             VehicleType::Bus,
             VehicleType::Train,
+            VehicleType::Truck,
+            VehicleType::Scooter,
+            VehicleType::Motorcycle,
         ] {
             let id = CarID(idx, *vt);
             if self.driving.does_car_exist(id) {
@@ -192,10 +200,16 @@ impl Sim {
             }
         }
 
-        let id = CarID(idx, VehicleType::Car);
-        // Only cars can be parked.
-        if self.parking.lookup_parked_car(id).is_some() {
-            return Some(id);
+        // Only cars, trucks, and motorcycles can be parked.
+        for vt in &[
+            VehicleType::Car,
+            VehicleType::Truck,
+            VehicleType::Motorcycle,
+        ] {
+            let id = CarID(idx, *vt);
+            if self.parking.lookup_parked_car(id).is_some() {
+                return Some(id);
+            }
         }
 
         None
@@ -296,6 +310,11 @@ impl Sim {
         &self.analytics
     }
 
+    /// Only present if `set_trip_paths` was called or `SimOptions::trip_paths` was used.
+    pub fn get_trip_paths(&self) -> Option<&TripPaths> {
+        self.trips.get_trip_paths()
+    }
+
     pub fn find_blockage_front(&self, car: CarID, map: &Map) -> String {
         self.driving
             .find_blockage_front(car, map, &self.intersections)
@@ -382,7 +401,20 @@ impl Sim {
     }
 
     pub fn generate_scenario(&self, map: &Map, name: String) -> Scenario {
-        self.trips.generate_scenario(map, name)
+        let mut scenario = self.trips.generate_scenario(map, name);
+        scenario.ride_hail = self.ridehail.get_fleet().cloned();
+        scenario.bike_share = self.trips.bike_share().get_system().cloned();
+        scenario
+    }
+
+    /// For each bike share station, how many bikes are docked there
+    pub fn bike_share_docked(&self) -> BTreeMap<BikeShareStationID, usize> {
+        self.trips.bike_share().docked_bikes()
+    }
+
+    /// Buildings with dockless vehicles in front of them, and how many
+    pub fn bike_share_dockless(&self) -> BTreeMap<BuildingID, usize> {
+        self.trips.bike_share().dockless_vehicles()
     }
 
     pub fn get_cap_counter(&self, l: LaneID) -> usize {
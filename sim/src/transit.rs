@@ -3,18 +3,30 @@ use std::collections::{BTreeMap, BTreeSet};
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_btreemap, serialize_btreemap};
-use geom::Time;
-use map_model::{BusRoute, BusRouteID, BusStopID, Map, Path, PathRequest, Position};
+use geom::{Duration, Time};
+use map_model::{BusRoute, BusRouteID, BusStopID, FarePayment, Map, Path, PathRequest, Position};
 
 use crate::sim::Ctx;
 use crate::{
-    CarID, Event, PedestrianID, PersonID, Router, TripID, TripManager, TripPhaseType, VehicleType,
-    WalkingSimState,
+    AgentID, CarID, Event, PedestrianID, PersonID, Router, TripID, TripManager, TripPhaseType,
+    VehicleType, WalkingSimState,
 };
 
 // These index stops along a route, not stops along a single sidewalk.
 type StopIdx = usize;
 
+/// Vehicles always wait at least this long at a stop, even if nobody boards or alights. This
+/// matches the fixed wait used before dwell time depended on passengers.
+const MIN_DWELL_TIME: Duration = Duration::const_seconds(10.0);
+/// Opening and closing the doors, and pulling in and out of the stop
+const DOOR_OPEN_CLOSE_TIME: Duration = Duration::const_seconds(5.0);
+/// Per passenger boarding at the front door, including paying the operator
+const FRONT_DOOR_BOARDING_TIME: Duration = Duration::const_seconds(3.5);
+/// Per passenger boarding through any door, having already paid
+const ALL_DOOR_BOARDING_TIME: Duration = Duration::const_seconds(2.0);
+/// Per passenger alighting through one door
+const ALIGHTING_TIME: Duration = Duration::const_seconds(2.0);
+
 #[derive(Serialize, Deserialize, Clone)]
 struct Stop {
     id: BusStopID,
@@ -36,6 +48,10 @@ struct Bus {
     route: BusRouteID,
     /// Where does each passenger want to deboard?
     passengers: Vec<(PersonID, Option<BusStopID>)>,
+    /// Nobody else can board once there are this many passengers.
+    capacity: usize,
+    doors: usize,
+    fare_payment: FarePayment,
     state: BusState,
 }
 
@@ -156,22 +172,37 @@ impl TransitSimState {
         self.routes[&bus_route.id].start.clone()
     }
 
-    pub fn bus_created(&mut self, bus: CarID, r: BusRouteID) {
-        let route = self.routes.get_mut(&r).unwrap();
+    pub fn bus_created(&mut self, bus: CarID, bus_route: &BusRoute) {
+        let route = self.routes.get_mut(&bus_route.id).unwrap();
         route.active_vehicles.insert(bus);
         self.buses.insert(
             bus,
             Bus {
                 car: bus,
-                route: r,
+                route: bus_route.id,
                 passengers: Vec::new(),
+                capacity: bus_route
+                    .vehicle_capacity
+                    .or_else(|| bus.1.default_capacity())
+                    .unwrap(),
+                doors: bus_route
+                    .vehicle_doors
+                    .or_else(|| bus.1.default_doors())
+                    .unwrap(),
+                fare_payment: bus_route
+                    .fare_payment
+                    .unwrap_or(if bus.1 == VehicleType::Bus {
+                        FarePayment::FrontDoor
+                    } else {
+                        FarePayment::AllDoor
+                    }),
                 state: BusState::DrivingToStop(0),
             },
         );
     }
 
-    /// If true, the bus is idling. If false, the bus actually arrived at a border and should now
-    /// vanish.
+    /// If the bus should idle at the stop, returns how long. If None, the bus actually arrived at
+    /// a border and should now vanish.
     pub fn bus_arrived_at_stop(
         &mut self,
         now: Time,
@@ -179,14 +210,14 @@ impl TransitSimState {
         trips: &mut TripManager,
         walking: &mut WalkingSimState,
         ctx: &mut Ctx,
-    ) -> bool {
+    ) -> Option<Duration> {
         let mut bus = self.buses.get_mut(&id).unwrap();
         match bus.state {
             BusState::DrivingToStop(stop_idx) => {
                 bus.state = BusState::AtStop(stop_idx);
                 let stop1 = self.routes[&bus.route].stops[stop_idx].id;
-                self.events
-                    .push(Event::BusArrivedAtStop(id, bus.route, stop1));
+                let mut boarding = 0;
+                let mut alighting = 0;
 
                 // Deboard existing passengers.
                 let mut still_riding = Vec::new();
@@ -196,18 +227,28 @@ impl TransitSimState {
                         self.events.push(Event::PassengerAlightsTransit(
                             person, bus.car, bus.route, stop1,
                         ));
+                        alighting += 1;
                     } else {
                         still_riding.push((person, maybe_stop2));
                     }
                 }
                 bus.passengers = still_riding;
 
-                // Board new passengers.
+                // Board new passengers, in the order they started waiting, until the bus is full.
                 let mut still_waiting = Vec::new();
                 for (ped, route, maybe_stop2, started_waiting) in
                     self.peds_waiting.remove(&stop1).unwrap()
                 {
-                    if bus.route == route {
+                    if bus.route == route && bus.passengers.len() >= bus.capacity {
+                        let trip = trips.agent_to_trip(AgentID::Pedestrian(ped)).unwrap();
+                        self.events.push(Event::PassengerDeniedBoarding(
+                            trips.trip_to_person(trip),
+                            bus.car,
+                            bus.route,
+                            stop1,
+                        ));
+                        still_waiting.push((ped, route, maybe_stop2, started_waiting));
+                    } else if bus.route == route {
                         let (trip, person) = trips.ped_boarded_bus(
                             now,
                             ped,
@@ -237,12 +278,17 @@ impl TransitSimState {
                             TripPhaseType::RidingBus(route, stop1, bus.car),
                         ));
                         bus.passengers.push((person, maybe_stop2));
+                        boarding += 1;
                     } else {
                         still_waiting.push((ped, route, maybe_stop2, started_waiting));
                     }
                 }
                 self.peds_waiting.insert(stop1, still_waiting);
-                true
+
+                let dwell = dwell_time(boarding, alighting, bus.doors, bus.fare_payment);
+                self.events
+                    .push(Event::BusArrivedAtStop(id, bus.route, stop1, dwell));
+                Some(dwell)
             }
             BusState::DrivingOffMap => {
                 self.routes
@@ -261,7 +307,7 @@ impl TransitSimState {
                     }
                     trips.transit_rider_reached_border(now, person, id, ctx);
                 }
-                false
+                None
             }
             BusState::AtStop(_) | BusState::Done => unreachable!(),
         }
@@ -274,8 +320,12 @@ impl TransitSimState {
             BusState::DrivingToStop(_) | BusState::DrivingOffMap | BusState::Done => unreachable!(),
             BusState::AtStop(stop_idx) => {
                 let stop = &route.stops[stop_idx];
-                self.events
-                    .push(Event::BusDepartedFromStop(id, bus.route, stop.id));
+                self.events.push(Event::BusDepartedFromStop(
+                    id,
+                    bus.route,
+                    stop.id,
+                    bus.passengers.len(),
+                ));
                 if let Some((req, path)) = stop.next_stop.clone() {
                     bus.state = BusState::DrivingToStop(stop_idx + 1);
                     Router::follow_bus_route(id, path, req.end.dist_along())
@@ -318,6 +368,12 @@ impl TransitSimState {
             for bus in &route.active_vehicles {
                 if let BusState::AtStop(idx) = self.buses[bus].state {
                     if route.stops[idx].id == stop1 {
+                        if self.buses[bus].passengers.len() >= self.buses[bus].capacity {
+                            self.events.push(Event::PassengerDeniedBoarding(
+                                person, *bus, route_id, stop1,
+                            ));
+                            continue;
+                        }
                         self.buses
                             .get_mut(bus)
                             .unwrap()
@@ -363,6 +419,10 @@ impl TransitSimState {
         &self.buses[&bus].passengers
     }
 
+    pub fn get_capacity(&self, bus: CarID) -> usize {
+        self.buses[&bus].capacity
+    }
+
     pub fn bus_route(&self, bus: CarID) -> BusRouteID {
         self.buses[&bus].route
     }
@@ -417,3 +477,87 @@ impl TransitSimState {
         &self.peds_waiting[&at]
     }
 }
+
+/// How long does a vehicle need to stay at a stop for everybody to board and alight? Passengers
+/// using the same door go one at a time, but different doors operate in parallel.
+fn dwell_time(
+    boarding: usize,
+    alighting: usize,
+    doors: usize,
+    fare_payment: FarePayment,
+) -> Duration {
+    let doors = doors.max(1) as f64;
+    let boarding = boarding as f64;
+    let alighting = alighting as f64;
+    let busiest_door = match fare_payment {
+        // Everybody boards at the front door. People alighting use the other doors first, but
+        // once those are busier than the front door, they spread out across every door.
+        FarePayment::FrontDoor => (FRONT_DOOR_BOARDING_TIME * boarding)
+            .max((FRONT_DOOR_BOARDING_TIME * boarding + ALIGHTING_TIME * alighting) / doors),
+        // Passengers spread out across all doors, letting people off before boarding.
+        FarePayment::AllDoor => {
+            (ALL_DOOR_BOARDING_TIME * boarding + ALIGHTING_TIME * alighting) / doors
+        }
+    };
+    (DOOR_OPEN_CLOSE_TIME + busiest_door).max(MIN_DWELL_TIME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(boarding: usize, alighting: usize, doors: usize, fare: FarePayment, expected: f64) {
+        assert_eq!(
+            dwell_time(boarding, alighting, doors, fare),
+            Duration::seconds(expected),
+            "{} boarding, {} alighting, {} doors, {:?}",
+            boarding,
+            alighting,
+            doors,
+            fare
+        );
+    }
+
+    #[test]
+    fn one_door() {
+        // Everybody shares the door, one at a time
+        check(4, 2, 1, FarePayment::FrontDoor, 5.0 + 4.0 * 3.5 + 2.0 * 2.0);
+        check(0, 10, 1, FarePayment::FrontDoor, 5.0 + 10.0 * 2.0);
+        // Nobody boards or alights
+        check(0, 0, 1, FarePayment::FrontDoor, 10.0);
+    }
+
+    #[test]
+    fn two_doors_front_boarding() {
+        // A few people alighting through the rear door finish before boarding does
+        check(4, 2, 2, FarePayment::FrontDoor, 5.0 + 4.0 * 3.5);
+        // With nobody boarding, people alight through both doors
+        check(0, 10, 2, FarePayment::FrontDoor, 5.0 + 10.0 * 2.0 / 2.0);
+        // Lots of people alighting also use the front door, before boarding starts
+        check(
+            2,
+            20,
+            2,
+            FarePayment::FrontDoor,
+            5.0 + (2.0 * 3.5 + 20.0 * 2.0) / 2.0,
+        );
+    }
+
+    #[test]
+    fn all_door_boarding() {
+        check(
+            8,
+            4,
+            4,
+            FarePayment::AllDoor,
+            5.0 + (8.0 * 2.0 + 4.0 * 2.0) / 4.0,
+        );
+        // Much faster than making everybody board at the front
+        assert!(
+            dwell_time(20, 0, 3, FarePayment::AllDoor)
+                < dwell_time(20, 0, 3, FarePayment::FrontDoor)
+        );
+        // Short stops still take the minimum time
+        check(1, 1, 4, FarePayment::AllDoor, 10.0);
+    }
+}
@@ -5,15 +5,17 @@ use serde::{Deserialize, Serialize};
 use abstutil::{deserialize_btreemap, serialize_btreemap, Counter};
 use geom::{Distance, Duration, Speed, Time};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, IntersectionID, Map, PathConstraints, PathRequest, Position,
+    BuildingID, BusRouteID, BusStopID, IntersectionID, Map, Path, PathConstraints, PathRequest,
+    Position,
 };
 
 use crate::sim::Ctx;
 use crate::{
-    AgentID, AgentType, AlertLocation, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal,
-    Event, IndividTrip, OrigPersonID, ParkedCar, ParkingSim, ParkingSpot, PedestrianID, PersonID,
-    PersonSpec, Scenario, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState, TripID,
-    TripPhaseType, TripPurpose, TripSpec, Vehicle, VehicleSpec, VehicleType, WalkingSimState,
+    AgentID, AgentType, AlertLocation, BikeShareDock, BikeShareSimState, BikeShareSystem, CarID,
+    Command, CreateCar, CreatePedestrian, DrivingGoal, Event, IndividTrip, OrigPersonID, ParkedCar,
+    ParkingSim, ParkingSpot, PedestrianID, PersonID, PersonSpec, RideHailSimState, RideRequest,
+    Router, Scenario, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState, TripID, TripPhaseType,
+    TripPurpose, TripSpec, Vehicle, VehicleSpec, VehicleType, WalkingSimState,
 };
 
 /// Manages people, each of which executes some trips through the day. Each trip is further broken
@@ -35,23 +37,38 @@ pub(crate) struct TripManager {
     unfinished_trips: usize,
 
     car_id_counter: usize,
+    /// Shared vehicles don't belong to anybody, but they're picked up and returned as part of
+    /// trips, so track them here.
+    bike_share: BikeShareSimState,
+    /// If present, driving trips reuse the paths here, and record any new ones.
+    trip_paths: Option<TripPaths>,
 
     events: Vec<Event>,
 }
 
 // Initialization
 impl TripManager {
-    pub fn new() -> TripManager {
+    pub fn new(trip_paths: Option<TripPaths>) -> TripManager {
         TripManager {
             trips: Vec::new(),
             people: Vec::new(),
             active_trip_mode: BTreeMap::new(),
             unfinished_trips: 0,
             car_id_counter: 0,
+            bike_share: BikeShareSimState::new(),
+            trip_paths,
             events: Vec::new(),
         }
     }
 
+    pub fn set_trip_paths(&mut self, trip_paths: TripPaths) {
+        self.trip_paths = Some(trip_paths);
+    }
+
+    pub fn get_trip_paths(&self) -> Option<&TripPaths> {
+        self.trip_paths.as_ref()
+    }
+
     // TODO assert the specs are correct yo
     pub fn new_person(
         &mut self,
@@ -88,6 +105,18 @@ impl TripManager {
         id
     }
 
+    pub fn seed_bike_share(
+        &mut self,
+        system: BikeShareSystem,
+        vehicles: Vec<(VehicleSpec, BikeShareDock)>,
+    ) {
+        self.bike_share.set_system(system);
+        for (spec, dock) in vehicles {
+            let id = CarID(self.new_car_id(), spec.vehicle_type);
+            self.bike_share.add_vehicle(spec.make(id, None), dock);
+        }
+    }
+
     pub fn new_trip(&mut self, person: PersonID, info: TripInfo, legs: Vec<TripLeg>) -> TripID {
         assert!(!legs.is_empty());
         // TODO Make sure the legs constitute a valid state machine.
@@ -157,7 +186,9 @@ impl TripManager {
 
         // Defer calculating the path until now, to handle live map edits.
         let maybe_req = spec.get_pathfinding_request(ctx.map);
-        let maybe_path = maybe_req.clone().and_then(|req| ctx.map.pathfind(req));
+        let maybe_path = maybe_req
+            .clone()
+            .and_then(|req| pathfind_vehicle(&mut self.trip_paths, trip, req, now, ctx.map));
 
         match spec {
             TripSpec::VehicleAppearing {
@@ -324,7 +355,7 @@ impl TripManager {
                     );
                 }
             }
-            TripSpec::UsingBike { start, .. } => {
+            TripSpec::UsingBike { start, .. } | TripSpec::UsingBikeAndRide { start, .. } => {
                 assert_eq!(person.state, PersonState::Inside(start));
                 person.state = PersonState::Trip(trip);
 
@@ -431,6 +462,140 @@ impl TripManager {
                     );
                 }
             }
+            TripSpec::UsingRideHail { start, goal } => {
+                assert_eq!(person.state, PersonState::Inside(start));
+                person.state = PersonState::Trip(trip);
+                self.events.push(Event::TripPhaseStarting(
+                    trip,
+                    person.id,
+                    None,
+                    TripPhaseType::WaitingForRideHail,
+                ));
+
+                match (
+                    ctx.map.get_b(start).driving_connection(ctx.map),
+                    ctx.map.get_b(goal).driving_connection(ctx.map),
+                ) {
+                    (Some((pickup_pos, _)), Some((dropoff_pos, _))) => {
+                        ctx.scheduler.push(
+                            now,
+                            Command::RequestRide(RideRequest {
+                                trip,
+                                person: person.id,
+                                requested_at: now,
+                                pickup: start,
+                                dropoff: goal,
+                                pickup_pos,
+                                dropoff_pos,
+                            }),
+                        );
+                    }
+                    _ => {
+                        self.cancel_trip(
+                            now,
+                            trip,
+                            format!(
+                                "UsingRideHail trip from {} to {} couldn't find where to meet the \
+                                 vehicle",
+                                start, goal
+                            ),
+                            None,
+                            ctx,
+                        );
+                    }
+                }
+            }
+            TripSpec::UsingBikeShare { start, goal } => {
+                assert_eq!(person.state, PersonState::Inside(start));
+                person.state = PersonState::Trip(trip);
+                let person = person.id;
+
+                // Go to the closest vehicle available right now. If there's nothing nearby, the
+                // only leg is walking to the goal.
+                if let Some((dock, walk_to)) = self
+                    .bike_share
+                    .find_vehicle(ctx.map.get_b(start).label_center, None, ctx.map)
+                    .and_then(|dock| dock.sidewalk_spot(ctx.map).map(|spot| (dock, spot)))
+                {
+                    let legs = &mut self.trips[trip.0].legs;
+                    legs.push_front(TripLeg::BikeShare(dock, goal));
+                    legs.push_front(TripLeg::Walk(walk_to));
+                }
+                if !self.trips[trip.0].spawn_ped(
+                    now,
+                    SidewalkSpot::building(start, ctx.map),
+                    &self.people[person.0],
+                    ctx.map,
+                    ctx.scheduler,
+                    &mut self.events,
+                ) {
+                    self.cancel_trip(
+                        now,
+                        trip,
+                        format!(
+                            "UsingBikeShare trip couldn't find the first path from {}",
+                            start
+                        ),
+                        None,
+                        ctx,
+                    );
+                }
+            }
+            TripSpec::UsingParkAndRide {
+                car,
+                start_bldg,
+                goal,
+            } => {
+                let start = SidewalkSpot::building(start_bldg, ctx.map);
+                let end = SidewalkSpot::building(goal, ctx.map);
+                let mut spec = TripSpec::UsingParkedCar {
+                    car,
+                    start_bldg,
+                    goal: DrivingGoal::ParkNear(goal),
+                };
+                if let Some(parked_car) = ctx.parking.lookup_parked_car(car).cloned() {
+                    let car_spot =
+                        SidewalkSpot::parking_spot(parked_car.spot, ctx.map, ctx.parking);
+                    let legs = &mut self.trips[trip.0].legs;
+                    if let Some((stop1, Some(stop2), route)) = ctx
+                        .map
+                        .should_use_transit(start.sidewalk_pos, car_spot.sidewalk_pos)
+                    {
+                        // The car was left somewhere near transit earlier, so ride back to it
+                        legs.pop_front();
+                        legs.push_front(TripLeg::Walk(car_spot.clone()));
+                        legs.push_front(TripLeg::RideBus(route, Some(stop2)));
+                        legs.push_front(TripLeg::Walk(SidewalkSpot::bus_stop(stop1, ctx.map)));
+                        spec = TripSpec::UsingTransit {
+                            start,
+                            goal: car_spot,
+                            route,
+                            stop1,
+                            maybe_stop2: Some(stop2),
+                        };
+                    } else if let Some((lot, (stop1, maybe_stop2, route))) =
+                        ctx.map.should_park_and_ride(
+                            ctx.parking.spot_to_driving_pos(
+                                parked_car.spot,
+                                &parked_car.vehicle,
+                                ctx.map,
+                            ),
+                            end.sidewalk_pos,
+                        )
+                    {
+                        // Drive to the lot instead of the destination
+                        legs.truncate(1);
+                        legs.push_back(TripLeg::Drive(car, DrivingGoal::ParkInLot(lot, goal)));
+                        legs.push_back(TripLeg::Walk(SidewalkSpot::bus_stop(stop1, ctx.map)));
+                        legs.push_back(TripLeg::RideBus(route, maybe_stop2));
+                        if maybe_stop2.is_some() {
+                            legs.push_back(TripLeg::Walk(end));
+                        }
+                    }
+                }
+                // Otherwise, just drive. Either way, the rest of the trip starts like any other.
+                self.start_trip(now, trip, spec, ctx);
+            }
         }
     }
 
@@ -462,7 +627,8 @@ impl TripManager {
         trip.total_distance += distance_crossed;
 
         match trip.legs.pop_front() {
-            Some(TripLeg::Drive(c, DrivingGoal::ParkNear(_))) => {
+            Some(TripLeg::Drive(c, DrivingGoal::ParkNear(_)))
+            | Some(TripLeg::Drive(c, DrivingGoal::ParkInLot(_, _))) => {
                 assert_eq!(car, c);
             }
             _ => unreachable!(),
@@ -516,7 +682,16 @@ impl TripManager {
         trip.total_blocked_time += blocked_time;
         trip.total_distance += distance_crossed;
 
-        trip.assert_walking_leg(SidewalkSpot::deferred_parking_spot());
+        // Usually the spot isn't known when the trip is planned, but park-and-ride trips returning
+        // to the car fill it in.
+        match trip.legs.pop_front() {
+            Some(TripLeg::Walk(to)) => {
+                if to != SidewalkSpot::deferred_parking_spot() {
+                    assert_eq!(to.connection, SidewalkPOI::ParkingSpot(spot));
+                }
+            }
+            _ => unreachable!(),
+        }
         let parked_car = ctx.parking.get_car_at_spot(spot).unwrap().clone();
         let drive_to = match trip.legs[0] {
             TripLeg::Drive(c, ref to) => {
@@ -541,16 +716,15 @@ impl TripManager {
                 start = Position::new(start.lane(), start.dist_along() + parked_car.vehicle.length);
             }
         }
-        let end = drive_to.goal_pos(PathConstraints::Car, ctx.map).unwrap();
+        let constraints = parked_car.vehicle.vehicle_type.to_constraints();
+        let end = drive_to.goal_pos(constraints, ctx.map).unwrap();
         let req = PathRequest {
             start,
             end,
-            constraints: PathConstraints::Car,
+            constraints,
         };
 
-        match ctx
-            .map
-            .pathfind(req.clone())
+        match pathfind_vehicle(&mut self.trip_paths, trip.id, req.clone(), now, ctx.map)
             .ok_or_else(|| format!("no path to drive from {} to {}", start, end))
             .and_then(|path| {
                 ctx.cap.validate_path(
@@ -607,6 +781,11 @@ impl TripManager {
         trip.total_distance += distance_crossed;
 
         trip.assert_walking_leg(spot.clone());
+        if let TripLeg::BikeShare(dock, goal) = trip.legs[0] {
+            let id = trip.id;
+            self.pick_up_shared_vehicle(now, id, dock, goal, spot, ctx);
+            return;
+        }
         let (bike, drive_to) = match trip.legs[0] {
             TripLeg::Drive(bike, ref to) => (bike, to.clone()),
             _ => unreachable!(),
@@ -616,7 +795,8 @@ impl TripManager {
             _ => unreachable!(),
         };
 
-        let end = if let Some(end) = drive_to.goal_pos(PathConstraints::Bike, ctx.map) {
+        let constraints = bike.1.to_constraints();
+        let end = if let Some(end) = drive_to.goal_pos(constraints, ctx.map) {
             end
         } else {
             let trip = trip.id;
@@ -632,7 +812,7 @@ impl TripManager {
         let req = PathRequest {
             start: driving_pos,
             end,
-            constraints: PathConstraints::Bike,
+            constraints,
         };
         let maybe_router = if req.start.lane() == req.end.lane() {
             // TODO Convert to a walking trip! Ideally, do this earlier and convert the trip to
@@ -690,14 +870,57 @@ impl TripManager {
         let trip = &mut self.trips[self.active_trip_mode.remove(&AgentID::Car(bike)).unwrap().0];
         trip.total_blocked_time += blocked_time;
         trip.total_distance += distance_crossed;
+        let id = trip.id;
 
         match trip.legs.pop_front() {
-            Some(TripLeg::Drive(c, DrivingGoal::ParkNear(_))) => {
+            Some(TripLeg::Drive(c, DrivingGoal::ParkNear(_)))
+            | Some(TripLeg::Drive(c, DrivingGoal::BikeRackAt(_))) => {
                 assert_eq!(c, bike);
             }
+            Some(TripLeg::BikeShare(dock, goal)) => {
+                let person = trip.person;
+                if !self.bike_share.return_vehicle(bike, dock, map) {
+                    let full = match dock {
+                        BikeShareDock::Station(s) => s,
+                        BikeShareDock::Dockless(_) => unreachable!(),
+                    };
+                    self.events.push(Event::BikeShareStationFull(person, full));
+                    let bike_pos = match bike_rack.connection {
+                        SidewalkPOI::BikeRack(pos) => pos,
+                        _ => unreachable!(),
+                    };
+                    // Try the next closest station with a free dock
+                    if let Some(next) = self.bike_share.free_dock_near(
+                        map.get_b(goal).label_center,
+                        Some(full),
+                        map,
+                    ) {
+                        self.trips[id.0]
+                            .legs
+                            .push_front(TripLeg::BikeShare(dock, goal));
+                        let vehicle = self.bike_share.get_vehicle(bike);
+                        if self.ride_shared_vehicle(
+                            now,
+                            id,
+                            vehicle,
+                            bike_pos,
+                            BikeShareDock::Station(next),
+                            map,
+                            scheduler,
+                        ) {
+                            return;
+                        }
+                        self.trips[id.0].legs.pop_front();
+                    }
+                    // Nowhere else to go, so leave it beside the full station
+                    self.bike_share.force_return_vehicle(bike, dock);
+                }
+                assert_eq!(self.people[person.0].on_bus.take(), Some(bike));
+            }
             _ => unreachable!(),
         };
 
+        let trip = &self.trips[id.0];
         if !trip.spawn_ped(
             now,
             bike_rack,
@@ -710,6 +933,135 @@ impl TripManager {
         }
     }
 
+    /// Somebody reached the place where they planned to pick up a shared vehicle.
+    fn pick_up_shared_vehicle(
+        &mut self,
+        now: Time,
+        id: TripID,
+        dock: BikeShareDock,
+        goal: BuildingID,
+        spot: SidewalkSpot,
+        ctx: &mut Ctx,
+    ) {
+        let person = self.trips[id.0].person;
+        let vehicle = if let Some(vehicle) = self.bike_share.take_vehicle(dock) {
+            vehicle
+        } else {
+            // Somebody else got here first. Try the next closest vehicle, or just walk.
+            if let BikeShareDock::Station(s) = dock {
+                self.events.push(Event::BikeShareStationEmpty(person, s));
+            }
+            self.trips[id.0].legs.pop_front();
+            if let Some((next, walk_to)) = self
+                .bike_share
+                .find_vehicle(spot.sidewalk_pos.pt(ctx.map), Some(dock), ctx.map)
+                .and_then(|next| next.sidewalk_spot(ctx.map).map(|s| (next, s)))
+            {
+                let legs = &mut self.trips[id.0].legs;
+                legs.push_front(TripLeg::BikeShare(next, goal));
+                legs.push_front(TripLeg::Walk(walk_to));
+            }
+            self.keep_walking(now, id, spot, ctx.map, ctx.scheduler);
+            return;
+        };
+
+        let bike_pos = match spot.connection {
+            SidewalkPOI::BikeRack(pos) => pos,
+            _ => unreachable!(),
+        };
+        if let Some(dropoff) = self.bike_share.pick_dropoff(vehicle.id, goal, ctx.map) {
+            let id2 = vehicle.id;
+            if self.ride_shared_vehicle(now, id, vehicle, bike_pos, dropoff, ctx.map, ctx.scheduler)
+            {
+                return;
+            }
+            self.bike_share.force_return_vehicle(id2, dock);
+        } else {
+            self.bike_share.force_return_vehicle(vehicle.id, dock);
+        }
+        // Riding doesn't help, so put the vehicle back and walk the rest of the way.
+        self.trips[id.0].legs.pop_front();
+        self.keep_walking(now, id, spot, ctx.map, ctx.scheduler);
+    }
+
+    /// Start riding a shared vehicle to the dropoff. The current leg must be BikeShare. Returns
+    /// false if there's no way to ride there.
+    fn ride_shared_vehicle(
+        &mut self,
+        now: Time,
+        id: TripID,
+        vehicle: Vehicle,
+        start_pos: Position,
+        dropoff: BikeShareDock,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) -> bool {
+        let end_spot = match dropoff.sidewalk_spot(map) {
+            Some(spot) => spot,
+            None => {
+                return false;
+            }
+        };
+        let req = PathRequest {
+            start: start_pos,
+            end: match end_spot.connection {
+                SidewalkPOI::BikeRack(pos) => pos,
+                _ => unreachable!(),
+            },
+            constraints: vehicle.vehicle_type.to_constraints(),
+        };
+        // Same restriction as personal bikes
+        if req.start.lane() == req.end.lane() {
+            return false;
+        }
+        let path = if let Some(path) = map.pathfind(req.clone()) {
+            path
+        } else {
+            return false;
+        };
+
+        let trip = &mut self.trips[id.0];
+        match trip.legs[0] {
+            TripLeg::BikeShare(ref mut dock, _) => {
+                *dock = dropoff;
+            }
+            _ => unreachable!(),
+        }
+        // The person doesn't own the vehicle, so remember which one they're on
+        self.people[trip.person.0].on_bus = Some(vehicle.id);
+        let router = Router::bike_then_stop(vehicle.id, path, end_spot);
+        scheduler.push(
+            now,
+            Command::SpawnCar(
+                CreateCar::for_appearing(vehicle, start_pos, router, req, id, trip.person),
+                true,
+            ),
+        );
+        true
+    }
+
+    /// After giving up on a shared vehicle, walk from here to the next leg.
+    fn keep_walking(
+        &mut self,
+        now: Time,
+        id: TripID,
+        from: SidewalkSpot,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) {
+        let trip = &self.trips[id.0];
+        if !trip.spawn_ped(
+            now,
+            SidewalkSpot::suddenly_appear(from.sidewalk_pos, map),
+            &self.people[trip.person.0],
+            map,
+            scheduler,
+            &mut self.events,
+        ) {
+            self.unfinished_trips -= 1;
+        }
+    }
+
     pub fn ped_reached_building(
         &mut self,
         now: Time,
@@ -844,6 +1196,53 @@ impl TripManager {
         }
     }
 
+    /// A ride-hailing vehicle arrived to pick somebody up. They'll ride along this path.
+    pub fn ride_hail_pickup(
+        &mut self,
+        trip: TripID,
+        car: CarID,
+        pickup: BuildingID,
+        req: PathRequest,
+    ) {
+        let person = self.trips[trip.0].person;
+        self.events
+            .push(Event::PersonLeavesBuilding(person, pickup));
+        self.events.push(Event::TripPhaseStarting(
+            trip,
+            person,
+            Some(req),
+            TripPhaseType::RidingRideHail(car),
+        ));
+        self.agent_starting_trip_leg(AgentID::Car(car), trip);
+        // The person doesn't own the vehicle, so remember which one they're in
+        self.people[person.0].on_bus = Some(car);
+    }
+
+    pub fn ride_hail_dropoff(
+        &mut self,
+        now: Time,
+        car: CarID,
+        blocked_time: Duration,
+        distance_crossed: Distance,
+        ctx: &mut Ctx,
+    ) {
+        let trip = &mut self.trips[self.active_trip_mode.remove(&AgentID::Car(car)).unwrap().0];
+        trip.total_blocked_time += blocked_time;
+        trip.total_distance += distance_crossed;
+
+        let b = match trip.legs.pop_front() {
+            Some(TripLeg::RideHail(b)) => b,
+            _ => unreachable!(),
+        };
+        assert_eq!(self.people[trip.person.0].on_bus.take(), Some(car));
+        self.people[trip.person.0].state = PersonState::Inside(b);
+        self.events
+            .push(Event::PersonEntersBuilding(trip.person, b));
+
+        let id = trip.id;
+        self.trip_finished(now, id, ctx);
+    }
+
     pub fn ped_reached_border(
         &mut self,
         now: Time,
@@ -1027,19 +1426,29 @@ impl TripManager {
 
         // Don't forget the car!
         if let Some(vehicle) = abandoned_vehicle {
-            if vehicle.vehicle_type == VehicleType::Car {
+            if self.bike_share.vehicle_abandoned(vehicle.id) {
+                self.people[person.0].on_bus = None;
+            } else if !vehicle.vehicle_type.is_bike_like() {
                 if let TripEndpoint::Bldg(b) = trip.info.end {
-                    let driving_lane = ctx.map.find_driving_lane_near_building(b);
+                    let driving_lane = ctx
+                        .map
+                        .find_lane_near_building(b, vehicle.vehicle_type.to_constraints());
                     if let Some(spot) = ctx
                         .parking
-                        .get_all_free_spots(Position::start(driving_lane), &vehicle, b, ctx.map)
+                        .get_all_free_spots(
+                            Position::start(driving_lane),
+                            &vehicle,
+                            b,
+                            now,
+                            ctx.map,
+                        )
                         // TODO Could pick something closer, but meh, cancelled trips are bugs
                         // anyway
                         .get(0)
                         .map(|(spot, _)| spot.clone())
                         .or_else(|| {
                             ctx.parking
-                                .path_to_free_parking_spot(driving_lane, &vehicle, b, ctx.map)
+                                .path_to_free_parking_spot(driving_lane, &vehicle, b, now, ctx.map)
                                 .map(|(_, spot, _)| spot)
                         })
                     {
@@ -1075,6 +1484,14 @@ impl TripManager {
                     assert_eq!(t, trip.id);
                 }
             }
+            if let TripLeg::BikeShare(_, _) = &trip.legs[0] {
+                if let Some(bike) = self.people[person.0].on_bus.take() {
+                    self.bike_share.vehicle_abandoned(bike);
+                    if let Some(t) = self.active_trip_mode.remove(&AgentID::Car(bike)) {
+                        assert_eq!(t, trip.id);
+                    }
+                }
+            }
         }
 
         self.start_delayed_trip(now, person, ctx);
@@ -1118,6 +1535,19 @@ impl TripManager {
             TripLeg::Walk(_) => AgentID::Pedestrian(person.ped),
             TripLeg::Drive(c, _) => AgentID::Car(*c),
             TripLeg::RideBus(_, _) => AgentID::BusPassenger(person.id, person.on_bus.unwrap()),
+            TripLeg::RideHail(_) => match person.on_bus {
+                Some(car) => AgentID::Car(car),
+                // Still waiting for the vehicle
+                None => {
+                    return TripResult::ModeChange;
+                }
+            },
+            TripLeg::BikeShare(_, _) => match person.on_bus {
+                Some(bike) => AgentID::Car(bike),
+                None => {
+                    return TripResult::ModeChange;
+                }
+            },
         };
         if self.active_trip_mode.get(&a) == Some(&id) {
             TripResult::Ok(a)
@@ -1147,7 +1577,11 @@ impl TripManager {
             self.unfinished_trips,
         )
     }
-    pub fn num_agents(&self, transit: &TransitSimState) -> Counter<AgentType> {
+    pub fn num_agents(
+        &self,
+        transit: &TransitSimState,
+        ridehail: &RideHailSimState,
+    ) -> Counter<AgentType> {
         let mut cnt = Counter::new();
         for a in self.active_trip_mode.keys() {
             cnt.inc(a.to_type());
@@ -1155,14 +1589,18 @@ impl TripManager {
         let (buses, trains) = transit.active_vehicles();
         cnt.add(AgentType::Bus, buses);
         cnt.add(AgentType::Train, trains);
+        // Ride-hailing vehicles with a passenger are already counted
+        cnt.add(AgentType::Car, ridehail.active_vehicles().1);
         cnt
     }
     pub fn num_commuters_vehicles(
         &self,
         transit: &TransitSimState,
+        ridehail: &RideHailSimState,
         walking: &WalkingSimState,
     ) -> CommutersVehiclesCounts {
         let (buses, trains) = transit.active_vehicles();
+        let (ride_hail_vehicles, _) = ridehail.active_vehicles();
         let mut cnt = CommutersVehiclesCounts {
             walking_commuters: 0,
             walking_to_from_transit: 0,
@@ -1170,24 +1608,41 @@ impl TripManager {
             walking_to_from_bike: 0,
 
             cyclists: 0,
+            scooter_riders: 0,
 
             sov_drivers: 0,
+            truck_drivers: 0,
+            motorcyclists: 0,
 
             buses,
             trains,
             bus_riders: 0,
             train_riders: 0,
+            ride_hail_vehicles,
+            ride_hail_passengers: 0,
         };
 
         for a in self.active_trip_mode.keys() {
             match a {
                 AgentID::Car(c) => match c.1 {
+                    VehicleType::Car if ridehail.is_fleet_vehicle(*c) => {
+                        cnt.ride_hail_passengers += 1;
+                    }
                     VehicleType::Car => {
                         cnt.sov_drivers += 1;
                     }
                     VehicleType::Bike => {
                         cnt.cyclists += 1;
                     }
+                    VehicleType::Truck => {
+                        cnt.truck_drivers += 1;
+                    }
+                    VehicleType::Scooter => {
+                        cnt.scooter_riders += 1;
+                    }
+                    VehicleType::Motorcycle => {
+                        cnt.motorcyclists += 1;
+                    }
                     VehicleType::Bus | VehicleType::Train => unreachable!(),
                 },
                 AgentID::BusPassenger(_, c) => match c.1 {
@@ -1197,7 +1652,7 @@ impl TripManager {
                     VehicleType::Train => {
                         cnt.train_riders += 1;
                     }
-                    VehicleType::Car | VehicleType::Bike => unreachable!(),
+                    _ => unreachable!(),
                 },
                 // These're counted separately
                 AgentID::Pedestrian(_) => {}
@@ -1267,6 +1722,13 @@ impl TripManager {
         self.trips[id.0].person
     }
 
+    pub fn bike_share(&self) -> &BikeShareSimState {
+        &self.bike_share
+    }
+    pub fn bike_share_mut(&mut self) -> &mut BikeShareSimState {
+        &mut self.bike_share
+    }
+
     pub fn all_arrivals_at_border(&self, at: IntersectionID) -> Vec<(Time, AgentType)> {
         let mut times = Vec::new();
         for t in &self.trips {
@@ -1280,9 +1742,14 @@ impl TripManager {
                         TripMode::Walk => AgentType::Pedestrian,
                         TripMode::Bike => AgentType::Bike,
                         TripMode::Drive => AgentType::Car,
+                        TripMode::Truck => AgentType::Truck,
+                        TripMode::Scooter => AgentType::Scooter,
+                        TripMode::Motorcycle => AgentType::Motorcycle,
                         // TODO Not true for long. People will be able to spawn at borders already
                         // on a bus.
                         TripMode::Transit => AgentType::Pedestrian,
+                        TripMode::RideHail => AgentType::Car,
+                        TripMode::BikeShare => AgentType::Bike,
                     };
                     times.push((t.info.departure, agent_type));
                 }
@@ -1320,6 +1787,59 @@ impl TripManager {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+/// The path each trip followed when it started driving, so the same routes can be replayed later.
+/// This is produced by the `traffic_assignment` tool.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TripPaths {
+    /// When the trip started driving, the request, and the path
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub paths: BTreeMap<TripID, (Time, PathRequest, Path)>,
+}
+
+impl TripPaths {
+    pub fn new() -> TripPaths {
+        TripPaths {
+            paths: BTreeMap::new(),
+        }
+    }
+}
+
+/// Pathfind for a car, truck, or motorcycle starting to drive now. If we're replaying paths per
+/// trip, reuse the previous path, as long as the request hasn't changed. Otherwise, record the new
+/// path.
+fn pathfind_vehicle(
+    trip_paths: &mut Option<TripPaths>,
+    trip: TripID,
+    req: PathRequest,
+    now: Time,
+    map: &Map,
+) -> Option<Path> {
+    let trip_paths = match trip_paths {
+        Some(ref mut trip_paths)
+            if matches!(
+                req.constraints,
+                PathConstraints::Car | PathConstraints::Truck | PathConstraints::Motorcycle
+            ) =>
+        {
+            trip_paths
+        }
+        _ => {
+            return map.pathfind_at(req, now);
+        }
+    };
+    if let Some((_, prev_req, path)) = trip_paths.paths.get(&trip) {
+        if *prev_req == req {
+            return Some(path.clone());
+        }
+    }
+    let path = map.pathfind_at(req.clone(), now)?;
+    trip_paths.paths.insert(trip, (now, req, path.clone()));
+    Some(path)
+}
+
 struct Trip {
     id: TripID,
     info: TripInfo,
@@ -1415,6 +1935,11 @@ pub(crate) enum TripLeg {
     Drive(CarID, DrivingGoal),
     /// Maybe get off at a stop, maybe ride off-map
     RideBus(BusRouteID, Option<BusStopID>),
+    /// Wait for a ride-hailing vehicle, then ride it to this building
+    RideHail(BuildingID),
+    /// Pick up a shared vehicle from the dock, then ride it and return it as close as possible to
+    /// the building. While riding, the dock is where it'll be returned.
+    BikeShare(BikeShareDock, BuildingID),
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, PartialOrd, Ord)]
@@ -1423,6 +1948,13 @@ pub enum TripMode {
     Bike,
     Transit,
     Drive,
+    Truck,
+    Scooter,
+    Motorcycle,
+    RideHail,
+    BikeShare,
+    ParkAndRide,
+    BikeAndRide,
 }
 
 impl TripMode {
@@ -1432,6 +1964,13 @@ impl TripMode {
             TripMode::Bike,
             TripMode::Transit,
             TripMode::Drive,
+            TripMode::Truck,
+            TripMode::Scooter,
+            TripMode::Motorcycle,
+            TripMode::RideHail,
+            TripMode::BikeShare,
+            TripMode::ParkAndRide,
+            TripMode::BikeAndRide,
         ]
     }
 
@@ -1441,6 +1980,13 @@ impl TripMode {
             TripMode::Bike => "bike",
             TripMode::Transit => "use transit",
             TripMode::Drive => "drive",
+            TripMode::Truck => "drive a truck",
+            TripMode::Scooter => "ride a scooter",
+            TripMode::Motorcycle => "ride a motorcycle",
+            TripMode::RideHail => "take a ride-hail",
+            TripMode::BikeShare => "use bike share",
+            TripMode::ParkAndRide => "park and ride",
+            TripMode::BikeAndRide => "bike and ride",
         }
     }
 
@@ -1451,6 +1997,13 @@ impl TripMode {
             TripMode::Bike => "biking",
             TripMode::Transit => "using transit",
             TripMode::Drive => "driving",
+            TripMode::Truck => "trucking",
+            TripMode::Scooter => "scooting",
+            TripMode::Motorcycle => "riding a motorcycle",
+            TripMode::RideHail => "riding in a ride-hail",
+            TripMode::BikeShare => "riding a shared bike",
+            TripMode::ParkAndRide => "driving to transit",
+            TripMode::BikeAndRide => "biking to transit",
         }
     }
 
@@ -1460,16 +2013,26 @@ impl TripMode {
             TripMode::Bike => "Bike",
             TripMode::Transit => "Bus",
             TripMode::Drive => "Car",
+            TripMode::Truck => "Truck",
+            TripMode::Scooter => "Scooter",
+            TripMode::Motorcycle => "Motorcycle",
+            TripMode::RideHail => "Ride-hail",
+            TripMode::BikeShare => "Bike share",
+            TripMode::ParkAndRide => "Park and ride",
+            TripMode::BikeAndRide => "Bike and ride",
         }
     }
 
     pub fn to_constraints(self) -> PathConstraints {
         match self {
             TripMode::Walk => PathConstraints::Pedestrian,
-            TripMode::Bike => PathConstraints::Bike,
+            TripMode::Bike | TripMode::BikeShare | TripMode::BikeAndRide => PathConstraints::Bike,
             // TODO WRONG
             TripMode::Transit => PathConstraints::Bus,
-            TripMode::Drive => PathConstraints::Car,
+            TripMode::Drive | TripMode::RideHail | TripMode::ParkAndRide => PathConstraints::Car,
+            TripMode::Truck => PathConstraints::Truck,
+            TripMode::Scooter => PathConstraints::Scooter,
+            TripMode::Motorcycle => PathConstraints::Motorcycle,
         }
     }
 
@@ -1480,6 +2043,22 @@ impl TripMode {
             // TODO The bijection breaks down... transit rider vs train vs bus...
             PathConstraints::Bus | PathConstraints::Train => TripMode::Transit,
             PathConstraints::Car => TripMode::Drive,
+            PathConstraints::Truck => TripMode::Truck,
+            PathConstraints::Scooter => TripMode::Scooter,
+            PathConstraints::Motorcycle => TripMode::Motorcycle,
+        }
+    }
+
+    /// What kind of vehicle does the person use for a trip with this mode? Transit, ride-hail, and
+    /// bike share riders don't own the vehicle.
+    pub fn vehicle_type(self) -> Option<VehicleType> {
+        match self {
+            TripMode::Walk | TripMode::Transit | TripMode::RideHail | TripMode::BikeShare => None,
+            TripMode::Bike | TripMode::BikeAndRide => Some(VehicleType::Bike),
+            TripMode::Drive | TripMode::ParkAndRide => Some(VehicleType::Car),
+            TripMode::Truck => Some(VehicleType::Truck),
+            TripMode::Scooter => Some(VehicleType::Scooter),
+            TripMode::Motorcycle => Some(VehicleType::Motorcycle),
         }
     }
 }
@@ -1500,52 +2079,50 @@ impl TripEndpoint {
         mode: TripMode,
         map: &Map,
     ) -> Option<PathRequest> {
+        // Ride-hailing passengers and bike share riders don't own the vehicle, but still use one
+        let vehicle_type = match mode {
+            TripMode::RideHail => Some(VehicleType::Car),
+            TripMode::BikeShare => Some(VehicleType::Bike),
+            _ => mode.vehicle_type(),
+        };
         Some(PathRequest {
-            start: pos(from, mode, true, map)?,
-            end: pos(to, mode, false, map)?,
-            constraints: match mode {
-                TripMode::Walk | TripMode::Transit => PathConstraints::Pedestrian,
-                TripMode::Drive => PathConstraints::Car,
-                TripMode::Bike => PathConstraints::Bike,
+            start: pos(from, vehicle_type, true, map)?,
+            end: pos(to, vehicle_type, false, map)?,
+            constraints: match vehicle_type {
+                Some(vt) => vt.to_constraints(),
+                None => PathConstraints::Pedestrian,
             },
         })
     }
 }
 
-fn pos(endpt: TripEndpoint, mode: TripMode, from: bool, map: &Map) -> Option<Position> {
+fn pos(
+    endpt: TripEndpoint,
+    vehicle_type: Option<VehicleType>,
+    from: bool,
+    map: &Map,
+) -> Option<Position> {
     match endpt {
-        TripEndpoint::Bldg(b) => match mode {
-            TripMode::Walk | TripMode::Transit => Some(map.get_b(b).sidewalk_pos),
-            TripMode::Bike => Some(DrivingGoal::ParkNear(b).goal_pos(PathConstraints::Bike, map)?),
-            TripMode::Drive => Some(
-                DrivingGoal::ParkNear(b)
-                    .goal_pos(PathConstraints::Car, map)
-                    .unwrap(),
-            ),
+        TripEndpoint::Bldg(b) => match vehicle_type {
+            None => Some(map.get_b(b).sidewalk_pos),
+            Some(vt) => DrivingGoal::ParkNear(b).goal_pos(vt.to_constraints(), map),
         },
-        TripEndpoint::Border(i) => match mode {
-            TripMode::Walk | TripMode::Transit => if from {
+        TripEndpoint::Border(i) => match vehicle_type {
+            None => if from {
                 SidewalkSpot::start_at_border(i, map)
             } else {
                 SidewalkSpot::end_at_border(i, map)
             }
             .map(|spot| spot.sidewalk_pos),
-            TripMode::Bike | TripMode::Drive => (if from {
+            Some(vt) => (if from {
                 map.get_i(i).some_outgoing_road(map)
             } else {
                 map.get_i(i).some_incoming_road(map)
             })
             .and_then(|dr| {
-                dr.lanes(
-                    if mode == TripMode::Bike {
-                        PathConstraints::Bike
-                    } else {
-                        PathConstraints::Car
-                    },
-                    map,
-                )
-                .get(0)
-                .map(|l| Position::start(*l))
+                dr.lanes(vt.to_constraints(), map)
+                    .get(0)
+                    .map(|l| Position::start(*l))
             }),
         },
         TripEndpoint::SuddenlyAppear(pos) => Some(pos),
@@ -1595,6 +2172,7 @@ pub struct Person {
     pub vehicles: Vec<Vehicle>,
 
     delayed_trips: Vec<(TripID, TripSpec)>,
+    /// Also used for ride-hailing vehicles and shared bikes
     on_bus: Option<CarID>,
 }
 
@@ -1619,11 +2197,18 @@ pub struct CommutersVehiclesCounts {
     pub walking_to_from_bike: usize,
 
     pub cyclists: usize,
+    pub scooter_riders: usize,
 
     pub sov_drivers: usize,
+    pub truck_drivers: usize,
+    pub motorcyclists: usize,
 
     pub buses: usize,
     pub trains: usize,
     pub bus_riders: usize,
     pub train_riders: usize,
+
+    /// Including vehicles driving without a passenger
+    pub ride_hail_vehicles: usize,
+    pub ride_hail_passengers: usize,
 }
@@ -14,6 +14,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     test_lane_changing(&import_map(abstutil::path(
         "../tests/input/lane_selection.osm",
     )))?;
+    test_traffic_signal_offset()?;
     test_map_importer()?;
     check_proposals()?;
     smoke_test()?;
@@ -227,3 +228,47 @@ fn test_lane_changing(map: &Map) -> Result<(), String> {
 
     Ok(())
 }
+
+/// Start a simulation on a traffic signal with an offset longer than the time elapsed, and make
+/// sure the signal starts partway through its cycle.
+fn test_traffic_signal_offset() -> Result<(), String> {
+    let mut map = import_map(abstutil::path("../tests/input/lane_selection.osm"));
+    let i = map
+        .all_intersections()
+        .iter()
+        .find(|i| i.is_traffic_signal())
+        .ok_or("lane_selection.osm doesn't have a traffic signal")?
+        .id;
+    let mut signal = map.get_traffic_signal(i).clone();
+    let durations: Vec<Duration> = signal
+        .stages
+        .iter()
+        .map(|s| s.phase_type.simple_duration())
+        .collect();
+    let cycle_length: Duration = durations.iter().cloned().sum();
+    // A few full cycles, plus partway into the first stage
+    let into_stage = durations[0] / 2.0;
+    signal.offset = cycle_length * 3.0 + into_stage;
+    map.incremental_edit_traffic_signal(signal);
+
+    let mut opts = sim::SimOptions::new("test_traffic_signal_offset");
+    opts.alerts = sim::AlertHandler::Silence;
+    let sim = sim::Sim::new(&map, opts, &mut Timer::throwaway());
+    let (stage, remaining) = sim.current_stage_and_remaining_time(i);
+    if stage != 0
+        || (remaining - (durations[0] - into_stage))
+            .inner_seconds()
+            .abs()
+            > 0.1
+    {
+        return Err(format!(
+            "{} with offset {} starts in stage {} with {} left; expected stage 0 with {} left",
+            i,
+            cycle_length * 3.0 + into_stage,
+            stage,
+            remaining,
+            durations[0] - into_stage
+        ));
+    }
+    Ok(())
+}